@@ -0,0 +1,207 @@
+//! Derives for the vertex buffer layouts of the demo's `GpuVertex` and `GpuInstance` traits.
+//!
+//! Each field of a `#[repr(C)]` struct becomes one attribute. Its offset is the size of the fields
+//! before it, its format comes from its type and its shader location from its position: one more
+//! than the field before it, starting at 0, unless overridden with `#[location(n)]`. The expansion
+//! fails to compile if the struct has padding no attribute would describe, which would throw
+//! those offsets off.
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(Clone, Copy, Pod, Zeroable, GpuInstance)]
+//! pub struct ModelMatrixInstance {
+//!     #[location(10)]
+//!     model_matrix_0: [f32; 4],
+//!     // at location 11
+//!     model_matrix_1: [f32; 4],
+//!     ...
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta, Type};
+
+/// Implement `GpuVertex`, stepping once per vertex.
+#[proc_macro_derive(GpuVertex, attributes(location))]
+pub fn derive_gpu_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(
+        &input,
+        quote!(crate::uniforms::vertex_input::GpuVertex),
+        quote!(Vertex),
+    )
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+/// Implement `GpuInstance`, stepping once per instance.
+#[proc_macro_derive(GpuInstance, attributes(location))]
+pub fn derive_gpu_instance(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(
+        &input,
+        quote!(crate::uniforms::instance_input::GpuInstance),
+        quote!(Instance),
+    )
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+fn expand(
+    input: &DeriveInput,
+    trait_path: TokenStream2,
+    step_mode: TokenStream2,
+) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "vertex layouts can't be derived for generic structs",
+        ));
+    }
+    if !is_repr_c(input) {
+        return Err(Error::new(
+            name.span(),
+            "vertex layouts can only be derived for `#[repr(C)]` structs",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    name.span(),
+                    "vertex layouts can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "vertex layouts can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut attributes = Vec::new();
+    let mut field_types = Vec::new();
+    let mut next_location = 0;
+    for field in fields {
+        let location = match location_override(&field.attrs)? {
+            Some(location) => location,
+            None => next_location,
+        };
+        next_location = location + 1;
+        let format = vertex_format(&field.ty)?;
+        attributes.push(quote! {
+            ::nannou::wgpu::VertexAttribute {
+                offset: (0 #(+ ::core::mem::size_of::<#field_types>())*)
+                    as ::nannou::wgpu::BufferAddress,
+                shader_location: #location,
+                format: ::nannou::wgpu::VertexFormat::#format,
+            }
+        });
+        field_types.push(&field.ty);
+    }
+
+    let padding_message = format!("`{}` has padding that no vertex attribute describes", name);
+    Ok(quote! {
+        impl #name {
+            /// One attribute for each field, in declaration order.
+            pub const ATTRIBUTES: &'static [::nannou::wgpu::VertexAttribute] = &[#(#attributes),*];
+        }
+
+        impl #trait_path for #name {
+            fn desc<'a>() -> ::nannou::wgpu::VertexBufferLayout<'a> {
+                ::nannou::wgpu::VertexBufferLayout {
+                    array_stride: ::core::mem::size_of::<#name>() as ::nannou::wgpu::BufferAddress,
+                    step_mode: ::nannou::wgpu::InputStepMode::#step_mode,
+                    attributes: #name::ATTRIBUTES,
+                }
+            }
+        }
+
+        const _: () = assert!(
+            ::core::mem::size_of::<#name>() == 0 #(+ ::core::mem::size_of::<#field_types>())*,
+            #padding_message
+        );
+    })
+}
+
+fn is_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| {
+        attr.path.is_ident("repr")
+            && matches!(attr.parse_meta(), Ok(Meta::List(list)) if list.nested.iter().any(|nested| {
+                matches!(nested, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("C"))
+            }))
+    })
+}
+
+// the `n` of a `#[location(n)]` among `attrs`
+fn location_override(attrs: &[syn::Attribute]) -> syn::Result<Option<u32>> {
+    let attr = match attrs.iter().find(|attr| attr.path.is_ident("location")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    match attr.parse_args::<Lit>()? {
+        Lit::Int(int) => int.base10_parse().map(Some),
+        lit => Err(Error::new(
+            lit.span(),
+            "expected a shader location, as in `#[location(3)]`",
+        )),
+    }
+}
+
+// the `wgpu::VertexFormat` variant of a scalar or an array of up to four scalars
+fn vertex_format(ty: &Type) -> syn::Result<Ident> {
+    let unsupported = || {
+        Error::new(
+            ty.span(),
+            "unsupported vertex attribute type: expected f32, u32 or i32, or an array of up to \
+             four of them, or an array of two or four u8, i8, u16 or i16",
+        )
+    };
+    let (scalar, len) = match ty {
+        Type::Path(path) => (scalar_name(path).ok_or_else(unsupported)?, 1),
+        Type::Array(array) => {
+            let scalar = match &*array.elem {
+                Type::Path(path) => scalar_name(path).ok_or_else(unsupported)?,
+                _ => return Err(unsupported()),
+            };
+            let len = match &array.len {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: Lit::Int(int), ..
+                }) => int.base10_parse::<usize>()?,
+                _ => return Err(unsupported()),
+            };
+            (scalar, len)
+        }
+        _ => return Err(unsupported()),
+    };
+    let base = match (scalar.as_str(), len) {
+        ("f32", 1..=4) => "Float32",
+        ("u32", 1..=4) => "Uint32",
+        ("i32", 1..=4) => "Sint32",
+        ("u8", 2 | 4) => "Uint8",
+        ("i8", 2 | 4) => "Sint8",
+        ("u16", 2 | 4) => "Uint16",
+        ("i16", 2 | 4) => "Sint16",
+        _ => return Err(unsupported()),
+    };
+    let format = if len == 1 {
+        base.to_string()
+    } else {
+        format!("{}x{}", base, len)
+    };
+    Ok(Ident::new(&format, Span::call_site()))
+}
+
+fn scalar_name(path: &syn::TypePath) -> Option<String> {
+    if path.qself.is_some() {
+        return None;
+    }
+    path.path.get_ident().map(|ident| ident.to_string())
+}
@@ -0,0 +1,246 @@
+pub mod player;
+
+pub use player::AnimationPlayer;
+
+use crate::scene_graph::NodeId;
+use nannou::glam::Quat;
+
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#animations
+
+/// The node property a `Channel` animates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Property {
+    // xyz
+    Translation,
+    // xyzw unit quaternion
+    Rotation,
+    // xyz
+    Scale,
+    // one weight per morph target of the node's mesh
+    MorphWeights,
+}
+
+/// How values between two keyframes are found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    // holds each keyframe until the next one
+    Step,
+    // linear, or spherical linear for rotations
+    Linear,
+    // a cubic Hermite spline; every keyframe stores an in-tangent, a value and an out-tangent
+    CubicSpline,
+}
+
+/// The keyframes of one property of one node.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub node: NodeId,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    // in seconds, strictly increasing
+    pub times: Vec<f32>,
+    // `components` floats per keyframe, or `3 * components` for cubic splines:
+    // [in-tangent, value, out-tangent]
+    pub values: Vec<f32>,
+    pub components: usize,
+}
+
+impl Channel {
+    /// Sample the channel at `time`, which is clamped to the keyframes, into `out`.
+    pub fn sample(&self, time: f32, out: &mut Vec<f32>) {
+        out.clear();
+        let n = self.components;
+        if self.times.is_empty() || n == 0 {
+            return;
+        }
+
+        // the keyframe at or before `time`, and how far it is towards the next one
+        let last = self.times.len() - 1;
+        let (i, t) = if time <= self.times[0] {
+            (0, 0.0)
+        } else if time >= self.times[last] {
+            (last, 0.0)
+        } else {
+            let next = self.times.partition_point(|&keyframe| keyframe <= time);
+            let i = next - 1;
+            (
+                i,
+                (time - self.times[i]) / (self.times[next] - self.times[i]),
+            )
+        };
+        let j = (i + 1).min(last);
+
+        match self.interpolation {
+            Interpolation::Step => out.extend_from_slice(self.value(i)),
+            Interpolation::Linear if self.property == Property::Rotation => {
+                let a = Quat::from_slice(self.value(i));
+                let b = Quat::from_slice(self.value(j));
+                out.extend_from_slice(&<[f32; 4]>::from(a.slerp(b, t)));
+            }
+            Interpolation::Linear => out.extend(
+                self.value(i)
+                    .iter()
+                    .zip(self.value(j))
+                    .map(|(a, b)| a + (b - a) * t),
+            ),
+            Interpolation::CubicSpline => {
+                let dt = self.times[j] - self.times[i];
+                let (t2, t3) = (t * t, t * t * t);
+                let p0 = &self.values[(3 * i + 1) * n..(3 * i + 2) * n];
+                let b0 = &self.values[(3 * i + 2) * n..(3 * i + 3) * n];
+                let a1 = &self.values[3 * j * n..(3 * j + 1) * n];
+                let p1 = &self.values[(3 * j + 1) * n..(3 * j + 2) * n];
+                out.extend((0..n).map(|k| {
+                    (2.0 * t3 - 3.0 * t2 + 1.0) * p0[k]
+                        + (t3 - 2.0 * t2 + t) * dt * b0[k]
+                        + (-2.0 * t3 + 3.0 * t2) * p1[k]
+                        + (t3 - t2) * dt * a1[k]
+                }));
+                if self.property == Property::Rotation {
+                    let rotation = Quat::from_slice(out).normalize();
+                    out.copy_from_slice(&<[f32; 4]>::from(rotation));
+                }
+            }
+        }
+    }
+
+    // the value of keyframe `i`, skipping the tangents of cubic splines
+    fn value(&self, i: usize) -> &[f32] {
+        let n = self.components;
+        match self.interpolation {
+            Interpolation::CubicSpline => &self.values[(3 * i + 1) * n..(3 * i + 2) * n],
+            _ => &self.values[i * n..(i + 1) * n],
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+}
+
+/// A set of channels played back together, such as a walk cycle.
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub channels: Vec<Channel>,
+    // the time of the last keyframe of any channel, in seconds
+    pub duration: f32,
+}
+
+impl AnimationClip {
+    pub fn new(channels: Vec<Channel>) -> Self {
+        let duration = channels.iter().map(Channel::duration).fold(0.0, f32::max);
+        Self { channels, duration }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_graph::SceneGraph;
+    use crate::transform::Transform;
+
+    fn channel(
+        property: Property,
+        interpolation: Interpolation,
+        times: Vec<f32>,
+        values: Vec<f32>,
+        components: usize,
+    ) -> Channel {
+        let node = SceneGraph::new().add_node(Transform::default(), None);
+        Channel {
+            node,
+            property,
+            interpolation,
+            times,
+            values,
+            components,
+        }
+    }
+
+    fn sampled(channel: &Channel, time: f32) -> Vec<f32> {
+        let mut out = Vec::new();
+        channel.sample(time, &mut out);
+        out
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, b) in actual.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn step_holds_each_keyframe() {
+        let channel = channel(
+            Property::MorphWeights,
+            Interpolation::Step,
+            vec![0.0, 1.0, 2.0],
+            vec![0.0, 10.0, 20.0],
+            1,
+        );
+        assert_close(&sampled(&channel, 0.0), &[0.0]);
+        assert_close(&sampled(&channel, 0.99), &[0.0]);
+        assert_close(&sampled(&channel, 1.0), &[10.0]);
+        assert_close(&sampled(&channel, 1.5), &[10.0]);
+    }
+
+    #[test]
+    fn linear_interpolates_and_clamps() {
+        let channel = channel(
+            Property::Translation,
+            Interpolation::Linear,
+            vec![1.0, 3.0],
+            vec![0.0, 0.0, 0.0, 4.0, -2.0, 8.0],
+            3,
+        );
+        assert_close(&sampled(&channel, 2.0), &[2.0, -1.0, 4.0]);
+        assert_close(&sampled(&channel, 2.5), &[3.0, -1.5, 6.0]);
+        // before the first and after the last keyframe
+        assert_close(&sampled(&channel, -5.0), &[0.0, 0.0, 0.0]);
+        assert_close(&sampled(&channel, 10.0), &[4.0, -2.0, 8.0]);
+        assert_eq!(channel.duration(), 3.0);
+    }
+
+    #[test]
+    fn linear_rotations_are_slerped() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        let mut values = <[f32; 4]>::from(a).to_vec();
+        values.extend_from_slice(&<[f32; 4]>::from(b));
+        let channel = channel(
+            Property::Rotation,
+            Interpolation::Linear,
+            vec![0.0, 1.0],
+            values,
+            4,
+        );
+        let halfway = Quat::from_rotation_y(std::f32::consts::FRAC_PI_4);
+        assert_close(&sampled(&channel, 0.5), &<[f32; 4]>::from(halfway));
+        let quarter = Quat::from_rotation_y(std::f32::consts::FRAC_PI_8);
+        assert_close(&sampled(&channel, 0.25), &<[f32; 4]>::from(quarter));
+    }
+
+    #[test]
+    fn cubic_spline_follows_the_tangents() {
+        // [in-tangent, value, out-tangent] per keyframe
+        let channel = channel(
+            Property::MorphWeights,
+            Interpolation::CubicSpline,
+            vec![0.0, 2.0],
+            vec![0.0, 0.0, 3.0, 1.0, 2.0, 0.0],
+            1,
+        );
+        assert_close(&sampled(&channel, 0.0), &[0.0]);
+        // h00 * 0 + h10 * dt * 3 + h01 * 2 + h11 * dt * 1 at t = 0.5
+        assert_close(&sampled(&channel, 1.0), &[1.5]);
+        assert_close(&sampled(&channel, 2.0), &[2.0]);
+        assert_close(&sampled(&channel, 3.0), &[2.0]);
+    }
+}
@@ -0,0 +1,406 @@
+use super::{AnimationClip, Property};
+use crate::morph::MorphWeights;
+use crate::scene_graph::{NodeId, SceneGraph};
+use crate::transform::Transform;
+use nannou::glam::{Quat, Vec3};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// One clip being played back by an `AnimationPlayer`.
+#[derive(Debug, Clone)]
+pub struct AnimationLayer {
+    // index into the clips passed to `AnimationPlayer::update`, checked by `play`
+    clip: usize,
+    // in seconds, within the clip
+    pub time: f32,
+    // 1.0 plays in real time; negative values play backwards
+    pub speed: f32,
+    pub looping: bool,
+    // how strongly the layer contributes to the pose, before fading
+    pub weight: f32,
+    fade: Option<Fade>,
+}
+
+#[derive(Debug, Clone)]
+struct Fade {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl AnimationLayer {
+    pub fn clip(&self) -> usize {
+        self.clip
+    }
+
+    // the weight after fading
+    pub fn effective_weight(&self) -> f32 {
+        let fade = match &self.fade {
+            Some(fade) => {
+                let t = (fade.elapsed / fade.duration).min(1.0);
+                fade.from + (fade.to - fade.from) * t
+            }
+            None => 1.0,
+        };
+        self.weight * fade
+    }
+
+    fn faded_out(&self) -> bool {
+        matches!(&self.fade, Some(fade) if fade.to == 0.0 && fade.elapsed >= fade.duration)
+    }
+
+    fn fade_to(&mut self, to: f32, duration: f32) {
+        let from = self.effective_weight() / self.weight.max(f32::EPSILON);
+        self.fade = if duration > 0.0 {
+            Some(Fade {
+                from,
+                to,
+                duration,
+                elapsed: 0.0,
+            })
+        } else {
+            Some(Fade {
+                from: to,
+                to,
+                duration: 1.0,
+                elapsed: 1.0,
+            })
+        };
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AnimationError {
+    #[error("there is no animation clip {clip}, as the player has {clip_count}")]
+    NoSuchClip { clip: usize, clip_count: usize },
+}
+
+/// Plays back `AnimationClip`s on the nodes of a `SceneGraph`.
+///
+/// Any number of clips can play at once as layers, blended by their weights. Where the weights of
+/// the layers animating a property add up to less than one, the rest is made up by the pose the
+/// node had before it was first animated.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    pub layers: Vec<AnimationLayer>,
+    // the length of the clips passed to `update`
+    clip_count: usize,
+    rest_transforms: HashMap<NodeId, Transform>,
+    rest_weights: HashMap<NodeId, Vec<f32>>,
+}
+
+// the weighted sum of the samples of one property of one node
+struct Accumulator {
+    weight: f32,
+    values: Vec<f32>,
+}
+
+impl AnimationPlayer {
+    /// A player of `clip_count` clips, which `update` is passed each time.
+    pub fn new(clip_count: usize) -> Self {
+        Self {
+            layers: Vec::new(),
+            clip_count,
+            rest_transforms: HashMap::new(),
+            rest_weights: HashMap::new(),
+        }
+    }
+
+    /// Start playing `clip` from the beginning, on top of the layers already playing.
+    pub fn play(&mut self, clip: usize) -> Result<&mut AnimationLayer, AnimationError> {
+        self.check_clip(clip)?;
+        self.layers.push(AnimationLayer {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            weight: 1.0,
+            fade: None,
+        });
+        Ok(self.layers.last_mut().unwrap())
+    }
+
+    /// Fade `clip` in over `duration` seconds while fading out every other layer.
+    pub fn cross_fade(
+        &mut self,
+        clip: usize,
+        duration: f32,
+    ) -> Result<&mut AnimationLayer, AnimationError> {
+        self.check_clip(clip)?;
+        for layer in self.layers.iter_mut() {
+            layer.fade_to(0.0, duration);
+        }
+        let layer = self.play(clip)?;
+        layer.fade = Some(Fade {
+            from: 0.0,
+            to: 1.0,
+            duration: duration.max(f32::EPSILON),
+            elapsed: 0.0,
+        });
+        Ok(layer)
+    }
+
+    fn check_clip(&self, clip: usize) -> Result<(), AnimationError> {
+        if clip < self.clip_count {
+            Ok(())
+        } else {
+            Err(AnimationError::NoSuchClip {
+                clip,
+                clip_count: self.clip_count,
+            })
+        }
+    }
+
+    /// Remove every layer playing `clip`.
+    #[allow(dead_code)]
+    pub fn stop(&mut self, clip: usize) {
+        self.layers.retain(|layer| layer.clip != clip);
+    }
+
+    /// Advance every layer by `dt` seconds, then pose the animated nodes and write the morph
+    /// weights of the animated meshes. Call before `SceneGraph::update_world_matrices`.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        clips: &[AnimationClip],
+        scene_graph: &mut SceneGraph,
+        morph_weights: &mut MorphWeights,
+    ) {
+        debug_assert_eq!(clips.len(), self.clip_count, "not the clips of the player");
+        for layer in self.layers.iter_mut() {
+            let duration = clips[layer.clip].duration;
+            layer.time += dt * layer.speed;
+            layer.time = if layer.looping && duration > 0.0 {
+                layer.time.rem_euclid(duration)
+            } else {
+                // non-looping clips hold their first or last pose
+                layer.time.clamp(0.0, duration)
+            };
+            if let Some(fade) = &mut layer.fade {
+                fade.elapsed += dt;
+            }
+        }
+        self.layers.retain(|layer| !layer.faded_out());
+
+        let mut accumulators: HashMap<(NodeId, Property), Accumulator> = HashMap::new();
+        let mut sample = Vec::new();
+        for layer in self.layers.iter() {
+            let weight = layer.effective_weight();
+            if weight <= 0.0 {
+                continue;
+            }
+            for channel in clips[layer.clip].channels.iter() {
+                channel.sample(layer.time, &mut sample);
+                let accumulator = accumulators
+                    .entry((channel.node, channel.property))
+                    .or_insert_with(|| Accumulator {
+                        weight: 0.0,
+                        values: vec![0.0; sample.len()],
+                    });
+                // q and -q are the same rotation; sum them on the same hemisphere
+                let sign = if channel.property == Property::Rotation
+                    && dot(&accumulator.values, &sample) < 0.0
+                {
+                    -1.0
+                } else {
+                    1.0
+                };
+                accumulator.weight += weight;
+                for (sum, value) in accumulator.values.iter_mut().zip(sample.iter()) {
+                    *sum += value * weight * sign;
+                }
+            }
+        }
+
+        for ((node, property), accumulator) in accumulators {
+            let rest = match property {
+                Property::MorphWeights => self
+                    .rest_weights
+                    .entry(node)
+                    .or_insert_with(|| morph_weights.get(&node).cloned().unwrap_or_default())
+                    .clone(),
+                _ => {
+                    let rest = self
+                        .rest_transforms
+                        .entry(node)
+                        .or_insert_with(|| scene_graph.local_transform(node).clone());
+                    match property {
+                        Property::Translation => rest.translation.to_array().to_vec(),
+                        Property::Rotation => <[f32; 4]>::from(rest.rotation).to_vec(),
+                        _ => rest.scale.to_array().to_vec(),
+                    }
+                }
+            };
+            let values = blend_with_rest(property, accumulator, &rest);
+
+            match property {
+                Property::Translation => {
+                    scene_graph.local_transform_mut(node).translation = Vec3::from_slice(&values)
+                }
+                Property::Rotation => {
+                    scene_graph.local_transform_mut(node).rotation =
+                        Quat::from_slice(&values).normalize()
+                }
+                Property::Scale => {
+                    scene_graph.local_transform_mut(node).scale = Vec3::from_slice(&values)
+                }
+                Property::MorphWeights => {
+                    morph_weights.insert(node, values);
+                }
+            }
+        }
+    }
+}
+
+// normalizes the accumulated samples, topping them up with the rest pose below a total weight of
+// one
+fn blend_with_rest(property: Property, accumulator: Accumulator, rest: &[f32]) -> Vec<f32> {
+    let Accumulator { weight, mut values } = accumulator;
+    if weight >= 1.0 {
+        values.iter_mut().for_each(|value| *value /= weight);
+        return values;
+    }
+    let sign = if property == Property::Rotation && dot(&values, rest) < 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+    for (i, value) in values.iter_mut().enumerate() {
+        *value += rest.get(i).copied().unwrap_or(0.0) * (1.0 - weight) * sign;
+    }
+    values
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{Channel, Interpolation};
+
+    // moves `node` along x from `from` to `to` over one second
+    fn slide(node: NodeId, from: f32, to: f32) -> AnimationClip {
+        AnimationClip::new(vec![Channel {
+            node,
+            property: Property::Translation,
+            interpolation: Interpolation::Linear,
+            times: vec![0.0, 1.0],
+            values: vec![from, 0.0, 0.0, to, 0.0, 0.0],
+            components: 3,
+        }])
+    }
+
+    fn setup() -> (SceneGraph, NodeId, MorphWeights) {
+        let mut scene_graph = SceneGraph::new();
+        let rest = Transform::new(Vec3::new(10.0, 0.0, 0.0), Quat::IDENTITY, Vec3::ONE);
+        let node = scene_graph.add_node(rest, None);
+        (scene_graph, node, MorphWeights::new())
+    }
+
+    fn x(scene_graph: &SceneGraph, node: NodeId) -> f32 {
+        scene_graph.local_transform(node).translation.x
+    }
+
+    #[test]
+    fn looping_layers_wrap_around() {
+        let (mut scene_graph, node, mut morph_weights) = setup();
+        let clips = [slide(node, 0.0, 1.0)];
+        let mut player = AnimationPlayer::new(clips.len());
+        player.play(0).unwrap();
+
+        player.update(1.25, &clips, &mut scene_graph, &mut morph_weights);
+        assert!((player.layers[0].time - 0.25).abs() < 1e-5);
+        assert!((x(&scene_graph, node) - 0.25).abs() < 1e-5);
+
+        // playing backwards wraps to the end
+        player.layers[0].speed = -1.0;
+        player.update(0.5, &clips, &mut scene_graph, &mut morph_weights);
+        assert!((x(&scene_graph, node) - 0.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn non_looping_layers_hold_the_last_pose() {
+        let (mut scene_graph, node, mut morph_weights) = setup();
+        let clips = [slide(node, 0.0, 1.0)];
+        let mut player = AnimationPlayer::new(clips.len());
+        player.play(0).unwrap().looping = false;
+
+        player.update(3.0, &clips, &mut scene_graph, &mut morph_weights);
+        assert_eq!(player.layers[0].time, 1.0);
+        assert!((x(&scene_graph, node) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn layers_blend_by_weight() {
+        let (mut scene_graph, node, mut morph_weights) = setup();
+        let clips = [slide(node, 0.0, 0.0), slide(node, 2.0, 2.0)];
+        let mut player = AnimationPlayer::new(clips.len());
+        player.play(0).unwrap().weight = 0.25;
+        player.play(1).unwrap().weight = 0.75;
+
+        player.update(0.5, &clips, &mut scene_graph, &mut morph_weights);
+        assert!((x(&scene_graph, node) - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn missing_weight_is_made_up_by_the_rest_pose() {
+        let (mut scene_graph, node, mut morph_weights) = setup();
+        let clips = [slide(node, 2.0, 2.0)];
+        let mut player = AnimationPlayer::new(clips.len());
+        player.play(0).unwrap().weight = 0.5;
+
+        player.update(0.5, &clips, &mut scene_graph, &mut morph_weights);
+        assert!((x(&scene_graph, node) - 6.0).abs() < 1e-5);
+        // the rest pose is remembered rather than blended with the animated pose again
+        player.update(0.5, &clips, &mut scene_graph, &mut morph_weights);
+        assert!((x(&scene_graph, node) - 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn cross_fades_hand_over_between_layers() {
+        let (mut scene_graph, node, mut morph_weights) = setup();
+        let clips = [slide(node, 0.0, 0.0), slide(node, 2.0, 2.0)];
+        let mut player = AnimationPlayer::new(clips.len());
+        player.play(0).unwrap();
+        player.update(0.25, &clips, &mut scene_graph, &mut morph_weights);
+
+        player.cross_fade(1, 1.0).unwrap();
+        player.update(0.5, &clips, &mut scene_graph, &mut morph_weights);
+        let weights: Vec<f32> = player
+            .layers
+            .iter()
+            .map(AnimationLayer::effective_weight)
+            .collect();
+        assert_eq!(weights, [0.5, 0.5]);
+        assert!((x(&scene_graph, node) - 1.0).abs() < 1e-5);
+
+        // the faded out layer is dropped once the fade is over
+        player.update(0.5, &clips, &mut scene_graph, &mut morph_weights);
+        assert_eq!(player.layers.len(), 1);
+        assert_eq!(player.layers[0].clip(), 1);
+        assert_eq!(player.layers[0].effective_weight(), 1.0);
+        assert!((x(&scene_graph, node) - 2.0).abs() < 1e-5);
+
+        player.stop(1);
+        assert!(player.layers.is_empty());
+    }
+
+    #[test]
+    fn unknown_clips_are_rejected() {
+        let (_, node, _) = setup();
+        let clips = [slide(node, 0.0, 1.0)];
+        let mut player = AnimationPlayer::new(clips.len());
+        assert!(matches!(
+            player.play(1),
+            Err(AnimationError::NoSuchClip {
+                clip: 1,
+                clip_count: 1
+            })
+        ));
+        assert!(player.cross_fade(3, 1.0).is_err());
+        assert!(player.layers.is_empty());
+    }
+}
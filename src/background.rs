@@ -0,0 +1,409 @@
+use crate::environment::EnvironmentMaps;
+use crate::light::EnvironmentLight;
+use crate::reflection::{self, LayoutEntries, PipelineInterface};
+use crate::shader::{ShaderError, ShaderLibrary};
+use crate::uniforms::background::BackgroundUniform;
+use crevice::std140::{AsStd140, Std140};
+use nannou::glam::{Mat4, Vec3};
+use nannou::wgpu;
+use nannou::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// What the background pass draws wherever no opaque geometry was drawn.
+#[derive(Debug, Clone)]
+pub enum Background {
+    /// The environment cube map lighting the scene, as bright and turned as it lights it.
+    Skybox {
+        // the mip level to sample, above 0.0 for a blurred background
+        lod: f32,
+    },
+    /// The equirectangular panorama the environment maps were baked from, as bright and turned as
+    /// the environment lights the scene.
+    Panorama,
+    /// A vertical gradient, from the nadir straight down through the horizon to the zenith
+    /// straight up.
+    Gradient {
+        zenith: Vec3,
+        horizon: Vec3,
+        nadir: Vec3,
+    },
+    /// A clear sky lit by the sun.
+    PhysicalSky(PhysicalSky),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Skybox { lod: 0.0 }
+    }
+}
+
+impl Background {
+    // the `BACKGROUND_*` constant of `background.wgsl` drawing this background
+    fn mode(&self) -> u32 {
+        match self {
+            Background::Skybox { .. } => 0,
+            Background::Panorama => 1,
+            Background::Gradient { .. } => 2,
+            Background::PhysicalSky(_) => 3,
+        }
+    }
+
+    /// The mode after this one, wrapping around, for cycling through them at runtime: a sharp
+    /// and a blurred skybox, the panorama, a daylight gradient and a physical sky with its sun
+    /// towards `sun_direction`.
+    pub fn next(&self, sun_direction: Vec3) -> Self {
+        match self {
+            Background::Skybox { lod } if *lod == 0.0 => Background::Skybox { lod: 4.0 },
+            Background::Skybox { .. } => Background::Panorama,
+            Background::Panorama => Background::Gradient {
+                zenith: Vec3::new(0.1, 0.25, 0.6),
+                horizon: Vec3::new(0.6, 0.7, 0.8),
+                nadir: Vec3::new(0.15, 0.13, 0.1),
+            },
+            Background::Gradient { .. } => Background::PhysicalSky(PhysicalSky {
+                sun_direction,
+                ..PhysicalSky::default()
+            }),
+            Background::PhysicalSky(_) => Background::default(),
+        }
+    }
+}
+
+/// The analytic daylight model of Preetham et al. 1999, "A Practical Analytic Model for
+/// Daylight".
+#[derive(Debug, Clone)]
+pub struct PhysicalSky {
+    // towards the sun, which is clamped to the horizon; needn't be normalized
+    pub sun_direction: Vec3,
+    // the haziness of the atmosphere, from 2.0 for a clear sky to 10.0 for a hazy one
+    pub turbidity: f32,
+    // scales the luminance of the sky, in kcd/m²
+    pub intensity: f32,
+}
+
+impl Default for PhysicalSky {
+    fn default() -> Self {
+        Self {
+            sun_direction: Vec3::new(0.5, 0.5, -1.0),
+            turbidity: 2.5,
+            intensity: 0.05,
+        }
+    }
+}
+
+impl PhysicalSky {
+    // the angle between the zenith and the sun
+    fn sun_theta(&self) -> f32 {
+        let direction = self.sun_direction.normalize_or_zero();
+        direction.y.clamp(0.0, 1.0).acos()
+    }
+
+    /// The coefficients A to E of the Perez luminance distribution, for the (x, y, Y) components
+    /// of the sky.
+    pub fn perez_coefficients(&self) -> [Vec3; 5] {
+        let t = self.turbidity;
+        [
+            Vec3::new(
+                -0.0193 * t - 0.2592,
+                -0.0167 * t - 0.2608,
+                0.1787 * t - 1.4630,
+            ),
+            Vec3::new(
+                -0.0665 * t + 0.0008,
+                -0.0950 * t + 0.0092,
+                -0.3554 * t + 0.4275,
+            ),
+            Vec3::new(
+                -0.0004 * t + 0.2125,
+                -0.0079 * t + 0.2102,
+                -0.0227 * t + 5.3251,
+            ),
+            Vec3::new(
+                -0.0641 * t - 0.8989,
+                -0.0441 * t - 1.6537,
+                0.1206 * t - 2.5771,
+            ),
+            Vec3::new(
+                -0.0033 * t + 0.0452,
+                -0.0109 * t + 0.0529,
+                -0.0670 * t + 0.3703,
+            ),
+        ]
+    }
+
+    /// The chromaticity and luminance (x, y, Y) of the sky straight up, with Y in kcd/m².
+    pub fn zenith(&self) -> Vec3 {
+        let t = self.turbidity;
+        let theta = self.sun_theta();
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let polynomial = |[a, b, c, d]: [f32; 4]| ((a * theta + b) * theta + c) * theta + d;
+        let x = t * t * polynomial([0.00166, -0.00375, 0.00209, 0.0])
+            + t * polynomial([-0.02903, 0.06377, -0.03202, 0.00394])
+            + polynomial([0.11693, -0.21196, 0.06052, 0.25886]);
+        let y = t * t * polynomial([0.00275, -0.00610, 0.00317, 0.0])
+            + t * polynomial([-0.04214, 0.08970, -0.04153, 0.00516])
+            + polynomial([0.15346, -0.26756, 0.06670, 0.26688]);
+        Vec3::new(x, y, luminance)
+    }
+}
+
+/// The pipeline drawing the `Background` into the forward pass, after the opaque geometry and
+/// behind it, at the far plane.
+///
+/// View rays are reconstructed from the inverse view-projection matrix, so any `CameraProjection`
+/// will do.
+pub struct BackgroundResources {
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    // samples the environment's maps
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    // the shaders `pipeline` was processed from
+    files: Vec<String>,
+    sample_count: u32,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+}
+
+impl BackgroundResources {
+    /// The entries of `[[group(0)]]` of `background.wgsl`.
+    pub fn layout_entries() -> LayoutEntries {
+        LayoutEntries::new()
+            .uniform_buffer::<BackgroundUniform>(wgpu::ShaderStage::FRAGMENT, false)
+            .sampler(wgpu::ShaderStage::FRAGMENT, true)
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::Cube,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+    }
+
+    /// `sample_count` and the formats are those of the forward pass's attachments.
+    pub fn new(
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+        environment: &EnvironmentMaps,
+        sample_count: u32,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+    ) -> Result<Self, ShaderError> {
+        let bind_group_layout = Self::layout_entries().build(device);
+        // panoramas wrap around horizontally
+        let sampler = wgpu::SamplerBuilder::new()
+            .address_mode_u(wgpu::AddressMode::Repeat)
+            .mipmap_filter(wgpu::FilterMode::Linear)
+            .build(device);
+        let uniform = BackgroundUniform::new(Mat4::IDENTITY, 0);
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("background_buffer"),
+            contents: uniform.as_std140().as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let bind_group = build_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &sampler,
+            environment,
+        );
+        let (pipeline, files) = build_pipeline(
+            device,
+            shaders,
+            &bind_group_layout,
+            sample_count,
+            color_format,
+            depth_format,
+        )?;
+
+        Ok(Self {
+            bind_group_layout,
+            uniform_buffer,
+            bind_group,
+            pipeline,
+            files,
+            sample_count,
+            color_format,
+            depth_format,
+        })
+    }
+
+    // whether the pipeline needs rebuilding after the shaders named `changed` were edited
+    pub fn depends_on(&self, changed: &[String]) -> bool {
+        changed.iter().any(|name| self.files.contains(name))
+    }
+
+    /// Rebuild the pipeline from the current `shaders`, keeping the last one if that fails.
+    pub fn rebuild_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+    ) -> Result<(), ShaderError> {
+        let (pipeline, files) = build_pipeline(
+            device,
+            shaders,
+            &self.bind_group_layout,
+            self.sample_count,
+            self.color_format,
+            self.depth_format,
+        )?;
+        self.pipeline = pipeline;
+        self.files = files;
+        Ok(())
+    }
+
+    /// Upload `background` as seen through `view_projection`, with the skybox and panorama
+    /// turned and scaled by `environment` like the light of the environment maps.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        background: &Background,
+        view_projection: Mat4,
+        environment: &EnvironmentLight,
+    ) {
+        let uniform = BackgroundUniform::new(view_projection.inverse(), background.mode());
+        let rotation = Mat4::from_rotation_y(environment.rotation);
+        let uniform = match background {
+            Background::Skybox { lod } => {
+                uniform.with_environment(rotation, environment.intensity, *lod)
+            }
+            Background::Panorama => uniform.with_environment(rotation, environment.intensity, 0.0),
+            Background::Gradient {
+                zenith,
+                horizon,
+                nadir,
+            } => uniform.with_gradient(*zenith, *horizon, *nadir),
+            Background::PhysicalSky(sky) => uniform.with_physical_sky(
+                sky.perez_coefficients(),
+                sky.zenith(),
+                sky.sun_direction.normalize_or_zero(),
+                sky.intensity,
+            ),
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, uniform.as_std140().as_bytes());
+    }
+
+    /// Record the background into the forward pass, once the opaque geometry is drawn.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn build_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    sampler: &wgpu::Sampler,
+    environment: &EnvironmentMaps,
+) -> wgpu::BindGroup {
+    wgpu::BindGroupBuilder::new()
+        .buffer_bytes(uniform_buffer, 0, None)
+        .sampler(sampler)
+        .texture_view(environment.cube_view())
+        .texture_view(environment.panorama_view())
+        .build(device, layout)
+}
+
+// the pipeline, and the shaders it was processed from
+fn build_pipeline(
+    device: &wgpu::Device,
+    shaders: &ShaderLibrary,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    sample_count: u32,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+) -> Result<(wgpu::RenderPipeline, Vec<String>), ShaderError> {
+    let shader = shaders.process("background.wgsl", &BTreeMap::new())?;
+    let module = shader.parse()?;
+    reflection::validate_module(
+        shader.name(),
+        &module,
+        &PipelineInterface {
+            vertex_buffers: &[],
+            bind_groups: &[BackgroundResources::layout_entries()],
+        },
+    )?;
+
+    let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        source: wgpu::ShaderSource::Wgsl(Cow::Owned(shader.source().to_string())),
+        flags: wgpu::ShaderFlags::default(),
+        label: Some("background"),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    // drawn at the far plane, where the depth is still cleared to 1.0
+    let pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &shader_module)
+        .fragment_shader(&shader_module)
+        .color_format(color_format)
+        .color_blend(wgpu::BlendComponent::REPLACE)
+        .alpha_blend(wgpu::BlendComponent::REPLACE)
+        .depth_format(depth_format)
+        .depth_write_enabled(false)
+        .depth_compare(wgpu::CompareFunction::LessEqual)
+        .sample_count(sample_count)
+        .build(device);
+    Ok((pipeline, shader.files().to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_cycles_through_every_mode() {
+        let mut background = Background::default();
+        let mut modes = Vec::new();
+        for _ in 0..5 {
+            modes.push(background.mode());
+            background = background.next(Vec3::Y);
+        }
+        assert_eq!(modes, vec![0, 0, 1, 2, 3]);
+        assert!(matches!(background, Background::Skybox { lod } if lod == 0.0));
+    }
+
+    #[test]
+    fn background_shader_matches_its_pipeline() {
+        let library = ShaderLibrary::builtin();
+        let shader = library
+            .process("background.wgsl", &BTreeMap::new())
+            .unwrap_or_else(|err| panic!("{}", err));
+        let module = shader.parse().unwrap_or_else(|err| panic!("{}", err));
+        reflection::validate_module(
+            "background.wgsl",
+            &module,
+            &PipelineInterface {
+                vertex_buffers: &[],
+                bind_groups: &[BackgroundResources::layout_entries()],
+            },
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    #[test]
+    fn the_physical_sky_whitens_with_turbidity() {
+        let sky = |turbidity| PhysicalSky {
+            sun_direction: Vec3::new(0.0, 1.0, -1.0),
+            turbidity,
+            ..Default::default()
+        };
+        let clear = sky(2.0).zenith();
+        let hazy = sky(8.0).zenith();
+        // a blue zenith lies below the white point (1/3, 1/3) of the chromaticity diagram
+        assert!(clear.x < 1.0 / 3.0 && clear.y < 1.0 / 3.0);
+        assert!(hazy.x > clear.x && hazy.y > clear.y);
+        assert!(clear.z > 0.0 && hazy.z > clear.z);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::culling::{self, CullingStats, Frustum};
+use crate::scene_graph::SceneGraph;
+use crate::uniforms::instance_input::model_matrix::ModelMatrixInstance;
+use crate::uniforms::instance_input::world_instance::WorldInstance;
+use crate::BasicEntity;
+use nannou::wgpu;
+use std::ops::Range;
+
+/// One `draw_indexed` of the forward pass: the visible instances of every entity sharing a mesh
+/// and a material.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    // index into `DrawContext::meshes`
+    pub mesh: usize,
+    // index into `DrawContext::materials`
+    pub material: usize,
+    // the first entity of the batch, whose model uniforms the draw binds. Skinned and morphed
+    // entities are deformed by their model uniforms, so they always get a batch of their own
+    pub entity: usize,
+    // into `Batcher::instances_buffer`
+    pub instances: Range<u32>,
+}
+
+// sorted by material first, so that consecutive batches tend to share a material bind group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct BatchKey {
+    material: usize,
+    mesh: usize,
+    // the entity, for those that can't share a batch
+    deformed: Option<usize>,
+}
+
+impl BatchKey {
+    fn new(entity: &BasicEntity, index: usize) -> Self {
+        Self {
+            material: entity.material,
+            mesh: entity.mesh,
+            deformed: if entity.is_deformed() {
+                Some(index)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Groups the visible instances of every entity by mesh and material, packing their world
+/// matrices into one instance buffer each frame, so that any number of entities sharing a mesh
+/// and a material are drawn with a single `draw_indexed`.
+pub struct Batcher {
+    instances_buffer: wgpu::Buffer,
+    // in instances
+    capacity: usize,
+    batches: Vec<Batch>,
+    // scratch space, kept from one frame to the next
+    keyed: Vec<(BatchKey, usize, WorldInstance)>,
+    instances: Vec<WorldInstance>,
+    visible: Vec<ModelMatrixInstance>,
+}
+
+impl Batcher {
+    /// A batcher with room for `capacity` instances before its buffer grows.
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            instances_buffer: create_instances_buffer(device, capacity),
+            capacity,
+            batches: Vec::new(),
+            keyed: Vec::new(),
+            instances: Vec::new(),
+            visible: Vec::new(),
+        }
+    }
+
+    /// The batches of the last `update`.
+    pub fn batches(&self) -> &[Batch] {
+        &self.batches
+    }
+
+    /// The `WorldInstance`s of every batch, to bind as the instance buffer of the forward pass.
+    pub fn instances_buffer(&self) -> &wgpu::Buffer {
+        &self.instances_buffer
+    }
+
+    /// Cull the instances of `entities` against `frustum` and rebuild the batches from those that
+    /// remain, uploading their world matrices. Returns how much of the scene was culled.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entities: &[BasicEntity],
+        scene_graph: &SceneGraph,
+        frustum: &Frustum,
+    ) -> CullingStats {
+        let stats = key_visible_instances(
+            entities,
+            scene_graph,
+            frustum,
+            &mut self.visible,
+            &mut self.keyed,
+        );
+        group_batches(&mut self.keyed, &mut self.batches, &mut self.instances);
+
+        if self.instances.len() > self.capacity {
+            self.capacity = self.instances.len().next_power_of_two();
+            self.instances_buffer = create_instances_buffer(device, self.capacity);
+        }
+        if !self.instances.is_empty() {
+            queue.write_buffer(
+                &self.instances_buffer,
+                0,
+                bytemuck::cast_slice(&self.instances),
+            );
+        }
+        stats
+    }
+}
+
+// culls every entity, collecting the world instances of those that remain under their batch keys
+fn key_visible_instances(
+    entities: &[BasicEntity],
+    scene_graph: &SceneGraph,
+    frustum: &Frustum,
+    visible: &mut Vec<ModelMatrixInstance>,
+    keyed: &mut Vec<(BatchKey, usize, WorldInstance)>,
+) -> CullingStats {
+    let mut stats = CullingStats::default();
+    keyed.clear();
+    for (index, entity) in entities.iter().enumerate() {
+        let world_matrix = scene_graph.world_matrix(entity.node);
+        culling::cull_entity(frustum, entity, &world_matrix, &mut stats, visible);
+        let key = BatchKey::new(entity, index);
+        keyed.extend(visible.iter().map(|instance| {
+            let instance = WorldInstance::new(world_matrix * instance.model_matrix());
+            (key, index, instance)
+        }));
+    }
+    stats
+}
+
+// sorts the keyed instances and makes a batch of each run sharing a key, packing the instances of
+// every batch next to each other
+fn group_batches(
+    keyed: &mut [(BatchKey, usize, WorldInstance)],
+    batches: &mut Vec<Batch>,
+    instances: &mut Vec<WorldInstance>,
+) {
+    // stable, so each batch keeps the order of its entities
+    keyed.sort_by_key(|(key, _, _)| *key);
+
+    batches.clear();
+    instances.clear();
+    let mut previous = None;
+    for (key, entity, instance) in keyed.iter() {
+        let start = instances.len() as u32;
+        instances.push(*instance);
+        match batches.last_mut() {
+            Some(batch) if previous == Some(*key) => batch.instances.end = start + 1,
+            _ => batches.push(Batch {
+                mesh: key.mesh,
+                material: key.material,
+                entity: *entity,
+                instances: start..start + 1,
+            }),
+        }
+        previous = Some(*key);
+    }
+}
+
+fn create_instances_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("batch_instances_buffer"),
+        size: (capacity * std::mem::size_of::<WorldInstance>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::Transform;
+    use crate::uniforms::model::ModelUniform;
+    use crate::uniforms::vertex_input::gltf_mesh_vertex::GltfMeshVertex;
+    use nannou::glam::{Mat4, Quat, Vec3};
+
+    // an entity `distance` in front of the camera, with an instance at each height in `heights`
+    fn entity(
+        scene_graph: &mut SceneGraph,
+        mesh: usize,
+        material: usize,
+        distance: f32,
+        heights: &[f32],
+    ) -> BasicEntity {
+        let translation = Vec3::new(0.0, 0.0, -distance);
+        let node =
+            scene_graph.add_node(Transform::new(translation, Quat::IDENTITY, Vec3::ONE), None);
+        let vertices = vec![
+            GltfMeshVertex {
+                position: [-0.1, -0.1, -0.1, 1.0],
+                ..Default::default()
+            },
+            GltfMeshVertex {
+                position: [0.1, 0.1, 0.1, 1.0],
+                ..Default::default()
+            },
+        ];
+        let instances = heights
+            .iter()
+            .map(|&y| ModelMatrixInstance::new(Mat4::from_translation(Vec3::new(0.0, y, 0.0))))
+            .collect();
+        BasicEntity::new(
+            node,
+            mesh,
+            material,
+            ModelUniform::new(Mat4::IDENTITY),
+            vertices,
+            Vec::new(),
+            instances,
+        )
+    }
+
+    // the translation column of the model matrix
+    fn translation(instance: &WorldInstance) -> Vec3 {
+        let floats: &[f32] = bytemuck::cast_slice(std::slice::from_ref(instance));
+        Vec3::from_slice(&floats[12..15])
+    }
+
+    #[test]
+    fn instances_are_grouped_by_material_and_mesh() {
+        let mut scene_graph = SceneGraph::new();
+        let mut entities = vec![
+            entity(&mut scene_graph, 1, 1, 5.0, &[0.1, 0.2]),
+            entity(&mut scene_graph, 0, 1, 5.0, &[0.3]),
+            entity(&mut scene_graph, 1, 1, 5.0, &[0.4]),
+            entity(&mut scene_graph, 1, 0, 5.0, &[0.5]),
+            entity(&mut scene_graph, 1, 1, 5.0, &[0.6]),
+            // behind the camera
+            entity(&mut scene_graph, 1, 1, -50.0, &[0.7]),
+            entity(&mut scene_graph, 1, 1, 5.0, &[0.8]),
+        ];
+        entities[4].skin = Some(0);
+        entities[6].skin = Some(0);
+        scene_graph.update_world_matrices();
+        let frustum = Frustum::from_view_projection(Mat4::perspective_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            0.1,
+            100.0,
+        ));
+
+        let mut visible = Vec::new();
+        let mut keyed = Vec::new();
+        let stats =
+            key_visible_instances(&entities, &scene_graph, &frustum, &mut visible, &mut keyed);
+        assert_eq!(stats.entities_tested, 7);
+        assert_eq!(stats.entities_visible, 6);
+        assert_eq!(stats.instances_tested, 8);
+        assert_eq!(stats.instances_visible, 7);
+
+        let mut batches = Vec::new();
+        let mut instances = Vec::new();
+        group_batches(&mut keyed, &mut batches, &mut instances);
+
+        // material first, then mesh, with every deformed entity in a batch of its own
+        let expected = [
+            (0, 1, 3, 0..1),
+            (1, 0, 1, 1..2),
+            (1, 1, 0, 2..5),
+            (1, 1, 4, 5..6),
+            (1, 1, 6, 6..7),
+        ];
+        assert_eq!(batches.len(), expected.len());
+        for (batch, (material, mesh, entity, range)) in batches.iter().zip(expected.iter()) {
+            assert_eq!(
+                (
+                    batch.material,
+                    batch.mesh,
+                    batch.entity,
+                    batch.instances.clone()
+                ),
+                (*material, *mesh, *entity, range.clone())
+            );
+        }
+
+        // the instances of a batch are contiguous and keep the order of their entities
+        let heights: Vec<f32> = instances
+            .iter()
+            .map(|instance| translation(instance).y)
+            .collect();
+        assert_eq!(heights, vec![0.5, 0.3, 0.1, 0.2, 0.4, 0.6, 0.8]);
+        for instance in &instances {
+            assert_eq!(translation(instance).z, -5.0);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use nannou::glam::{Mat4, Vec3};
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    // a zero-sized box at the origin when there are no points
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Self {
+        let mut points = points.into_iter();
+        let first = match points.next() {
+            Some(point) => point,
+            None => return Self::new(Vec3::ZERO, Vec3::ZERO),
+        };
+        points.fold(Self::new(first, first), |aabb, point| {
+            Self::new(aabb.min.min(point), aabb.max.max(point))
+        })
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// Whether the boxes overlap, counting touching boxes.
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    /// The point of the box nearest to `point`, which is `point` itself if it is inside.
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        point.clamp(self.min, self.max)
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.closest_point(sphere.center)
+            .distance_squared(sphere.center)
+            <= sphere.radius * sphere.radius
+    }
+
+    /// The smallest axis-aligned box around this box once transformed by `matrix`.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        // Arvo's method: project the half extents onto each world axis
+        let center = matrix.transform_point3(self.center());
+        let half_extents = self.half_extents();
+        let extent = matrix.x_axis.truncate().abs() * half_extents.x
+            + matrix.y_axis.truncate().abs() * half_extents.y
+            + matrix.z_axis.truncate().abs() * half_extents.z;
+        Self::new(center - extent, center + extent)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// A sphere containing this sphere once transformed by `matrix`; exact unless the scale is
+    /// non-uniform.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        let scale = matrix
+            .x_axis
+            .truncate()
+            .length()
+            .max(matrix.y_axis.truncate().length())
+            .max(matrix.z_axis.truncate().length());
+        Self::new(matrix.transform_point3(self.center), self.radius * scale)
+    }
+}
+
+/// The bounding volumes of a mesh. The sphere is the cheaper test, the box the tighter one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl Bounds {
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Vec3>,
+        I::IntoIter: Clone,
+    {
+        let points = points.into_iter();
+        let aabb = Aabb::from_points(points.clone());
+        // centred on the box, which is close to minimal for most meshes and needs one more pass
+        let center = aabb.center();
+        let radius = points
+            .map(|point| point.distance_squared(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+        Self {
+            aabb,
+            sphere: BoundingSphere::new(center, radius),
+        }
+    }
+
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        Self {
+            aabb: self.aabb.transformed(matrix),
+            sphere: self.sphere.transformed(matrix),
+        }
+    }
+
+    pub fn union(&self, other: &Bounds) -> Self {
+        let aabb = self.aabb.union(&other.aabb);
+        // enclose both spheres
+        let offset = other.sphere.center - self.sphere.center;
+        let distance = offset.length();
+        let sphere = if distance + other.sphere.radius <= self.sphere.radius {
+            self.sphere
+        } else if distance + self.sphere.radius <= other.sphere.radius {
+            other.sphere
+        } else {
+            let radius = (distance + self.sphere.radius + other.sphere.radius) * 0.5;
+            let center = self.sphere.center + offset * ((radius - self.sphere.radius) / distance);
+            BoundingSphere::new(center, radius)
+        };
+        Self { aabb, sphere }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::glam::Quat;
+
+    fn contains(outer: &BoundingSphere, inner: &BoundingSphere) -> bool {
+        outer.center.distance(inner.center) + inner.radius <= outer.radius + 1e-5
+    }
+
+    fn bounds(center: Vec3, radius: f32) -> Bounds {
+        Bounds {
+            aabb: Aabb::new(center - Vec3::splat(radius), center + Vec3::splat(radius)),
+            sphere: BoundingSphere::new(center, radius),
+        }
+    }
+
+    #[test]
+    fn transformed_aabb_encloses_the_rotated_box() {
+        let aabb = Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0));
+        let matrix = Mat4::from_rotation_translation(
+            Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+            Vec3::new(5.0, 0.0, 0.0),
+        );
+        let transformed = aabb.transformed(&matrix);
+        let extent = Vec3::new(std::f32::consts::SQRT_2, std::f32::consts::SQRT_2, 1.0);
+        let center = Vec3::new(5.0, 0.0, 0.0);
+        assert!(transformed.min.abs_diff_eq(center - extent, 1e-5));
+        assert!(transformed.max.abs_diff_eq(center + extent, 1e-5));
+    }
+
+    #[test]
+    fn union_encloses_both_spheres() {
+        let pairs = [
+            // disjoint
+            (
+                bounds(Vec3::ZERO, 1.0),
+                bounds(Vec3::new(4.0, 0.0, 0.0), 2.0),
+            ),
+            // overlapping
+            (
+                bounds(Vec3::ZERO, 2.0),
+                bounds(Vec3::new(1.0, 1.0, 1.0), 2.0),
+            ),
+            // one inside the other, either way round
+            (
+                bounds(Vec3::ZERO, 5.0),
+                bounds(Vec3::new(1.0, 0.0, 0.0), 1.0),
+            ),
+            (
+                bounds(Vec3::new(1.0, 0.0, 0.0), 1.0),
+                bounds(Vec3::ZERO, 5.0),
+            ),
+        ];
+        for (a, b) in pairs.iter() {
+            let union = a.union(b);
+            assert!(contains(&union.sphere, &a.sphere), "{:?}", union);
+            assert!(contains(&union.sphere, &b.sphere), "{:?}", union);
+            assert_eq!(union.aabb, a.aabb.union(&b.aabb));
+        }
+
+        // the union of disjoint spheres is the tightest sphere around both
+        let (a, b) = &pairs[0];
+        assert!((a.union(b).sphere.radius - 3.5).abs() < 1e-5);
+        // a contained sphere adds nothing
+        let (a, b) = &pairs[2];
+        assert_eq!(a.union(b).sphere, a.sphere);
+        assert_eq!(b.union(a).sphere, a.sphere);
+    }
+}
@@ -0,0 +1,278 @@
+use super::Bvh;
+#[cfg(test)]
+use super::Nearest;
+use crate::bounds::Aabb;
+#[cfg(test)]
+use crate::bounds::BoundingSphere;
+use crate::picking::Ray;
+use crate::uniforms::vertex_input::gltf_mesh_vertex::GltfMeshVertex;
+use nannou::glam::Vec3;
+
+/// A `Bvh` over the triangles of a mesh, in model space.
+#[derive(Debug, Clone, Default)]
+pub struct TriangleBvh {
+    bvh: Bvh,
+    triangles: Vec<[Vec3; 3]>,
+}
+
+impl TriangleBvh {
+    /// A tree over the triangle list `indices` into `vertices`.
+    pub fn new(vertices: &[GltfMeshVertex], indices: &[u32]) -> Self {
+        let position = |index: u32| {
+            let [x, y, z, _] = vertices[index as usize].position;
+            Vec3::new(x, y, z)
+        };
+        Self::from_triangles(
+            indices
+                .chunks_exact(3)
+                .map(|triangle| {
+                    [
+                        position(triangle[0]),
+                        position(triangle[1]),
+                        position(triangle[2]),
+                    ]
+                })
+                .collect(),
+        )
+    }
+
+    pub fn from_triangles(triangles: Vec<[Vec3; 3]>) -> Self {
+        let aabbs: Vec<Aabb> = triangles
+            .iter()
+            .map(|triangle| Aabb::from_points(triangle.iter().copied()))
+            .collect();
+        Self {
+            bvh: Bvh::build(&aabbs),
+            triangles,
+        }
+    }
+
+    pub fn triangle(&self, index: usize) -> [Vec3; 3] {
+        self.triangles[index]
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// The box around every triangle.
+    #[cfg(test)]
+    pub fn aabb(&self) -> Option<Aabb> {
+        self.bvh.aabb()
+    }
+
+    /// The nearest triangle along `ray`, from either side, and the distance to it.
+    pub fn cast_ray(&self, ray: &Ray, max_distance: f32) -> Option<(usize, f32)> {
+        self.bvh.cast_ray(ray, max_distance, |triangle| {
+            ray.intersect_triangle(self.triangles[triangle])
+        })
+    }
+
+    /// The triangles overlapping `aabb`.
+    #[cfg(test)]
+    pub fn overlap_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut overlapping = Vec::new();
+        self.bvh.overlap_aabb(aabb, |triangle| {
+            if triangle_overlaps_aabb(self.triangles[triangle], aabb) {
+                overlapping.push(triangle);
+            }
+        });
+        overlapping
+    }
+
+    /// The triangles overlapping `sphere`.
+    #[cfg(test)]
+    pub fn overlap_sphere(&self, sphere: &BoundingSphere) -> Vec<usize> {
+        let mut overlapping = Vec::new();
+        self.bvh.overlap_sphere(sphere, |triangle| {
+            let closest = closest_point_on_triangle(self.triangles[triangle], sphere.center);
+            if closest.distance_squared(sphere.center) <= sphere.radius * sphere.radius {
+                overlapping.push(triangle);
+            }
+        });
+        overlapping
+    }
+
+    /// The point of the mesh nearest to `point`, no further than `max_distance`, and its
+    /// triangle.
+    #[cfg(test)]
+    pub fn nearest_point(&self, point: Vec3, max_distance: f32) -> Option<Nearest> {
+        self.bvh.nearest(point, max_distance, |triangle| {
+            closest_point_on_triangle(self.triangles[triangle], point)
+        })
+    }
+}
+
+/// The point of triangle `abc` nearest to `point`.
+#[cfg(test)]
+pub fn closest_point_on_triangle([a, b, c]: [Vec3; 3], point: Vec3) -> Vec3 {
+    // Ericson, Real-Time Collision Detection 5.1.5: find the Voronoi region of the triangle that
+    // `point` is in, from its vertices to its edges to its face
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    // degenerate triangles land here with a zero denominator
+    let denominator = va + vb + vc;
+    if denominator.abs() < f32::EPSILON {
+        return a;
+    }
+    a + ab * (vb / denominator) + ac * (vc / denominator)
+}
+
+/// Whether triangle `abc` overlaps `aabb`.
+#[cfg(test)]
+pub fn triangle_overlaps_aabb([a, b, c]: [Vec3; 3], aabb: &Aabb) -> bool {
+    // Akenine-Möller: the separating axis theorem, over the box's axes, the triangle's normal
+    // and the cross products of their edges, with the box centered on the origin
+    let center = aabb.center();
+    let half_extents = aabb.half_extents();
+    let vertices = [a - center, b - center, c - center];
+    let edges = [
+        vertices[1] - vertices[0],
+        vertices[2] - vertices[1],
+        vertices[0] - vertices[2],
+    ];
+    let separated_along = |axis: Vec3| {
+        let projections = vertices.map(|vertex| vertex.dot(axis));
+        let min = projections[0].min(projections[1]).min(projections[2]);
+        let max = projections[0].max(projections[1]).max(projections[2]);
+        let radius = half_extents.dot(axis.abs());
+        min > radius || max < -radius
+    };
+    let normal = edges[0].cross(edges[1]);
+    [Vec3::X, Vec3::Y, Vec3::Z]
+        .iter()
+        .flat_map(|box_axis| edges.iter().map(move |edge| box_axis.cross(*edge)))
+        .chain([Vec3::X, Vec3::Y, Vec3::Z, normal])
+        // parallel edges cross to nothing, which separates nothing
+        .filter(|axis| axis.length_squared() > f32::EPSILON * f32::EPSILON)
+        .all(|axis| !separated_along(axis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 4x4 grid of unit squares in the XZ plane, two triangles each, from the origin to (4, 0, 4)
+    fn grid() -> TriangleBvh {
+        let mut triangles = Vec::new();
+        for x in 0..4 {
+            for z in 0..4 {
+                let corner = Vec3::new(x as f32, 0.0, z as f32);
+                let (a, b, c, d) = (
+                    corner,
+                    corner + Vec3::X,
+                    corner + Vec3::X + Vec3::Z,
+                    corner + Vec3::Z,
+                );
+                triangles.push([a, b, c]);
+                triangles.push([a, c, d]);
+            }
+        }
+        TriangleBvh::from_triangles(triangles)
+    }
+
+    #[test]
+    fn rays_hit_the_nearest_triangle() {
+        let bvh = grid();
+        let ray = Ray::new(Vec3::new(2.25, 3.0, 1.75), -Vec3::Y);
+        let (triangle, distance) = bvh.cast_ray(&ray, f32::INFINITY).unwrap();
+        assert!((distance - 3.0).abs() < 1e-5);
+        assert!(ray.intersect_triangle(bvh.triangle(triangle)).is_some());
+        assert_eq!(bvh.cast_ray(&ray, 2.0), None);
+
+        let beside = Ray::new(Vec3::new(5.0, 3.0, 1.0), -Vec3::Y);
+        assert_eq!(bvh.cast_ray(&beside, f32::INFINITY), None);
+    }
+
+    #[test]
+    fn overlap_queries_test_the_triangles_themselves() {
+        let bvh = grid();
+        // inside one triangle of the first square, and inside the box of the other one
+        let aabb = Aabb::new(Vec3::new(0.8, -0.1, 0.1), Vec3::new(0.9, 0.1, 0.2));
+        let overlapping = bvh.overlap_aabb(&aabb);
+        assert_eq!(overlapping.len(), 1);
+        assert!(triangle_overlaps_aabb(bvh.triangle(overlapping[0]), &aabb));
+        let above = Aabb::new(Vec3::new(0.0, 0.5, 0.0), Vec3::splat(4.0));
+        assert!(bvh.overlap_aabb(&above).is_empty());
+
+        let sphere = BoundingSphere::new(Vec3::new(2.0, 0.5, 2.0), 0.6);
+        // the six triangles with a corner at (2, 0, 2)
+        assert_eq!(bvh.overlap_sphere(&sphere).len(), 6);
+        assert!(bvh
+            .overlap_sphere(&BoundingSphere::new(Vec3::new(2.0, 0.5, 2.0), 0.4))
+            .is_empty());
+    }
+
+    #[test]
+    fn finds_the_nearest_point_on_the_mesh() {
+        let bvh = grid();
+        let nearest = bvh
+            .nearest_point(Vec3::new(1.3, 2.0, 2.6), f32::INFINITY)
+            .unwrap();
+        assert!(nearest.point.abs_diff_eq(Vec3::new(1.3, 0.0, 2.6), 1e-5));
+        assert!((nearest.distance - 2.0).abs() < 1e-5);
+
+        let nearest = bvh
+            .nearest_point(Vec3::new(6.0, 0.0, 5.0), f32::INFINITY)
+            .unwrap();
+        assert!(nearest.point.abs_diff_eq(Vec3::new(4.0, 0.0, 4.0), 1e-5));
+        assert_eq!(bvh.nearest_point(Vec3::new(6.0, 0.0, 5.0), 1.0), None);
+    }
+
+    #[test]
+    fn builds_from_indexed_vertices() {
+        let vertices: Vec<GltfMeshVertex> = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]
+            .iter()
+            .map(|[x, y]| GltfMeshVertex {
+                position: [*x, *y, 0.0, 1.0],
+                ..Default::default()
+            })
+            .collect();
+        let bvh = TriangleBvh::new(&vertices, &[0, 1, 2, 0, 2, 3]);
+        assert_eq!(bvh.len(), 2);
+        assert_eq!(
+            bvh.triangle(1),
+            [Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0), Vec3::Y]
+        );
+        assert_eq!(
+            bvh.aabb(),
+            Some(Aabb::new(Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0)))
+        );
+    }
+}
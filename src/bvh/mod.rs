@@ -0,0 +1,414 @@
+pub mod mesh;
+pub mod scene;
+
+pub use mesh::TriangleBvh;
+pub use scene::SceneBvh;
+
+use crate::bounds::Aabb;
+#[cfg(test)]
+use crate::bounds::BoundingSphere;
+use crate::picking::Ray;
+use nannou::glam::Vec3;
+
+// the parent of the root
+const NO_PARENT: u32 = u32::MAX;
+
+// refitting lets the boxes grow as primitives move apart; past this factor of the surface area the
+// tree was built with, queries slow down enough that rebuilding pays off
+const REBUILD_GROWTH: f32 = 2.0;
+
+#[derive(Debug, Clone)]
+struct Node {
+    aabb: Aabb,
+    parent: u32,
+    children: Children,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Children {
+    // the primitive of a leaf
+    Leaf(usize),
+    Branch(u32, u32),
+}
+
+/// The point of a primitive nearest to a query point.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Nearest {
+    pub primitive: usize,
+    pub point: Vec3,
+    pub distance: f32,
+}
+
+/// A bounding volume hierarchy: a binary tree of axis-aligned boxes over primitives known only by
+/// their index and box, with one primitive in each leaf.
+///
+/// Queries narrow the primitives down to those whose boxes pass, then hand each to a closure for
+/// the exact test, so the same tree serves triangles and whole entities alike.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    // the root first
+    nodes: Vec<Node>,
+    // the leaf of each primitive
+    leaves: Vec<u32>,
+    // of the root, as built
+    built_area: f32,
+}
+
+impl Bvh {
+    /// A tree over primitives with the boxes `aabbs`, split at the median along the longest axis
+    /// of their centers at every level.
+    pub fn build(aabbs: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(aabbs.len() * 2),
+            leaves: vec![0; aabbs.len()],
+            built_area: 0.0,
+        };
+        if aabbs.is_empty() {
+            return bvh;
+        }
+        let centers: Vec<Vec3> = aabbs.iter().map(Aabb::center).collect();
+        let mut primitives: Vec<usize> = (0..aabbs.len()).collect();
+        bvh.build_node(aabbs, &centers, &mut primitives, NO_PARENT);
+        bvh.built_area = bvh.nodes[0].aabb.surface_area();
+        bvh
+    }
+
+    fn build_node(
+        &mut self,
+        aabbs: &[Aabb],
+        centers: &[Vec3],
+        primitives: &mut [usize],
+        parent: u32,
+    ) -> u32 {
+        let index = self.nodes.len() as u32;
+        let aabb = primitives[1..]
+            .iter()
+            .fold(aabbs[primitives[0]], |aabb, &primitive| {
+                aabb.union(&aabbs[primitive])
+            });
+        self.nodes.push(Node {
+            aabb,
+            parent,
+            children: Children::Leaf(primitives[0]),
+        });
+        if primitives.len() == 1 {
+            self.leaves[primitives[0]] = index;
+            return index;
+        }
+
+        let center_bounds =
+            Aabb::from_points(primitives.iter().map(|&primitive| centers[primitive]));
+        let extent = center_bounds.max - center_bounds.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let middle = primitives.len() / 2;
+        primitives.select_nth_unstable_by(middle, |&a, &b| {
+            centers[a][axis]
+                .partial_cmp(&centers[b][axis])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let (left, right) = primitives.split_at_mut(middle);
+        let left = self.build_node(aabbs, centers, left, index);
+        let right = self.build_node(aabbs, centers, right, index);
+        self.nodes[index as usize].children = Children::Branch(left, right);
+        index
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// The box around every primitive.
+    pub fn aabb(&self) -> Option<Aabb> {
+        self.nodes.first().map(|root| root.aabb)
+    }
+
+    /// Move `primitive` to `aabb`, growing or shrinking the boxes above it to fit. The shape of the
+    /// tree stays the same, see `needs_rebuild`.
+    pub fn refit(&mut self, primitive: usize, aabb: Aabb) {
+        let mut index = self.leaves[primitive];
+        self.nodes[index as usize].aabb = aabb;
+        index = self.nodes[index as usize].parent;
+        while index != NO_PARENT {
+            let Node {
+                aabb: old_aabb,
+                parent,
+                children,
+            } = self.nodes[index as usize].clone();
+            let aabb = match children {
+                Children::Branch(left, right) => self.nodes[left as usize]
+                    .aabb
+                    .union(&self.nodes[right as usize].aabb),
+                Children::Leaf(_) => unreachable!("leaves have no children to refit around"),
+            };
+            if aabb == old_aabb {
+                break;
+            }
+            self.nodes[index as usize].aabb = aabb;
+            index = parent;
+        }
+    }
+
+    /// Whether refitting has loosened the tree enough that it should be built again.
+    pub fn needs_rebuild(&self) -> bool {
+        self.aabb().map_or(false, |aabb| {
+            aabb.surface_area() > self.built_area.max(f32::EPSILON) * REBUILD_GROWTH
+        })
+    }
+
+    /// The nearest primitive along `ray`, no further than `max_distance`. `hit` measures the
+    /// distance along the ray to a primitive, or returns `None` if the ray misses it.
+    pub fn cast_ray(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        mut hit: impl FnMut(usize) -> Option<f32>,
+    ) -> Option<(usize, f32)> {
+        let mut nearest = None;
+        let mut limit = max_distance;
+        let mut stack = Vec::new();
+        if let Some(distance) = self.enter(0, ray, limit) {
+            stack.push((0, distance));
+        }
+        while let Some((index, entry)) = stack.pop() {
+            // something nearer was hit since the node was pushed
+            if entry > limit {
+                continue;
+            }
+            match self.nodes[index as usize].children {
+                Children::Leaf(primitive) => {
+                    if let Some(distance) = hit(primitive).filter(|distance| *distance <= limit) {
+                        limit = distance;
+                        nearest = Some((primitive, distance));
+                    }
+                }
+                Children::Branch(left, right) => {
+                    // the nearer child goes on top of the stack, to shrink `limit` early
+                    match (self.enter(left, ray, limit), self.enter(right, ray, limit)) {
+                        (Some(l), Some(r)) if l <= r => stack.extend([(right, r), (left, l)]),
+                        (Some(l), Some(r)) => stack.extend([(left, l), (right, r)]),
+                        (Some(l), None) => stack.push((left, l)),
+                        (None, Some(r)) => stack.push((right, r)),
+                        (None, None) => (),
+                    }
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Visit every primitive whose box `ray` enters within `max_distance`, in no particular order.
+    pub fn ray_candidates(&self, ray: &Ray, max_distance: f32, mut visit: impl FnMut(usize)) {
+        self.visit(
+            |aabb| {
+                ray.intersect_aabb(aabb)
+                    .map_or(false, |d| d <= max_distance)
+            },
+            &mut visit,
+        );
+    }
+
+    /// Visit every primitive whose box overlaps `aabb`.
+    #[cfg(test)]
+    pub fn overlap_aabb(&self, aabb: &Aabb, mut visit: impl FnMut(usize)) {
+        self.visit(|node| node.overlaps(aabb), &mut visit);
+    }
+
+    /// Visit every primitive whose box overlaps `sphere`.
+    #[cfg(test)]
+    pub fn overlap_sphere(&self, sphere: &BoundingSphere, mut visit: impl FnMut(usize)) {
+        self.visit(|node| node.intersects_sphere(sphere), &mut visit);
+    }
+
+    /// The primitive nearest to `point`, no further than `max_distance`. `closest` finds the point
+    /// of a primitive nearest to `point`.
+    #[cfg(test)]
+    pub fn nearest(
+        &self,
+        point: Vec3,
+        max_distance: f32,
+        mut closest: impl FnMut(usize) -> Vec3,
+    ) -> Option<Nearest> {
+        let mut nearest = None;
+        let mut limit = max_distance * max_distance;
+        let squared_distance = |index: u32| {
+            let aabb = &self.nodes[index as usize].aabb;
+            aabb.closest_point(point).distance_squared(point)
+        };
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push((0, squared_distance(0)));
+        }
+        while let Some((index, node_distance)) = stack.pop() {
+            if node_distance > limit {
+                continue;
+            }
+            match self.nodes[index as usize].children {
+                Children::Leaf(primitive) => {
+                    let closest = closest(primitive);
+                    let distance = closest.distance_squared(point);
+                    if distance <= limit {
+                        limit = distance;
+                        nearest = Some(Nearest {
+                            primitive,
+                            point: closest,
+                            distance: distance.sqrt(),
+                        });
+                    }
+                }
+                Children::Branch(left, right) => {
+                    let (l, r) = (squared_distance(left), squared_distance(right));
+                    if l <= r {
+                        stack.extend([(right, r), (left, l)]);
+                    } else {
+                        stack.extend([(left, l), (right, r)]);
+                    }
+                }
+            }
+        }
+        nearest
+    }
+
+    // the distance along `ray` to the box of `index`, if it is within `limit`
+    fn enter(&self, index: u32, ray: &Ray, limit: f32) -> Option<f32> {
+        self.nodes
+            .get(index as usize)
+            .and_then(|node| ray.intersect_aabb(&node.aabb))
+            .filter(|distance| *distance <= limit)
+    }
+
+    // visit the primitives of every leaf reached through boxes passing `test`
+    fn visit(&self, test: impl Fn(&Aabb) -> bool, visit: &mut impl FnMut(usize)) {
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index as usize];
+            if !test(&node.aabb) {
+                continue;
+            }
+            match node.children {
+                Children::Leaf(primitive) => visit(primitive),
+                Children::Branch(left, right) => stack.extend([left, right]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // boxes 4 units wide scattered over a 100 unit cube, by a linear congruential generator
+    fn scattered_boxes(count: usize) -> Vec<Aabb> {
+        let mut state: u32 = 12345;
+        let mut random = move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1 << 24) as f32 * 100.0
+        };
+        (0..count)
+            .map(|_| {
+                let min = Vec3::new(random(), random(), random());
+                Aabb::new(min, min + Vec3::splat(4.0))
+            })
+            .collect()
+    }
+
+    fn sorted(mut primitives: Vec<usize>) -> Vec<usize> {
+        primitives.sort_unstable();
+        primitives
+    }
+
+    #[test]
+    fn overlap_queries_match_brute_force() {
+        let aabbs = scattered_boxes(500);
+        let bvh = Bvh::build(&aabbs);
+        let query = Aabb::new(Vec3::splat(20.0), Vec3::splat(45.0));
+        let mut found = Vec::new();
+        bvh.overlap_aabb(&query, |primitive| found.push(primitive));
+        let expected: Vec<usize> = (0..aabbs.len())
+            .filter(|&i| aabbs[i].overlaps(&query))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(sorted(found), expected);
+
+        let sphere = BoundingSphere::new(Vec3::splat(60.0), 15.0);
+        let mut found = Vec::new();
+        bvh.overlap_sphere(&sphere, |primitive| found.push(primitive));
+        let expected: Vec<usize> = (0..aabbs.len())
+            .filter(|&i| aabbs[i].intersects_sphere(&sphere))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(sorted(found), expected);
+    }
+
+    #[test]
+    fn ray_and_nearest_queries_find_the_closest_box() {
+        let aabbs = scattered_boxes(500);
+        let bvh = Bvh::build(&aabbs);
+        // from outside the cube, aimed at one of the boxes, which may be behind others
+        let origin = Vec3::new(-10.0, 50.0, 50.0);
+        let ray = Ray::new(origin, aabbs[250].center() - origin);
+        let hit = bvh.cast_ray(&ray, f32::INFINITY, |i| ray.intersect_aabb(&aabbs[i]));
+        let expected = (0..aabbs.len())
+            .filter_map(|i| ray.intersect_aabb(&aabbs[i]).map(|distance| (i, distance)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        assert!(expected.is_some());
+        assert_eq!(hit, expected);
+
+        let mut candidates = Vec::new();
+        bvh.ray_candidates(&ray, f32::INFINITY, |i| candidates.push(i));
+        let expected: Vec<usize> = (0..aabbs.len())
+            .filter(|&i| ray.intersect_aabb(&aabbs[i]).is_some())
+            .collect();
+        assert_eq!(sorted(candidates), expected);
+
+        let point = Vec3::new(30.0, 70.0, 10.0);
+        let nearest = bvh
+            .nearest(point, f32::INFINITY, |i| aabbs[i].closest_point(point))
+            .unwrap();
+        let expected = (0..aabbs.len())
+            .map(|i| aabbs[i].closest_point(point).distance(point))
+            .fold(f32::INFINITY, f32::min);
+        assert_eq!(nearest.distance, expected);
+        assert_eq!(
+            bvh.nearest(point, expected * 0.5, |i| aabbs[i].center()),
+            None
+        );
+    }
+
+    #[test]
+    fn refitting_moves_primitives_and_flags_loose_trees() {
+        let mut aabbs = scattered_boxes(100);
+        let mut bvh = Bvh::build(&aabbs);
+        assert!(!bvh.needs_rebuild());
+
+        let moved = Aabb::new(Vec3::splat(500.0), Vec3::splat(501.0));
+        aabbs[42] = moved;
+        bvh.refit(42, moved);
+        let mut found = Vec::new();
+        bvh.overlap_aabb(&moved, |primitive| found.push(primitive));
+        assert_eq!(found, [42]);
+        assert_eq!(bvh.aabb().unwrap().max, Vec3::splat(501.0));
+        assert!(bvh.needs_rebuild());
+        assert!(!Bvh::build(&aabbs).needs_rebuild());
+    }
+
+    #[test]
+    fn empty_trees_find_nothing() {
+        let bvh = Bvh::build(&[]);
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+        assert_eq!(bvh.cast_ray(&ray, f32::INFINITY, |_| Some(0.0)), None);
+        assert_eq!(bvh.nearest(Vec3::ZERO, f32::INFINITY, |_| Vec3::ZERO), None);
+        bvh.overlap_aabb(&Aabb::new(Vec3::ZERO, Vec3::ONE), |_| panic!());
+        assert!(bvh.is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+use super::Bvh;
+use crate::bounds::Aabb;
+#[cfg(test)]
+use crate::bounds::BoundingSphere;
+use crate::picking::{self, Hit, Ray};
+use crate::scene_graph::SceneGraph;
+use crate::BasicEntity;
+use nannou::glam::Mat4;
+#[cfg(test)]
+use nannou::glam::Vec3;
+
+/// An instance of an entity, by its indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntityInstance {
+    // index into the entities the tree was built over
+    pub entity: usize,
+    // index into `BasicEntity::instances`
+    pub instance: usize,
+}
+
+/// The point of an instance nearest to a query point.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NearestInstance {
+    pub entity_instance: EntityInstance,
+    pub point: Vec3,
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Item {
+    entity_instance: EntityInstance,
+    // the world matrix of the entity's node times the instance's model matrix
+    model_matrix: Mat4,
+}
+
+/// A `Bvh` over the world-space boxes of every instance of every entity of a scene.
+///
+/// `update` keeps the tree in step with the scene graph by refitting the boxes of the instances
+/// that moved, and rebuilds it once refitting has loosened it too much or instances come and go.
+#[derive(Debug, Clone, Default)]
+pub struct SceneBvh {
+    bvh: Bvh,
+    // the primitives of `bvh`
+    items: Vec<Item>,
+    // the number of instances of each entity, as of the last build
+    instance_counts: Vec<usize>,
+}
+
+impl SceneBvh {
+    /// A tree over `entities`, placed by the world matrices of `scene_graph`.
+    pub fn new(entities: &[BasicEntity], scene_graph: &SceneGraph) -> Self {
+        let mut items = Vec::new();
+        for (entity_index, entity) in entities.iter().enumerate() {
+            let world_matrix = scene_graph.world_matrix(entity.node);
+            for (instance_index, instance) in entity.instances.iter().enumerate() {
+                items.push(Item {
+                    entity_instance: EntityInstance {
+                        entity: entity_index,
+                        instance: instance_index,
+                    },
+                    model_matrix: world_matrix * instance.model_matrix(),
+                });
+            }
+        }
+        let aabbs: Vec<Aabb> = items
+            .iter()
+            .map(|item| world_aabb(entities, item))
+            .collect();
+        Self {
+            bvh: Bvh::build(&aabbs),
+            items,
+            instance_counts: entities
+                .iter()
+                .map(|entity| entity.instances.len())
+                .collect(),
+        }
+    }
+
+    /// Catch up with the world matrices of `scene_graph` and the instances of `entities`, which
+    /// must be the entities the tree was built over, with any instances added or removed.
+    pub fn update(&mut self, entities: &[BasicEntity], scene_graph: &SceneGraph) {
+        let counts_changed = entities.len() != self.instance_counts.len()
+            || entities
+                .iter()
+                .zip(&self.instance_counts)
+                .any(|(entity, count)| entity.instances.len() != *count);
+        if counts_changed {
+            *self = Self::new(entities, scene_graph);
+            return;
+        }
+
+        for (primitive, item) in self.items.iter_mut().enumerate() {
+            let entity = &entities[item.entity_instance.entity];
+            let model_matrix = scene_graph.world_matrix(entity.node)
+                * entity.instances[item.entity_instance.instance].model_matrix();
+            if model_matrix != item.model_matrix {
+                item.model_matrix = model_matrix;
+                self.bvh.refit(primitive, world_aabb(entities, item));
+            }
+        }
+        if self.bvh.needs_rebuild() {
+            *self = Self::new(entities, scene_graph);
+        }
+    }
+
+    /// The number of instances in the tree.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// The nearest hit of `ray` on every instance of `entities` it hits, nearest first, testing
+    /// only the instances whose boxes it enters.
+    pub fn cast_ray(&self, ray: &Ray, entities: &[BasicEntity]) -> Vec<Hit> {
+        let mut hits = Vec::new();
+        self.bvh.ray_candidates(ray, f32::INFINITY, |primitive| {
+            let item = &self.items[primitive];
+            let EntityInstance { entity, instance } = item.entity_instance;
+            if let Some((distance, normal)) =
+                picking::intersect_mesh(ray, &entities[entity], &item.model_matrix)
+            {
+                hits.push(Hit {
+                    entity,
+                    instance,
+                    distance,
+                    point: ray.at(distance),
+                    normal,
+                });
+            }
+        });
+        hits.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        hits
+    }
+
+    /// The instances whose world-space boxes overlap `aabb`.
+    #[cfg(test)]
+    pub fn overlap_aabb(&self, aabb: &Aabb) -> Vec<EntityInstance> {
+        let mut overlapping = Vec::new();
+        self.bvh.overlap_aabb(aabb, |primitive| {
+            overlapping.push(self.items[primitive].entity_instance)
+        });
+        overlapping.sort_unstable();
+        overlapping
+    }
+
+    /// The instances whose world-space boxes overlap `sphere`.
+    #[cfg(test)]
+    pub fn overlap_sphere(&self, sphere: &BoundingSphere) -> Vec<EntityInstance> {
+        let mut overlapping = Vec::new();
+        self.bvh.overlap_sphere(sphere, |primitive| {
+            overlapping.push(self.items[primitive].entity_instance)
+        });
+        overlapping.sort_unstable();
+        overlapping
+    }
+
+    /// The instance nearest to `point`, no further than `max_distance`, and the point of it
+    /// nearest to `point`.
+    ///
+    /// The point is on the triangles of entities that have a `TriangleBvh`, found in model space,
+    /// so it is exact for rotated, translated and uniformly scaled instances, and on their
+    /// world-space box otherwise.
+    #[cfg(test)]
+    pub fn nearest(
+        &self,
+        point: Vec3,
+        max_distance: f32,
+        entities: &[BasicEntity],
+    ) -> Option<NearestInstance> {
+        self.bvh
+            .nearest(point, max_distance, |primitive| {
+                let item = &self.items[primitive];
+                let entity = &entities[item.entity_instance.entity];
+                let triangles = entity
+                    .triangles
+                    .as_ref()
+                    .filter(|_| item.model_matrix.determinant().abs() >= f32::EPSILON);
+                let local_nearest = triangles.and_then(|triangles| {
+                    let local_point = item.model_matrix.inverse().transform_point3(point);
+                    triangles.nearest_point(local_point, f32::INFINITY)
+                });
+                match local_nearest {
+                    Some(nearest) => item.model_matrix.transform_point3(nearest.point),
+                    None => world_aabb(entities, item).closest_point(point),
+                }
+            })
+            .map(|nearest| NearestInstance {
+                entity_instance: self.items[nearest.primitive].entity_instance,
+                point: nearest.point,
+                distance: nearest.distance,
+            })
+    }
+}
+
+fn world_aabb(entities: &[BasicEntity], item: &Item) -> Aabb {
+    entities[item.entity_instance.entity]
+        .bounds
+        .aabb
+        .transformed(&item.model_matrix)
+}
+
+/// Scenes for the tests of the queries against them.
+#[cfg(test)]
+pub mod fixtures {
+    use crate::bvh::TriangleBvh;
+    use crate::scene_graph::SceneGraph;
+    use crate::transform::Transform;
+    use crate::uniforms::instance_input::model_matrix::ModelMatrixInstance;
+    use crate::uniforms::model::ModelUniform;
+    use crate::uniforms::vertex_input::gltf_mesh_vertex::GltfMeshVertex;
+    use crate::BasicEntity;
+    use nannou::glam::{Mat4, Vec3};
+    use std::sync::Arc;
+
+    // a unit quad in the XY plane, facing +Z, with its `TriangleBvh` and an instance at each of
+    // `translations`
+    pub fn quad(scene_graph: &mut SceneGraph, translations: &[Vec3]) -> BasicEntity {
+        let vertices: Vec<GltfMeshVertex> = [[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]]
+            .iter()
+            .map(|[x, y]| GltfMeshVertex {
+                position: [*x, *y, 0.0, 1.0],
+                ..Default::default()
+            })
+            .collect();
+        let indices = vec![0, 1, 2, 0, 2, 3];
+        let instances = translations
+            .iter()
+            .map(|translation| ModelMatrixInstance::new(Mat4::from_translation(*translation)))
+            .collect();
+        let node = scene_graph.add_node(Transform::default(), None);
+        let triangles = Arc::new(TriangleBvh::new(&vertices, &indices));
+        let mut entity = BasicEntity::new(
+            node,
+            0,
+            0,
+            ModelUniform::new(Mat4::IDENTITY),
+            vertices,
+            indices,
+            instances,
+        );
+        entity.triangles = Some(triangles);
+        entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::quad;
+    use super::*;
+    use crate::transform::Transform;
+    use nannou::glam::Quat;
+
+    fn instance(entity: usize, instance: usize) -> EntityInstance {
+        EntityInstance { entity, instance }
+    }
+
+    // quads along the X axis, one entity with instances at x = 0 and x = 4 and one at x = 8
+    fn scene() -> (Vec<BasicEntity>, SceneGraph) {
+        let mut scene_graph = SceneGraph::new();
+        let entities = vec![
+            quad(&mut scene_graph, &[Vec3::ZERO, Vec3::new(4.0, 0.0, 0.0)]),
+            quad(&mut scene_graph, &[Vec3::new(8.0, 0.0, 0.0)]),
+        ];
+        scene_graph.update_world_matrices();
+        (entities, scene_graph)
+    }
+
+    #[test]
+    fn agrees_with_casting_against_every_entity() {
+        let (entities, scene_graph) = scene();
+        let bvh = SceneBvh::new(&entities, &scene_graph);
+        assert_eq!(bvh.len(), 3);
+        for origin in [Vec3::new(4.2, 0.1, 5.0), Vec3::new(-3.0, 0.0, 5.0)] {
+            let ray = Ray::new(origin, Vec3::new(4.0, 0.0, 0.0) - origin);
+            assert_eq!(
+                bvh.cast_ray(&ray, &entities),
+                picking::cast_ray(&ray, &entities, &scene_graph)
+            );
+        }
+        let ray = Ray::new(Vec3::new(8.0, 0.0, 5.0), -Vec3::Z);
+        let hits = bvh.cast_ray(&ray, &entities);
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].entity, hits[0].instance), (1, 0));
+    }
+
+    #[test]
+    fn answers_overlap_and_nearest_queries_in_world_space() {
+        let (entities, scene_graph) = scene();
+        let bvh = SceneBvh::new(&entities, &scene_graph);
+        let aabb = Aabb::new(Vec3::new(3.0, -1.0, -1.0), Vec3::new(9.0, 1.0, 1.0));
+        assert_eq!(bvh.overlap_aabb(&aabb), [instance(0, 1), instance(1, 0)]);
+        let sphere = BoundingSphere::new(Vec3::new(2.0, 0.0, 0.0), 1.6);
+        assert_eq!(
+            bvh.overlap_sphere(&sphere),
+            [instance(0, 0), instance(0, 1)]
+        );
+
+        let nearest = bvh
+            .nearest(Vec3::new(7.0, 0.2, 2.0), f32::INFINITY, &entities)
+            .unwrap();
+        assert_eq!(nearest.entity_instance, instance(1, 0));
+        assert!(nearest.point.abs_diff_eq(Vec3::new(7.5, 0.2, 0.0), 1e-5));
+        assert_eq!(bvh.nearest(Vec3::new(7.0, 0.2, 2.0), 1.0, &entities), None);
+    }
+
+    #[test]
+    fn follows_moving_nodes() {
+        let (mut entities, mut scene_graph) = scene();
+        let mut bvh = SceneBvh::new(&entities, &scene_graph);
+        let far_away = Vec3::new(0.0, 100.0, 0.0);
+        *scene_graph.local_transform_mut(entities[1].node) =
+            Transform::new(far_away, Quat::IDENTITY, Vec3::ONE);
+        scene_graph.update_world_matrices();
+        bvh.update(&entities, &scene_graph);
+
+        let ray = Ray::new(Vec3::new(8.0, 0.0, 5.0), -Vec3::Z);
+        assert!(bvh.cast_ray(&ray, &entities).is_empty());
+        let ray = Ray::new(Vec3::new(8.0, 100.0, 5.0), -Vec3::Z);
+        assert_eq!(bvh.cast_ray(&ray, &entities).len(), 1);
+        let around = BoundingSphere::new(far_away + Vec3::new(8.0, 0.0, 0.0), 1.0);
+        assert_eq!(bvh.overlap_sphere(&around), [instance(1, 0)]);
+
+        // and rebuilds when instances come and go
+        entities[0].instances.pop();
+        bvh.update(&entities, &scene_graph);
+        assert_eq!(bvh.len(), 2);
+        assert_eq!(
+            bvh.overlap_aabb(&Aabb::new(Vec3::splat(-10.0), Vec3::splat(10.0))),
+            [instance(0, 0)]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use super::{ease_velocity, key_axis, key_down, smoothing_factor, CameraController, PointerInput};
+use crate::transform::Transform;
+use nannou::event::{Key, MouseButton, WindowEvent};
+use nannou::glam::{EulerRot, Quat, Vec3};
+use nannou::state::Keys;
+
+/// A walking camera that stays upright.
+///
+/// Dragging with `look_button` turns the camera, with the pitch held within `max_pitch` of the
+/// horizon. WASD walks across the horizontal plane whatever the pitch, Space and LControl move
+/// straight up and down and LShift runs.
+#[derive(Debug, Clone)]
+pub struct FirstPersonController {
+    pub position: Vec3,
+    // around the world Y axis, in radians; 0 looks down -Z
+    pub yaw: f32,
+    // in radians; positive values look up
+    pub pitch: f32,
+    // the largest angle above or below the horizon, in radians
+    pub max_pitch: f32,
+    // world units per second
+    pub move_speed: f32,
+    // multiplies `move_speed` while LShift is held
+    pub run: f32,
+    // radians per point dragged
+    pub look_speed: f32,
+    pub look_button: MouseButton,
+    // smoothing time constants in seconds; 0 follows the input immediately
+    pub move_damping: f32,
+    pub look_damping: f32,
+    velocity: Vec3,
+    target_yaw: f32,
+    target_pitch: f32,
+    pointer: PointerInput,
+}
+
+impl Default for FirstPersonController {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, 0.0, 0.0)
+    }
+}
+
+impl FirstPersonController {
+    pub fn new(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        let max_pitch = 89f32.to_radians();
+        let pitch = pitch.clamp(-max_pitch, max_pitch);
+        Self {
+            position,
+            yaw,
+            pitch,
+            max_pitch,
+            move_speed: 3.0,
+            run: 2.5,
+            look_speed: 0.004,
+            look_button: MouseButton::Right,
+            move_damping: 0.1,
+            look_damping: 0.03,
+            velocity: Vec3::ZERO,
+            target_yaw: yaw,
+            target_pitch: pitch,
+            pointer: PointerInput::default(),
+        }
+    }
+
+    /// Stands wherever `transform` currently is, facing the same way; any roll is dropped.
+    pub fn from_transform(transform: &Transform) -> Self {
+        let (yaw, pitch, _roll) = transform.rotation.to_euler(EulerRot::YXZ);
+        Self::new(transform.translation, yaw, pitch)
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+
+    // `update`, with `held` telling which keys are down
+    fn advance(&mut self, held: impl Fn(Key) -> bool, dt: f32, transform: &mut Transform) {
+        let look = self.pointer.take_drag(self.look_button) * self.look_speed;
+        self.pointer.clear();
+        self.target_yaw -= look.x;
+        self.target_pitch = (self.target_pitch + look.y).clamp(-self.max_pitch, self.max_pitch);
+
+        let t = smoothing_factor(self.look_damping, dt);
+        self.yaw += (self.target_yaw - self.yaw) * t;
+        self.pitch += (self.target_pitch - self.pitch) * t;
+
+        // walk relative to the heading only, so looking down does not slow the camera
+        let heading = Quat::from_rotation_y(self.yaw);
+        let horizontal = heading
+            * Vec3::new(
+                key_axis(&held, Key::D, Key::A),
+                0.0,
+                key_axis(&held, Key::S, Key::W),
+            );
+        let direction =
+            (horizontal + Vec3::Y * key_axis(&held, Key::Space, Key::LControl)).normalize_or_zero();
+        let speed = if held(Key::LShift) {
+            self.move_speed * self.run
+        } else {
+            self.move_speed
+        };
+        self.position +=
+            ease_velocity(&mut self.velocity, direction * speed, self.move_damping, dt);
+
+        transform.translation = self.position;
+        transform.rotation = self.rotation();
+    }
+}
+
+impl CameraController for FirstPersonController {
+    fn event(&mut self, event: &WindowEvent) {
+        self.pointer.event(event);
+    }
+
+    fn update(&mut self, keys: &Keys, dt: f32, transform: &mut Transform) {
+        self.advance(key_down(keys), dt, transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    // the position after holding W and D for a second at `fps` frames per second, facing +X
+    fn walk(fps: u32) -> Vec3 {
+        let mut controller = FirstPersonController::new(Vec3::ZERO, -FRAC_PI_2, 0.5);
+        let mut transform = Transform::default();
+        for _ in 0..fps {
+            controller.advance(
+                |key| key == Key::W || key == Key::D,
+                1.0 / fps as f32,
+                &mut transform,
+            );
+        }
+        transform.translation
+    }
+
+    #[test]
+    fn movement_does_not_depend_on_the_frame_rate() {
+        let (slow, fast) = (walk(20), walk(240));
+        assert!(slow.abs_diff_eq(fast, 1e-3), "{} != {}", slow, fast);
+        // diagonally across the ground, whatever the pitch
+        assert_eq!(fast.y, 0.0);
+        assert!(fast.x > 1.0 && (fast.x - fast.z).abs() < 1e-4, "{}", fast);
+    }
+}
@@ -0,0 +1,132 @@
+use super::{ease_velocity, key_axis, key_down, smoothing_factor, CameraController, PointerInput};
+use crate::transform::Transform;
+use nannou::event::{Key, MouseButton, WindowEvent};
+use nannou::glam::{Quat, Vec3};
+use nannou::state::Keys;
+
+/// A free-flying camera with six degrees of freedom.
+///
+/// WASD moves along the view plane, Space and LControl move along the camera's up axis, Q and E
+/// roll and LShift speeds everything up. Dragging with `look_button` turns the camera around its
+/// own axes, so looking past straight up simply flips over instead of stopping.
+#[derive(Debug, Clone)]
+pub struct FlyController {
+    pub position: Vec3,
+    pub orientation: Quat,
+    // world units per second
+    pub move_speed: f32,
+    // multiplies `move_speed` while LShift is held
+    pub boost: f32,
+    // radians per point dragged
+    pub look_speed: f32,
+    // radians per second
+    pub roll_speed: f32,
+    pub look_button: MouseButton,
+    // smoothing time constants in seconds; 0 follows the input immediately
+    pub move_damping: f32,
+    pub look_damping: f32,
+    velocity: Vec3,
+    target_orientation: Quat,
+    pointer: PointerInput,
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, Quat::IDENTITY)
+    }
+}
+
+impl FlyController {
+    pub fn new(position: Vec3, orientation: Quat) -> Self {
+        Self {
+            position,
+            orientation,
+            move_speed: 5.0,
+            boost: 4.0,
+            look_speed: 0.005,
+            roll_speed: 1.5,
+            look_button: MouseButton::Right,
+            move_damping: 0.15,
+            look_damping: 0.05,
+            velocity: Vec3::ZERO,
+            target_orientation: orientation,
+            pointer: PointerInput::default(),
+        }
+    }
+
+    /// Takes off from wherever `transform` currently is.
+    pub fn from_transform(transform: &Transform) -> Self {
+        Self::new(transform.translation, transform.rotation)
+    }
+
+    // `update`, with `held` telling which keys are down
+    fn advance(&mut self, held: impl Fn(Key) -> bool, dt: f32, transform: &mut Transform) {
+        let look = self.pointer.take_drag(self.look_button) * self.look_speed;
+        let roll = key_axis(&held, Key::Q, Key::E) * self.roll_speed * dt;
+        self.pointer.clear();
+        // around the camera's own axes, so the turn rate is the same whichever way it faces
+        self.target_orientation = (self.target_orientation
+            * Quat::from_rotation_y(-look.x)
+            * Quat::from_rotation_x(look.y)
+            * Quat::from_rotation_z(roll))
+        .normalize();
+        self.orientation = self.orientation.slerp(
+            self.target_orientation,
+            smoothing_factor(self.look_damping, dt),
+        );
+
+        let direction = Vec3::new(
+            key_axis(&held, Key::D, Key::A),
+            key_axis(&held, Key::Space, Key::LControl),
+            key_axis(&held, Key::S, Key::W),
+        )
+        .normalize_or_zero();
+        let speed = if held(Key::LShift) {
+            self.move_speed * self.boost
+        } else {
+            self.move_speed
+        };
+        let target_velocity = self.orientation * direction * speed;
+        self.position += ease_velocity(&mut self.velocity, target_velocity, self.move_damping, dt);
+
+        transform.translation = self.position;
+        transform.rotation = self.orientation;
+    }
+}
+
+impl CameraController for FlyController {
+    fn event(&mut self, event: &WindowEvent) {
+        self.pointer.event(event);
+    }
+
+    fn update(&mut self, keys: &Keys, dt: f32, transform: &mut Transform) {
+        self.advance(key_down(keys), dt, transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the position after holding W for a second at `fps` frames per second
+    fn fly_forward(fps: u32) -> Vec3 {
+        let mut controller = FlyController::default();
+        let mut transform = Transform::default();
+        for _ in 0..fps {
+            controller.advance(|key| key == Key::W, 1.0 / fps as f32, &mut transform);
+        }
+        transform.translation
+    }
+
+    #[test]
+    fn movement_does_not_depend_on_the_frame_rate() {
+        let (slow, fast) = (fly_forward(20), fly_forward(240));
+        assert!(slow.abs_diff_eq(fast, 1e-3), "{} != {}", slow, fast);
+        // easing up to 5 units per second with a time constant of 0.15 seconds
+        assert!(
+            fast.abs_diff_eq(Vec3::new(0.0, 0.0, -4.25), 1e-2),
+            "{}",
+            fast
+        );
+    }
+}
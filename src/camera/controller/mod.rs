@@ -0,0 +1,118 @@
+pub mod first_person;
+pub mod fly;
+pub mod orbit;
+
+pub use first_person::FirstPersonController;
+pub use fly::FlyController;
+pub use orbit::OrbitController;
+
+use crate::transform::Transform;
+use nannou::event::{Key, MouseButton, MouseScrollDelta, WindowEvent};
+use nannou::glam::{Vec2, Vec3};
+use nannou::state::Keys;
+
+/// Turns window input into camera motion.
+///
+/// Feed every window event to `event`, then call `update` once per frame to move the camera. The
+/// controllers assume a right-handed, Y-up world with cameras looking down their -Z axis.
+pub trait CameraController {
+    fn event(&mut self, event: &WindowEvent);
+    // `dt` in seconds; `transform` is the camera transform, which is overwritten
+    fn update(&mut self, keys: &Keys, dt: f32, transform: &mut Transform);
+}
+
+// the mouse motion accumulated between two updates
+#[derive(Debug, Clone, Default)]
+struct PointerInput {
+    last_position: Option<Vec2>,
+    pressed: Vec<MouseButton>,
+    // in points, with +y up, keyed by the button held while dragging
+    drag: Vec<(MouseButton, Vec2)>,
+    // in lines, positive when scrolling up / away from the user
+    scroll: f32,
+}
+
+// roughly the number of points a mouse wheel line scrolls on most platforms
+const POINTS_PER_LINE: f32 = 20.0;
+
+impl PointerInput {
+    fn event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::MousePressed(button) if !self.pressed.contains(&button) => {
+                self.pressed.push(button)
+            }
+            WindowEvent::MouseReleased(button) => self.pressed.retain(|&b| b != button),
+            WindowEvent::MouseMoved(position) => {
+                if let Some(last_position) = self.last_position {
+                    let delta = position - last_position;
+                    for &button in self.pressed.iter() {
+                        match self.drag.iter_mut().find(|(b, _)| *b == button) {
+                            Some((_, drag)) => *drag += delta,
+                            None => self.drag.push((button, delta)),
+                        }
+                    }
+                }
+                self.last_position = Some(position);
+            }
+            WindowEvent::MouseWheel(delta, _) => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / POINTS_PER_LINE,
+                };
+            }
+            // the cursor may be released outside of the window
+            WindowEvent::MouseExited | WindowEvent::Unfocused => {
+                self.pressed.clear();
+                self.last_position = None;
+            }
+            _ => (),
+        }
+    }
+
+    // the drag distance of `button` since the last call
+    fn take_drag(&mut self, button: MouseButton) -> Vec2 {
+        match self.drag.iter().position(|(b, _)| *b == button) {
+            Some(i) => self.drag.swap_remove(i).1,
+            None => Vec2::ZERO,
+        }
+    }
+
+    fn take_scroll(&mut self) -> f32 {
+        std::mem::take(&mut self.scroll)
+    }
+
+    fn clear(&mut self) {
+        self.drag.clear();
+        self.scroll = 0.0;
+    }
+}
+
+// the fraction of the remaining distance to cover this frame, for a smoothing time constant of
+// `damping` seconds; independent of the frame rate
+fn smoothing_factor(damping: f32, dt: f32) -> f32 {
+    if damping <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-dt / damping).exp()
+    }
+}
+
+// eases `velocity` towards `target` with a smoothing time constant of `damping` seconds, returning
+// the distance covered over the `dt` seconds. The easing is integrated exactly rather than stepped
+// at the final velocity, so the camera covers the same ground at any frame rate
+fn ease_velocity(velocity: &mut Vec3, target: Vec3, damping: f32, dt: f32) -> Vec3 {
+    let t = smoothing_factor(damping, dt);
+    let distance = target * dt + (*velocity - target) * damping.max(0.0) * t;
+    *velocity = velocity.lerp(target, t);
+    distance
+}
+
+// whether `key` is held down
+fn key_down(keys: &Keys) -> impl Fn(Key) -> bool + '_ {
+    move |key| keys.down.contains(&key)
+}
+
+// 1 while `positive` is held, -1 while `negative` is, 0 for both or neither
+fn key_axis(held: &impl Fn(Key) -> bool, positive: Key, negative: Key) -> f32 {
+    held(positive) as i32 as f32 - held(negative) as i32 as f32
+}
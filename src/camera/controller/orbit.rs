@@ -0,0 +1,176 @@
+use super::{smoothing_factor, CameraController, PointerInput};
+use crate::transform::Transform;
+use nannou::event::{MouseButton, WindowEvent};
+use nannou::glam::{EulerRot, Quat, Vec3};
+use nannou::state::Keys;
+
+/// A turntable camera circling a pivot point.
+///
+/// Dragging with the left button rotates around the pivot, dragging with the right or middle
+/// button pans the pivot across the view plane and the mouse wheel zooms in and out. The camera
+/// stays upright: yaw turns around the world Y axis and pitch stops short of the poles.
+#[derive(Debug, Clone)]
+pub struct OrbitController {
+    pub pivot: Vec3,
+    // from the pivot to the camera, in world units
+    pub distance: f32,
+    // around the world Y axis, in radians; 0 looks down -Z
+    pub yaw: f32,
+    // in radians; positive values look down onto the pivot
+    pub pitch: f32,
+    // radians per point dragged
+    pub rotate_speed: f32,
+    // the fraction of `distance` panned per point dragged
+    pub pan_speed: f32,
+    // the factor `distance` shrinks by per wheel line is `1 + zoom_speed`
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    // the smoothing time constant of every motion, in seconds; 0 follows the input immediately
+    pub damping: f32,
+    target_pivot: Vec3,
+    target_distance: f32,
+    target_yaw: f32,
+    target_pitch: f32,
+    pointer: PointerInput,
+}
+
+// keeps the view direction away from the world up axis
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+impl Default for OrbitController {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, 5.0, 0.0, 0.0)
+    }
+}
+
+impl OrbitController {
+    pub fn new(pivot: Vec3, distance: f32, yaw: f32, pitch: f32) -> Self {
+        let pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        let mut controller = Self {
+            pivot,
+            distance,
+            yaw,
+            pitch,
+            rotate_speed: 0.01,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            damping: 0.08,
+            target_pivot: pivot,
+            target_distance: distance,
+            target_yaw: yaw,
+            target_pitch: pitch,
+            pointer: PointerInput::default(),
+        };
+        // zooming works on the logarithm of the distance, so it must stay positive
+        controller.set_distance(distance);
+        controller.distance = controller.target_distance;
+        controller
+    }
+
+    /// Orbits `pivot` from wherever `transform` currently is.
+    pub fn from_transform(transform: &Transform, pivot: Vec3) -> Self {
+        let offset = transform.translation - pivot;
+        let distance = offset.length();
+        let direction = offset.normalize_or_zero();
+        // the camera sits at `pivot + rotation * Z * distance`
+        let yaw = direction.x.atan2(direction.z);
+        let pitch = direction.y.asin();
+        Self::new(pivot, distance, yaw, pitch)
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.target_distance = distance.clamp(self.min_distance, self.max_distance);
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, -self.pitch, 0.0)
+    }
+
+    fn target_rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.target_yaw, -self.target_pitch, 0.0)
+    }
+}
+
+impl CameraController for OrbitController {
+    fn event(&mut self, event: &WindowEvent) {
+        self.pointer.event(event);
+    }
+
+    fn update(&mut self, _keys: &Keys, dt: f32, transform: &mut Transform) {
+        let rotate = self.pointer.take_drag(MouseButton::Left);
+        self.target_yaw -= rotate.x * self.rotate_speed;
+        self.target_pitch =
+            (self.target_pitch - rotate.y * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let pan = self.pointer.take_drag(MouseButton::Right)
+            + self.pointer.take_drag(MouseButton::Middle);
+        // drag the scene along with the cursor, scaled so it feels the same at any distance
+        let rotation = self.target_rotation();
+        self.target_pivot -= (rotation * Vec3::X * pan.x + rotation * Vec3::Y * pan.y)
+            * self.pan_speed
+            * self.distance;
+
+        let scroll = self.pointer.take_scroll();
+        self.target_distance = (self.target_distance * (1.0 + self.zoom_speed).powf(-scroll))
+            .clamp(self.min_distance, self.max_distance);
+        self.pointer.clear();
+
+        let t = smoothing_factor(self.damping, dt);
+        self.pivot = self.pivot.lerp(self.target_pivot, t);
+        self.yaw += (self.target_yaw - self.yaw) * t;
+        self.pitch += (self.target_pitch - self.pitch) * t;
+        // interpolate the zoom in log space so it eases evenly at every distance
+        self.distance =
+            (self.distance.ln() + (self.target_distance.ln() - self.distance.ln()) * t).exp();
+
+        let rotation = self.rotation();
+        transform.rotation = rotation;
+        transform.translation = self.pivot + rotation * Vec3::Z * self.distance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::glam::Vec2;
+
+    #[test]
+    fn from_transform_recovers_the_orbit() {
+        let pivot = Vec3::new(1.0, 2.0, 3.0);
+        let mut orbit = OrbitController::new(pivot, 4.0, 0.7, 0.3);
+        let mut transform = Transform::default();
+        orbit.update(&Keys::default(), 0.1, &mut transform);
+        let forward = transform.rotation * -Vec3::Z;
+        assert!(forward.abs_diff_eq((pivot - transform.translation).normalize(), 1e-5));
+
+        let recovered = OrbitController::from_transform(&transform, pivot);
+        assert!((recovered.distance - 4.0).abs() < 1e-4);
+        assert!((recovered.yaw - 0.7).abs() < 1e-4);
+        assert!((recovered.pitch - 0.3).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pitch_stops_short_of_the_poles() {
+        assert_eq!(
+            OrbitController::new(Vec3::ZERO, 5.0, 0.0, 3.0).pitch,
+            MAX_PITCH
+        );
+
+        let mut orbit = OrbitController {
+            damping: 0.0,
+            ..OrbitController::default()
+        };
+        let mut transform = Transform::default();
+        // drag far down, which tips the camera over the top of the pivot
+        orbit.event(&WindowEvent::MousePressed(MouseButton::Left));
+        orbit.event(&WindowEvent::MouseMoved(Vec2::ZERO));
+        orbit.event(&WindowEvent::MouseMoved(Vec2::new(0.0, -10_000.0)));
+        orbit.update(&Keys::default(), 1.0 / 60.0, &mut transform);
+        assert_eq!(orbit.pitch, MAX_PITCH);
+        let forward = transform.rotation * -Vec3::Z;
+        assert!(forward.y < 0.0 && forward.y > -1.0);
+    }
+}
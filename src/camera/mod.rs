@@ -1,5 +1,7 @@
+pub mod controller;
 pub mod projection;
 
+use crate::scene_graph::{NodeId, SceneGraph};
 use crate::transform::{Handedness, Transform, Transformable};
 use crate::uniforms::camera::CameraUniform;
 use nannou::prelude::{Mat4, Quat, Vec3};
@@ -10,12 +12,17 @@ pub trait Camera {
 }
 
 pub struct BasicCamera<P: CameraProjection> {
+    // relative to `parent`, or to world space if the camera is not attached to the scene graph
     pub transform: Transform,
     pub projection: P,
+    pub parent: Option<NodeId>,
+    // how far in front of the camera the scene is sharpest, in world units, for depth of field
+    pub focus_distance: f32,
+    parent_matrix: Mat4,
 }
 
-impl<P: CameraProjection> From<BasicCamera<P>> for CameraUniform {
-    fn from(basic_camera: BasicCamera<P>) -> Self {
+impl<P: CameraProjection> From<&BasicCamera<P>> for CameraUniform {
+    fn from(basic_camera: &BasicCamera<P>) -> Self {
         let view_matrix = basic_camera.view_mat4();
         let projection_matrix = basic_camera.projection().projection_mat4();
         CameraUniform::new(view_matrix, projection_matrix)
@@ -28,7 +35,7 @@ impl<P: CameraProjection> Camera for BasicCamera<P> {
     }
     //  transform vertices from world-space to view/camera space
     fn view_mat4(&self) -> Mat4 {
-        Mat4::inverse(&self.transform.mat4x4())
+        Mat4::inverse(&self.world_mat4())
     }
 }
 
@@ -40,31 +47,85 @@ where
         Self {
             transform,
             projection: camera_projection,
+            parent: None,
+            focus_distance: 5.0,
+            parent_matrix: Mat4::IDENTITY,
         }
     }
 
-    pub fn focus_on_target(&mut self, target: Transform) -> Quat {
-        let handedness = self.transform.handededness();
+    // attach the camera to a scene graph node, e.g. to build a camera rig on a moving vehicle
+    pub fn attach_to(&mut self, parent: Option<NodeId>) {
+        self.parent = parent;
+    }
 
-        match handedness {
-            Handedness::Left => Quat::from_mat4(&Mat4::look_at_lh(
-                self.transform.translation,
-                target.translation,
-                self.transform.y_axis(),
-            )),
-            Handedness::Right => Quat::from_mat4(&Mat4::look_at_rh(
-                self.transform.translation,
-                target.translation,
-                self.transform.y_axis(),
-            )),
-        }
+    // pull in the world matrix of the parent node; call after `SceneGraph::update_world_matrices`
+    pub fn update_parent_matrix(&mut self, scene_graph: &SceneGraph) {
+        self.parent_matrix = self
+            .parent
+            .map_or(Mat4::IDENTITY, |parent| scene_graph.world_matrix(parent));
     }
 
-    pub fn set_position(&mut self, position: Vec3) -> () {
-        self.transform.translation = position;
+    //  transform vertices from camera space to world space
+    pub fn world_mat4(&self) -> Mat4 {
+        self.parent_matrix * self.transform.mat4x4()
     }
 
-    pub fn set_rotation(&mut self, rotation: Quat) -> () {
+    // turn the camera towards `target`, in world space, without moving it; returns the new
+    // rotation, which like `transform` is relative to `parent`. Call after `update_parent_matrix`
+    #[allow(dead_code)]
+    pub fn focus_on_target(&mut self, target: Transform) -> Quat {
+        let handedness = self.transform.handededness();
+        let world_matrix = self.world_mat4();
+        let eye = world_matrix.transform_point3(Vec3::ZERO);
+        let up = self
+            .parent_matrix
+            .transform_vector3(self.transform.y_axis());
+
+        // `look_at_*` builds a view matrix; the camera's world rotation is its inverse
+        let view_matrix = match handedness {
+            Handedness::Left => Mat4::look_at_lh(eye, target.translation, up),
+            Handedness::Right => Mat4::look_at_rh(eye, target.translation, up),
+        };
+        let world_rotation = Quat::from_mat4(&view_matrix).inverse();
+        let (_, parent_rotation, _) = self.parent_matrix.to_scale_rotation_translation();
+        let rotation = parent_rotation.inverse() * world_rotation;
         self.transform.rotation = rotation;
+        rotation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use projection::PerspectiveProjection;
+
+    #[test]
+    fn attached_cameras_focus_in_world_space() {
+        let mut scene_graph = SceneGraph::new();
+        let rig = scene_graph.add_node(
+            Transform::new(
+                Vec3::new(10.0, 0.0, 0.0),
+                Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+                Vec3::ONE,
+            ),
+            None,
+        );
+        scene_graph.update_world_matrices();
+        let mut camera = BasicCamera::new(
+            Transform::new(Vec3::new(0.0, 2.0, 0.0), Quat::IDENTITY, Vec3::ONE),
+            PerspectiveProjection::default(),
+        );
+        camera.attach_to(Some(rig));
+        camera.update_parent_matrix(&scene_graph);
+
+        let target = Vec3::new(0.0, 2.0, 5.0);
+        camera.focus_on_target(Transform::new(target, Quat::IDENTITY, Vec3::ONE));
+        let world_matrix = camera.world_mat4();
+        let eye = world_matrix.transform_point3(Vec3::ZERO);
+        let forward = world_matrix.transform_vector3(-Vec3::Z);
+        assert!(eye.abs_diff_eq(Vec3::new(10.0, 2.0, 0.0), 1e-5));
+        assert!(forward.abs_diff_eq((target - eye).normalize(), 1e-5));
+        // and it stays upright
+        assert!(world_matrix.transform_vector3(Vec3::X).y.abs() < 1e-5);
     }
 }
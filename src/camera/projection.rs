@@ -1,4 +1,4 @@
-use nannou::prelude::Mat4;
+use nannou::prelude::{Mat4, Vec4};
 
 use super::Handedness;
 pub trait CameraProjection {
@@ -6,6 +6,9 @@ pub trait CameraProjection {
     fn update(&mut self, width: usize, height: usize);
 }
 
+// All projections map view space depth to the [0, 1] range of wgpu's normalized device
+// coordinates; right-handed projections look down -Z, left-handed ones down +Z.
+
 #[derive(Debug, Clone)]
 pub struct PerspectiveProjection {
     pub handedness: Handedness,
@@ -46,13 +49,532 @@ impl Default for PerspectiveProjection {
 }
 
 impl PerspectiveProjection {
-    fn new(handedness: Handedness, fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+    #[allow(dead_code)]
+    pub fn new(handedness: Handedness, fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        Self {
+            handedness,
+            fov,
+            aspect_ratio,
+            near,
+            far,
+        }
+    }
+}
+
+/// The extent of an orthographic view volume.
+#[derive(Debug, Clone)]
+pub enum OrthographicScaling {
+    // the view volume is `height` world units tall, and as wide as the aspect ratio demands
+    Height(f32),
+    // fixed bounds in view space, which stay the same when the window is resized
+    #[allow(dead_code)]
+    Bounds {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct OrthographicProjection {
+    pub handedness: Handedness,
+    pub scaling: OrthographicScaling,
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl CameraProjection for OrthographicProjection {
+    fn projection_mat4(&self) -> Mat4 {
+        let (left, right, bottom, top) = self.bounds();
+        match self.handedness {
+            Handedness::Right => {
+                Mat4::orthographic_rh(left, right, bottom, top, self.near, self.far)
+            }
+            Handedness::Left => {
+                Mat4::orthographic_lh(left, right, bottom, top, self.near, self.far)
+            }
+        }
+    }
+
+    fn update(&mut self, width: usize, height: usize) {
+        self.aspect_ratio = width as f32 / height as f32;
+    }
+}
+
+impl Default for OrthographicProjection {
+    fn default() -> Self {
+        OrthographicProjection {
+            handedness: Handedness::Right,
+            scaling: OrthographicScaling::Height(2.0),
+            aspect_ratio: 1.0,
+            near: 0.0,
+            far: 1000.0,
+        }
+    }
+}
+
+impl OrthographicProjection {
+    #[allow(dead_code)]
+    pub fn new(
+        handedness: Handedness,
+        scaling: OrthographicScaling,
+        aspect_ratio: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        Self {
+            handedness,
+            scaling,
+            aspect_ratio,
+            near,
+            far,
+        }
+    }
+
+    /// The view space `(left, right, bottom, top)` bounds of the view volume.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        match self.scaling {
+            OrthographicScaling::Height(height) => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect_ratio;
+                (-half_width, half_width, -half_height, half_height)
+            }
+            OrthographicScaling::Bounds {
+                left,
+                right,
+                bottom,
+                top,
+            } => (left, right, bottom, top),
+        }
+    }
+}
+
+/// The projection of the scene camera, which is either of the projections the renderer supports.
+#[derive(Debug, Clone)]
+pub enum Projection {
+    Perspective(PerspectiveProjection),
+    Orthographic(OrthographicProjection),
+}
+
+impl CameraProjection for Projection {
+    fn projection_mat4(&self) -> Mat4 {
+        match self {
+            Projection::Perspective(projection) => projection.projection_mat4(),
+            Projection::Orthographic(projection) => projection.projection_mat4(),
+        }
+    }
+
+    fn update(&mut self, width: usize, height: usize) {
+        match self {
+            Projection::Perspective(projection) => projection.update(width, height),
+            Projection::Orthographic(projection) => projection.update(width, height),
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective(PerspectiveProjection::default())
+    }
+}
+
+impl Projection {
+    pub fn handedness(&self) -> &Handedness {
+        match self {
+            Projection::Perspective(projection) => &projection.handedness,
+            Projection::Orthographic(projection) => &projection.handedness,
+        }
+    }
+
+    pub fn near(&self) -> f32 {
+        match self {
+            Projection::Perspective(projection) => projection.near,
+            Projection::Orthographic(projection) => projection.near,
+        }
+    }
+
+    pub fn far(&self) -> f32 {
+        match self {
+            Projection::Perspective(projection) => projection.far,
+            Projection::Orthographic(projection) => projection.far,
+        }
+    }
+
+    /// The view space `(left, right, bottom, top)` bounds of the view volume at `depth` in front
+    /// of the camera.
+    pub fn bounds_at(&self, depth: f32) -> (f32, f32, f32, f32) {
+        match self {
+            Projection::Perspective(projection) => {
+                let half_height = depth * (projection.fov / 2.0).tan();
+                let half_width = half_height * projection.aspect_ratio;
+                (-half_width, half_width, -half_height, half_height)
+            }
+            Projection::Orthographic(projection) => projection.bounds(),
+        }
+    }
+}
+
+/// A perspective projection without a far plane, which maps the near plane to a depth of 1.0 and
+/// infinity to 0.0.
+///
+/// Floating point depth values are densest close to 0.0, so reversing the depth range spreads the
+/// precision evenly over the view distance, which avoids z-fighting in large scenes. Pipelines
+/// rendering with this projection need a `Greater` depth comparison, and the depth buffer must be
+/// cleared to 0.0.
+///
+/// Not supported by the renderer yet, whose depth state is `LessEqual` against a depth buffer
+/// cleared to 1.0; only picking and culling handle it.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct InfiniteReverseZProjection {
+    pub handedness: Handedness,
+    pub fov: f32,
+    pub aspect_ratio: f32,
+    pub near: f32,
+}
+
+impl CameraProjection for InfiniteReverseZProjection {
+    fn projection_mat4(&self) -> Mat4 {
+        match self.handedness {
+            Handedness::Right => {
+                Mat4::perspective_infinite_reverse_rh(self.fov, self.aspect_ratio, self.near)
+            }
+            Handedness::Left => {
+                Mat4::perspective_infinite_reverse_lh(self.fov, self.aspect_ratio, self.near)
+            }
+        }
+    }
+
+    fn update(&mut self, width: usize, height: usize) {
+        self.aspect_ratio = width as f32 / height as f32;
+    }
+}
+
+impl Default for InfiniteReverseZProjection {
+    fn default() -> Self {
+        InfiniteReverseZProjection {
+            handedness: Handedness::Right,
+            fov: std::f32::consts::PI / 4.0,
+            aspect_ratio: 1.0,
+            near: 0.1,
+        }
+    }
+}
+
+impl InfiniteReverseZProjection {
+    #[allow(dead_code)]
+    pub fn new(handedness: Handedness, fov: f32, aspect_ratio: f32, near: f32) -> Self {
+        Self {
+            handedness,
+            fov,
+            aspect_ratio,
+            near,
+        }
+    }
+}
+
+/// An asymmetric perspective frustum, as cast by a projector with lens shift.
+///
+/// `lens_shift` moves the frustum off its axis, in units of the half-width and half-height of the
+/// near plane: a shift of `(0.0, 1.0)` puts the bottom edge of the image on the optical axis. As
+/// with a real lens, resizing keeps the vertical field of view and the shift.
+///
+/// Not selectable as the scene camera's `Projection` yet: the shadow cascades only fit symmetric
+/// frusta.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct OffAxisProjection {
+    pub handedness: Handedness,
+    // vertical field of view of the unshifted frustum
+    pub fov: f32,
+    pub aspect_ratio: f32,
+    pub lens_shift: [f32; 2],
+    pub near: f32,
+    pub far: f32,
+}
+
+impl CameraProjection for OffAxisProjection {
+    fn projection_mat4(&self) -> Mat4 {
+        let (left, right, bottom, top) = self.bounds();
+        let (n, f) = (self.near, self.far);
+        let x_scale = 2.0 * n / (right - left);
+        let y_scale = 2.0 * n / (top - bottom);
+        let x_offset = (right + left) / (right - left);
+        let y_offset = (top + bottom) / (top - bottom);
+        match self.handedness {
+            Handedness::Right => Mat4::from_cols(
+                Vec4::new(x_scale, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, y_scale, 0.0, 0.0),
+                Vec4::new(x_offset, y_offset, f / (n - f), -1.0),
+                Vec4::new(0.0, 0.0, n * f / (n - f), 0.0),
+            ),
+            Handedness::Left => Mat4::from_cols(
+                Vec4::new(x_scale, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, y_scale, 0.0, 0.0),
+                Vec4::new(-x_offset, -y_offset, f / (f - n), 1.0),
+                Vec4::new(0.0, 0.0, -n * f / (f - n), 0.0),
+            ),
+        }
+    }
+
+    fn update(&mut self, width: usize, height: usize) {
+        self.aspect_ratio = width as f32 / height as f32;
+    }
+}
+
+impl Default for OffAxisProjection {
+    fn default() -> Self {
+        OffAxisProjection {
+            handedness: Handedness::Right,
+            fov: std::f32::consts::PI / 4.0,
+            aspect_ratio: 1.0,
+            lens_shift: [0.0, 0.0],
+            near: 1.0,
+            far: 1000.0,
+        }
+    }
+}
+
+impl OffAxisProjection {
+    #[allow(dead_code)]
+    pub fn new(
+        handedness: Handedness,
+        fov: f32,
+        aspect_ratio: f32,
+        lens_shift: [f32; 2],
+        near: f32,
+        far: f32,
+    ) -> Self {
         Self {
             handedness,
             fov,
             aspect_ratio,
+            lens_shift,
             near,
             far,
         }
     }
+
+    /// The frustum through the rectangle `(left, right, bottom, top)` on the near plane, e.g. as
+    /// measured when calibrating a projector.
+    #[allow(dead_code)]
+    pub fn from_bounds(
+        handedness: Handedness,
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        let half_width = (right - left) / 2.0;
+        let half_height = (top - bottom) / 2.0;
+        Self {
+            handedness,
+            fov: 2.0 * (half_height / near).atan(),
+            aspect_ratio: half_width / half_height,
+            lens_shift: [
+                (right + left) / 2.0 / half_width,
+                (top + bottom) / 2.0 / half_height,
+            ],
+            near,
+            far,
+        }
+    }
+
+    /// The `(left, right, bottom, top)` bounds of the frustum on the near plane.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        let half_height = self.near * (self.fov / 2.0).tan();
+        let half_width = half_height * self.aspect_ratio;
+        let center_x = self.lens_shift[0] * half_width;
+        let center_y = self.lens_shift[1] * half_height;
+        (
+            center_x - half_width,
+            center_x + half_width,
+            center_y - half_height,
+            center_y + half_height,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::prelude::Vec3;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_mat4_eq(a: Mat4, b: Mat4) {
+        assert!(a.abs_diff_eq(b, EPSILON), "{:?} != {:?}", a, b);
+    }
+
+    fn assert_ndc_eq(projection: Mat4, point: Vec3, expected: Vec3) {
+        let ndc = projection.project_point3(point);
+        assert!(ndc.abs_diff_eq(expected, EPSILON), "{:?} != {:?}", ndc, expected);
+    }
+
+    #[test]
+    fn perspective_matches_glam() {
+        let rh = PerspectiveProjection::new(Handedness::Right, 1.0, 1.5, 0.1, 100.0);
+        assert_mat4_eq(rh.projection_mat4(), Mat4::perspective_rh(1.0, 1.5, 0.1, 100.0));
+        let lh = PerspectiveProjection::new(Handedness::Left, 1.0, 1.5, 0.1, 100.0);
+        assert_mat4_eq(lh.projection_mat4(), Mat4::perspective_lh(1.0, 1.5, 0.1, 100.0));
+    }
+
+    #[test]
+    fn perspective_update_sets_aspect_ratio() {
+        let mut projection = PerspectiveProjection::default();
+        projection.update(1920, 1080);
+        let expected = Mat4::perspective_rh(std::f32::consts::PI / 4.0, 16.0 / 9.0, 1.0, 1000.0);
+        assert_mat4_eq(projection.projection_mat4(), expected);
+    }
+
+    #[test]
+    fn orthographic_height_matches_glam() {
+        let mut projection = OrthographicProjection::new(
+            Handedness::Right,
+            OrthographicScaling::Height(10.0),
+            1.0,
+            0.1,
+            50.0,
+        );
+        projection.update(800, 400);
+        let expected = Mat4::orthographic_rh(-10.0, 10.0, -5.0, 5.0, 0.1, 50.0);
+        assert_mat4_eq(projection.projection_mat4(), expected);
+
+        projection.handedness = Handedness::Left;
+        let expected = Mat4::orthographic_lh(-10.0, 10.0, -5.0, 5.0, 0.1, 50.0);
+        assert_mat4_eq(projection.projection_mat4(), expected);
+    }
+
+    #[test]
+    fn orthographic_bounds_ignore_resize() {
+        let mut projection = OrthographicProjection::new(
+            Handedness::Right,
+            OrthographicScaling::Bounds {
+                left: -1.0,
+                right: 3.0,
+                bottom: -2.0,
+                top: 2.0,
+            },
+            1.0,
+            0.0,
+            10.0,
+        );
+        projection.update(1920, 1080);
+        let expected = Mat4::orthographic_rh(-1.0, 3.0, -2.0, 2.0, 0.0, 10.0);
+        assert_mat4_eq(projection.projection_mat4(), expected);
+    }
+
+    #[test]
+    fn projection_bounds_follow_the_view_volume() {
+        let perspective = Projection::Perspective(PerspectiveProjection::new(
+            Handedness::Right,
+            std::f32::consts::FRAC_PI_2,
+            2.0,
+            0.1,
+            100.0,
+        ));
+        let (left, right, bottom, top) = perspective.bounds_at(3.0);
+        assert!((right - 6.0).abs() < EPSILON && (left + 6.0).abs() < EPSILON);
+        assert!((top - 3.0).abs() < EPSILON && (bottom + 3.0).abs() < EPSILON);
+
+        let orthographic = Projection::Orthographic(OrthographicProjection::new(
+            Handedness::Right,
+            OrthographicScaling::Height(4.0),
+            2.0,
+            0.1,
+            100.0,
+        ));
+        assert_eq!(orthographic.bounds_at(1.0), (-4.0, 4.0, -2.0, 2.0));
+        assert_eq!(orthographic.bounds_at(50.0), (-4.0, 4.0, -2.0, 2.0));
+    }
+
+    #[test]
+    fn infinite_reverse_z_matches_glam() {
+        let mut rh = InfiniteReverseZProjection::new(Handedness::Right, 1.0, 1.0, 0.1);
+        rh.update(300, 200);
+        let expected = Mat4::perspective_infinite_reverse_rh(1.0, 1.5, 0.1);
+        assert_mat4_eq(rh.projection_mat4(), expected);
+
+        let lh = InfiniteReverseZProjection::new(Handedness::Left, 1.0, 1.5, 0.1);
+        let expected = Mat4::perspective_infinite_reverse_lh(1.0, 1.5, 0.1);
+        assert_mat4_eq(lh.projection_mat4(), expected);
+    }
+
+    #[test]
+    fn infinite_reverse_z_maps_near_to_one() {
+        let projection = InfiniteReverseZProjection::new(Handedness::Right, 1.0, 1.0, 0.5);
+        let ndc = projection
+            .projection_mat4()
+            .project_point3(Vec3::new(0.0, 0.0, -0.5));
+        assert!((ndc.z - 1.0).abs() < EPSILON);
+        let far = projection
+            .projection_mat4()
+            .project_point3(Vec3::new(0.0, 0.0, -1.0e6));
+        assert!(far.z > 0.0 && far.z < 1.0e-5);
+    }
+
+    #[test]
+    fn unshifted_off_axis_matches_perspective() {
+        let rh = OffAxisProjection::new(Handedness::Right, 1.0, 1.5, [0.0, 0.0], 0.1, 100.0);
+        assert_mat4_eq(rh.projection_mat4(), Mat4::perspective_rh(1.0, 1.5, 0.1, 100.0));
+        let lh = OffAxisProjection::new(Handedness::Left, 1.0, 1.5, [0.0, 0.0], 0.1, 100.0);
+        assert_mat4_eq(lh.projection_mat4(), Mat4::perspective_lh(1.0, 1.5, 0.1, 100.0));
+    }
+
+    #[test]
+    fn off_axis_bounds_map_to_ndc_corners() {
+        let (left, right, bottom, top, near, far) = (-0.5, 1.5, 0.0, 1.0, 1.0, 10.0);
+        let rh = OffAxisProjection::from_bounds(
+            Handedness::Right,
+            left,
+            right,
+            bottom,
+            top,
+            near,
+            far,
+        );
+        let m = rh.projection_mat4();
+        assert_ndc_eq(m, Vec3::new(left, bottom, -near), Vec3::new(-1.0, -1.0, 0.0));
+        assert_ndc_eq(m, Vec3::new(right, top, -near), Vec3::new(1.0, 1.0, 0.0));
+        // the far plane is the near rectangle scaled by far / near
+        let scale = far / near;
+        assert_ndc_eq(
+            m,
+            Vec3::new(right * scale, bottom * scale, -far),
+            Vec3::new(1.0, -1.0, 1.0),
+        );
+
+        let lh = OffAxisProjection::from_bounds(
+            Handedness::Left,
+            left,
+            right,
+            bottom,
+            top,
+            near,
+            far,
+        );
+        let m = lh.projection_mat4();
+        assert_ndc_eq(m, Vec3::new(left, top, near), Vec3::new(-1.0, 1.0, 0.0));
+        assert_ndc_eq(
+            m,
+            Vec3::new(right * scale, bottom * scale, far),
+            Vec3::new(1.0, -1.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn off_axis_update_keeps_fov_and_shift() {
+        let mut projection =
+            OffAxisProjection::new(Handedness::Right, 1.0, 1.0, [0.0, 1.0], 1.0, 100.0);
+        projection.update(400, 200);
+        let (left, right, bottom, top) = projection.bounds();
+        let half_height = (0.5f32).tan();
+        assert!((bottom - 0.0).abs() < EPSILON);
+        assert!((top - 2.0 * half_height).abs() < EPSILON);
+        assert!((right - left - 4.0 * half_height).abs() < EPSILON);
+    }
 }
@@ -0,0 +1,216 @@
+use crate::bounds::{Aabb, BoundingSphere, Bounds};
+use crate::uniforms::instance_input::model_matrix::ModelMatrixInstance;
+use crate::BasicEntity;
+use nannou::glam::{Mat4, Vec3, Vec4};
+
+/// The six planes of a view frustum, pointing inwards.
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    // xyz is the unit normal, w the distance along it; a point p is inside when
+    // dot(xyz, p) + w >= 0 for every plane
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extract the planes of a wgpu view-projection, with clip-space depth in [0, 1].
+    ///
+    /// Works for reverse-Z and infinite projections too: the far plane of an infinite projection
+    /// is degenerate and culls nothing.
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let (r0, r1, r2, r3) = (
+            view_projection.row(0),
+            view_projection.row(1),
+            view_projection.row(2),
+            view_projection.row(3),
+        );
+        let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2];
+        for plane in planes.iter_mut() {
+            let length = plane.truncate().length();
+            *plane = if length > f32::EPSILON {
+                *plane / length
+            } else {
+                // contains every point
+                Vec4::new(0.0, 0.0, 0.0, 1.0)
+            };
+        }
+        Self { planes }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let normal = plane.truncate();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+
+    /// Conservative: may keep some bounds that are just outside a corner of the frustum.
+    pub fn intersects(&self, bounds: &Bounds) -> bool {
+        self.intersects_sphere(&bounds.sphere) && self.intersects_aabb(&bounds.aabb)
+    }
+}
+
+/// How much of the scene survived culling in one frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CullingStats {
+    pub entities_tested: u32,
+    pub entities_visible: u32,
+    pub instances_tested: u32,
+    pub instances_visible: u32,
+}
+
+/// Collect the instances of `entity` that intersect `frustum` into `visible`, given the world
+/// matrix of the entity, and record the outcome in `stats`.
+pub fn cull_entity(
+    frustum: &Frustum,
+    entity: &BasicEntity,
+    world_matrix: &Mat4,
+    stats: &mut CullingStats,
+    visible: &mut Vec<ModelMatrixInstance>,
+) {
+    visible.clear();
+    stats.entities_tested += 1;
+    stats.instances_tested += entity.instances.len() as u32;
+
+    // the bounds are those of the bind or rest pose, which need not contain the deformed mesh
+    if entity.is_deformed() {
+        visible.extend_from_slice(&entity.instances);
+        stats.entities_visible += 1;
+        stats.instances_visible += visible.len() as u32;
+        return;
+    }
+
+    let instance_bounds: Vec<Bounds> = entity
+        .instances
+        .iter()
+        .map(|instance| {
+            entity
+                .bounds
+                .transformed(&(*world_matrix * instance.model_matrix()))
+        })
+        .collect();
+    let entity_bounds = match instance_bounds.split_first() {
+        Some((first, rest)) => rest.iter().fold(*first, |bounds, b| bounds.union(b)),
+        None => return,
+    };
+    if !frustum.intersects(&entity_bounds) {
+        return;
+    }
+
+    if instance_bounds.len() == 1 {
+        visible.extend_from_slice(&entity.instances);
+    } else {
+        visible.extend(
+            entity
+                .instances
+                .iter()
+                .zip(instance_bounds.iter())
+                .filter(|(_, bounds)| frustum.intersects(bounds))
+                .map(|(instance, _)| *instance),
+        );
+    }
+    if !visible.is_empty() {
+        stats.entities_visible += 1;
+        stats.instances_visible += visible.len() as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_has_plane(frustum: &Frustum, expected: Vec4) {
+        assert!(
+            frustum
+                .planes
+                .iter()
+                .any(|plane| plane.abs_diff_eq(expected, 1e-5)),
+            "{:?} not in {:?}",
+            expected,
+            frustum.planes
+        );
+    }
+
+    fn sphere(x: f32, y: f32, z: f32, radius: f32) -> BoundingSphere {
+        BoundingSphere::new(Vec3::new(x, y, z), radius)
+    }
+
+    fn cube(x: f32, y: f32, z: f32, half_size: f32) -> Aabb {
+        let center = Vec3::new(x, y, z);
+        Aabb::new(
+            center - Vec3::splat(half_size),
+            center + Vec3::splat(half_size),
+        )
+    }
+
+    #[test]
+    fn perspective_planes_are_extracted() {
+        let frustum = Frustum::from_view_projection(Mat4::perspective_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            1.0,
+            10.0,
+        ));
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        assert_has_plane(&frustum, Vec4::new(diagonal, 0.0, -diagonal, 0.0));
+        assert_has_plane(&frustum, Vec4::new(-diagonal, 0.0, -diagonal, 0.0));
+        assert_has_plane(&frustum, Vec4::new(0.0, diagonal, -diagonal, 0.0));
+        assert_has_plane(&frustum, Vec4::new(0.0, -diagonal, -diagonal, 0.0));
+        // near at z = -1, far at z = -10
+        assert_has_plane(&frustum, Vec4::new(0.0, 0.0, -1.0, -1.0));
+        assert_has_plane(&frustum, Vec4::new(0.0, 0.0, 1.0, 10.0));
+    }
+
+    #[test]
+    fn infinite_reverse_z_far_plane_culls_nothing() {
+        let frustum = Frustum::from_view_projection(Mat4::perspective_infinite_reverse_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            1.0,
+        ));
+        assert_has_plane(&frustum, Vec4::new(0.0, 0.0, -1.0, -1.0));
+        assert_has_plane(&frustum, Vec4::new(0.0, 0.0, 0.0, 1.0));
+
+        assert!(frustum.intersects_sphere(&sphere(0.0, 0.0, -1.0e6, 1.0)));
+        assert!(frustum.intersects_aabb(&cube(0.0, 0.0, -1.0e6, 1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 0.0, -0.5, 0.1)));
+        assert!(!frustum.intersects_aabb(&cube(0.0, 0.0, -0.5, 0.1)));
+    }
+
+    #[test]
+    fn spheres_and_boxes_are_classified() {
+        let frustum = Frustum::from_view_projection(Mat4::perspective_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            1.0,
+            10.0,
+        ));
+        let cases = [
+            // inside
+            ((0.0, 0.0, -5.0), true),
+            // behind the camera, past the far plane and beside the frustum
+            ((0.0, 0.0, 5.0), false),
+            ((0.0, 0.0, -12.0), false),
+            ((20.0, 0.0, -5.0), false),
+            // straddling the far and the left plane
+            ((0.0, 0.0, -10.5), true),
+            ((-5.5, 0.0, -5.0), true),
+        ];
+        for &((x, y, z), expected) in cases.iter() {
+            assert_eq!(frustum.intersects_sphere(&sphere(x, y, z, 1.0)), expected);
+            assert_eq!(frustum.intersects_aabb(&cube(x, y, z, 1.0)), expected);
+            let bounds = Bounds {
+                aabb: cube(x, y, z, 1.0),
+                sphere: sphere(x, y, z, 1.0),
+            };
+            assert_eq!(frustum.intersects(&bounds), expected, "{:?}", (x, y, z));
+        }
+    }
+}
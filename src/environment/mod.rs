@@ -0,0 +1,576 @@
+use crate::reflection::{self, LayoutEntries, PipelineInterface};
+use crate::shader::{ShaderError, ShaderLibrary};
+use crate::uniforms::arena::UniformArena;
+use crate::uniforms::environment::EnvironmentBakeUniform;
+use nannou::glam::Vec3;
+use nannou::wgpu;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+
+// Image-based lighting, by the split-sum approximation of Karis 2013, "Real Shading in Unreal
+// Engine 4": https://cdn2.unrealengine.com/Resources/files/2013SiggraphPresentationsNotes-26915738.pdf
+
+/// An image of linear radiance, e.g. an equirectangular panorama of an environment.
+#[derive(Debug, Clone)]
+pub struct HdrImage {
+    width: u32,
+    height: u32,
+    // row by row, from the top
+    pixels: Vec<Vec3>,
+}
+
+impl HdrImage {
+    pub fn new(width: u32, height: u32, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// An equirectangular panorama of radiance `nadir` straight down, `zenith` straight up and a
+    /// linear blend of the two by height in between.
+    pub fn gradient(width: u32, height: u32, nadir: Vec3, zenith: Vec3) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| {
+                let latitude = (y as f32 + 0.5) / height as f32 * std::f32::consts::PI;
+                let color = nadir.lerp(zenith, latitude.cos() * 0.5 + 0.5);
+                (0..width).map(move |_| color)
+            })
+            .collect();
+        Self::new(width, height, pixels)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[cfg(test)]
+    pub fn pixel(&self, x: u32, y: u32) -> Vec3 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // as the texels of an `Rgba16Float` texture
+    fn to_rgba16f_bytes(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| [pixel.x, pixel.y, pixel.z, 1.0])
+            .flat_map(|channel| f16_bits(channel).to_le_bytes())
+            .collect()
+    }
+}
+
+/// The sizes and sample counts of the maps `EnvironmentBaker` bakes.
+#[derive(Debug, Clone)]
+pub struct EnvironmentSettings {
+    // texels along an edge of the top mip level of the environment cube map
+    pub resolution: u32,
+    pub irradiance_resolution: u32,
+    pub irradiance_sample_count: u32,
+    // of the top mip level, which reflects like a mirror
+    pub prefiltered_resolution: u32,
+    // each level is filtered for a roughness `1 / (prefiltered_mip_count - 1)` higher than the
+    // one above it
+    pub prefiltered_mip_count: u32,
+    pub prefiltered_sample_count: u32,
+    pub brdf_lut_resolution: u32,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 512,
+            irradiance_resolution: 32,
+            irradiance_sample_count: 1024,
+            prefiltered_resolution: 256,
+            prefiltered_mip_count: 6,
+            prefiltered_sample_count: 512,
+            brdf_lut_resolution: 256,
+        }
+    }
+}
+
+/// The maps an environment lights the scene with, bound at `[[group(2)]]` by `LightResources`.
+pub struct EnvironmentMaps {
+    // the equirectangular panorama the maps were baked from
+    panorama_view: wgpu::TextureView,
+    // the environment itself, with every mip level
+    cube_view: wgpu::TextureView,
+    // the cosine-weighted average of the environment around each direction
+    irradiance_view: wgpu::TextureView,
+    // the environment reflected by ever rougher surfaces, down the mip levels
+    prefiltered_view: wgpu::TextureView,
+    prefiltered_mip_count: u32,
+    // the scale and bias to F0 of specular reflections, by n.v and roughness
+    brdf_lut_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    _textures: Vec<wgpu::Texture>,
+}
+
+impl EnvironmentMaps {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    pub const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+    pub fn panorama_view(&self) -> &wgpu::TextureView {
+        &self.panorama_view
+    }
+
+    pub fn cube_view(&self) -> &wgpu::TextureView {
+        &self.cube_view
+    }
+
+    pub fn irradiance_view(&self) -> &wgpu::TextureView {
+        &self.irradiance_view
+    }
+
+    pub fn prefiltered_view(&self) -> &wgpu::TextureView {
+        &self.prefiltered_view
+    }
+
+    /// The mip level of the prefiltered map for a roughness of 1.0.
+    pub fn max_prefiltered_lod(&self) -> f32 {
+        (self.prefiltered_mip_count - 1) as f32
+    }
+
+    pub fn brdf_lut_view(&self) -> &wgpu::TextureView {
+        &self.brdf_lut_view
+    }
+
+    /// Samples every map, trilinearly.
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+}
+
+/// The pipelines baking `EnvironmentMaps` from an equirectangular panorama on the GPU.
+pub struct EnvironmentBaker {
+    panorama_bind_group_layout: wgpu::BindGroupLayout,
+    cube_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    cube_from_equirect: wgpu::RenderPipeline,
+    downsample: wgpu::RenderPipeline,
+    irradiance: wgpu::RenderPipeline,
+    prefilter: wgpu::RenderPipeline,
+    brdf_lut: wgpu::RenderPipeline,
+}
+
+// a face of a mip level of a cube map, drawn by a bake pipeline
+struct FacePass<'a> {
+    pipeline: &'a wgpu::RenderPipeline,
+    // index into the bind groups of the bake, or `None` for the BRDF lookup table
+    source: Option<usize>,
+    target: wgpu::TextureViewHandle,
+    // of the pass's `EnvironmentBakeUniform`
+    offset: wgpu::DynamicOffset,
+}
+
+impl EnvironmentBaker {
+    pub fn new(device: &wgpu::Device, shaders: &ShaderLibrary) -> Result<Self, ShaderError> {
+        let panorama_entries = bake_layout_entries(wgpu::TextureViewDimension::D2);
+        let cube_entries = bake_layout_entries(wgpu::TextureViewDimension::Cube);
+        let panorama_bind_group_layout = panorama_entries.build(device);
+        let cube_bind_group_layout = cube_entries.build(device);
+        let panorama_layout = (&panorama_entries, &panorama_bind_group_layout);
+        let cube_layout = (&cube_entries, &cube_bind_group_layout);
+
+        // panoramas wrap around horizontally
+        let sampler = wgpu::SamplerBuilder::new()
+            .address_mode_u(wgpu::AddressMode::Repeat)
+            .mipmap_filter(wgpu::FilterMode::Linear)
+            .build(device);
+
+        let build =
+            |name, layout, format| build_bake_pipeline(device, shaders, name, layout, format);
+        Ok(Self {
+            cube_from_equirect: build(
+                "ibl_cube_from_equirect.wgsl",
+                Some(panorama_layout),
+                EnvironmentMaps::FORMAT,
+            )?,
+            downsample: build(
+                "ibl_downsample.wgsl",
+                Some(cube_layout),
+                EnvironmentMaps::FORMAT,
+            )?,
+            irradiance: build(
+                "ibl_irradiance.wgsl",
+                Some(cube_layout),
+                EnvironmentMaps::FORMAT,
+            )?,
+            prefilter: build(
+                "ibl_prefilter.wgsl",
+                Some(cube_layout),
+                EnvironmentMaps::FORMAT,
+            )?,
+            brdf_lut: build("ibl_brdf_lut.wgsl", None, EnvironmentMaps::BRDF_LUT_FORMAT)?,
+            panorama_bind_group_layout,
+            cube_bind_group_layout,
+            sampler,
+        })
+    }
+
+    /// Bake the maps lighting the scene with the equirectangular panorama `panorama`, submitting
+    /// the passes to `queue`.
+    pub fn bake(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        panorama: &HdrImage,
+        settings: &EnvironmentSettings,
+    ) -> EnvironmentMaps {
+        let panorama_texture = wgpu::TextureBuilder::new()
+            .size([panorama.width(), panorama.height()])
+            .format(EnvironmentMaps::FORMAT)
+            .usage(wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST)
+            .build(device);
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &panorama_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &panorama.to_rgba16f_bytes(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(8 * panorama.width()),
+                rows_per_image: NonZeroU32::new(panorama.height()),
+            },
+            panorama_texture.extent(),
+        );
+
+        let mip_count = 32 - settings.resolution.leading_zeros();
+        let cube = cube_texture(device, settings.resolution, mip_count);
+        let irradiance = cube_texture(device, settings.irradiance_resolution, 1);
+        let prefiltered_mip_count = settings.prefiltered_mip_count.max(2);
+        let prefiltered = cube_texture(
+            device,
+            settings.prefiltered_resolution,
+            prefiltered_mip_count,
+        );
+        let brdf_lut = wgpu::TextureBuilder::new()
+            .size([settings.brdf_lut_resolution, settings.brdf_lut_resolution])
+            .format(EnvironmentMaps::BRDF_LUT_FORMAT)
+            .usage(wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+            .build(device);
+        let cube_view = cube
+            .view()
+            .dimension(wgpu::TextureViewDimension::Cube)
+            .build();
+
+        // the views the passes sample: the panorama, each mip level of the cube for the level
+        // below it, then the whole cube
+        let mut sources: Vec<(&wgpu::BindGroupLayout, wgpu::TextureViewHandle)> = vec![(
+            &self.panorama_bind_group_layout,
+            panorama_texture.create_view(&panorama_texture.view().into_descriptor()),
+        )];
+        for mip in 0..mip_count - 1 {
+            sources.push((&self.cube_bind_group_layout, cube_mip_view(&cube, mip)));
+        }
+        let whole_cube = sources.len();
+        sources.push((
+            &self.cube_bind_group_layout,
+            cube.create_view(&cube_view.descriptor()),
+        ));
+
+        let mut arena = UniformArena::new(
+            device,
+            (6 * (mip_count + 1 + prefiltered_mip_count) + 1) as usize,
+        );
+        let mut passes = Vec::new();
+        for face in 0..6 {
+            let uniform = EnvironmentBakeUniform::new(face, 0.0, settings.resolution, 1);
+            passes.push(FacePass {
+                pipeline: &self.cube_from_equirect,
+                source: Some(0),
+                target: face_view(&cube, face, 0),
+                offset: arena.push(&uniform),
+            });
+        }
+        for mip in 1..mip_count {
+            for face in 0..6 {
+                let uniform = EnvironmentBakeUniform::new(face, 0.0, settings.resolution, 1);
+                passes.push(FacePass {
+                    pipeline: &self.downsample,
+                    source: Some(mip as usize),
+                    target: face_view(&cube, face, mip),
+                    offset: arena.push(&uniform),
+                });
+            }
+        }
+        for face in 0..6 {
+            let uniform = EnvironmentBakeUniform::new(
+                face,
+                0.0,
+                settings.resolution,
+                settings.irradiance_sample_count,
+            );
+            passes.push(FacePass {
+                pipeline: &self.irradiance,
+                source: Some(whole_cube),
+                target: face_view(&irradiance, face, 0),
+                offset: arena.push(&uniform),
+            });
+        }
+        for mip in 0..prefiltered_mip_count {
+            let roughness = mip as f32 / (prefiltered_mip_count - 1) as f32;
+            for face in 0..6 {
+                let uniform = EnvironmentBakeUniform::new(
+                    face,
+                    roughness,
+                    settings.resolution,
+                    settings.prefiltered_sample_count,
+                );
+                passes.push(FacePass {
+                    pipeline: &self.prefilter,
+                    source: Some(whole_cube),
+                    target: face_view(&prefiltered, face, mip),
+                    offset: arena.push(&uniform),
+                });
+            }
+        }
+        passes.push(FacePass {
+            pipeline: &self.brdf_lut,
+            source: None,
+            target: brdf_lut.create_view(&brdf_lut.view().into_descriptor()),
+            offset: 0,
+        });
+        arena.flush(device, queue);
+
+        let bind_groups: Vec<wgpu::BindGroup> = sources
+            .iter()
+            .map(|(layout, view)| {
+                wgpu::BindGroupBuilder::new()
+                    .buffer_bytes(
+                        arena.buffer(),
+                        0,
+                        UniformArena::binding_size::<EnvironmentBakeUniform>(),
+                    )
+                    .sampler(&self.sampler)
+                    .texture_view(view)
+                    .build(device, layout)
+            })
+            .collect();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("environment_bake"),
+        });
+        for pass in &passes {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("environment_bake"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &pass.target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pass.pipeline);
+            if let Some(source) = pass.source {
+                render_pass.set_bind_group(0, &bind_groups[source], &[pass.offset]);
+            }
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        EnvironmentMaps {
+            panorama_view: panorama_texture.view().build(),
+            cube_view,
+            irradiance_view: irradiance
+                .view()
+                .dimension(wgpu::TextureViewDimension::Cube)
+                .build(),
+            prefiltered_view: prefiltered
+                .view()
+                .dimension(wgpu::TextureViewDimension::Cube)
+                .build(),
+            prefiltered_mip_count,
+            brdf_lut_view: brdf_lut.view().build(),
+            sampler: wgpu::SamplerBuilder::new()
+                .mipmap_filter(wgpu::FilterMode::Linear)
+                .build(device),
+            _textures: vec![panorama_texture, cube, irradiance, prefiltered, brdf_lut],
+        }
+    }
+}
+
+// `[[group(0)]]` of the bake pipelines sampling a texture of `view_dimension`
+fn bake_layout_entries(view_dimension: wgpu::TextureViewDimension) -> LayoutEntries {
+    LayoutEntries::new()
+        .uniform_buffer::<EnvironmentBakeUniform>(wgpu::ShaderStage::FRAGMENT, true)
+        .sampler(wgpu::ShaderStage::FRAGMENT, true)
+        .texture(
+            wgpu::ShaderStage::FRAGMENT,
+            false,
+            view_dimension,
+            wgpu::TextureSampleType::Float { filterable: true },
+        )
+}
+
+// the bake pipelines draw a single triangle covering the target, and only the BRDF lookup table
+// samples nothing
+fn build_bake_pipeline(
+    device: &wgpu::Device,
+    shaders: &ShaderLibrary,
+    name: &str,
+    layout: Option<(&LayoutEntries, &wgpu::BindGroupLayout)>,
+    format: wgpu::TextureFormat,
+) -> Result<wgpu::RenderPipeline, ShaderError> {
+    let shader = shaders.process(name, &BTreeMap::new())?;
+    let module = shader.parse()?;
+    let entries: Vec<LayoutEntries> = layout
+        .iter()
+        .map(|(entries, _)| (*entries).clone())
+        .collect();
+    reflection::validate_module(
+        name,
+        &module,
+        &PipelineInterface {
+            vertex_buffers: &[],
+            bind_groups: &entries,
+        },
+    )?;
+
+    let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        source: wgpu::ShaderSource::Wgsl(Cow::Owned(shader.source().to_string())),
+        flags: wgpu::ShaderFlags::default(),
+        label: Some(name),
+    });
+    let bind_group_layouts: Vec<&wgpu::BindGroupLayout> =
+        layout.iter().map(|(_, layout)| *layout).collect();
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    Ok(
+        wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &shader_module)
+            .fragment_shader(&shader_module)
+            .color_format(format)
+            .color_blend(wgpu::BlendComponent::REPLACE)
+            .alpha_blend(wgpu::BlendComponent::REPLACE)
+            .build(device),
+    )
+}
+
+// six layers of `resolution` squared texels, to be viewed as a cube
+fn cube_texture(device: &wgpu::Device, resolution: u32, mip_count: u32) -> wgpu::Texture {
+    wgpu::TextureBuilder::new()
+        .size([resolution, resolution])
+        .depth(6)
+        .dimension(wgpu::TextureDimension::D2)
+        .mip_level_count(mip_count)
+        .format(EnvironmentMaps::FORMAT)
+        .usage(wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+        .build(device)
+}
+
+// mip level `mip` of face `face` of `cube`, as a render target
+fn face_view(cube: &wgpu::Texture, face: u32, mip: u32) -> wgpu::TextureViewHandle {
+    let mut descriptor = cube.view().layer(face).into_descriptor();
+    descriptor.base_mip_level = mip;
+    descriptor.mip_level_count = NonZeroU32::new(1);
+    cube.create_view(&descriptor)
+}
+
+// mip level `mip` of every face of `cube` alone, as sampled by the level below it
+fn cube_mip_view(cube: &wgpu::Texture, mip: u32) -> wgpu::TextureViewHandle {
+    let mut descriptor = cube
+        .view()
+        .dimension(wgpu::TextureViewDimension::Cube)
+        .into_descriptor();
+    descriptor.base_mip_level = mip;
+    descriptor.mip_level_count = NonZeroU32::new(1);
+    cube.create_view(&descriptor)
+}
+
+// the nearest IEEE 754 half-precision float to `value`, with ties to even, flushing what's too
+// small for the normal range to zero and what's too large to infinity
+fn f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    if value.is_nan() {
+        return sign | 0x7e00;
+    }
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    // the 10 bits of the mantissa kept, rounded to nearest and halfway cases to an even mantissa
+    let truncated = (bits & 0x007f_ffff) >> 13;
+    let remainder = bits & 0x1fff;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && truncated & 1 == 1);
+    let mantissa = truncated + round_up as u32;
+    if exponent <= 0 {
+        sign
+    } else if exponent >= 0x1f || (exponent == 0x1e && mantissa > 0x3ff) {
+        sign | 0x7c00
+    } else {
+        // rounding up may carry into the exponent, which is still right
+        sign | (((exponent as u32) << 10) + mantissa) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bake_shaders_match_their_pipelines() {
+        let library = ShaderLibrary::builtin();
+        let panorama = bake_layout_entries(wgpu::TextureViewDimension::D2);
+        let cube = bake_layout_entries(wgpu::TextureViewDimension::Cube);
+        let shaders = [
+            ("ibl_cube_from_equirect.wgsl", vec![panorama]),
+            ("ibl_downsample.wgsl", vec![cube.clone()]),
+            ("ibl_irradiance.wgsl", vec![cube.clone()]),
+            ("ibl_prefilter.wgsl", vec![cube]),
+            ("ibl_brdf_lut.wgsl", Vec::new()),
+        ];
+        for (name, bind_groups) in shaders.iter() {
+            let shader = library
+                .process(name, &BTreeMap::new())
+                .unwrap_or_else(|err| panic!("{}", err));
+            let module = shader.parse().unwrap_or_else(|err| panic!("{}", err));
+            reflection::validate_module(
+                name,
+                &module,
+                &PipelineInterface {
+                    vertex_buffers: &[],
+                    bind_groups,
+                },
+            )
+            .unwrap_or_else(|err| panic!("{}", err));
+        }
+    }
+
+    #[test]
+    fn converts_to_half_precision() {
+        assert_eq!(f16_bits(0.0), 0x0000);
+        assert_eq!(f16_bits(-0.0), 0x8000);
+        assert_eq!(f16_bits(1.0), 0x3c00);
+        assert_eq!(f16_bits(-2.0), 0xc000);
+        assert_eq!(f16_bits(0.1), 0x2e66);
+        assert_eq!(f16_bits(65504.0), 0x7bff);
+        assert_eq!(f16_bits(1.0e6), 0x7c00);
+        assert_eq!(f16_bits(1.0e-8), 0x0000);
+        // halfway cases round to the even mantissa: 2049 down to 2048, 2051 up to 2052
+        assert_eq!(f16_bits(2049.0), 0x6800);
+        assert_eq!(f16_bits(2051.0), 0x6802);
+        assert_eq!(f16_bits(2049.5), 0x6801);
+        // 65520 lies halfway between the largest half and the next power of two
+        assert_eq!(f16_bits(65520.0), 0x7c00);
+    }
+
+    #[test]
+    fn gradients_blend_from_nadir_to_zenith() {
+        let image = HdrImage::gradient(4, 2, Vec3::ZERO, Vec3::ONE);
+        assert_eq!(image.pixel(0, 0), image.pixel(3, 0));
+        assert!(image.pixel(0, 0).x > 0.5 && image.pixel(0, 1).x < 0.5);
+        assert!((image.pixel(0, 0).x + image.pixel(0, 1).x - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::render_graph::{RenderGraph, TextureDesc, TextureHandle};
+use nannou::wgpu;
+
+/// The render targets of the forward pass, sized to the window and rebuilt whenever it is
+/// resized.
+///
+/// The forward pass draws HDR colors into a multisampled color target with a matching depth
+/// target, and resolves them into a single-sampled target that `PostProcessResources` reads,
+/// along with the depth target, on the way to `TonemapResources`. The window itself should be
+/// created with a single sample, as multisampling happens here.
+pub struct FrameResources {
+    size: [u32; 2],
+    sample_count: u32,
+    targets: Targets,
+}
+
+/// The targets of `FrameResources` within a `RenderGraph`.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTargets {
+    // multisampled if `resolve` is `Some`
+    pub color: TextureHandle,
+    pub resolve: Option<TextureHandle>,
+    pub depth: TextureHandle,
+}
+
+impl FrameTargets {
+    /// The single-sampled target holding the HDR frame once the forward pass has resolved.
+    pub fn output(&self) -> TextureHandle {
+        self.resolve.unwrap_or(self.color)
+    }
+}
+
+impl FrameResources {
+    pub const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    pub fn new(device: &wgpu::Device, size: [u32; 2], sample_count: u32) -> Self {
+        Self {
+            size,
+            sample_count,
+            targets: Targets::new(device, size, sample_count),
+        }
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Rebuild the targets at `size`, in pixels, unless they already have that size. Nothing is
+    /// rebuilt while the window is minimized to zero pixels.
+    pub fn resize(&mut self, device: &wgpu::Device, size: [u32; 2]) {
+        if size == self.size || size[0] == 0 || size[1] == 0 {
+            return;
+        }
+        self.targets = Targets::new(device, size, self.sample_count);
+        self.size = size;
+    }
+
+    /// Import the targets into `graph`.
+    pub fn add_to_graph<'a>(&'a self, graph: &mut RenderGraph<'a>) -> FrameTargets {
+        let Targets {
+            depth,
+            depth_view,
+            color,
+            resolve,
+            resolve_view,
+        } = &self.targets;
+        let depth = graph.import_texture("depth", depth_view, TextureDesc::of(depth));
+        let resolve = graph.import_texture("resolve", resolve_view, TextureDesc::of(resolve));
+        match color {
+            Some((texture, view)) => FrameTargets {
+                color: graph.import_texture("color", view, TextureDesc::of(texture)),
+                resolve: Some(resolve),
+                depth,
+            },
+            None => FrameTargets {
+                color: resolve,
+                resolve: None,
+                depth,
+            },
+        }
+    }
+}
+
+// everything sized by `size` and `sample_count`
+struct Targets {
+    depth: wgpu::Texture,
+    depth_view: wgpu::TextureView,
+    // `None` with a single sample, when the forward pass draws straight into `resolve`
+    color: Option<(wgpu::Texture, wgpu::TextureView)>,
+    resolve: wgpu::Texture,
+    resolve_view: wgpu::TextureView,
+}
+
+impl Targets {
+    fn new(device: &wgpu::Device, size: [u32; 2], sample_count: u32) -> Self {
+        let depth = wgpu::TextureBuilder::new()
+            .size(size)
+            .format(FrameResources::DEPTH_FORMAT)
+            // read by the post-process effects
+            .usage(wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+            .sample_count(sample_count)
+            .build(device);
+        let depth_view = depth.view().build();
+        let color = if sample_count > 1 {
+            let color = wgpu::TextureBuilder::new()
+                .size(size)
+                .format(FrameResources::COLOR_FORMAT)
+                .usage(wgpu::TextureUsage::RENDER_ATTACHMENT)
+                .sample_count(sample_count)
+                .build(device);
+            let color_view = color.view().build();
+            Some((color, color_view))
+        } else {
+            None
+        };
+        let resolve = wgpu::TextureBuilder::new()
+            .size(size)
+            .format(FrameResources::COLOR_FORMAT)
+            .usage(wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+            .build(device);
+        let resolve_view = resolve.view().build();
+        Self {
+            depth,
+            depth_view,
+            color,
+            resolve,
+            resolve_view,
+        }
+    }
+}
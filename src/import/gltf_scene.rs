@@ -0,0 +1,939 @@
+use crate::animation::{AnimationClip, Channel, Interpolation, Property};
+use crate::bvh::TriangleBvh;
+use crate::camera::projection::{
+    OrthographicProjection, OrthographicScaling, PerspectiveProjection, Projection,
+};
+use crate::material::Material;
+use crate::morph::{MorphDelta, MorphTargets, MorphWeights};
+use crate::scene_graph::{NodeId, SceneGraph};
+use crate::skin::Skin;
+use crate::transform::{Handedness, Transform};
+use crate::uniforms::instance_input::model_matrix::ModelMatrixInstance;
+use crate::uniforms::model::ModelUniform;
+use crate::uniforms::vertex_input::gltf_mesh_vertex::GltfMeshVertex;
+use crate::BasicEntity;
+use gltf::animation::util::ReadOutputs;
+use gltf::mesh::Mode;
+use nannou::glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use nannou::image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#meshes
+
+/// The entities and cameras reachable from the default scene of a glTF file. The node hierarchy
+/// itself is added to the `SceneGraph` passed to `import`.
+pub struct GltfScene {
+    pub entities: Vec<BasicEntity>,
+    pub cameras: Vec<GltfCamera>,
+    // `BasicEntity::material` indexes into this; index 0 is the glTF default material
+    pub materials: Vec<Material>,
+    // `BasicEntity::skin` indexes into this
+    pub skins: Vec<Skin>,
+    // the default weights of every node with a morphed mesh
+    pub morph_weights: MorphWeights,
+    // every animation of the file, including those animating nodes outside of the scene
+    pub animations: Vec<AnimationClip>,
+    // every primitive imported so far, by glTF mesh and primitive index, so that nodes
+    // instancing the same mesh share its geometry rather than import it again
+    primitive_meshes: HashMap<(usize, usize), PrimitiveMesh>,
+}
+
+// the geometry of an imported primitive, as shared by the entities of every node instancing it
+#[derive(Clone)]
+struct PrimitiveMesh {
+    // `BasicEntity::mesh`
+    mesh: usize,
+    vertices: Arc<Vec<GltfMeshVertex>>,
+    indices: Arc<Vec<u32>>,
+    morph_targets: Option<MorphTargets>,
+    triangles: Arc<TriangleBvh>,
+}
+
+pub struct GltfCamera {
+    // attach a `BasicCamera` with an identity transform to this node to look through it
+    pub node: NodeId,
+    pub projection: Projection,
+}
+
+/// Errors that might occur while importing a glTF file.
+#[derive(Debug, Error)]
+pub enum GltfImportError {
+    #[error("failed to load glTF file {path:?}: {err}")]
+    Gltf {
+        path: PathBuf,
+        #[source]
+        err: gltf::Error,
+    },
+    #[error("glTF file {path:?} contains no scenes")]
+    NoScene { path: PathBuf },
+    #[error("glTF file {path:?}: primitive {primitive} of mesh {mesh} has no POSITION attribute")]
+    MissingPositions {
+        path: PathBuf,
+        mesh: usize,
+        primitive: usize,
+    },
+    #[error(
+        "glTF file {path:?}: primitive {primitive} of mesh {mesh} uses unsupported mode {mode:?}"
+    )]
+    UnsupportedMode {
+        path: PathBuf,
+        mesh: usize,
+        primitive: usize,
+        mode: Mode,
+    },
+    #[error("glTF file {path:?}: joint node {node} of skin {skin} is not part of the scene")]
+    MissingJoint {
+        path: PathBuf,
+        skin: usize,
+        node: usize,
+    },
+}
+
+/// Import the default scene (or the first scene, if no default is set) of a `.gltf` or `.glb`
+/// file. Both embedded and external buffers are resolved relative to `path`.
+pub fn import<P: AsRef<Path>>(
+    path: P,
+    scene_graph: &mut SceneGraph,
+) -> Result<GltfScene, GltfImportError> {
+    let path = path.as_ref();
+    let (document, buffers, images) = gltf::import(path).map_err(|err| GltfImportError::Gltf {
+        path: path.to_path_buf(),
+        err,
+    })?;
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| GltfImportError::NoScene {
+            path: path.to_path_buf(),
+        })?;
+
+    let images: Vec<Arc<RgbaImage>> = images
+        .iter()
+        .map(|image| Arc::new(to_rgba_image(image)))
+        .collect();
+    let mut materials = vec![Material::default()];
+    materials.extend(
+        document
+            .materials()
+            .map(|material| import_material(&material, &images)),
+    );
+
+    let mut gltf_scene = GltfScene {
+        entities: Vec::new(),
+        cameras: Vec::new(),
+        materials,
+        skins: Vec::new(),
+        morph_weights: MorphWeights::new(),
+        animations: Vec::new(),
+        primitive_meshes: HashMap::new(),
+    };
+    // the scene graph node of each glTF node in the scene, by glTF node index
+    let mut node_ids = vec![None; document.nodes().count()];
+    for node in scene.nodes() {
+        import_node(
+            path,
+            &buffers,
+            &node,
+            None,
+            scene_graph,
+            &mut node_ids,
+            &mut gltf_scene,
+        )?;
+    }
+    for skin in document.skins() {
+        gltf_scene
+            .skins
+            .push(import_skin(path, &buffers, &skin, &node_ids)?);
+    }
+    gltf_scene.animations = document
+        .animations()
+        .map(|animation| import_animation(&buffers, &animation, &node_ids))
+        .collect();
+
+    scene_graph.update_world_matrices();
+    for entity in gltf_scene.entities.iter_mut() {
+        entity.model_uniforms = ModelUniform::new(scene_graph.world_matrix(entity.node));
+    }
+    Ok(gltf_scene)
+}
+
+fn import_node(
+    path: &Path,
+    buffers: &[gltf::buffer::Data],
+    node: &gltf::Node,
+    parent: Option<NodeId>,
+    scene_graph: &mut SceneGraph,
+    node_ids: &mut [Option<NodeId>],
+    gltf_scene: &mut GltfScene,
+) -> Result<(), GltfImportError> {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    let local = Transform::new(
+        Vec3::from(translation),
+        Quat::from_array(rotation),
+        Vec3::from(scale),
+    );
+    let id = scene_graph.add_node(local, parent);
+    node_ids[node.index()] = Some(id);
+
+    if let Some(mesh) = node.mesh() {
+        // the node's weights override those of its mesh
+        if let Some(weights) = node.weights().or_else(|| mesh.weights()) {
+            gltf_scene.morph_weights.insert(id, weights.to_vec());
+        }
+        for primitive in mesh.primitives() {
+            let key = (mesh.index(), primitive.index());
+            let primitive_mesh = match gltf_scene.primitive_meshes.get(&key) {
+                Some(primitive_mesh) => primitive_mesh.clone(),
+                None => {
+                    let (vertices, indices, morph_targets) =
+                        import_primitive(path, buffers, &mesh, &primitive)?;
+                    let primitive_mesh = PrimitiveMesh {
+                        mesh: gltf_scene.primitive_meshes.len(),
+                        triangles: Arc::new(TriangleBvh::new(&vertices, &indices)),
+                        vertices: Arc::new(vertices),
+                        indices: Arc::new(indices),
+                        morph_targets,
+                    };
+                    gltf_scene
+                        .primitive_meshes
+                        .insert(key, primitive_mesh.clone());
+                    primitive_mesh
+                }
+            };
+            let material = primitive.material().index().map_or(0, |index| index + 1);
+            let mut entity = BasicEntity::new(
+                id,
+                primitive_mesh.mesh,
+                material,
+                // filled in once the world matrices are known
+                ModelUniform::new(Mat4::IDENTITY),
+                primitive_mesh.vertices,
+                primitive_mesh.indices,
+                vec![ModelMatrixInstance::new(Mat4::IDENTITY)],
+            );
+            entity.triangles = Some(primitive_mesh.triangles);
+            entity.skin = node.skin().map(|skin| skin.index());
+            entity.morph_targets = primitive_mesh.morph_targets;
+            gltf_scene.entities.push(entity);
+        }
+    }
+
+    if let Some(camera) = node.camera() {
+        let projection = match camera.projection() {
+            gltf::camera::Projection::Perspective(perspective) => {
+                let default = PerspectiveProjection::default();
+                Projection::Perspective(PerspectiveProjection {
+                    // glTF cameras look down -Z with +Y up
+                    handedness: Handedness::Right,
+                    fov: perspective.yfov(),
+                    aspect_ratio: perspective.aspect_ratio().unwrap_or(default.aspect_ratio),
+                    near: perspective.znear(),
+                    // an infinite far plane is not yet supported; fall back to the default
+                    far: perspective.zfar().unwrap_or(default.far),
+                })
+            }
+            gltf::camera::Projection::Orthographic(orthographic) => {
+                Projection::Orthographic(OrthographicProjection {
+                    handedness: Handedness::Right,
+                    // `ymag` is half the height of the view volume; the width follows the window
+                    // like that of perspective cameras
+                    scaling: OrthographicScaling::Height(2.0 * orthographic.ymag()),
+                    aspect_ratio: orthographic.xmag() / orthographic.ymag(),
+                    near: orthographic.znear(),
+                    far: orthographic.zfar(),
+                })
+            }
+        };
+        gltf_scene.cameras.push(GltfCamera {
+            node: id,
+            projection,
+        });
+    }
+
+    for child in node.children() {
+        import_node(
+            path,
+            buffers,
+            &child,
+            Some(id),
+            scene_graph,
+            node_ids,
+            gltf_scene,
+        )?;
+    }
+    Ok(())
+}
+
+fn import_skin(
+    path: &Path,
+    buffers: &[gltf::buffer::Data],
+    skin: &gltf::Skin,
+    node_ids: &[Option<NodeId>],
+) -> Result<Skin, GltfImportError> {
+    let joints = skin
+        .joints()
+        .map(|joint| {
+            node_ids[joint.index()].ok_or_else(|| GltfImportError::MissingJoint {
+                path: path.to_path_buf(),
+                skin: skin.index(),
+                node: joint.index(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // without inverse bind matrices, the joints are already in their bind pose at the origin
+    let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+    let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+        Some(matrices) => matrices.map(|m| Mat4::from_cols_array_2d(&m)).collect(),
+        None => vec![Mat4::IDENTITY; joints.len()],
+    };
+    Ok(Skin {
+        joints,
+        inverse_bind_matrices,
+    })
+}
+
+// channels targeting nodes outside of the scene are dropped
+fn import_animation(
+    buffers: &[gltf::buffer::Data],
+    animation: &gltf::Animation,
+    node_ids: &[Option<NodeId>],
+) -> AnimationClip {
+    let channels = animation
+        .channels()
+        .filter_map(|channel| {
+            let node = node_ids[channel.target().node().index()]?;
+            let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+            let times: Vec<f32> = reader.read_inputs()?.collect();
+            let (property, values): (Property, Vec<f32>) = match reader.read_outputs()? {
+                ReadOutputs::Translations(translations) => {
+                    (Property::Translation, translations.flatten().collect())
+                }
+                ReadOutputs::Rotations(rotations) => {
+                    (Property::Rotation, rotations.into_f32().flatten().collect())
+                }
+                ReadOutputs::Scales(scales) => (Property::Scale, scales.flatten().collect()),
+                ReadOutputs::MorphTargetWeights(weights) => {
+                    (Property::MorphWeights, weights.into_f32().collect())
+                }
+            };
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+            let values_per_keyframe = match interpolation {
+                Interpolation::CubicSpline => 3 * times.len(),
+                _ => times.len(),
+            };
+            if values_per_keyframe == 0 {
+                return None;
+            }
+            Some(Channel {
+                node,
+                property,
+                interpolation,
+                components: values.len() / values_per_keyframe,
+                times,
+                values,
+            })
+        })
+        .collect();
+    AnimationClip::new(channels)
+}
+
+fn import_material(material: &gltf::Material, images: &[Arc<RgbaImage>]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let image = |texture: gltf::Texture| images[texture.source().index()].clone();
+    let alpha_cutoff = match material.alpha_mode() {
+        gltf::material::AlphaMode::Mask => material.alpha_cutoff().unwrap_or(0.5),
+        // blending is not supported yet, so blended materials are drawn opaque
+        gltf::material::AlphaMode::Opaque | gltf::material::AlphaMode::Blend => 0.0,
+    };
+    Material {
+        base_color_factor: Vec4::from(pbr.base_color_factor()),
+        base_color_texture: pbr.base_color_texture().map(|info| image(info.texture())),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .map(|info| image(info.texture())),
+        normal_texture: material.normal_texture().map(|info| image(info.texture())),
+        normal_scale: material.normal_texture().map_or(1.0, |info| info.scale()),
+        occlusion_texture: material
+            .occlusion_texture()
+            .map(|info| image(info.texture())),
+        occlusion_strength: material
+            .occlusion_texture()
+            .map_or(1.0, |info| info.strength()),
+        emissive_factor: Vec3::from(material.emissive_factor()),
+        emissive_texture: material
+            .emissive_texture()
+            .map(|info| image(info.texture())),
+        alpha_cutoff,
+    }
+}
+
+// widens the decoded glTF image to RGBA8, keeping the most significant byte of 16-bit channels
+fn to_rgba_image(data: &gltf::image::Data) -> RgbaImage {
+    use gltf::image::Format;
+    let (channels, bytes_per_channel, bgr) = match data.format {
+        Format::R8 => (1, 1, false),
+        Format::R8G8 => (2, 1, false),
+        Format::R8G8B8 => (3, 1, false),
+        Format::R8G8B8A8 => (4, 1, false),
+        Format::B8G8R8 => (3, 1, true),
+        Format::B8G8R8A8 => (4, 1, true),
+        Format::R16 => (1, 2, false),
+        Format::R16G16 => (2, 2, false),
+        Format::R16G16B16 => (3, 2, false),
+        Format::R16G16B16A16 => (4, 2, false),
+    };
+
+    let mut image = RgbaImage::new(data.width, data.height);
+    let texels = data.pixels.chunks_exact(channels * bytes_per_channel);
+    for (pixel, texel) in image.pixels_mut().zip(texels) {
+        // little endian, so the high byte comes last
+        let channel = |c: usize| texel[c * bytes_per_channel + bytes_per_channel - 1];
+        let rgba = match channels {
+            // luminance and luminance-alpha
+            1 => [channel(0), channel(0), channel(0), 255],
+            2 => [channel(0), channel(0), channel(0), channel(1)],
+            3 => [channel(0), channel(1), channel(2), 255],
+            _ => [channel(0), channel(1), channel(2), channel(3)],
+        };
+        *pixel = match bgr {
+            true => Rgba([rgba[2], rgba[1], rgba[0], rgba[3]]),
+            false => Rgba(rgba),
+        };
+    }
+    image
+}
+
+// the vertices and indices of a primitive, and its morph targets if it has any
+type PrimitiveGeometry = (Vec<GltfMeshVertex>, Vec<u32>, Option<MorphTargets>);
+
+fn import_primitive(
+    path: &Path,
+    buffers: &[gltf::buffer::Data],
+    mesh: &gltf::Mesh,
+    primitive: &gltf::Primitive,
+) -> Result<PrimitiveGeometry, GltfImportError> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let positions = reader
+        .read_positions()
+        .ok_or_else(|| GltfImportError::MissingPositions {
+            path: path.to_path_buf(),
+            mesh: mesh.index(),
+            primitive: primitive.index(),
+        })?;
+    let mut vertices: Vec<GltfMeshVertex> = positions
+        .map(|[x, y, z]| GltfMeshVertex {
+            position: [x, y, z, 1.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+            ..Default::default()
+        })
+        .collect();
+
+    // non-indexed geometry is drawn in vertex order
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    let mut indices = match primitive.mode() {
+        Mode::Triangles => indices,
+        Mode::TriangleStrip => triangle_strip_to_list(&indices),
+        Mode::TriangleFan => triangle_fan_to_list(&indices),
+        mode => {
+            return Err(GltfImportError::UnsupportedMode {
+                path: path.to_path_buf(),
+                mesh: mesh.index(),
+                primitive: primitive.index(),
+                mode,
+            })
+        }
+    };
+
+    if let Some(colors) = reader.read_colors(0) {
+        for (vertex, color) in vertices.iter_mut().zip(colors.into_rgba_f32()) {
+            vertex.color = color;
+        }
+    }
+    if let Some(tex_coords) = reader.read_tex_coords(0) {
+        for (vertex, uv) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+            vertex.tex_coords_0 = uv;
+        }
+    }
+    if let Some(tex_coords) = reader.read_tex_coords(1) {
+        for (vertex, uv) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+            vertex.tex_coords_1 = uv;
+        }
+    }
+    if let Some(tex_coords) = reader.read_tex_coords(2) {
+        for (vertex, uv) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+            vertex.tex_coords_2 = uv;
+        }
+    }
+    if let Some(joints) = reader.read_joints(0) {
+        for (vertex, [j0, j1, j2, j3]) in vertices.iter_mut().zip(joints.into_u16()) {
+            vertex.skin_index = [j0 as u32, j1 as u32, j2 as u32, j3 as u32];
+        }
+    }
+    if let Some(weights) = reader.read_weights(0) {
+        for (vertex, weights) in vertices.iter_mut().zip(weights.into_f32()) {
+            // normalized weights keep the skinned vertex from drifting towards the origin
+            let sum: f32 = weights.iter().sum();
+            let scale = if sum > 0.0 { 1.0 / sum } else { 0.0 };
+            vertex.skin_weight = weights.map(|weight| weight * scale);
+        }
+    }
+
+    let vertex_count = vertices.len();
+    let mut morph_targets = MorphTargets {
+        target_count: 0,
+        deltas: Vec::new(),
+    };
+    for (positions, normals, tangents) in reader.read_morph_targets() {
+        let start = morph_targets.deltas.len();
+        morph_targets
+            .deltas
+            .resize(start + vertex_count, MorphDelta::default());
+        let deltas = &mut morph_targets.deltas[start..];
+        for (delta, [x, y, z]) in deltas.iter_mut().zip(positions.into_iter().flatten()) {
+            delta.position = [x, y, z, 0.0];
+        }
+        for (delta, [x, y, z]) in deltas.iter_mut().zip(normals.into_iter().flatten()) {
+            delta.normal = [x, y, z, 0.0];
+        }
+        for (delta, [x, y, z]) in deltas.iter_mut().zip(tangents.into_iter().flatten()) {
+            delta.tangent = [x, y, z, 0.0];
+        }
+        morph_targets.target_count += 1;
+    }
+
+    match reader.read_normals() {
+        Some(normals) => {
+            for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                vertex.normal = normal;
+            }
+            match reader.read_tangents() {
+                Some(tangents) => {
+                    for (vertex, [x, y, z, w]) in vertices.iter_mut().zip(tangents) {
+                        let normal = Vec3::from(vertex.normal);
+                        let tangent = Vec3::new(x, y, z);
+                        vertex.tangent = tangent.into();
+                        vertex.bitangent = (normal.cross(tangent) * w).into();
+                    }
+                }
+                None => generate_tangents(&mut vertices, &indices),
+            }
+        }
+        None => {
+            // the spec asks for flat normals, so every triangle gets its own three vertices
+            vertices = indices.iter().map(|&i| vertices[i as usize]).collect();
+            morph_targets.deltas = morph_targets
+                .deltas
+                .chunks(vertex_count.max(1))
+                .flat_map(|target| indices.iter().map(move |&i| target[i as usize]))
+                .collect();
+            indices = (0..vertices.len() as u32).collect();
+            generate_flat_normals(&mut vertices);
+            // provided tangents are per original vertex and are ignored without normals
+            generate_tangents(&mut vertices, &indices);
+        }
+    }
+
+    let morph_targets = Some(morph_targets).filter(|targets| targets.target_count > 0);
+    Ok((vertices, indices, morph_targets))
+}
+
+fn triangle_strip_to_list(strip: &[u32]) -> Vec<u32> {
+    let mut list = Vec::with_capacity(strip.len().saturating_sub(2) * 3);
+    for (i, window) in strip.windows(3).enumerate() {
+        // every other triangle is flipped to keep a consistent winding order
+        if i % 2 == 0 {
+            list.extend_from_slice(&[window[0], window[1], window[2]]);
+        } else {
+            list.extend_from_slice(&[window[1], window[0], window[2]]);
+        }
+    }
+    list
+}
+
+fn triangle_fan_to_list(fan: &[u32]) -> Vec<u32> {
+    let mut list = Vec::with_capacity(fan.len().saturating_sub(2) * 3);
+    for window in fan.get(1..).unwrap_or(&[]).windows(2) {
+        list.extend_from_slice(&[fan[0], window[0], window[1]]);
+    }
+    list
+}
+
+fn position(vertex: &GltfMeshVertex) -> Vec3 {
+    let [x, y, z, _] = vertex.position;
+    Vec3::new(x, y, z)
+}
+
+// expects an unindexed triangle list
+fn generate_flat_normals(vertices: &mut [GltfMeshVertex]) {
+    for triangle in vertices.chunks_exact_mut(3) {
+        let (p0, p1, p2) = (
+            position(&triangle[0]),
+            position(&triangle[1]),
+            position(&triangle[2]),
+        );
+        let normal = (p1 - p0).cross(p2 - p0).normalize_or_zero();
+        for vertex in triangle.iter_mut() {
+            vertex.normal = normal.into();
+        }
+    }
+}
+
+// per-vertex tangent frames accumulated from the UV gradients of the surrounding triangles
+// http://foundationsofgameenginedev.com/FGED2-sample.pdf
+fn generate_tangents(vertices: &mut [GltfMeshVertex], indices: &[u32]) {
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        );
+        let e1 = position(&vertices[i1]) - position(&vertices[i0]);
+        let e2 = position(&vertices[i2]) - position(&vertices[i0]);
+        let uv0 = Vec2::from(vertices[i0].tex_coords_0);
+        let d1 = Vec2::from(vertices[i1].tex_coords_0) - uv0;
+        let d2 = Vec2::from(vertices[i2].tex_coords_0) - uv0;
+
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det.abs() <= f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (e1 * d2.y - e2 * d1.y) * r;
+        let bitangent = (e2 * d1.x - e1 * d2.x) * r;
+        for &i in &[i0, i1, i2] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let normal = Vec3::from(vertex.normal);
+        // Gram-Schmidt against the normal; vertices without usable UVs get any perpendicular axis
+        let tangent = (tangents[i] - normal * normal.dot(tangents[i]))
+            .try_normalize()
+            .unwrap_or_else(|| normal.any_orthonormal_vector());
+        let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = tangent.into();
+        vertex.bitangent = (normal.cross(tangent) * handedness).into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a unit quad in the XY plane with UVs following XY and tangents pointing the wrong way
+    fn write_quad_without_normals() -> PathBuf {
+        let mut bin = Vec::new();
+        let corners = [[0.0f32, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+        for [x, y] in corners.iter() {
+            for value in [*x, *y, 0.0].iter() {
+                bin.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for [x, y] in corners.iter() {
+            for value in [*x, *y].iter() {
+                bin.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for _ in corners.iter() {
+            for value in [0.0f32, 1.0, 0.0, 1.0].iter() {
+                bin.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for index in [0u16, 1, 2, 2, 1, 3].iter() {
+            bin.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [{{ "mesh": 0 }}],
+                "meshes": [{{ "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1, "TANGENT": 2 }},
+                    "indices": 3
+                }}] }}],
+                "buffers": [{{ "uri": "quad.bin", "byteLength": {} }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+                    {{ "buffer": 0, "byteOffset": 48, "byteLength": 32 }},
+                    {{ "buffer": 0, "byteOffset": 80, "byteLength": 64 }},
+                    {{ "buffer": 0, "byteOffset": 144, "byteLength": 12 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                       "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC2" }},
+                    {{ "bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC4" }},
+                    {{ "bufferView": 3, "componentType": 5123, "count": 6, "type": "SCALAR" }}
+                ]
+            }}"#,
+            bin.len()
+        );
+
+        let dir = std::env::temp_dir().join(format!("gltf-quad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap_or_else(|err| panic!("{}", err));
+        std::fs::write(dir.join("quad.bin"), &bin).unwrap_or_else(|err| panic!("{}", err));
+        let path = dir.join("quad.gltf");
+        std::fs::write(&path, json).unwrap_or_else(|err| panic!("{}", err));
+        path
+    }
+
+    // a binary glTF of `node_count` nodes instancing one mesh of one primitive, drawn in glTF
+    // `mode`, with each of `attributes` as a float accessor of the named type
+    fn mesh_glb(
+        attributes: &[(&str, &str, &[f32])],
+        indices: Option<&[u16]>,
+        mode: u32,
+        node_count: usize,
+    ) -> Vec<u8> {
+        let mut bin: Vec<u8> = Vec::new();
+        let mut views = Vec::new();
+        let mut accessors = Vec::new();
+        let mut semantics = Vec::new();
+        for (i, &(semantic, kind, values)) in attributes.iter().enumerate() {
+            let components = match kind {
+                "VEC2" => 2,
+                "VEC3" => 3,
+                _ => 4,
+            };
+            let (mut min, mut max) = (vec![f32::MAX; components], vec![f32::MIN; components]);
+            for (j, value) in values.iter().enumerate() {
+                min[j % components] = min[j % components].min(*value);
+                max[j % components] = max[j % components].max(*value);
+            }
+            views.push(format!(
+                r#"{{ "buffer": 0, "byteOffset": {}, "byteLength": {} }}"#,
+                bin.len(),
+                values.len() * 4
+            ));
+            accessors.push(format!(
+                r#"{{ "bufferView": {}, "componentType": 5126, "count": {}, "type": "{}",
+                      "min": {:?}, "max": {:?} }}"#,
+                i,
+                values.len() / components,
+                kind,
+                min,
+                max
+            ));
+            semantics.push(format!(r#""{}": {}"#, semantic, i));
+            bin.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+        }
+        let indices = indices.map_or(String::new(), |indices| {
+            views.push(format!(
+                r#"{{ "buffer": 0, "byteOffset": {}, "byteLength": {} }}"#,
+                bin.len(),
+                indices.len() * 2
+            ));
+            accessors.push(format!(
+                r#"{{ "bufferView": {}, "componentType": 5123, "count": {}, "type": "SCALAR" }}"#,
+                attributes.len(),
+                indices.len()
+            ));
+            bin.extend(indices.iter().flat_map(|index| index.to_le_bytes()));
+            format!(r#", "indices": {}"#, attributes.len())
+        });
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+
+        let mut json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": {:?} }}],
+                "nodes": [{}],
+                "meshes": [{{ "primitives": [{{
+                    "attributes": {{ {} }}, "mode": {}{}
+                }}] }}],
+                "buffers": [{{ "byteLength": {} }}],
+                "bufferViews": [{}],
+                "accessors": [{}]
+            }}"#,
+            (0..node_count).collect::<Vec<_>>(),
+            vec![r#"{ "mesh": 0 }"#; node_count].join(", "),
+            semantics.join(", "),
+            mode,
+            indices,
+            bin.len(),
+            views.join(", "),
+            accessors.join(", ")
+        )
+        .into_bytes();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+
+        let mut glb = Vec::new();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    fn import_glb_primitive(glb: &[u8]) -> PrimitiveGeometry {
+        let (document, buffers, _) =
+            gltf::import_slice(glb).unwrap_or_else(|err| panic!("{}", err));
+        let mesh = document.meshes().next().unwrap();
+        let primitive = mesh.primitives().next().unwrap();
+        import_primitive(Path::new("test.glb"), &buffers, &mesh, &primitive)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // four corners of a unit quad in the XY plane, facing +Z, in strip order
+    const QUAD_STRIP: [f32; 12] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0];
+    const QUAD_NORMALS: [f32; 12] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+
+    // the normal of each triangle of an indexed triangle list, by its winding
+    fn winding_normals(vertices: &[GltfMeshVertex], indices: &[u32]) -> Vec<Vec3> {
+        indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| position(&vertices[triangle[i] as usize]));
+                (b - a).cross(c - a).normalize()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn triangle_strips_become_lists_with_a_consistent_winding() {
+        let glb = mesh_glb(
+            &[
+                ("POSITION", "VEC3", &QUAD_STRIP),
+                ("NORMAL", "VEC3", &QUAD_NORMALS),
+            ],
+            None,
+            5,
+            1,
+        );
+        let (vertices, indices, _) = import_glb_primitive(&glb);
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 2, 1, 3]);
+        for normal in winding_normals(&vertices, &indices) {
+            assert!(normal.abs_diff_eq(Vec3::Z, 1e-5), "{:?}", normal);
+        }
+    }
+
+    #[test]
+    fn triangle_fans_become_lists_around_their_first_vertex() {
+        // the strip's corners, reordered to go around the quad
+        let fan = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        let glb = mesh_glb(
+            &[
+                ("POSITION", "VEC3", &fan),
+                ("NORMAL", "VEC3", &QUAD_NORMALS),
+            ],
+            Some(&[0, 1, 2, 3]),
+            6,
+            1,
+        );
+        let (vertices, indices, _) = import_glb_primitive(&glb);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+        for normal in winding_normals(&vertices, &indices) {
+            assert!(normal.abs_diff_eq(Vec3::Z, 1e-5), "{:?}", normal);
+        }
+    }
+
+    #[test]
+    fn missing_attributes_get_their_defaults() {
+        let glb = mesh_glb(
+            &[("POSITION", "VEC3", &QUAD_STRIP[..9])],
+            Some(&[0, 1, 2]),
+            4,
+            1,
+        );
+        let (vertices, indices, morph_targets) = import_glb_primitive(&glb);
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert!(morph_targets.is_none());
+        for vertex in &vertices {
+            // flat normals, following the winding
+            assert!(Vec3::from(vertex.normal).abs_diff_eq(Vec3::Z, 1e-5));
+            assert_eq!(vertex.position[3], 1.0);
+            assert_eq!(vertex.color, [1.0; 4]);
+            assert_eq!(vertex.tex_coords_0, [0.0; 2]);
+            assert_eq!(vertex.skin_index, [0; 4]);
+            assert_eq!(vertex.skin_weight, [0.0; 4]);
+        }
+    }
+
+    #[test]
+    fn nodes_instancing_a_mesh_share_its_geometry() {
+        let glb = mesh_glb(
+            &[
+                ("POSITION", "VEC3", &QUAD_STRIP),
+                ("NORMAL", "VEC3", &QUAD_NORMALS),
+            ],
+            None,
+            5,
+            3,
+        );
+        let dir = std::env::temp_dir().join(format!("gltf-instances-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap_or_else(|err| panic!("{}", err));
+        let path = dir.join("instances.glb");
+        std::fs::write(&path, glb).unwrap_or_else(|err| panic!("{}", err));
+
+        let mut scene_graph = SceneGraph::new();
+        let scene = import(&path, &mut scene_graph).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(scene.entities.len(), 3);
+        let first = &scene.entities[0];
+        for entity in &scene.entities[1..] {
+            assert_ne!(entity.node, first.node);
+            assert_eq!(entity.mesh, first.mesh);
+            assert!(Arc::ptr_eq(&entity.vertices, &first.vertices));
+            assert!(Arc::ptr_eq(&entity.indices, &first.indices));
+            assert!(Arc::ptr_eq(
+                entity.triangles.as_ref().unwrap(),
+                first.triangles.as_ref().unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn provided_tangents_are_ignored_without_normals() {
+        let path = write_quad_without_normals();
+        let (document, buffers, _) = gltf::import(&path).unwrap_or_else(|err| panic!("{}", err));
+        let mesh = document.meshes().next().unwrap();
+        let primitive = mesh.primitives().next().unwrap();
+        let (vertices, indices, _) = import_primitive(&path, &buffers, &mesh, &primitive)
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        // flat shading de-indexes the two triangles
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, (0..6).collect::<Vec<u32>>());
+        for vertex in &vertices {
+            assert!(Vec3::from(vertex.normal).abs_diff_eq(Vec3::Z, 1e-5));
+            // generated from the UVs rather than the provided +Y tangents
+            assert!(Vec3::from(vertex.tangent).abs_diff_eq(Vec3::X, 1e-5));
+            assert!(Vec3::from(vertex.bitangent).abs_diff_eq(Vec3::Y, 1e-5));
+        }
+    }
+}
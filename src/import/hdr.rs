@@ -0,0 +1,48 @@
+use crate::environment::HdrImage;
+use nannou::glam::Vec3;
+use nannou::image::codecs::hdr::HdrDecoder;
+use nannou::image::ImageError;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Errors that might occur while importing a Radiance `.hdr` file.
+#[derive(Debug, Error)]
+pub enum HdrImportError {
+    #[error("failed to open HDR image {path:?}: {err}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        err: io::Error,
+    },
+    #[error("failed to decode HDR image {path:?}: {err}")]
+    Decode {
+        path: PathBuf,
+        #[source]
+        err: ImageError,
+    },
+}
+
+/// Import the linear radiance of a Radiance RGBE `.hdr` file, e.g. an equirectangular panorama
+/// of an environment.
+pub fn import<P: AsRef<Path>>(path: P) -> Result<HdrImage, HdrImportError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| HdrImportError::Io {
+        path: path.to_path_buf(),
+        err,
+    })?;
+    let decode_error = |err| HdrImportError::Decode {
+        path: path.to_path_buf(),
+        err,
+    };
+    let decoder = HdrDecoder::new(BufReader::new(file)).map_err(decode_error)?;
+    let metadata = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()
+        .map_err(decode_error)?
+        .into_iter()
+        .map(|pixel| Vec3::from(pixel.0))
+        .collect();
+    Ok(HdrImage::new(metadata.width, metadata.height, pixels))
+}
@@ -0,0 +1,405 @@
+use crate::environment::EnvironmentMaps;
+use crate::reflection::LayoutEntries;
+use crate::shadow::point::PointShadow;
+use crate::shadow::{shadow_slots, DirectionalShadow};
+use crate::transform::{Transform, Transformable};
+use crate::uniforms::directional_light::DirectionalLightUniforms;
+use crate::uniforms::environment::EnvironmentUniform;
+use crate::uniforms::light_counts::LightCountsUniform;
+use crate::uniforms::point_light::PointLightUniforms;
+use crate::uniforms::spot_light::SpotLightUniforms;
+use crevice::std140::{AsStd140, Std140};
+use nannou::glam::{Mat4, Vec3, Vec4};
+use nannou::wgpu;
+use nannou::wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+// https://github.com/KhronosGroup/glTF/tree/main/extensions/2.0/Khronos/KHR_lights_punctual
+//
+// As in glTF, directional and spot lights shine along the -Z axis of their transform; the
+// translation of directional lights and the rotation of point lights are ignored.
+
+/// A light infinitely far away, e.g. the sun.
+#[derive(Debug, Clone)]
+pub struct DirectionalLight {
+    pub transform: Transform,
+    pub color: Vec3,
+    pub intensity: f32,
+    // `None` for a light that casts no shadows
+    pub shadow: Option<DirectionalShadow>,
+}
+
+/// A light emitting in every direction from a single point.
+#[derive(Debug, Clone)]
+pub struct PointLight {
+    pub transform: Transform,
+    pub color: Vec3,
+    pub intensity: f32,
+    // the distance at which the light falls off to zero; `None` for an inverse square falloff only
+    pub range: Option<f32>,
+    // `None` for a light that casts no shadows
+    pub shadow: Option<PointShadow>,
+}
+
+/// A point light restricted to a cone, fading out between the inner and outer cone angles.
+#[derive(Debug, Clone)]
+pub struct SpotLight {
+    pub transform: Transform,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: Option<f32>,
+    // half-angles in radians, with `0 <= inner_cone_angle < outer_cone_angle <= PI / 2`
+    pub inner_cone_angle: f32,
+    pub outer_cone_angle: f32,
+}
+
+/// The light of the surroundings, from every direction at once, as baked into `EnvironmentMaps`.
+#[derive(Debug, Clone)]
+pub struct EnvironmentLight {
+    pub intensity: f32,
+    // in radians about +Y, turning the environment around the scene
+    pub rotation: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            transform: Transform::default(),
+            color: Vec3::ONE,
+            intensity: 1.0,
+            shadow: None,
+        }
+    }
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            transform: Transform::default(),
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: None,
+            shadow: None,
+        }
+    }
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self {
+            transform: Transform::default(),
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: None,
+            inner_cone_angle: 0.0,
+            outer_cone_angle: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+impl Default for EnvironmentLight {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            rotation: 0.0,
+        }
+    }
+}
+
+impl DirectionalLight {
+    // `shadow_slot` as handed out by `shadow::shadow_slots`
+    pub fn uniforms(&self, shadow_slot: Option<u32>) -> DirectionalLightUniforms {
+        let shadow = self.shadow.clone().unwrap_or_default();
+        DirectionalLightUniforms::new(
+            self.transform.mat4x4(),
+            Vec4::from((self.color, self.intensity)),
+            shadow_slot.map_or(-1, |slot| slot as i32),
+            shadow.depth_bias,
+            shadow.normal_bias,
+        )
+    }
+}
+
+impl PointLight {
+    // the distance up to which the light casts shadows
+    pub fn shadow_far(&self) -> f32 {
+        let far = self.shadow.as_ref().map_or(0.0, |shadow| shadow.far);
+        self.range.unwrap_or(far)
+    }
+
+    // `shadow_slot` as handed out by `shadow::point::point_shadow_slots`
+    pub fn uniforms(&self, shadow_slot: Option<u32>) -> PointLightUniforms {
+        let shadow = self.shadow.clone().unwrap_or_default();
+        PointLightUniforms::new(
+            self.transform.translation,
+            self.range.unwrap_or(0.0),
+            self.color,
+            self.intensity,
+            shadow_slot.map_or(-1, |slot| slot as i32),
+            shadow.depth_bias,
+            shadow.normal_bias,
+            self.shadow_far(),
+        )
+    }
+}
+
+impl From<&SpotLight> for SpotLightUniforms {
+    fn from(light: &SpotLight) -> Self {
+        SpotLightUniforms::new(
+            light.transform.translation,
+            light.range.unwrap_or(0.0),
+            -light.transform.z_axis().normalize(),
+            light.intensity,
+            light.color,
+            light.inner_cone_angle.cos(),
+            light.outer_cone_angle.cos(),
+        )
+    }
+}
+
+/// Every light in the scene. The lists may grow or shrink between frames.
+#[derive(Debug, Clone, Default)]
+pub struct Lights {
+    pub directional: Vec<DirectionalLight>,
+    pub point: Vec<PointLight>,
+    pub spot: Vec<SpotLight>,
+    pub environment: EnvironmentLight,
+}
+
+impl Lights {
+    /// Whether any light casts shadows.
+    pub fn cast_shadows(&self) -> bool {
+        self.directional.iter().any(|light| light.shadow.is_some())
+            || self.point.iter().any(|light| light.shadow.is_some())
+    }
+}
+
+/// The light storage buffers and the environment maps, bound at `[[group(2)]]`.
+///
+/// Each buffer only grows, and is reallocated (along with the bind group) when a light list
+/// outgrows it; `LightCountsUniform` tells the shader how many elements are in use.
+pub struct LightResources {
+    bind_group_layout: wgpu::BindGroupLayout,
+    counts_buffer: wgpu::Buffer,
+    directional: LightBuffer,
+    point: LightBuffer,
+    spot: LightBuffer,
+    environment_buffer: wgpu::Buffer,
+    environment: EnvironmentMaps,
+    bind_group: wgpu::BindGroup,
+}
+
+struct LightBuffer {
+    buffer: wgpu::Buffer,
+    // in elements
+    capacity: usize,
+}
+
+impl LightBuffer {
+    fn new<T: AsStd140>(device: &wgpu::Device, label: &str, capacity: usize) -> Self {
+        // empty storage buffers can't be bound, so keep room for at least one light
+        let capacity = capacity.max(1).next_power_of_two();
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * T::std140_size_static()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer, capacity }
+    }
+
+    // returns `true` if the buffer had to be reallocated
+    fn write<T: AsStd140>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+        lights: &[T],
+    ) -> bool {
+        let grown = lights.len() > self.capacity;
+        if grown {
+            *self = Self::new::<T>(device, label, lights.len());
+        }
+        if !lights.is_empty() {
+            let bytes: Vec<u8> = lights
+                .iter()
+                .flat_map(|light| light.as_std140().as_bytes().to_vec())
+                .collect();
+            queue.write_buffer(&self.buffer, 0, &bytes);
+        }
+        grown
+    }
+}
+
+impl LightResources {
+    /// The entries of `bind_group_layout`.
+    pub fn layout_entries() -> LayoutEntries {
+        LayoutEntries::new()
+            .uniform_buffer::<LightCountsUniform>(wgpu::ShaderStage::FRAGMENT, false)
+            .storage_buffer(wgpu::ShaderStage::FRAGMENT, false, true)
+            .storage_buffer(wgpu::ShaderStage::FRAGMENT, false, true)
+            .storage_buffer(wgpu::ShaderStage::FRAGMENT, false, true)
+            .uniform_buffer::<EnvironmentUniform>(wgpu::ShaderStage::FRAGMENT, false)
+            .sampler(wgpu::ShaderStage::FRAGMENT, true)
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::Cube,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::Cube,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+    }
+
+    pub fn new(device: &wgpu::Device, environment: EnvironmentMaps) -> Self {
+        let bind_group_layout = Self::layout_entries().build(device);
+
+        let counts = LightCountsUniform::new(0, 0, 0).as_std140();
+        let counts_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("light_counts_buffer"),
+            contents: counts.as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let directional =
+            LightBuffer::new::<DirectionalLightUniforms>(device, "directional_lights_buffer", 1);
+        let point = LightBuffer::new::<PointLightUniforms>(device, "point_lights_buffer", 1);
+        let spot = LightBuffer::new::<SpotLightUniforms>(device, "spot_lights_buffer", 1);
+        let environment_uniform =
+            EnvironmentUniform::new(Mat4::IDENTITY, 1.0, environment.max_prefiltered_lod())
+                .as_std140();
+        let environment_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("environment_buffer"),
+            contents: environment_uniform.as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let bind_group = build_bind_group(
+            device,
+            &bind_group_layout,
+            &counts_buffer,
+            &directional,
+            &point,
+            &spot,
+            &environment_buffer,
+            &environment,
+        );
+
+        Self {
+            bind_group_layout,
+            counts_buffer,
+            directional,
+            point,
+            spot,
+            environment_buffer,
+            environment,
+            bind_group,
+        }
+    }
+
+    fn rebuild_bind_group(&mut self, device: &wgpu::Device) {
+        self.bind_group = build_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.counts_buffer,
+            &self.directional,
+            &self.point,
+            &self.spot,
+            &self.environment_buffer,
+            &self.environment,
+        );
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Upload the current state of `lights`, growing the storage buffers if necessary.
+    ///
+    /// `point_shadow_slots` holds the shadow cube of each point light this frame, see
+    /// `ShadowResources::point_shadow_slots`.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lights: &Lights,
+        point_shadow_slots: &[Option<u32>],
+    ) {
+        let directional: Vec<DirectionalLightUniforms> = lights
+            .directional
+            .iter()
+            .zip(shadow_slots(&lights.directional))
+            .map(|(light, slot)| light.uniforms(slot))
+            .collect();
+        let point: Vec<PointLightUniforms> = lights
+            .point
+            .iter()
+            .enumerate()
+            .map(|(i, light)| light.uniforms(point_shadow_slots.get(i).copied().flatten()))
+            .collect();
+        let spot: Vec<SpotLightUniforms> = lights.spot.iter().map(Into::into).collect();
+
+        let counts = LightCountsUniform::new(
+            directional.len() as u32,
+            point.len() as u32,
+            spot.len() as u32,
+        )
+        .as_std140();
+        queue.write_buffer(&self.counts_buffer, 0, counts.as_bytes());
+
+        let environment = EnvironmentUniform::new(
+            Mat4::from_rotation_y(lights.environment.rotation),
+            lights.environment.intensity,
+            self.environment.max_prefiltered_lod(),
+        )
+        .as_std140();
+        queue.write_buffer(&self.environment_buffer, 0, environment.as_bytes());
+
+        let mut grown =
+            self.directional
+                .write(device, queue, "directional_lights_buffer", &directional);
+        grown |= self
+            .point
+            .write(device, queue, "point_lights_buffer", &point);
+        grown |= self.spot.write(device, queue, "spot_lights_buffer", &spot);
+
+        if grown {
+            self.rebuild_bind_group(device);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    counts_buffer: &wgpu::Buffer,
+    directional: &LightBuffer,
+    point: &LightBuffer,
+    spot: &LightBuffer,
+    environment_buffer: &wgpu::Buffer,
+    environment: &EnvironmentMaps,
+) -> wgpu::BindGroup {
+    wgpu::BindGroupBuilder::new()
+        .buffer_bytes(counts_buffer, 0, None)
+        .buffer_bytes(&directional.buffer, 0, None)
+        .buffer_bytes(&point.buffer, 0, None)
+        .buffer_bytes(&spot.buffer, 0, None)
+        .buffer_bytes(environment_buffer, 0, None)
+        .sampler(environment.sampler())
+        .texture_view(environment.irradiance_view())
+        .texture_view(environment.prefiltered_view())
+        .texture_view(environment.brdf_lut_view())
+        .build(device, layout)
+}
@@ -1,43 +1,131 @@
+mod animation;
+mod background;
+mod batch;
+mod bounds;
+mod bvh;
 mod camera;
+mod culling;
+mod environment;
+mod frame_resources;
+mod import;
+mod light;
+mod material;
+mod mesh;
+mod morph;
+mod picking;
+mod post;
+mod reflection;
+mod render_graph;
+mod scene_graph;
+mod shader;
+mod shadow;
+mod skin;
+mod tonemap;
 mod transform;
 mod uniforms;
 
-use crate::transform::Transform;
+use crate::bounds::Bounds;
+use crate::transform::{Transform, Transformable};
+use animation::{AnimationClip, AnimationPlayer};
+use background::{Background, BackgroundResources};
+use batch::{Batch, Batcher};
+use bvh::{SceneBvh, TriangleBvh};
 use bytemuck::{Pod, Zeroable};
-use camera::projection::PerspectiveProjection;
+use camera::controller::{CameraController, FirstPersonController, FlyController, OrbitController};
+use camera::projection::{CameraProjection, Projection};
 use camera::BasicCamera;
-use crevice::std140::{AsStd140, Std140};
+use culling::{CullingStats, Frustum};
+use environment::{EnvironmentBaker, EnvironmentSettings, HdrImage};
+use frame_resources::FrameResources;
+use light::{DirectionalLight, LightResources, Lights};
+use material::{GpuMaterial, Material, MaterialResources};
+use mesh::GpuMesh;
+use morph::{MorphResources, MorphTargets, MorphWeights};
 use nannou::prelude::*;
-use nannou::wgpu::BufferInitDescriptor;
+use picking::{Hit, Ray};
+use post::{
+    Bloom, ChromaticAberration, DepthOfField, Fxaa, PostEffect, PostProcessResources,
+    PostProcessStack, Ssao, Vignette,
+};
+use reflection::{LayoutEntries, PipelineInterface};
+use render_graph::{RenderGraph, TransientTextures};
+use scene_graph::{NodeId, SceneGraph};
+use shader::{PermutationCache, ShaderError, ShaderFeatures, ShaderLibrary, ShaderWatcher};
+use shadow::point::PointShadowSettings;
+use shadow::{CascadeSettings, DirectionalShadow, ShadowResources};
+use skin::{Skin, SkinResources};
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use tonemap::{TonemapResources, Tonemapping};
+use uniforms::arena::UniformArena;
 use uniforms::camera::CameraUniform;
-use uniforms::directional_light::DirectionalLightUniforms;
 use uniforms::instance_input::model_matrix::ModelMatrixInstance;
+use uniforms::instance_input::world_instance::WorldInstance;
 use uniforms::instance_input::GpuInstance;
 use uniforms::model::ModelUniform;
 use uniforms::vertex_input::gltf_mesh_vertex::GltfMeshVertex;
 use uniforms::vertex_input::GpuVertex;
 pub struct BasicEntity {
-    pub model_uniforms: &ModelUniform,
-    pub vertices: &Vec<GltfMeshVertex>,
-    pub indices: &Vec<i32>,
-    pub instances: &Vec<ModelMatrixInstance>,
+    // the scene graph node the entity is attached to; `model_uniforms` follows its world matrix
+    pub node: NodeId,
+    // index into `DrawContext::meshes`; entities with the same `vertices` and `indices` share one
+    pub mesh: usize,
+    // index into `DrawContext::materials`
+    pub material: usize,
+    // index into `DrawContext::skins`, for skinned meshes
+    pub skin: Option<usize>,
+    // weighted by the entry of `node` in `DrawContext::morph_weights`
+    pub morph_targets: Option<MorphTargets>,
+    pub model_uniforms: ModelUniform,
+    // of the vertices, in model space
+    pub bounds: Bounds,
+    // shared between entities of the same mesh
+    pub vertices: Arc<Vec<GltfMeshVertex>>,
+    pub indices: Arc<Vec<u32>>,
+    // over `vertices` and `indices`, for picking and spatial queries; shared between entities
+    // of the same mesh
+    pub triangles: Option<Arc<TriangleBvh>>,
+    pub instances: Vec<ModelMatrixInstance>,
 }
 impl BasicEntity {
     pub fn new(
-        model_uniforms: &ModelUniform,
-        vertices: &Vec<GltfMeshVertex>,
-        indices: &Vec<i32>,
-        instances: &Vec<ModelMatrixInstance>,
+        node: NodeId,
+        mesh: usize,
+        material: usize,
+        model_uniforms: ModelUniform,
+        vertices: impl Into<Arc<Vec<GltfMeshVertex>>>,
+        indices: impl Into<Arc<Vec<u32>>>,
+        instances: Vec<ModelMatrixInstance>,
     ) -> Self {
+        let vertices = vertices.into();
+        let indices = indices.into();
+        let bounds = Bounds::from_points(vertices.iter().map(|vertex| {
+            let [x, y, z, _] = vertex.position;
+            vec3(x, y, z)
+        }));
         Self {
+            node,
+            mesh,
+            material,
+            skin: None,
+            morph_targets: None,
             model_uniforms,
+            bounds,
             vertices,
             indices,
+            triangles: None,
             instances,
         }
     }
+
+    // skinned and morphed entities are drawn with model uniforms of their own
+    pub fn is_deformed(&self) -> bool {
+        self.skin.is_some() || self.morph_targets.is_some()
+    }
 }
 
 fn main() {
@@ -45,121 +133,369 @@ fn main() {
 }
 struct Model {
     draw_cxt: DrawContext,
+    // drives `draw_cxt.camera`; keys 1, 2 and 3 switch between orbit, fly and first-person
+    camera_controller: Box<dyn CameraController>,
+    // behind the transient attachments of each frame's render graph
+    transient_textures: RefCell<TransientTextures>,
+    // where the left mouse button went down, to tell clicks from camera drags
+    click_start: Option<Point2>,
+    // the nearest entity under the last click
+    selection: Option<Hit>,
 }
 
 pub struct DrawContext {
     // - global uniforms
+    camera: BasicCamera<Projection>,
     camera_uniforms: CameraUniform,
-    directional_lights: Vec<DirectionalLightUniforms>,
+    // this frame's camera and model uniforms
+    uniform_arena: UniformArena,
+    camera_offset: wgpu::DynamicOffset,
+    // of the model uniforms of each entity in `world`, at the same index; entities that aren't
+    // deformed all share one slot, as their instances carry their world matrices
+    model_offsets: Vec<wgpu::DynamicOffset>,
+    scene_bindings: SceneBindings,
+    // - lights
+    lights: Lights,
+    light_resources: LightResources,
+    shadows: ShadowResources,
+    // - background
+    background: Background,
+    background_resources: BackgroundResources,
+    // - skeletons
+    skins: Vec<Skin>,
+    skin_resources: SkinResources,
+    morph_weights: MorphWeights,
+    morph_resources: MorphResources,
+    // - animation
+    animations: Vec<AnimationClip>,
+    // the N key cross-fades to the next of `animations`
+    animation_player: AnimationPlayer,
     // - scene graph
+    scene_graph: SceneGraph,
     world: Vec<BasicEntity>,
+    // over the instances of `world`, for picking
+    scene_bvh: SceneBvh,
+    // indexed by `BasicEntity::mesh`
+    meshes: Vec<GpuMesh>,
+    // the visible instances of `world`, grouped into draws
+    batcher: Batcher,
+    // of the last `update`
+    culling_stats: CullingStats,
+    // - materials
+    material_resources: MaterialResources,
+    materials: Vec<GpuMaterial>,
     // - renderer
-    // cmd_encoder: wgpu::CommandEncoder,
-    // surface_texture: wgpu::Texture,
-    // depth_texture: wgpu::Texture,
-    pipelines: Vec<Box<dyn Drawable>>,
+    frame_resources: FrameResources,
+    post_process: PostProcessStack,
+    post_resources: PostProcessResources,
+    tonemapping: Tonemapping,
+    tonemap_resources: TonemapResources,
+    shaders: ShaderLibrary,
+    // reloads `shaders` as they are edited; `None` when their sources aren't around
+    shader_watcher: Option<ShaderWatcher>,
+    // by the features of the batches drawn with them
+    pipelines: PermutationCache<BasicPipeline<GltfMeshVertex, WorldInstance>>,
+    // of the last pipelines that failed to build, drawn over the frame until the shaders change
+    shader_errors: Vec<ShaderError>,
+}
+
+impl DrawContext {
+    // write this frame's camera and model uniforms into the arena and upload them
+    fn upload_uniforms(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let arena = &mut self.uniform_arena;
+        arena.clear();
+        self.camera_offset = arena.push(&self.camera_uniforms);
+        let shared_offset = arena.push(&ModelUniform::new(Mat4::IDENTITY));
+        self.model_offsets = self
+            .world
+            .iter()
+            .map(|entity| {
+                if entity.is_deformed() {
+                    arena.push(&entity.model_uniforms)
+                } else {
+                    shared_offset
+                }
+            })
+            .collect();
+        if arena.flush(device, queue) {
+            self.scene_bindings
+                .rebuild(device, arena, &self.skin_resources, &self.morph_resources);
+        }
+    }
+
+    // the permutation of `basic.wgsl` drawing `batch`
+    fn batch_features(&self, batch: &Batch) -> ShaderFeatures {
+        let mut features = ShaderFeatures::INSTANCED;
+        features.set(
+            ShaderFeatures::SKINNED,
+            self.world[batch.entity].skin.is_some(),
+        );
+        features.set(
+            ShaderFeatures::NORMAL_MAPPED,
+            self.materials[batch.material].normal_mapped,
+        );
+        features.set(ShaderFeatures::SHADOWED, self.lights.cast_shadows());
+        features
+    }
+
+    fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        features: ShaderFeatures,
+    ) -> Result<BasicPipeline<GltfMeshVertex, WorldInstance>, ShaderError> {
+        BasicPipeline::new(
+            device,
+            &self.shaders,
+            features,
+            &self.scene_bindings.layout,
+            self.material_resources.bind_group_layout(),
+            self.light_resources.bind_group_layout(),
+            self.shadows.bind_group_layout(),
+            &self.frame_resources.sample_count(),
+            &FrameResources::COLOR_FORMAT,
+            &FrameResources::DEPTH_FORMAT,
+        )
+    }
+
+    // build the pipelines of this frame's batches that haven't been built yet. Batches without
+    // one are skipped while the shaders fail to build
+    fn prepare_pipelines(&mut self, device: &wgpu::Device) {
+        if !self.shader_errors.is_empty() {
+            return;
+        }
+        let features: Vec<ShaderFeatures> = self
+            .batcher
+            .batches()
+            .iter()
+            .map(|batch| self.batch_features(batch))
+            .collect();
+        for features in features {
+            if self.pipelines.get(features).is_some() {
+                continue;
+            }
+            match self.build_pipeline(device, features) {
+                Ok(pipeline) => self.pipelines.insert(features, pipeline),
+                Err(err) => {
+                    self.shader_errors.push(err);
+                    return;
+                }
+            }
+        }
+    }
+
+    // load the shaders edited since the last frame and rebuild the pipelines using them. A
+    // pipeline that fails to build keeps drawing with its last good version
+    fn reload_shaders(&mut self, device: &wgpu::Device) {
+        let watcher = match &mut self.shader_watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+        let changed = match watcher.poll(&mut self.shaders) {
+            Ok(changed) => changed,
+            Err(err) => {
+                self.shader_errors = vec![err];
+                return;
+            }
+        };
+        if changed.is_empty() {
+            return;
+        }
+
+        // rebuild everything that is stale even once a rebuild fails, as the next poll won't
+        // report the same changes again
+        let mut errors = Vec::new();
+        let stale: Vec<ShaderFeatures> = self
+            .pipelines
+            .iter()
+            .filter(|(_, pipeline)| pipeline.depends_on(&changed))
+            .map(|(features, _)| features)
+            .collect();
+        for features in stale {
+            match self.build_pipeline(device, features) {
+                Ok(pipeline) => self.pipelines.insert(features, pipeline),
+                Err(err) => errors.push(err),
+            }
+        }
+        if self.background_resources.depends_on(&changed) {
+            if let Err(err) = self
+                .background_resources
+                .rebuild_pipeline(device, &self.shaders)
+            {
+                errors.push(err);
+            }
+        }
+        if self.post_resources.depends_on(&changed) {
+            if let Err(err) = self.post_resources.rebuild_pipelines(device, &self.shaders) {
+                errors.push(err);
+            }
+        }
+        if self.tonemap_resources.depends_on(&changed) {
+            if let Err(err) = self
+                .tonemap_resources
+                .rebuild_pipelines(device, &self.shaders)
+            {
+                errors.push(err);
+            }
+        }
+        // a broken file shared by several pipelines fails each of them the same way
+        let mut messages = HashSet::new();
+        errors.retain(|err| messages.insert(err.to_string()));
+        self.shader_errors = errors;
+    }
+
+    /// Rebuild the frame resources at `size`, in pixels, and fit the camera's aspect ratio to it.
+    /// The camera uniforms are uploaded along with the rest by the next `update`.
+    fn resize(&mut self, device: &wgpu::Device, size: [u32; 2]) {
+        if size[0] == 0 || size[1] == 0 {
+            return;
+        }
+        if size != self.frame_resources.size() {
+            self.frame_resources.resize(device, size);
+            self.post_resources.resize(device, size);
+            self.tonemap_resources
+                .resize(device, self.post_resources.output_view(), size);
+        }
+        self.camera
+            .projection
+            .update(size[0] as usize, size[1] as usize);
+        self.camera_uniforms = CameraUniform::from(&self.camera);
+    }
+}
+
+// `[[group(0)]]` of the forward pass: the camera and model uniforms, bound at dynamic offsets
+// into the uniform arena, and the buffers deforming skinned and morphed meshes
+struct SceneBindings {
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SceneBindings {
+    // the entries of `layout`
+    fn layout_entries() -> LayoutEntries {
+        LayoutEntries::new()
+            .uniform_buffer::<CameraUniform>(wgpu::ShaderStage::VERTEX_FRAGMENT, true)
+            .uniform_buffer::<ModelUniform>(wgpu::ShaderStage::VERTEX_FRAGMENT, true)
+            .storage_buffer(wgpu::ShaderStage::VERTEX, false, true)
+            .storage_buffer(wgpu::ShaderStage::VERTEX, false, true)
+            .storage_buffer(wgpu::ShaderStage::VERTEX, false, true)
+    }
+
+    fn new(
+        device: &wgpu::Device,
+        arena: &UniformArena,
+        skins: &SkinResources,
+        morphs: &MorphResources,
+    ) -> Self {
+        let layout = Self::layout_entries().build(device);
+        let bind_group = build_scene_bind_group(device, &layout, arena, skins, morphs);
+        Self { layout, bind_group }
+    }
+
+    // after the arena has grown
+    fn rebuild(
+        &mut self,
+        device: &wgpu::Device,
+        arena: &UniformArena,
+        skins: &SkinResources,
+        morphs: &MorphResources,
+    ) {
+        self.bind_group = build_scene_bind_group(device, &self.layout, arena, skins, morphs);
+    }
+}
+
+fn build_scene_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    arena: &UniformArena,
+    skins: &SkinResources,
+    morphs: &MorphResources,
+) -> wgpu::BindGroup {
+    wgpu::BindGroupBuilder::new()
+        .buffer_bytes(
+            arena.buffer(),
+            0,
+            UniformArena::binding_size::<CameraUniform>(),
+        )
+        .buffer_bytes(
+            arena.buffer(),
+            0,
+            UniformArena::binding_size::<ModelUniform>(),
+        )
+        .buffer_bytes(skins.joints_buffer(), 0, None)
+        .buffer_bytes(morphs.deltas_buffer(), 0, None)
+        .buffer_bytes(morphs.weights_buffer(), 0, None)
+        .build(device, layout)
 }
 
 pub trait Drawable {
-    fn draw(&self, render_pass: wgpu::RenderPass, entity: &BasicEntity) -> ();
+    // draws one of `draw_cxt.batcher`'s batches
+    fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        batch: &Batch,
+        draw_cxt: &'a DrawContext,
+    );
 }
 
-struct BasicPipeline<Vertex, Instance, CameraUniform, ModelUniform>
+struct BasicPipeline<Vertex, Instance>
 where
     Vertex: GpuVertex,
     Instance: GpuInstance,
-    CameraUniform: AsStd140,
-    ModelUniform: AsStd140,
 {
-    shader_module: wgpu::ShaderModule,
+    // the shaders the pipeline was processed from
+    files: Vec<String>,
     _vertex: PhantomData<Vertex>,
-    vertices_buffer: wgpu::Buffer,
     _instance: PhantomData<Instance>,
-    instances_buffer: wgpu::Buffer,
-    indices_buffer: wgpu::Buffer,
-    _camera_uniform: PhantomData<CameraUniform>,
-    camera_uniform_buffer: wgpu::Buffer,
-    _model_uniform: PhantomData<ModelUniform>,
-    model_uniform_buffer: wgpu::Buffer,
-    bind_group_0: wgpu::BindGroup,
     pipeline: wgpu::RenderPipeline,
 }
 
-impl<Vertex, Instance, CameraUniform, ModelUniform>
-    BasicPipeline<Vertex, Instance, CameraUniform, ModelUniform>
+impl<Vertex, Instance> BasicPipeline<Vertex, Instance>
 where
     Vertex: GpuVertex + Pod + Zeroable,
     Instance: GpuInstance + Pod + Zeroable,
-    CameraUniform: AsStd140 + Copy,
-    ModelUniform: AsStd140 + Copy,
 {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         device: &wgpu::Device,
-        vertices: &Vec<Vertex>,
-        indices: &Vec<i32>,
-        instances: &Vec<Instance>,
-        camera_uniform: &CameraUniform,
-        model_uniform: &ModelUniform,
+        shaders: &ShaderLibrary,
+        features: ShaderFeatures,
+        scene_bind_group_layout: &wgpu::BindGroupLayout,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+        light_bind_group_layout: &wgpu::BindGroupLayout,
+        shadow_bind_group_layout: &wgpu::BindGroupLayout,
         sample_count: &u32,
         dst_format: &wgpu::TextureFormat,
         depth_format: &wgpu::TextureFormat,
-    ) -> Self {
+    ) -> Result<Self, ShaderError> {
         // Load shader modules.
-        let shader_str = include_str!("basic.wgsl");
+        let shader = shaders.process("basic.wgsl", &features.defines())?;
+        let module = shader.parse()?;
+        reflection::validate_module(
+            shader.name(),
+            &module,
+            &PipelineInterface {
+                vertex_buffers: &[Vertex::desc(), Instance::desc()],
+                bind_groups: &basic_bind_group_entries(),
+            },
+        )?;
 
         let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&shader_str)),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(shader.source().to_string())),
             flags: wgpu::ShaderFlags::default(),
             label: None,
         });
 
-        // Create the vertex, normal and index buffers.
-
-        let vertices_bytes = bytemuck::cast_slice(&vertices);
-        let indices_bytes = bytemuck::cast_slice(&indices);
-        let instances_bytes = bytemuck::cast_slice(&instances);
-        let camera_uniform_bytes = camera_uniform.as_std140().as_bytes();
-        let model_uniform_bytes = model_uniform.as_std140().as_bytes();
-
-        let vertices_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: vertices_bytes,
-            usage: wgpu::BufferUsage::VERTEX,
-        });
-        let indices_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: indices_bytes,
-            usage: wgpu::BufferUsage::INDEX,
-        });
-        let instances_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: instances_bytes,
-            usage: wgpu::BufferUsage::VERTEX,
-        });
-        let camera_uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: camera_uniform_bytes,
-            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-        });
-        let model_uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: model_uniform_bytes,
-            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-        });
-
-        let bind_group_0_layout = wgpu::BindGroupLayoutBuilder::new()
-            .uniform_buffer(wgpu::ShaderStage::VERTEX_FRAGMENT, false)
-            .uniform_buffer(wgpu::ShaderStage::VERTEX_FRAGMENT, false)
-            .build(device);
-
-        let bind_group_0 = wgpu::BindGroupBuilder::new()
-            .buffer::<CameraUniform>(&camera_uniform_buffer, 0..1)
-            .buffer::<ModelUniform>(&model_uniform_buffer, 0..1)
-            .build(&device, &bind_group_0_layout);
+        // The uniforms live in the `DrawContext`'s uniform arena, the geometry comes from each
+        // batch's `GpuMesh` and the instances from the `Batcher`.
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[&bind_group_0_layout],
+            bind_group_layouts: &[
+                scene_bind_group_layout,
+                material_bind_group_layout,
+                light_bind_group_layout,
+                shadow_bind_group_layout,
+            ],
             push_constant_ranges: &[],
         });
 
@@ -169,86 +505,57 @@ where
                 .color_format(*dst_format)
                 .color_blend(wgpu::BlendComponent::REPLACE)
                 .alpha_blend(wgpu::BlendComponent::REPLACE)
-                .add_vertex_buffer::<Vertex>(&wgpu::vertex_attr_array![
-                    // [[location(0)]] position: vec4<f32>;
-                    0 => Float32x4,
-                    // [[location(1)]] normal: vec3<f32>;
-                    1 => Float32x3,
-                    // [[location(2)]] tangent: vec3<f32>;
-                    2 => Float32x3,
-                    // [[location(3)]] bitangent: vec3<f32>;
-                    3 => Float32x3,
-                    // [[location(4)]] color: vec4<f32>;
-                    4 => Float32x4,
-                    // [[location(5)]] tex_coords_0: vec2<f32>;
-                    5 => Float32x2,
-                    // [[location(6)]] tex_coords_1: vec2<f32>;
-                    6 => Float32x2,
-                    // [[location(7)]] tex_coords_2: vec2<f32>;
-                    7 => Float32x2,
-                    // [[location(8)]] skin_weight: vec3<f32>;
-                    8 => Float32x3,
-                    // [[location(9)]] skin_index: vec3<f32>;
-                    9 => Float32x3,
-                ])
-                .add_instance_buffer::<Instance>(&[
-                    // [[location(10)]] model_matrix_0: vec4<f32>;
-                    wgpu::VertexAttribute {
-                        shader_location: 10,
-                        format: wgpu::VertexFormat::Float32x4,
-                        offset: std::mem::size_of::<[f32; 4]>() as u64 * 0,
-                    },
-                    // [[location(11)]] model_matrix_1: vec4<f32>;
-                    wgpu::VertexAttribute {
-                        shader_location: 11,
-                        format: wgpu::VertexFormat::Float32x4,
-                        offset: std::mem::size_of::<[f32; 4]>() as u64 * 1,
-                    },
-                    // [[location(12)]] model_matrix_2: vec4<f32>;
-                    wgpu::VertexAttribute {
-                        shader_location: 12,
-                        format: wgpu::VertexFormat::Float32x4,
-                        offset: std::mem::size_of::<[f32; 4]>() as u64 * 2,
-                    },
-                    // [[location(13)]] model_matrix_3: vec4<f32>;
-                    wgpu::VertexAttribute {
-                        shader_location: 13,
-                        format: wgpu::VertexFormat::Float32x4,
-                        offset: std::mem::size_of::<[f32; 4]>() as u64 * 3,
-                    },
-                ])
+                .add_vertex_buffer_layout(Vertex::desc())
+                .add_vertex_buffer_layout(Instance::desc())
                 .depth_format(*depth_format)
                 .sample_count(*sample_count)
                 .build(device);
 
-        BasicPipeline {
-            shader_module,
+        Ok(BasicPipeline {
+            files: shader.files().to_vec(),
             _vertex: PhantomData,
-            vertices_buffer,
             _instance: PhantomData,
-            instances_buffer,
-            indices_buffer,
-            _camera_uniform: PhantomData,
-            camera_uniform_buffer,
-            _model_uniform: PhantomData,
-            model_uniform_buffer,
-            bind_group_0,
             pipeline: render_pipeline,
-        }
+        })
+    }
+
+    // whether the pipeline needs rebuilding after the shaders named `changed` were edited
+    fn depends_on(&self, changed: &[String]) -> bool {
+        changed.iter().any(|name| self.files.contains(name))
     }
 }
 
-impl<Vertex, Instance, CameraUniform, ModelUniform> Drawable
-    for BasicPipeline<Vertex, Instance, CameraUniform, ModelUniform>
+// the layouts of `[[group(0)]]` to `[[group(3)]]` of `basic.wgsl`
+fn basic_bind_group_entries() -> [LayoutEntries; 4] {
+    [
+        SceneBindings::layout_entries(),
+        MaterialResources::layout_entries(),
+        LightResources::layout_entries(),
+        ShadowResources::layout_entries(),
+    ]
+}
+
+impl<Vertex, Instance> Drawable for BasicPipeline<Vertex, Instance>
 where
     Vertex: GpuVertex,
     Instance: GpuInstance,
-    CameraUniform: AsStd140,
-    ModelUniform: AsStd140,
 {
-    fn draw<'a>(&'a self, render_pass: wgpu::RenderPass<'a>, entity: &BasicEntity) -> () {
+    fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        batch: &Batch,
+        draw_cxt: &'a DrawContext,
+    ) {
+        let mesh = &draw_cxt.meshes[batch.mesh];
         render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, &self.bind_group_0, &[]);
+        render_pass.set_bind_group(
+            0,
+            &draw_cxt.scene_bindings.bind_group,
+            &[draw_cxt.camera_offset, draw_cxt.model_offsets[batch.entity]],
+        );
+        render_pass.set_bind_group(1, &draw_cxt.materials[batch.material].bind_group, &[]);
+        render_pass.set_bind_group(2, draw_cxt.light_resources.bind_group(), &[]);
+        render_pass.set_bind_group(3, draw_cxt.shadows.bind_group(), &[]);
 
         /*
         ```wgsl
@@ -261,119 +568,515 @@ where
           [[location(5)]] tex_coords_0: vec2<f32>;
           [[location(6)]] tex_coords_1: vec2<f32>;
           [[location(7)]] tex_coords_2: vec2<f32>;
-          [[location(8)]] skin_weight: vec3<f32>;
-          [[location(9)]] skin_index: vec3<f32>;
+          [[location(8)]] skin_weight: vec4<f32>;
+          [[location(9)]] skin_index: vec4<u32>;
         };
-        struct ModelMatrixInstance {
+        struct WorldInstance {
           [[location(10)]] model_matrix_0: vec4<f32>;
           [[location(11)]] model_matrix_1: vec4<f32>;
           [[location(12)]] model_matrix_2: vec4<f32>;
           [[location(13)]] model_matrix_3: vec4<f32>;
+          [[location(14)]] normal_matrix_0: vec3<f32>;
+          [[location(15)]] normal_matrix_1: vec3<f32>;
+          [[location(16)]] normal_matrix_2: vec3<f32>;
         };
         ```
                                                                                                                                     -- slot 1 --			-- slot 2 --
                 render_pipeline = { vertex: wgpu::VertexState { buffers: &[Vertex::desc(), InstanceRaw::desc()], ..} , ..}
         */
 
-        render_pass.set_vertex_buffer(0, self.vertices_buffer.slice(..));
-        render_pass.set_vertex_buffer(1, self.instances_buffer.slice(..));
+        render_pass.set_vertex_buffer(0, mesh.vertices_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, draw_cxt.batcher.instances_buffer().slice(..));
 
-        render_pass.set_index_buffer(self.indices_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(
-            0..entity.indices.len() as u32,
-            0,
-            0..entity.instances.len() as _,
-        );
+        render_pass.set_index_buffer(mesh.indices_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.index_count, 0, batch.instances.clone());
     }
 }
 
 fn model(app: &App) -> Model {
-    let w_id = app.new_window().size(1024, 576).view(view).build().unwrap();
+    let w_id = app
+        .new_window()
+        .size(1024, 576)
+        // multisampling happens in the `FrameResources`
+        .msaa_samples(1)
+        .view(view)
+        .event(event)
+        .build()
+        .unwrap();
 
     // The gpu device associated with the window's swapchain
     let window = app.window(w_id).unwrap();
     let device = window.swap_chain_device();
-    let dst_format = Frame::TEXTURE_FORMAT;
-    let depth_format = wgpu::TextureFormat::Depth32Float;
-    let msaa_samples = window.msaa_samples();
+    let queue = window.swap_chain_queue();
+    let msaa_samples = Frame::DEFAULT_MSAA_SAMPLES;
     let (win_w, win_h) = window.inner_size_pixels();
-
-    let depth_texture = wgpu::TextureBuilder::new()
-        .size([win_w, win_h])
-        .format(depth_format)
-        .usage(wgpu::TextureUsage::RENDER_ATTACHMENT)
-        .sample_count(msaa_samples)
-        .build(device);
-    let depth_texture_view = depth_texture.view().build();
+    let frame_resources = FrameResources::new(device, [win_w, win_h], msaa_samples);
 
     // create world :
+    let mut scene_graph = SceneGraph::new();
 
-    // entitiy-1 :
-
-    let model_uniform = ModelUniform::new(mat4(
-        vec4(1., 0., 0., 0.),
-        vec4(0., 1., 0., 0.),
-        vec4(0., 0., 1., 0.),
-        vec4(0., 0., 0., 1.),
-    ));
-    let vertices = vec![GltfMeshVertex::default()];
-    let instances = vec![ModelMatrixInstance::default()];
-    let indices = vec![0];
-
-    let entity1 = BasicEntity::new(&model_uniform, &vertices, &indices, &instances);
-
-    let world = vec![entity1];
-
-    // camera
-    let camera = BasicCamera::new(Transform::default(), PerspectiveProjection::default());
-    let camera_uniforms = CameraUniform::from(camera);
+    // an optional .gltf/.glb path may be passed as the first argument
+    let (world, gltf_cameras, materials, skins, morph_weights, animations) =
+        match std::env::args().nth(1) {
+            Some(path) => match import::gltf_scene::import(&path, &mut scene_graph) {
+                Ok(gltf_scene) => (
+                    gltf_scene.entities,
+                    gltf_scene.cameras,
+                    gltf_scene.materials,
+                    gltf_scene.skins,
+                    gltf_scene.morph_weights,
+                    gltf_scene.animations,
+                ),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            },
+            None => (
+                Vec::new(),
+                Vec::new(),
+                vec![Material::default()],
+                Vec::new(),
+                MorphWeights::new(),
+                Vec::new(),
+            ),
+        };
 
-    // lights
-    let directional_lights = vec![DirectionalLightUniforms::new(
-        mat4(
+    // entitiy-1 :
+    let world = if world.is_empty() {
+        let model_uniform = ModelUniform::new(mat4(
             vec4(1., 0., 0., 0.),
             vec4(0., 1., 0., 0.),
             vec4(0., 0., 1., 0.),
             vec4(0., 0., 0., 1.),
-        ),
-        vec4(1., 0., 0., 0.),
-    )];
+        ));
+        let vertices = vec![GltfMeshVertex::default()];
+        let instances = vec![ModelMatrixInstance::default()];
+        let indices = vec![0];
+        let node = scene_graph.add_node(Transform::default(), None);
+
+        vec![BasicEntity::new(
+            node,
+            0,
+            0,
+            model_uniform,
+            vertices,
+            indices,
+            instances,
+        )]
+    } else {
+        world
+    };
 
-    let basic_pipeline = BasicPipeline::new(
+    // camera
+    let mut camera = BasicCamera::new(Transform::default(), Projection::default());
+    // look through the first camera of the imported scene, if there is one
+    let camera_controller: Box<dyn CameraController> = match gltf_cameras.into_iter().next() {
+        Some(gltf_camera) => {
+            camera.projection = gltf_camera.projection;
+            camera.attach_to(Some(gltf_camera.node));
+            camera.update_parent_matrix(&scene_graph);
+            // fly off from the imported viewpoint
+            Box::new(FlyController::from_transform(&camera.transform))
+        }
+        None => Box::new(OrbitController::new(Vec3::ZERO, 5.0, 0.0, 0.3)),
+    };
+    camera.projection.update(win_w as usize, win_h as usize);
+    let camera_uniforms = CameraUniform::from(&camera);
+
+    // lights: a single sun, shining down at an angle
+    let lights = Lights {
+        directional: vec![DirectionalLight {
+            transform: Transform::new(
+                Vec3::ZERO,
+                Quat::from_rotation_y(PI / 4.) * Quat::from_rotation_x(-PI / 3.),
+                Vec3::ONE,
+            ),
+            shadow: Some(DirectionalShadow::default()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // an optional equirectangular .hdr panorama lighting the scene may be passed as the second
+    // argument, otherwise a dim grey sky lights it
+    let panorama = match std::env::args().nth(2) {
+        Some(path) => match import::hdr::import(&path) {
+            Ok(panorama) => panorama,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        None => HdrImage::gradient(64, 32, Vec3::splat(0.02), Vec3::splat(0.1)),
+    };
+    let shaders = ShaderLibrary::builtin();
+    let environment = match EnvironmentBaker::new(device, &shaders) {
+        Ok(baker) => baker.bake(device, queue, &panorama, &EnvironmentSettings::default()),
+        Err(err) => panic!("{}", err),
+    };
+    let background_resources = match BackgroundResources::new(
         device,
-        &vertices,
-        &indices,
-        &instances,
-        &camera_uniforms,
-        &model_uniform,
-        &msaa_samples,
-        &dst_format,
-        &depth_format,
-    );
+        &shaders,
+        &environment,
+        msaa_samples,
+        FrameResources::COLOR_FORMAT,
+        FrameResources::DEPTH_FORMAT,
+    ) {
+        Ok(background_resources) => background_resources,
+        Err(err) => panic!("{}", err),
+    };
+    let post_resources = match PostProcessResources::new(
+        device,
+        &shaders,
+        frame_resources.size(),
+        frame_resources.sample_count(),
+    ) {
+        Ok(post_resources) => post_resources,
+        Err(err) => panic!("{}", err),
+    };
+    // the effects that don't suit every scene are there to be enabled
+    let post_process = PostProcessStack::new()
+        .with(PostEffect::Ssao(Ssao::default()))
+        .with_disabled(PostEffect::DepthOfField(DepthOfField::default()))
+        .with(PostEffect::Bloom(Bloom::default()))
+        .with(PostEffect::Fxaa(Fxaa::default()))
+        .with_disabled(PostEffect::ChromaticAberration(
+            ChromaticAberration::default(),
+        ))
+        .with(PostEffect::Vignette(Vignette::default()));
+    let tonemap_resources = match TonemapResources::new(
+        device,
+        &shaders,
+        post_resources.output_view(),
+        frame_resources.size(),
+    ) {
+        Ok(tonemap_resources) => tonemap_resources,
+        Err(err) => panic!("{}", err),
+    };
+    let mut light_resources = LightResources::new(device, environment);
+    light_resources.update(device, queue, &lights, &[]);
+    let skin_resources = SkinResources::new(device, &skins);
+    let morph_resources = MorphResources::new(device, &world);
+    let shadows = match ShadowResources::new(
+        device,
+        &shaders,
+        CascadeSettings::default(),
+        PointShadowSettings::default(),
+        &skin_resources,
+        &morph_resources,
+    ) {
+        Ok(shadows) => shadows,
+        Err(err) => panic!("{}", err),
+    };
 
-    let pipelines: Vec<Box<dyn Drawable>> = vec![Box::new(basic_pipeline)];
+    // play the first animation of the imported scene, if there is one
+    let mut animation_player = AnimationPlayer::new(animations.len());
+    if !animations.is_empty() {
+        if let Err(err) = animation_player.play(0) {
+            panic!("{}", err);
+        }
+    }
+    let meshes = mesh::upload_meshes(device, &world);
+    let batcher = Batcher::new(device, world.len());
+
+    // materials
+    let material_resources = MaterialResources::new(device, queue);
+    let materials = materials
+        .iter()
+        .map(|material| material_resources.upload(device, queue, material))
+        .collect();
+
+    // a slot for the camera, one shared by the entities that aren't deformed and at most one for
+    // each entity
+    let uniform_arena = UniformArena::new(device, 2 + world.len());
+    let scene_bindings =
+        SceneBindings::new(device, &uniform_arena, &skin_resources, &morph_resources);
 
     Model {
         draw_cxt: DrawContext {
+            camera,
             camera_uniforms,
-            directional_lights,
+            uniform_arena,
+            camera_offset: 0,
+            model_offsets: Vec::new(),
+            scene_bindings,
+            lights,
+            light_resources,
+            shadows,
+            background: Background::default(),
+            background_resources,
+            skins,
+            skin_resources,
+            morph_weights,
+            morph_resources,
+            animations,
+            animation_player,
+            scene_bvh: SceneBvh::new(&world, &scene_graph),
+            scene_graph,
             world,
-            pipelines,
+            meshes,
+            batcher,
+            culling_stats: CullingStats::default(),
+            material_resources,
+            materials,
+            frame_resources,
+            post_process,
+            post_resources,
+            tonemapping: Tonemapping::default(),
+            tonemap_resources,
+            // the pipelines are built by `update` once the batches are known
+            shaders,
+            shader_watcher: ShaderWatcher::builtin_dir()
+                .and_then(|dir| ShaderWatcher::new(dir, Duration::from_millis(250)).ok()),
+            pipelines: PermutationCache::new(),
+            shader_errors: Vec::new(),
         },
+        camera_controller,
+        transient_textures: RefCell::new(TransientTextures::new()),
+        click_start: None,
+        selection: None,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: WindowEvent) {
+    // also sent when the window goes fullscreen or moves to a monitor with another scale factor
+    if let WindowEvent::Resized(_) = event {
+        let window = app.main_window();
+        let (width, height) = window.inner_size_pixels();
+        model
+            .draw_cxt
+            .resize(window.swap_chain_device(), [width, height]);
+    }
+
+    // select whatever is under a click, as long as the mouse didn't move while the button was
+    // down, in which case it was dragging the camera
+    match event {
+        WindowEvent::MousePressed(MouseButton::Left) => {
+            model.click_start = Some(app.mouse.position())
+        }
+        WindowEvent::MouseReleased(MouseButton::Left) => {
+            let position = app.mouse.position();
+            if let Some(start) = model.click_start.take() {
+                if start.distance(position) < 3.0 {
+                    model.selection = pick(app, &model.draw_cxt, position);
+                }
+            }
+        }
+        _ => (),
     }
+
+    if let WindowEvent::KeyPressed(key) = event {
+        let transform = &model.draw_cxt.camera.transform;
+        match key {
+            // swap the controller, picking up from wherever the camera is now
+            Key::Key1 => {
+                let pivot = transform.translation + transform.rotation * -Vec3::Z * 5.0;
+                model.camera_controller =
+                    Box::new(OrbitController::from_transform(transform, pivot));
+            }
+            Key::Key2 => {
+                model.camera_controller = Box::new(FlyController::from_transform(transform))
+            }
+            Key::Key3 => {
+                model.camera_controller = Box::new(FirstPersonController::from_transform(transform))
+            }
+            // cycle through the tonemapping operators, and switch between auto and manual exposure
+            Key::T => {
+                let tonemapping = &mut model.draw_cxt.tonemapping;
+                tonemapping.tonemapper = tonemapping.tonemapper.next();
+            }
+            Key::X => model.draw_cxt.tonemapping.toggle_exposure(),
+            // cycle through the backgrounds, with the physical sky's sun behind the first light
+            Key::B => {
+                let draw_cxt = &mut model.draw_cxt;
+                let sun_direction = draw_cxt
+                    .lights
+                    .directional
+                    .first()
+                    .map_or(Vec3::Y, |light| light.transform.z_axis());
+                draw_cxt.background = draw_cxt.background.next(sun_direction);
+            }
+            // fade over to the next animation of the scene, in half a second
+            Key::N if !model.draw_cxt.animations.is_empty() => {
+                let player = &mut model.draw_cxt.animation_player;
+                let current = player.layers.last().map_or(0, |layer| layer.clip());
+                let next = (current + 1) % model.draw_cxt.animations.len();
+                if let Err(err) = player.cross_fade(next, 0.5) {
+                    eprintln!("{}", err);
+                }
+            }
+            _ => (),
+        }
+    }
+    model.camera_controller.event(&event);
+}
+
+// the nearest entity under `position`, in window points
+fn pick(app: &App, draw_cxt: &DrawContext, position: Point2) -> Option<Hit> {
+    let window_rect = app.main_window().rect();
+    let ndc = position / (window_rect.wh() * 0.5);
+    let ray = Ray::from_ndc(&draw_cxt.camera, ndc);
+    draw_cxt
+        .scene_bvh
+        .cast_ray(&ray, &draw_cxt.world)
+        .into_iter()
+        .next()
 }
 
-fn update(_app: &App, _model: &mut Model, _update: Update) {}
+fn update(app: &App, model: &mut Model, update: Update) {
+    let window = app.main_window();
+    let device = window.swap_chain_device();
+    let queue = window.swap_chain_queue();
+    let draw_cxt = &mut model.draw_cxt;
+
+    let dt = update.since_last.as_secs_f32();
+
+    model
+        .camera_controller
+        .update(&app.keys, dt, &mut draw_cxt.camera.transform);
+    draw_cxt.animation_player.update(
+        dt,
+        &draw_cxt.animations,
+        &mut draw_cxt.scene_graph,
+        &mut draw_cxt.morph_weights,
+    );
+
+    // propagate any transforms that changed since the last frame
+    draw_cxt.scene_graph.update_world_matrices();
+    draw_cxt
+        .skin_resources
+        .update(queue, &draw_cxt.skins, &draw_cxt.scene_graph);
+    draw_cxt
+        .morph_resources
+        .update(queue, &draw_cxt.world, &draw_cxt.morph_weights);
+    for (i, entity) in draw_cxt.world.iter_mut().enumerate() {
+        let joint_offset = draw_cxt.skin_resources.joint_offset(entity.skin);
+        entity.model_uniforms = ModelUniform::new(draw_cxt.scene_graph.world_matrix(entity.node))
+            .with_joint_offset(joint_offset)
+            .with_morph_slot(draw_cxt.morph_resources.slot(i));
+    }
+    draw_cxt
+        .scene_bvh
+        .update(&draw_cxt.world, &draw_cxt.scene_graph);
+    draw_cxt.camera.update_parent_matrix(&draw_cxt.scene_graph);
+    draw_cxt.camera_uniforms = CameraUniform::from(&draw_cxt.camera);
+    draw_cxt.upload_uniforms(device, queue);
+
+    // frustum culling, batching the visible instances by mesh and material
+    let frustum = Frustum::from_view_projection(draw_cxt.camera_uniforms.view_projection());
+    draw_cxt.culling_stats = draw_cxt.batcher.update(
+        device,
+        queue,
+        &draw_cxt.world,
+        &draw_cxt.scene_graph,
+        &frustum,
+    );
+    draw_cxt.reload_shaders(device);
+    draw_cxt.prepare_pipelines(device);
+
+    // the shadows pick which point lights cast shadows this frame, so they go first
+    draw_cxt.shadows.update(
+        device,
+        queue,
+        &draw_cxt.lights,
+        &draw_cxt.camera,
+        &draw_cxt.world,
+    );
+    draw_cxt.light_resources.update(
+        device,
+        queue,
+        &draw_cxt.lights,
+        draw_cxt.shadows.point_shadow_slots(),
+    );
+    draw_cxt.background_resources.update(
+        queue,
+        &draw_cxt.background,
+        draw_cxt.camera_uniforms.view_projection(),
+        &draw_cxt.lights.environment,
+    );
+    draw_cxt.post_resources.update(
+        device,
+        queue,
+        &draw_cxt.post_process,
+        draw_cxt.camera.projection.projection_mat4(),
+        draw_cxt.camera.focus_distance,
+    );
+    draw_cxt
+        .tonemap_resources
+        .update(queue, &draw_cxt.tonemapping, dt);
+}
 
 fn view(app: &App, model: &Model, frame: Frame) {
+    let window = app.main_window();
+    let device = window.swap_chain_device();
+    let draw_cxt = &model.draw_cxt;
+    let mut transient_textures = model.transient_textures.borrow_mut();
+    let mut graph = RenderGraph::new(&mut transient_textures);
+
+    let (shadow_map, point_shadow_map) =
+        draw_cxt
+            .shadows
+            .add_to_graph(&mut graph, &draw_cxt.world, &draw_cxt.meshes);
+    let targets = draw_cxt.frame_resources.add_to_graph(&mut graph);
+    let post_output = draw_cxt
+        .post_resources
+        .add_to_graph(&mut graph, device, targets);
+    draw_cxt
+        .tonemap_resources
+        .add_to_graph(&mut graph, post_output, &frame);
+    let clear = wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT);
+    let forward = graph.add_pass("forward");
+    let forward = match targets.resolve {
+        Some(resolve) => forward.resolved_color_attachment(targets.color, resolve, clear),
+        None => forward.color_attachment(targets.color, clear),
+    };
+    forward
+        .depth_attachment(targets.depth, wgpu::LoadOp::Clear(1.0))
+        .read_texture(shadow_map)
+        .read_texture(point_shadow_map)
+        .render(move |render_pass, _| {
+            for batch in draw_cxt.batcher.batches() {
+                if let Some(pipeline) = draw_cxt.pipelines.get(draw_cxt.batch_features(batch)) {
+                    pipeline.draw(render_pass, batch, draw_cxt);
+                }
+            }
+            draw_cxt.background_resources.draw(render_pass);
+        });
+
     let mut encoder = frame.command_encoder();
-    let mut render_pass = wgpu::RenderPassBuilder::new()
-        .color_attachment(frame.swap_chain_texture(), |color| color)
-        .begin(&mut encoder);
+    if let Err(err) = graph.execute(device, &mut encoder) {
+        panic!("{}", err);
+    }
+    drop(encoder);
 
-    for pipeline in model.draw_cxt.pipelines.iter() {
-        for entity in model.draw_cxt.world.iter() {
-            pipeline.draw(render_pass, entity);
-        }
+    if !draw_cxt.shader_errors.is_empty() {
+        draw_shader_errors(app, &frame, &draw_cxt.shader_errors);
+    }
+}
+
+// the errors over a darkened frame, along the top of the window
+fn draw_shader_errors(app: &App, frame: &Frame, errors: &[ShaderError]) {
+    let draw = app.draw();
+    let window_rect = app.main_window().rect();
+    draw.rect()
+        .xy(window_rect.xy())
+        .wh(window_rect.wh())
+        .color(rgba(0.0, 0.0, 0.0, 0.75));
+    let text_rect = window_rect.pad(20.0);
+    let text = errors
+        .iter()
+        .map(ShaderError::to_string)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    draw.text(&text)
+        .xy(text_rect.xy())
+        .wh(text_rect.wh())
+        .font_size(14)
+        .left_justify()
+        .align_text_top()
+        .color(rgb(1.0, 0.4, 0.4));
+    if let Err(err) = draw.to_frame(app, frame) {
+        panic!("{:?}", err);
     }
 }
@@ -0,0 +1,220 @@
+use crate::reflection::LayoutEntries;
+use crate::uniforms::material::MaterialUniform;
+use crevice::std140::{AsStd140, Std140};
+use nannou::glam::{Vec3, Vec4};
+use nannou::image::{Rgba, RgbaImage};
+use nannou::wgpu;
+use nannou::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#metallic-roughness-material
+
+/// A glTF metallic-roughness material. Every texture is optional and is multiplied with its
+/// factor; missing textures are replaced by white (or a flat normal) when uploaded to the GPU.
+///
+/// All textures are sampled with `TEXCOORD_0`.
+#[derive(Clone)]
+pub struct Material {
+    pub base_color_factor: Vec4,
+    // sRGB
+    pub base_color_texture: Option<Arc<RgbaImage>>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    // linear; roughness in the green channel, metalness in the blue channel
+    pub metallic_roughness_texture: Option<Arc<RgbaImage>>,
+    // linear; tangent-space normals
+    pub normal_texture: Option<Arc<RgbaImage>>,
+    pub normal_scale: f32,
+    // linear; occlusion in the red channel
+    pub occlusion_texture: Option<Arc<RgbaImage>>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: Vec3,
+    // sRGB
+    pub emissive_texture: Option<Arc<RgbaImage>>,
+    // fragments with a lower alpha are discarded; 0.0 renders the material fully opaque
+    pub alpha_cutoff: f32,
+}
+
+impl Default for Material {
+    // the glTF defaults: a white, fully metallic and fully rough surface
+    fn default() -> Self {
+        Self {
+            base_color_factor: Vec4::ONE,
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: Vec3::ZERO,
+            emissive_texture: None,
+            alpha_cutoff: 0.0,
+        }
+    }
+}
+
+impl From<&Material> for MaterialUniform {
+    fn from(material: &Material) -> Self {
+        MaterialUniform::new(
+            material.base_color_factor,
+            material.emissive_factor,
+            material.metallic_factor,
+            material.roughness_factor,
+            material.normal_scale,
+            material.occlusion_strength,
+            material.alpha_cutoff,
+        )
+    }
+}
+
+/// A material uploaded to the GPU, bound at `[[group(1)]]`.
+pub struct GpuMaterial {
+    _uniform_buffer: wgpu::Buffer,
+    _textures: Vec<wgpu::Texture>,
+    pub bind_group: wgpu::BindGroup,
+    // has a normal texture of its own, rather than the flat fallback
+    pub normal_mapped: bool,
+}
+
+/// The bind group layout, sampler and fallback textures shared by every `GpuMaterial`.
+pub struct MaterialResources {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    white_srgb: wgpu::Texture,
+    white_linear: wgpu::Texture,
+    flat_normal: wgpu::Texture,
+}
+
+impl MaterialResources {
+    /// The entries of `bind_group_layout`.
+    pub fn layout_entries() -> LayoutEntries {
+        let mut entries = LayoutEntries::new()
+            .uniform_buffer::<MaterialUniform>(wgpu::ShaderStage::FRAGMENT, false)
+            .sampler(wgpu::ShaderStage::FRAGMENT, true);
+        // base color, metallic-roughness, normal, occlusion, emissive
+        for _ in 0..5 {
+            entries = entries.texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            );
+        }
+        entries
+    }
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let bind_group_layout = Self::layout_entries().build(device);
+
+        let sampler = wgpu::SamplerBuilder::new()
+            .address_mode(wgpu::AddressMode::Repeat)
+            .build(device);
+
+        let white = RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        let flat_normal = RgbaImage::from_pixel(1, 1, Rgba([128, 128, 255, 255]));
+
+        Self {
+            bind_group_layout,
+            sampler,
+            white_srgb: upload_texture(device, queue, &white, wgpu::TextureFormat::Rgba8UnormSrgb),
+            white_linear: upload_texture(device, queue, &white, wgpu::TextureFormat::Rgba8Unorm),
+            flat_normal: upload_texture(
+                device,
+                queue,
+                &flat_normal,
+                wgpu::TextureFormat::Rgba8Unorm,
+            ),
+        }
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn upload(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        material: &Material,
+    ) -> GpuMaterial {
+        let material_uniform = MaterialUniform::from(material).as_std140();
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("material_uniform_buffer"),
+            contents: material_uniform.as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let mut textures = Vec::new();
+        let mut texture_view = |image: &Option<Arc<RgbaImage>>,
+                                format: wgpu::TextureFormat,
+                                fallback: &wgpu::Texture| match image
+        {
+            Some(image) => {
+                let texture = upload_texture(device, queue, image, format);
+                let view = texture.view().build();
+                textures.push(texture);
+                view
+            }
+            None => fallback.view().build(),
+        };
+        let srgb = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let linear = wgpu::TextureFormat::Rgba8Unorm;
+        let base_color = texture_view(&material.base_color_texture, srgb, &self.white_srgb);
+        let metallic_roughness = texture_view(
+            &material.metallic_roughness_texture,
+            linear,
+            &self.white_linear,
+        );
+        let normal = texture_view(&material.normal_texture, linear, &self.flat_normal);
+        let occlusion = texture_view(&material.occlusion_texture, linear, &self.white_linear);
+        let emissive = texture_view(&material.emissive_texture, srgb, &self.white_srgb);
+
+        let bind_group = wgpu::BindGroupBuilder::new()
+            .buffer_bytes(&uniform_buffer, 0, None)
+            .sampler(&self.sampler)
+            .texture_view(&base_color)
+            .texture_view(&metallic_roughness)
+            .texture_view(&normal)
+            .texture_view(&occlusion)
+            .texture_view(&emissive)
+            .build(device, &self.bind_group_layout);
+
+        GpuMaterial {
+            _uniform_buffer: uniform_buffer,
+            _textures: textures,
+            bind_group,
+            normal_mapped: material.normal_texture.is_some(),
+        }
+    }
+}
+
+fn upload_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: &RgbaImage,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    let texture = wgpu::TextureBuilder::new()
+        .size([image.width(), image.height()])
+        .format(format)
+        .usage(wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST)
+        .build(device);
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        image.as_raw(),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(4 * image.width()),
+            rows_per_image: NonZeroU32::new(image.height()),
+        },
+        texture.extent(),
+    );
+    texture
+}
@@ -0,0 +1,52 @@
+use crate::BasicEntity;
+use nannou::wgpu;
+use nannou::wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+/// The vertex and index buffers of a mesh, shared by every `BasicEntity` with that
+/// `BasicEntity::mesh`. The instances come from the `Batcher`.
+pub struct GpuMesh {
+    pub vertices_buffer: wgpu::Buffer,
+    pub indices_buffer: wgpu::Buffer,
+    pub index_count: u32,
+}
+
+impl GpuMesh {
+    pub fn new(device: &wgpu::Device, entity: &BasicEntity) -> Self {
+        let vertices_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("mesh_vertices_buffer"),
+            contents: bytemuck::cast_slice(&entity.vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let indices_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("mesh_indices_buffer"),
+            contents: bytemuck::cast_slice(&entity.indices),
+            usage: wgpu::BufferUsage::INDEX,
+        });
+        Self {
+            vertices_buffer,
+            indices_buffer,
+            index_count: entity.indices.len() as u32,
+        }
+    }
+}
+
+/// Upload the mesh of every entity, each from the first entity referencing it, so that
+/// `BasicEntity::mesh` indexes into the result. Every index below the largest must be in use.
+pub fn upload_meshes(device: &wgpu::Device, entities: &[BasicEntity]) -> Vec<GpuMesh> {
+    let count = entities
+        .iter()
+        .map(|entity| entity.mesh + 1)
+        .max()
+        .unwrap_or(0);
+    let mut meshes: Vec<Option<GpuMesh>> = (0..count).map(|_| None).collect();
+    for entity in entities {
+        if meshes[entity.mesh].is_none() {
+            meshes[entity.mesh] = Some(GpuMesh::new(device, entity));
+        }
+    }
+    meshes
+        .into_iter()
+        .enumerate()
+        .map(|(index, mesh)| mesh.unwrap_or_else(|| panic!("no entity has mesh {}", index)))
+        .collect()
+}
@@ -0,0 +1,132 @@
+use crate::scene_graph::NodeId;
+use crate::BasicEntity;
+use bytemuck::{Pod, Zeroable};
+use nannou::wgpu;
+use nannou::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use std::collections::HashMap;
+
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#morph-targets
+
+/// The weight of each morph target, by the node whose mesh the targets belong to. Every
+/// primitive of the mesh shares the weights of its node.
+pub type MorphWeights = HashMap<NodeId, Vec<f32>>;
+
+/// The offset of one vertex in one morph target. The fourth component of each vector is padding.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MorphDelta {
+    pub position: [f32; 4],
+    pub normal: [f32; 4],
+    pub tangent: [f32; 4],
+}
+
+// three `[f32; 4]`s under `repr(C)`, so without padding, and valid for any bits
+unsafe impl Zeroable for MorphDelta {}
+unsafe impl Pod for MorphDelta {}
+
+/// The morph targets of a mesh. The vertex shader adds the deltas of every target, scaled by its
+/// weight, to the vertex before it is skinned.
+#[derive(Debug, Clone)]
+pub struct MorphTargets {
+    pub target_count: usize,
+    // target-major: the delta of vertex `v` in target `t` is at `t * vertex_count + v`
+    pub deltas: Vec<MorphDelta>,
+}
+
+/// Where the morph targets of an entity live in the buffers of `MorphResources`.
+#[derive(Debug, Clone, Copy)]
+pub struct MorphSlot {
+    // in deltas
+    pub delta_offset: u32,
+    // in weights
+    pub weight_offset: u32,
+    pub target_count: u32,
+    pub vertex_count: u32,
+}
+
+/// The morph target deltas of every entity in one storage buffer, and their weights in another
+/// that is rewritten every frame.
+pub struct MorphResources {
+    deltas_buffer: wgpu::Buffer,
+    weights_buffer: wgpu::Buffer,
+    // by entity
+    slots: Vec<Option<MorphSlot>>,
+    weight_count: u32,
+}
+
+impl MorphResources {
+    /// The deltas are uploaded once; `entities` must not gain morph targets afterwards.
+    pub fn new(device: &wgpu::Device, entities: &[BasicEntity]) -> Self {
+        let mut deltas: Vec<MorphDelta> = Vec::new();
+        let mut weight_count = 0;
+        let slots = entities
+            .iter()
+            .map(|entity| {
+                entity.morph_targets.as_ref().map(|targets| {
+                    let slot = MorphSlot {
+                        delta_offset: deltas.len() as u32,
+                        weight_offset: weight_count,
+                        target_count: targets.target_count as u32,
+                        vertex_count: entity.vertices.len() as u32,
+                    };
+                    deltas.extend_from_slice(&targets.deltas);
+                    weight_count += targets.target_count as u32;
+                    slot
+                })
+            })
+            .collect();
+
+        // bindings cannot be empty
+        if deltas.is_empty() {
+            deltas.push(MorphDelta::default());
+        }
+        let deltas_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("morph_deltas_buffer"),
+            contents: bytemuck::cast_slice(&deltas),
+            usage: wgpu::BufferUsage::STORAGE,
+        });
+        let weights_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("morph_weights_buffer"),
+            size: weight_count.max(1) as wgpu::BufferAddress * 4,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            deltas_buffer,
+            weights_buffer,
+            slots,
+            weight_count,
+        }
+    }
+
+    pub fn deltas_buffer(&self) -> &wgpu::Buffer {
+        &self.deltas_buffer
+    }
+
+    pub fn weights_buffer(&self) -> &wgpu::Buffer {
+        &self.weights_buffer
+    }
+
+    /// The slot of the entity at `index` in the entities passed to `new`, for `ModelUniform`.
+    pub fn slot(&self, index: usize) -> Option<MorphSlot> {
+        self.slots.get(index).copied().flatten()
+    }
+
+    /// Upload the current weights of every morphed entity. Weights missing from `weights` are 0.
+    pub fn update(&self, queue: &wgpu::Queue, entities: &[BasicEntity], weights: &MorphWeights) {
+        if self.weight_count == 0 {
+            return;
+        }
+        let mut data = Vec::with_capacity(self.weight_count as usize);
+        for (entity, slot) in entities.iter().zip(self.slots.iter()) {
+            if let Some(slot) = slot {
+                let node_weights = weights.get(&entity.node).map_or(&[][..], Vec::as_slice);
+                data.extend(
+                    (0..slot.target_count as usize)
+                        .map(|i| node_weights.get(i).copied().unwrap_or(0.0)),
+                );
+            }
+        }
+        queue.write_buffer(&self.weights_buffer, 0, bytemuck::cast_slice(&data));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::camera::Camera;
+#[cfg(test)]
+use crate::scene_graph::SceneGraph;
+use crate::BasicEntity;
+use nannou::glam::{Mat3, Mat4, Vec2, Vec3};
+
+/// A half-line in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    // unit length, so that distances along the ray are world units
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// The ray through `ndc`, in normalized device coordinates, away from `camera`.
+    ///
+    /// Works with any `CameraProjection`: the ray is found by unprojecting two depths well inside
+    /// the `[0, 1]` range, which are finite even for infinite and reverse-Z projections, and
+    /// starts on the plane of the camera, at the camera itself for perspective projections.
+    pub fn from_ndc(camera: &dyn Camera, ndc: Vec2) -> Self {
+        let view = camera.view_mat4();
+        let eye = view.inverse().w_axis.truncate();
+        let inverse = (camera.projection().projection_mat4() * view).inverse();
+        let a = inverse.project_point3(ndc.extend(0.25));
+        let b = inverse.project_point3(ndc.extend(0.75));
+        let mut direction = (b - a).normalize();
+        // the depths may be reversed
+        if direction.dot(a - eye) < 0.0 {
+            direction = -direction;
+        }
+        Self {
+            origin: a - direction * direction.dot(a - eye),
+            direction,
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// The distance to the nearest point of `sphere` along the ray, or 0.0 if the ray starts
+    /// inside it.
+    pub fn intersect_sphere(&self, sphere: &BoundingSphere) -> Option<f32> {
+        let to_center = sphere.center - self.origin;
+        let along = to_center.dot(self.direction);
+        let squared_miss = to_center.length_squared() - along * along;
+        let squared_radius = sphere.radius * sphere.radius;
+        if squared_miss > squared_radius {
+            return None;
+        }
+        let half_chord = (squared_radius - squared_miss).sqrt();
+        if along + half_chord < 0.0 {
+            return None;
+        }
+        Some((along - half_chord).max(0.0))
+    }
+
+    /// The distance to the nearest point of `aabb` along the ray, or 0.0 if the ray starts
+    /// inside it.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        // the slab method; an axis-parallel ray divides by zero into infinities, which compare
+        // correctly unless the ray lies exactly on a face
+        let inverse = self.direction.recip();
+        let t0 = (aabb.min - self.origin) * inverse;
+        let t1 = (aabb.max - self.origin) * inverse;
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element();
+        if near <= far {
+            Some(near)
+        } else {
+            None
+        }
+    }
+
+    /// The distance to triangle `abc` along the ray, from either side of the triangle.
+    pub fn intersect_triangle(&self, [a, b, c]: [Vec3; 3]) -> Option<f32> {
+        // Möller-Trumbore
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let determinant = ab.dot(p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+        let to_origin = self.origin - a;
+        let u = to_origin.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = to_origin.cross(ab);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = ac.dot(q) * inverse_determinant;
+        if distance >= 0.0 {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where a `Ray` hit an instance of an entity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    // index into the entities the ray was cast against
+    pub entity: usize,
+    // index into `BasicEntity::instances`
+    pub instance: usize,
+    // along the ray, in world units
+    pub distance: f32,
+    pub point: Vec3,
+    // the unit normal of the triangle that was hit, facing the ray
+    pub normal: Vec3,
+}
+
+/// The nearest hit of `ray` on every instance of `entities` it hits, nearest first.
+///
+/// Each instance is tested against its world-space bounds before its triangles. Skinned and
+/// morphed meshes are hit in their rest pose. Only kept as the reference `SceneBvh::cast_ray`,
+/// which finds the same hits without visiting every instance, is tested against.
+#[cfg(test)]
+pub fn cast_ray(ray: &Ray, entities: &[BasicEntity], scene_graph: &SceneGraph) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    for (entity_index, entity) in entities.iter().enumerate() {
+        let world_matrix = scene_graph.world_matrix(entity.node);
+        for (instance_index, instance) in entity.instances.iter().enumerate() {
+            let model_matrix = world_matrix * instance.model_matrix();
+            let bounds = entity.bounds.transformed(&model_matrix);
+            if ray.intersect_sphere(&bounds.sphere).is_none()
+                || ray.intersect_aabb(&bounds.aabb).is_none()
+            {
+                continue;
+            }
+            if let Some((distance, normal)) = intersect_mesh(ray, entity, &model_matrix) {
+                hits.push(Hit {
+                    entity: entity_index,
+                    instance: instance_index,
+                    distance,
+                    point: ray.at(distance),
+                    normal,
+                });
+            }
+        }
+    }
+    hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    hits
+}
+
+/// The distance along `ray` to the nearest triangle of `entity` placed by `model_matrix`, and its
+/// world-space unit normal, facing the ray. Uses the entity's `TriangleBvh` if it has one.
+pub fn intersect_mesh(ray: &Ray, entity: &BasicEntity, model_matrix: &Mat4) -> Option<(f32, Vec3)> {
+    if model_matrix.determinant().abs() < f32::EPSILON {
+        return None;
+    }
+    // in model space; the direction isn't renormalized, so distances stay in world units
+    let inverse = model_matrix.inverse();
+    let local_ray = Ray {
+        origin: inverse.transform_point3(ray.origin),
+        direction: inverse.transform_vector3(ray.direction),
+    };
+    let (distance, [a, b, c]) = match &entity.triangles {
+        Some(triangles) => triangles
+            .cast_ray(&local_ray, f32::INFINITY)
+            .map(|(triangle, distance)| (distance, triangles.triangle(triangle)))?,
+        None => {
+            let position = |index: u32| {
+                let [x, y, z, _] = entity.vertices[index as usize].position;
+                Vec3::new(x, y, z)
+            };
+            entity
+                .indices
+                .chunks_exact(3)
+                .filter_map(|triangle| {
+                    let triangle = [
+                        position(triangle[0]),
+                        position(triangle[1]),
+                        position(triangle[2]),
+                    ];
+                    local_ray
+                        .intersect_triangle(triangle)
+                        .map(|distance| (distance, triangle))
+                })
+                .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))?
+        }
+    };
+
+    // normals transform by the inverse transpose
+    let normal = (Mat3::from_mat4(inverse).transpose() * (b - a).cross(c - a)).normalize();
+    let normal = if normal.dot(ray.direction) > 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    Some((distance, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::scene::fixtures::quad;
+    use crate::camera::projection::{
+        InfiniteReverseZProjection, OrthographicProjection, PerspectiveProjection,
+    };
+    use crate::camera::BasicCamera;
+    use crate::transform::Transform;
+    use nannou::glam::Quat;
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-4), "{} != {}", a, b);
+    }
+
+    // a camera at (0, 0, 5), looking down -Z
+    fn camera_transform() -> Transform {
+        Transform::new(Vec3::new(0.0, 0.0, 5.0), Quat::IDENTITY, Vec3::ONE)
+    }
+
+    #[test]
+    fn center_rays_leave_the_camera_along_its_view_direction() {
+        let perspective = BasicCamera::new(camera_transform(), PerspectiveProjection::default());
+        let reverse_z = BasicCamera::new(camera_transform(), InfiniteReverseZProjection::default());
+        let orthographic = BasicCamera::new(camera_transform(), OrthographicProjection::default());
+        let cameras: [&dyn Camera; 3] = [&perspective, &reverse_z, &orthographic];
+        for camera in cameras.iter() {
+            let ray = Ray::from_ndc(*camera, Vec2::ZERO);
+            assert_vec3_eq(ray.origin, Vec3::new(0.0, 0.0, 5.0));
+            assert_vec3_eq(ray.direction, -Vec3::Z);
+        }
+
+        // the corners of an orthographic view stay parallel to its center
+        let ray = Ray::from_ndc(&orthographic, Vec2::ONE);
+        assert_vec3_eq(ray.origin, Vec3::new(1.0, 1.0, 5.0));
+        assert_vec3_eq(ray.direction, -Vec3::Z);
+    }
+
+    #[test]
+    fn rays_hit_bounding_volumes_in_front_of_them() {
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+        let aabb = Aabb::new(Vec3::new(2.0, -1.0, -1.0), Vec3::new(3.0, 1.0, 1.0));
+        assert_eq!(ray.intersect_aabb(&aabb), Some(2.0));
+        let sphere = BoundingSphere::new(Vec3::new(4.0, 0.0, 0.0), 1.0);
+        assert_eq!(ray.intersect_sphere(&sphere), Some(3.0));
+
+        let behind = Ray::new(Vec3::new(6.0, 0.0, 0.0), Vec3::X);
+        assert_eq!(behind.intersect_aabb(&aabb), None);
+        assert_eq!(behind.intersect_sphere(&sphere), None);
+        let inside = Ray::new(Vec3::new(2.5, 0.0, 0.0), Vec3::Y);
+        assert_eq!(inside.intersect_aabb(&aabb), Some(0.0));
+    }
+
+    #[test]
+    fn hits_are_sorted_by_distance() {
+        let mut scene_graph = SceneGraph::new();
+        let entities = vec![
+            quad(&mut scene_graph, &[Vec3::new(0.0, 0.0, -5.0)]),
+            quad(
+                &mut scene_graph,
+                &[Vec3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)],
+            ),
+        ];
+        scene_graph.update_world_matrices();
+
+        let camera = BasicCamera::new(camera_transform(), PerspectiveProjection::default());
+        let ray = Ray::from_ndc(&camera, Vec2::ZERO);
+        let hits = cast_ray(&ray, &entities, &scene_graph);
+        let hit_instances: Vec<_> = hits.iter().map(|hit| (hit.entity, hit.instance)).collect();
+        assert_eq!(hit_instances, [(1, 1), (0, 0)]);
+        assert!((hits[0].distance - 4.0).abs() < 1e-4);
+        assert_vec3_eq(hits[1].point, Vec3::new(0.0, 0.0, -5.0));
+        assert_vec3_eq(hits[1].normal, Vec3::Z);
+
+        // the back of a quad faces the ray too
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -10.0), Vec3::Z);
+        let hits = cast_ray(&ray, &entities, &scene_graph);
+        assert_eq!(hits[0].entity, 0);
+        assert_vec3_eq(hits[0].normal, -Vec3::Z);
+    }
+}
@@ -0,0 +1,45 @@
+use crate::uniforms::post::BloomUniform;
+use serde::{Deserialize, Serialize};
+
+/// Physically based bloom, after Jimenez 2014, "Next Generation Post Processing in Call of Duty:
+/// Advanced Warfare": the frame is downsampled into a chain of mips, each blurred, which are then
+/// upsampled and summed back up the chain and mixed into the frame. There is no threshold, every
+/// pixel blooms in proportion to its brightness.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bloom {
+    // how much of the bloom is mixed into the frame
+    pub intensity: f32,
+    // of the tent filter upsampling each mip, in texture coordinates; wider spreads the bloom out
+    pub filter_radius: f32,
+    // the most mips the frame is downsampled into, the first being half its size
+    pub mip_count: u32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            intensity: 0.04,
+            filter_radius: 0.005,
+            mip_count: 6,
+        }
+    }
+}
+
+impl Bloom {
+    pub(super) fn uniform(&self) -> BloomUniform {
+        BloomUniform::new(self.filter_radius, self.intensity)
+    }
+
+    // the sizes of the mips of a frame of `size` pixels, halving until a side would drop below a
+    // pixel
+    pub(super) fn mip_sizes(&self, size: [u32; 2]) -> Vec<[u32; 2]> {
+        let mut sizes = Vec::new();
+        let mut size = size;
+        while sizes.len() < self.mip_count as usize && size[0] >= 2 && size[1] >= 2 {
+            size = [size[0] / 2, size[1] / 2];
+            sizes.push(size);
+        }
+        sizes
+    }
+}
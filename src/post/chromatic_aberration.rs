@@ -0,0 +1,22 @@
+use crate::uniforms::post::ChromaticAberrationUniform;
+use serde::{Deserialize, Serialize};
+
+/// Splits red and blue apart towards the edges of the frame, like a cheap lens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChromaticAberration {
+    // how far red and blue are pulled apart at the edges, in texture coordinates
+    pub intensity: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self { intensity: 0.005 }
+    }
+}
+
+impl ChromaticAberration {
+    pub(super) fn uniform(&self) -> ChromaticAberrationUniform {
+        ChromaticAberrationUniform::new(self.intensity)
+    }
+}
@@ -0,0 +1,43 @@
+use crate::uniforms::post::DepthOfFieldUniform;
+use nannou::glam::Mat4;
+use serde::{Deserialize, Serialize};
+
+/// Blurs what is in front of or behind the camera's `focus_distance`, gathering a disk of samples
+/// around each pixel as large as its circle of confusion, after Dennis Gustafsson 2018, "Bokeh
+/// depth of field in a single pass". What is out of focus in front bleeds over what is in focus,
+/// but not the other way around.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DepthOfField {
+    // the blur of what is infinitely far behind the focus, as a fraction of `max_radius`; what is
+    // at half the focus distance is blurred as much
+    pub aperture: f32,
+    // the largest circle of confusion, in pixels
+    pub max_radius: f32,
+    // per pixel, up to `DepthOfField::MAX_SAMPLES`
+    pub sample_count: u32,
+}
+
+impl Default for DepthOfField {
+    fn default() -> Self {
+        Self {
+            aperture: 0.5,
+            max_radius: 8.0,
+            sample_count: 32,
+        }
+    }
+}
+
+impl DepthOfField {
+    pub const MAX_SAMPLES: u32 = 128;
+
+    pub(super) fn uniform(&self, projection: Mat4, focus_distance: f32) -> DepthOfFieldUniform {
+        DepthOfFieldUniform::new(
+            projection,
+            focus_distance,
+            self.aperture,
+            self.max_radius,
+            self.sample_count.clamp(1, Self::MAX_SAMPLES),
+        )
+    }
+}
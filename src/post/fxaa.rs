@@ -0,0 +1,30 @@
+use crate::uniforms::post::FxaaUniform;
+use serde::{Deserialize, Serialize};
+
+/// Fast approximate anti-aliasing, after Timothy Lottes' FXAA: blurs along the edges it finds in
+/// the luma of the frame. Runs on the HDR frame, on luma compressed into the displayable range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fxaa {
+    // the longest an edge is searched along, in pixels
+    pub span_max: f32,
+    // keep the search from blowing up on dark and low contrast edges
+    pub reduce_mul: f32,
+    pub reduce_min: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Self {
+            span_max: 8.0,
+            reduce_mul: 1.0 / 8.0,
+            reduce_min: 1.0 / 128.0,
+        }
+    }
+}
+
+impl Fxaa {
+    pub(super) fn uniform(&self) -> FxaaUniform {
+        FxaaUniform::new(self.span_max, self.reduce_mul, self.reduce_min)
+    }
+}
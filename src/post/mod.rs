@@ -0,0 +1,980 @@
+mod bloom;
+mod chromatic_aberration;
+mod depth_of_field;
+mod fxaa;
+mod ssao;
+mod vignette;
+
+pub use bloom::Bloom;
+pub use chromatic_aberration::ChromaticAberration;
+pub use depth_of_field::DepthOfField;
+pub use fxaa::Fxaa;
+pub use ssao::Ssao;
+pub use vignette::Vignette;
+
+use crate::frame_resources::{FrameResources, FrameTargets};
+use crate::reflection::{self, LayoutEntries, PipelineInterface};
+use crate::render_graph::{PassResources, RenderGraph, TextureDesc, TextureHandle};
+use crate::shader::{ShaderError, ShaderLibrary};
+use crate::uniforms::arena::UniformArena;
+use crate::uniforms::post::{
+    BloomUniform, ChromaticAberrationUniform, DepthOfFieldUniform, FxaaUniform, SsaoUniform,
+    VignetteUniform,
+};
+use crevice::std140::AsStd140;
+use nannou::glam::Mat4;
+use nannou::wgpu;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+/// One effect of a `PostProcessStack`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum PostEffect {
+    Bloom(Bloom),
+    Fxaa(Fxaa),
+    Ssao(Ssao),
+    DepthOfField(DepthOfField),
+    Vignette(Vignette),
+    ChromaticAberration(ChromaticAberration),
+}
+
+impl PostEffect {
+    // whether it samples the depth buffer
+    fn reads_depth(&self) -> bool {
+        matches!(self, PostEffect::Ssao(_) | PostEffect::DepthOfField(_))
+    }
+}
+
+/// An effect of a `PostProcessStack`, along with whether it runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostEffectSlot {
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub effect: PostEffect,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// The effects applied to the HDR frame between the forward pass and the tonemapping, each to the
+/// output of the one before it. Disabled effects keep their place in the stack.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PostProcessStack {
+    pub effects: Vec<PostEffectSlot>,
+}
+
+impl PostProcessStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `effect`, enabled.
+    pub fn with(mut self, effect: PostEffect) -> Self {
+        self.push(effect);
+        self
+    }
+
+    /// Append `effect`, disabled until its slot is enabled.
+    pub fn with_disabled(mut self, effect: PostEffect) -> Self {
+        self.effects.push(PostEffectSlot {
+            enabled: false,
+            effect,
+        });
+        self
+    }
+
+    pub fn push(&mut self, effect: PostEffect) {
+        self.effects.push(PostEffectSlot {
+            enabled: true,
+            effect,
+        });
+    }
+
+    /// The effects that run, in order.
+    pub fn enabled(&self) -> impl Iterator<Item = &PostEffect> {
+        self.effects
+            .iter()
+            .filter(|slot| slot.enabled)
+            .map(|slot| &slot.effect)
+    }
+}
+
+/// The passes of a `PostProcessStack`, from the HDR target and depth buffer of `FrameResources`
+/// into an HDR target of their own, which `TonemapResources` samples.
+///
+/// Every effect but the last writes a transient texture of the graph, so that effects share
+/// memory, and the bind groups sampling them are built as their passes first run. They are kept
+/// until the stack, the size or the graph's transient textures change. The depth buffer is
+/// resolved into a single-sampled target once, for every effect reading it.
+pub struct PostProcessResources {
+    layouts: Layouts,
+    sampler: wgpu::Sampler,
+    // this frame's effect uniforms
+    uniform_arena: UniformArena,
+    // of the depth buffer of the forward pass
+    sample_count: u32,
+    output: wgpu::Texture,
+    output_view: wgpu::TextureView,
+    pipelines: Pipelines,
+    // this frame's enabled effects, with the dynamic offsets of their uniforms
+    effects: Vec<(PostEffect, wgpu::DynamicOffset)>,
+    bind_groups: RefCell<BindGroupCache>,
+    // the groups declared by `add_to_graph` so far, numbering their slots in `bind_groups`
+    group_count: Cell<usize>,
+}
+
+// the bind groups of the last frame's passes, in the order `add_to_graph` declared them
+#[derive(Default)]
+struct BindGroupCache {
+    // the `PassResources::transient_generation` of the views they bind
+    transient_generation: u64,
+    bind_groups: Vec<Option<wgpu::BindGroup>>,
+}
+
+struct Layouts {
+    input: wgpu::BindGroupLayout,
+    texture: wgpu::BindGroupLayout,
+    depth: wgpu::BindGroupLayout,
+    forward_depth: wgpu::BindGroupLayout,
+    bloom: wgpu::BindGroupLayout,
+    fxaa: wgpu::BindGroupLayout,
+    ssao: wgpu::BindGroupLayout,
+    depth_of_field: wgpu::BindGroupLayout,
+    vignette: wgpu::BindGroupLayout,
+    chromatic_aberration: wgpu::BindGroupLayout,
+}
+
+struct Pipelines {
+    depth_resolve: wgpu::RenderPipeline,
+    copy: wgpu::RenderPipeline,
+    // the first downsample averages the frame's pixels weighted against fireflies
+    bloom_downsample_first: wgpu::RenderPipeline,
+    bloom_downsample: wgpu::RenderPipeline,
+    bloom_upsample: wgpu::RenderPipeline,
+    bloom_composite: wgpu::RenderPipeline,
+    fxaa: wgpu::RenderPipeline,
+    ssao: wgpu::RenderPipeline,
+    ssao_apply: wgpu::RenderPipeline,
+    depth_of_field: wgpu::RenderPipeline,
+    vignette: wgpu::RenderPipeline,
+    chromatic_aberration: wgpu::RenderPipeline,
+    // the shaders they were processed from
+    files: Vec<String>,
+}
+
+// how one bind group of a pass is built, once the graph has allocated its textures
+#[derive(Clone, Copy)]
+enum Group<'a> {
+    // the frame so far, at `[[group(0)]]` of `post.wgsl`
+    Input(TextureHandle),
+    // an effect's uniform in the arena
+    Params {
+        layout: &'a wgpu::BindGroupLayout,
+        size: Option<wgpu::BufferSize>,
+        offset: wgpu::DynamicOffset,
+    },
+    Texture(&'a wgpu::BindGroupLayout, TextureHandle),
+}
+
+impl PostProcessResources {
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+    pub const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+    /// The entries of `[[group(0)]]` of `post.wgsl`, the frame so far.
+    pub fn input_layout_entries() -> LayoutEntries {
+        LayoutEntries::new()
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .sampler(wgpu::ShaderStage::FRAGMENT, true)
+    }
+
+    /// The entries of `[[group(1)]]` of an effect's shader, its uniform.
+    pub fn params_layout_entries<T: AsStd140>() -> LayoutEntries {
+        LayoutEntries::new().uniform_buffer::<T>(wgpu::ShaderStage::FRAGMENT, true)
+    }
+
+    /// The entries of `[[group(2)]]` of an effect's shader sampling a second texture.
+    pub fn texture_layout_entries() -> LayoutEntries {
+        LayoutEntries::new().texture(
+            wgpu::ShaderStage::FRAGMENT,
+            false,
+            wgpu::TextureViewDimension::D2,
+            wgpu::TextureSampleType::Float { filterable: true },
+        )
+    }
+
+    /// The entries of `[[group(2)]]` of an effect's shader reading the resolved depth buffer.
+    pub fn depth_layout_entries() -> LayoutEntries {
+        LayoutEntries::new().texture(
+            wgpu::ShaderStage::FRAGMENT,
+            false,
+            wgpu::TextureViewDimension::D2,
+            wgpu::TextureSampleType::Float { filterable: false },
+        )
+    }
+
+    /// The entries of `[[group(0)]]` of `post_depth_resolve.wgsl`. The depth buffer is bound as a
+    /// float texture, as there are no multisampled depth textures in WGSL.
+    pub fn forward_depth_layout_entries(sample_count: u32) -> LayoutEntries {
+        LayoutEntries::new().texture(
+            wgpu::ShaderStage::FRAGMENT,
+            sample_count > 1,
+            wgpu::TextureViewDimension::D2,
+            wgpu::TextureSampleType::Float { filterable: false },
+        )
+    }
+
+    /// `size` and `sample_count` are those of the `FrameResources` the effects read.
+    pub fn new(
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+        size: [u32; 2],
+        sample_count: u32,
+    ) -> Result<Self, ShaderError> {
+        let layouts = Layouts::new(device, sample_count);
+        let pipelines = Pipelines::new(device, shaders, &layouts, sample_count)?;
+        let sampler = wgpu::SamplerBuilder::new()
+            .address_mode(wgpu::AddressMode::ClampToEdge)
+            .mag_filter(wgpu::FilterMode::Linear)
+            .min_filter(wgpu::FilterMode::Linear)
+            .build(device);
+        let (output, output_view) = build_output(device, size);
+        Ok(Self {
+            layouts,
+            sampler,
+            uniform_arena: UniformArena::new(device, 8),
+            sample_count,
+            output,
+            output_view,
+            pipelines,
+            effects: Vec::new(),
+            bind_groups: RefCell::new(BindGroupCache::default()),
+            group_count: Cell::new(0),
+        })
+    }
+
+    /// The view of the target the last effect writes, for `TonemapResources`.
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        &self.output_view
+    }
+
+    /// Rebuild the output at `size`, in pixels, after `FrameResources::resize`.
+    pub fn resize(&mut self, device: &wgpu::Device, size: [u32; 2]) {
+        if size != self.output.size() {
+            let (output, output_view) = build_output(device, size);
+            self.output = output;
+            self.output_view = output_view;
+            // the frame's targets were rebuilt along with the output
+            self.bind_groups.get_mut().bind_groups.clear();
+        }
+    }
+
+    // whether the pipelines need rebuilding after the shaders named `changed` were edited
+    pub fn depends_on(&self, changed: &[String]) -> bool {
+        changed
+            .iter()
+            .any(|name| self.pipelines.files.contains(name))
+    }
+
+    /// Rebuild the pipelines from the current `shaders`, keeping the last ones if that fails.
+    pub fn rebuild_pipelines(
+        &mut self,
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+    ) -> Result<(), ShaderError> {
+        self.pipelines = Pipelines::new(device, shaders, &self.layouts, self.sample_count)?;
+        Ok(())
+    }
+
+    /// Upload the uniforms of this frame's enabled effects. `projection` and `focus_distance` are
+    /// those of the camera the frame is drawn from.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        stack: &PostProcessStack,
+        projection: Mat4,
+        focus_distance: f32,
+    ) {
+        let arena = &mut self.uniform_arena;
+        arena.clear();
+        let effects: Vec<_> = stack
+            .enabled()
+            .map(|effect| {
+                let offset = match effect {
+                    PostEffect::Bloom(bloom) => arena.push(&bloom.uniform()),
+                    PostEffect::Fxaa(fxaa) => arena.push(&fxaa.uniform()),
+                    PostEffect::Ssao(ssao) => arena.push(&ssao.uniform(projection)),
+                    PostEffect::DepthOfField(depth_of_field) => {
+                        arena.push(&depth_of_field.uniform(projection, focus_distance))
+                    }
+                    PostEffect::Vignette(vignette) => arena.push(&vignette.uniform()),
+                    PostEffect::ChromaticAberration(chromatic_aberration) => {
+                        arena.push(&chromatic_aberration.uniform())
+                    }
+                };
+                (effect.clone(), offset)
+            })
+            .collect();
+        let grown = arena.flush(device, queue);
+        if grown || effects != self.effects {
+            self.bind_groups.get_mut().bind_groups.clear();
+        }
+        self.effects = effects;
+    }
+
+    /// Add the passes of the effects enabled in the last `update`, from the targets of the
+    /// forward pass into the output. Returns the output's handle.
+    pub fn add_to_graph<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        device: &'a wgpu::Device,
+        targets: FrameTargets,
+    ) -> TextureHandle {
+        let output = graph.import_texture(
+            "post_output",
+            &self.output_view,
+            TextureDesc::of(&self.output),
+        );
+        let desc = graph.texture_desc(output);
+        let pipelines = &self.pipelines;
+        let layouts = &self.layouts;
+        self.group_count.set(0);
+
+        let mut source = targets.output();
+        if self.effects.is_empty() {
+            self.add_pass(
+                graph,
+                device,
+                "post_copy",
+                &pipelines.copy,
+                output,
+                &[Group::Input(source)],
+            );
+            return output;
+        }
+
+        // resolved once, for every effect reading it
+        let depth = if self.effects.iter().any(|(effect, _)| effect.reads_depth()) {
+            Some(self.add_depth_resolve(graph, device, targets.depth))
+        } else {
+            None
+        };
+        for (index, (effect, offset)) in self.effects.iter().enumerate() {
+            let target = if index + 1 == self.effects.len() {
+                output
+            } else {
+                graph.create_texture(&format!("post_{}", index), desc)
+            };
+            let input = Group::Input(source);
+            let params = |layout, size| Group::Params {
+                layout,
+                size,
+                offset: *offset,
+            };
+            match effect {
+                PostEffect::Bloom(bloom) => {
+                    let params =
+                        params(&layouts.bloom, UniformArena::binding_size::<BloomUniform>());
+                    self.add_bloom(graph, device, bloom, source, target, params);
+                }
+                PostEffect::Fxaa(_) => {
+                    let params = params(&layouts.fxaa, UniformArena::binding_size::<FxaaUniform>());
+                    self.add_pass(
+                        graph,
+                        device,
+                        "fxaa",
+                        &pipelines.fxaa,
+                        target,
+                        &[input, params],
+                    );
+                }
+                PostEffect::Ssao(_) => {
+                    let params = params(&layouts.ssao, UniformArena::binding_size::<SsaoUniform>());
+                    let occlusion = graph.create_texture(
+                        "ssao",
+                        TextureDesc {
+                            format: Self::OCCLUSION_FORMAT,
+                            ..desc
+                        },
+                    );
+                    self.add_pass(
+                        graph,
+                        device,
+                        "ssao",
+                        &pipelines.ssao,
+                        occlusion,
+                        &[
+                            input,
+                            params,
+                            Group::Texture(&layouts.depth, depth.unwrap()),
+                        ],
+                    );
+                    self.add_pass(
+                        graph,
+                        device,
+                        "ssao_apply",
+                        &pipelines.ssao_apply,
+                        target,
+                        &[input, params, Group::Texture(&layouts.texture, occlusion)],
+                    );
+                }
+                PostEffect::DepthOfField(_) => {
+                    let params = params(
+                        &layouts.depth_of_field,
+                        UniformArena::binding_size::<DepthOfFieldUniform>(),
+                    );
+                    self.add_pass(
+                        graph,
+                        device,
+                        "depth_of_field",
+                        &pipelines.depth_of_field,
+                        target,
+                        &[
+                            input,
+                            params,
+                            Group::Texture(&layouts.depth, depth.unwrap()),
+                        ],
+                    );
+                }
+                PostEffect::Vignette(_) => {
+                    let params = params(
+                        &layouts.vignette,
+                        UniformArena::binding_size::<VignetteUniform>(),
+                    );
+                    self.add_pass(
+                        graph,
+                        device,
+                        "vignette",
+                        &pipelines.vignette,
+                        target,
+                        &[input, params],
+                    );
+                }
+                PostEffect::ChromaticAberration(_) => {
+                    let params = params(
+                        &layouts.chromatic_aberration,
+                        UniformArena::binding_size::<ChromaticAberrationUniform>(),
+                    );
+                    self.add_pass(
+                        graph,
+                        device,
+                        "chromatic_aberration",
+                        &pipelines.chromatic_aberration,
+                        target,
+                        &[input, params],
+                    );
+                }
+            }
+            source = target;
+        }
+        output
+    }
+
+    // resolve the depth buffer of the forward pass into a single-sampled target
+    fn add_depth_resolve<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        device: &'a wgpu::Device,
+        forward_depth: TextureHandle,
+    ) -> TextureHandle {
+        let depth = graph.create_texture(
+            "post_depth",
+            TextureDesc {
+                size: graph.texture_desc(forward_depth).size,
+                format: Self::DEPTH_FORMAT,
+                sample_count: 1,
+            },
+        );
+        self.add_pass(
+            graph,
+            device,
+            "depth_resolve",
+            &self.pipelines.depth_resolve,
+            depth,
+            &[Group::Texture(&self.layouts.forward_depth, forward_depth)],
+        );
+        depth
+    }
+
+    // downsample `source` into a chain of mips, upsample them back up while summing them, and mix
+    // the sum into `source`
+    fn add_bloom<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        device: &'a wgpu::Device,
+        bloom: &Bloom,
+        source: TextureHandle,
+        target: TextureHandle,
+        params: Group<'a>,
+    ) {
+        let pipelines = &self.pipelines;
+        let mips: Vec<TextureHandle> = bloom
+            .mip_sizes(graph.texture_desc(source).size)
+            .into_iter()
+            .enumerate()
+            .map(|(level, size)| {
+                graph.create_texture(
+                    &format!("bloom_downsample_{}", level),
+                    TextureDesc {
+                        size,
+                        format: FrameResources::COLOR_FORMAT,
+                        sample_count: 1,
+                    },
+                )
+            })
+            .collect();
+        let last = match mips.last() {
+            Some(&last) => last,
+            None => {
+                self.add_pass(
+                    graph,
+                    device,
+                    "bloom_copy",
+                    &pipelines.copy,
+                    target,
+                    &[Group::Input(source)],
+                );
+                return;
+            }
+        };
+
+        let mut previous = source;
+        for (level, &mip) in mips.iter().enumerate() {
+            let pipeline = if level == 0 {
+                &pipelines.bloom_downsample_first
+            } else {
+                &pipelines.bloom_downsample
+            };
+            let name = format!("bloom_downsample_{}", level);
+            self.add_pass(
+                graph,
+                device,
+                &name,
+                pipeline,
+                mip,
+                &[Group::Input(previous)],
+            );
+            previous = mip;
+        }
+
+        // each mip of the upsampled chain holds its downsampled mip plus the blurred ones below
+        let mut sum = last;
+        for (level, &mip) in mips.iter().enumerate().rev().skip(1) {
+            let name = format!("bloom_upsample_{}", level);
+            let upsampled = graph.create_texture(&name, graph.texture_desc(mip));
+            self.add_pass(
+                graph,
+                device,
+                &name,
+                &pipelines.bloom_upsample,
+                upsampled,
+                &[
+                    Group::Input(sum),
+                    params,
+                    Group::Texture(&self.layouts.texture, mip),
+                ],
+            );
+            sum = upsampled;
+        }
+        self.add_pass(
+            graph,
+            device,
+            "bloom_composite",
+            &pipelines.bloom_composite,
+            target,
+            &[
+                Group::Input(source),
+                params,
+                Group::Texture(&self.layouts.texture, sum),
+            ],
+        );
+    }
+
+    // a pass drawing `pipeline` over the whole of `target`, with `groups` bound in order
+    fn add_pass<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        device: &'a wgpu::Device,
+        name: &str,
+        pipeline: &'a wgpu::RenderPipeline,
+        target: TextureHandle,
+        groups: &[Group<'a>],
+    ) {
+        let groups = groups.to_vec();
+        let first_group = self.group_count.get();
+        self.group_count.set(first_group + groups.len());
+        let mut pass = graph.add_pass(name);
+        for group in groups.iter() {
+            match *group {
+                Group::Input(texture) | Group::Texture(_, texture) => {
+                    pass = pass.read_texture(texture)
+                }
+                Group::Params { .. } => (),
+            }
+        }
+        let label = name.to_string();
+        pass.color_attachment(target, wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT))
+            .encode(move |encoder, resources| {
+                let mut cache = self.bind_groups.borrow_mut();
+                if cache.transient_generation != resources.transient_generation() {
+                    cache.transient_generation = resources.transient_generation();
+                    cache.bind_groups.clear();
+                }
+                let end = first_group + groups.len();
+                if cache.bind_groups.len() < end {
+                    cache.bind_groups.resize_with(end, || None);
+                }
+                for (slot, group) in cache.bind_groups[first_group..end].iter_mut().zip(&groups) {
+                    if slot.is_none() {
+                        *slot = Some(self.bind_group(device, resources, group));
+                    }
+                }
+                let bind_groups = &cache.bind_groups[first_group..end];
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(&label),
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: resources.texture_view(target),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                render_pass.set_pipeline(pipeline);
+                for (index, (bind_group, group)) in bind_groups.iter().zip(&groups).enumerate() {
+                    let offsets = match group {
+                        Group::Params { offset, .. } => std::slice::from_ref(offset),
+                        Group::Input(_) | Group::Texture(..) => &[],
+                    };
+                    render_pass.set_bind_group(index as u32, bind_group.as_ref().unwrap(), offsets);
+                }
+                render_pass.draw(0..3, 0..1);
+            });
+    }
+
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        resources: &PassResources,
+        group: &Group,
+    ) -> wgpu::BindGroup {
+        match *group {
+            Group::Input(texture) => wgpu::BindGroupBuilder::new()
+                .texture_view(resources.texture_view(texture))
+                .sampler(&self.sampler)
+                .build(device, &self.layouts.input),
+            Group::Params { layout, size, .. } => wgpu::BindGroupBuilder::new()
+                .buffer_bytes(self.uniform_arena.buffer(), 0, size)
+                .build(device, layout),
+            Group::Texture(layout, texture) => wgpu::BindGroupBuilder::new()
+                .texture_view(resources.texture_view(texture))
+                .build(device, layout),
+        }
+    }
+}
+
+fn build_output(device: &wgpu::Device, size: [u32; 2]) -> (wgpu::Texture, wgpu::TextureView) {
+    let output = wgpu::TextureBuilder::new()
+        .size(size)
+        .format(FrameResources::COLOR_FORMAT)
+        .usage(wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+        .build(device);
+    let output_view = output.view().build();
+    (output, output_view)
+}
+
+// the bind group layouts of the post-process pipelines
+#[derive(Debug, Clone, Copy)]
+enum GroupLayout {
+    Input,
+    Texture,
+    Depth,
+    ForwardDepth,
+    Bloom,
+    Fxaa,
+    Ssao,
+    DepthOfField,
+    Vignette,
+    ChromaticAberration,
+}
+
+impl GroupLayout {
+    fn entries(self, sample_count: u32) -> LayoutEntries {
+        type Resources = PostProcessResources;
+        match self {
+            GroupLayout::Input => Resources::input_layout_entries(),
+            GroupLayout::Texture => Resources::texture_layout_entries(),
+            GroupLayout::Depth => Resources::depth_layout_entries(),
+            GroupLayout::ForwardDepth => Resources::forward_depth_layout_entries(sample_count),
+            GroupLayout::Bloom => Resources::params_layout_entries::<BloomUniform>(),
+            GroupLayout::Fxaa => Resources::params_layout_entries::<FxaaUniform>(),
+            GroupLayout::Ssao => Resources::params_layout_entries::<SsaoUniform>(),
+            GroupLayout::DepthOfField => Resources::params_layout_entries::<DepthOfFieldUniform>(),
+            GroupLayout::Vignette => Resources::params_layout_entries::<VignetteUniform>(),
+            GroupLayout::ChromaticAberration => {
+                Resources::params_layout_entries::<ChromaticAberrationUniform>()
+            }
+        }
+    }
+}
+
+impl Layouts {
+    fn new(device: &wgpu::Device, sample_count: u32) -> Self {
+        let build = |layout: GroupLayout| layout.entries(sample_count).build(device);
+        Self {
+            input: build(GroupLayout::Input),
+            texture: build(GroupLayout::Texture),
+            depth: build(GroupLayout::Depth),
+            forward_depth: build(GroupLayout::ForwardDepth),
+            bloom: build(GroupLayout::Bloom),
+            fxaa: build(GroupLayout::Fxaa),
+            ssao: build(GroupLayout::Ssao),
+            depth_of_field: build(GroupLayout::DepthOfField),
+            vignette: build(GroupLayout::Vignette),
+            chromatic_aberration: build(GroupLayout::ChromaticAberration),
+        }
+    }
+
+    fn get(&self, layout: GroupLayout) -> &wgpu::BindGroupLayout {
+        match layout {
+            GroupLayout::Input => &self.input,
+            GroupLayout::Texture => &self.texture,
+            GroupLayout::Depth => &self.depth,
+            GroupLayout::ForwardDepth => &self.forward_depth,
+            GroupLayout::Bloom => &self.bloom,
+            GroupLayout::Fxaa => &self.fxaa,
+            GroupLayout::Ssao => &self.ssao,
+            GroupLayout::DepthOfField => &self.depth_of_field,
+            GroupLayout::Vignette => &self.vignette,
+            GroupLayout::ChromaticAberration => &self.chromatic_aberration,
+        }
+    }
+}
+
+// a post-process pipeline: its shader, the `#define`s it is processed with, the layouts of its
+// bind groups and the format of its target
+struct Program {
+    shader: &'static str,
+    defines: BTreeMap<String, String>,
+    groups: Vec<GroupLayout>,
+    format: wgpu::TextureFormat,
+}
+
+impl Program {
+    fn new(shader: &'static str, groups: &[GroupLayout]) -> Self {
+        Self {
+            shader,
+            defines: BTreeMap::new(),
+            groups: groups.to_vec(),
+            format: FrameResources::COLOR_FORMAT,
+        }
+    }
+
+    fn define(mut self, name: &str) -> Self {
+        self.defines.insert(name.to_string(), String::new());
+        self
+    }
+
+    fn format(self, format: wgpu::TextureFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    // the entries of `groups`, to validate the shader against
+    fn interface(&self, sample_count: u32) -> Vec<LayoutEntries> {
+        self.groups
+            .iter()
+            .map(|layout| layout.entries(sample_count))
+            .collect()
+    }
+}
+
+// every post-process pipeline, in the order of the fields of `Pipelines`
+fn programs(sample_count: u32) -> Vec<Program> {
+    use GroupLayout::*;
+    let depth_resolve = Program::new("post_depth_resolve.wgsl", &[ForwardDepth])
+        .format(PostProcessResources::DEPTH_FORMAT);
+    vec![
+        if sample_count > 1 {
+            depth_resolve.define("MULTISAMPLED")
+        } else {
+            depth_resolve
+        },
+        Program::new("post_copy.wgsl", &[Input]),
+        Program::new("bloom_downsample.wgsl", &[Input]).define("KARIS_AVERAGE"),
+        Program::new("bloom_downsample.wgsl", &[Input]),
+        Program::new("bloom_upsample.wgsl", &[Input, Bloom, Texture]),
+        Program::new("bloom_composite.wgsl", &[Input, Bloom, Texture]),
+        Program::new("fxaa.wgsl", &[Input, Fxaa]),
+        Program::new("ssao_occlusion.wgsl", &[Input, Ssao, Depth])
+            .format(PostProcessResources::OCCLUSION_FORMAT),
+        Program::new("ssao_apply.wgsl", &[Input, Ssao, Texture]),
+        Program::new("depth_of_field.wgsl", &[Input, DepthOfField, Depth]),
+        Program::new("vignette.wgsl", &[Input, Vignette]),
+        Program::new("chromatic_aberration.wgsl", &[Input, ChromaticAberration]),
+    ]
+}
+
+impl Pipelines {
+    fn new(
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+        layouts: &Layouts,
+        sample_count: u32,
+    ) -> Result<Self, ShaderError> {
+        let mut files = Vec::new();
+        let mut pipelines = Vec::new();
+        for program in programs(sample_count) {
+            let name = program.shader;
+            let shader = shaders.process(name, &program.defines)?;
+            let module = shader.parse()?;
+            reflection::validate_module(
+                name,
+                &module,
+                &PipelineInterface {
+                    vertex_buffers: &[],
+                    bind_groups: &program.interface(sample_count),
+                },
+            )?;
+            files.extend(shader.files().iter().cloned());
+            let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(shader.source().to_string())),
+                flags: wgpu::ShaderFlags::default(),
+                label: Some(name),
+            });
+            let bind_group_layouts: Vec<&wgpu::BindGroupLayout> = program
+                .groups
+                .iter()
+                .map(|&layout| layouts.get(layout))
+                .collect();
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+            pipelines.push(
+                wgpu::RenderPipelineBuilder::from_layout(&layout, &module)
+                    .fragment_shader(&module)
+                    // no blending, which the float depth target doesn't support
+                    .color_state(wgpu::ColorTargetState {
+                        format: program.format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrite::ALL,
+                    })
+                    .build(device),
+            );
+        }
+
+        files.sort();
+        files.dedup();
+        let mut pipelines = pipelines.into_iter();
+        let mut next = || pipelines.next().unwrap();
+        Ok(Self {
+            depth_resolve: next(),
+            copy: next(),
+            bloom_downsample_first: next(),
+            bloom_downsample: next(),
+            bloom_upsample: next(),
+            bloom_composite: next(),
+            fxaa: next(),
+            ssao: next(),
+            ssao_apply: next(),
+            depth_of_field: next(),
+            vignette: next(),
+            chromatic_aberration: next(),
+            files,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post_process_shaders_match_their_pipelines() {
+        let library = ShaderLibrary::builtin();
+        for &sample_count in [1, 4].iter() {
+            for program in programs(sample_count) {
+                let shader = library
+                    .process(program.shader, &program.defines)
+                    .unwrap_or_else(|err| panic!("{}", err));
+                let module = shader.parse().unwrap_or_else(|err| panic!("{}", err));
+                reflection::validate_module(
+                    program.shader,
+                    &module,
+                    &PipelineInterface {
+                        vertex_buffers: &[],
+                        bind_groups: &program.interface(sample_count),
+                    },
+                )
+                .unwrap_or_else(|err| panic!("{}", err));
+            }
+        }
+    }
+
+    #[test]
+    fn stack_round_trips_through_json() {
+        let stack = PostProcessStack::new()
+            .with(PostEffect::Ssao(Ssao::default()))
+            .with_disabled(PostEffect::DepthOfField(DepthOfField::default()))
+            .with(PostEffect::Bloom(Bloom {
+                intensity: 0.1,
+                ..Default::default()
+            }))
+            .with(PostEffect::Fxaa(Fxaa::default()))
+            .with(PostEffect::ChromaticAberration(
+                ChromaticAberration::default(),
+            ))
+            .with(PostEffect::Vignette(Vignette::default()));
+        let json = serde_json::to_string(&stack).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PostProcessStack>(&json).unwrap(),
+            stack
+        );
+
+        // missing parameters take their defaults, and effects are enabled unless said otherwise
+        let stack: PostProcessStack = serde_json::from_str(
+            r#"{ "effects": [{ "type": "Vignette", "intensity": 0.8 }, { "type": "Fxaa", "enabled": false }] }"#,
+        )
+        .unwrap();
+        let effects: Vec<&PostEffect> = stack.enabled().collect();
+        assert_eq!(
+            effects,
+            [&PostEffect::Vignette(Vignette {
+                intensity: 0.8,
+                ..Default::default()
+            })]
+        );
+        assert_eq!(stack.effects.len(), 2);
+    }
+
+    #[test]
+    fn bloom_mips_halve_down_to_a_pixel() {
+        let bloom = Bloom::default();
+        assert_eq!(
+            bloom.mip_sizes([1024, 576]),
+            [
+                [512, 288],
+                [256, 144],
+                [128, 72],
+                [64, 36],
+                [32, 18],
+                [16, 9]
+            ]
+        );
+        assert_eq!(bloom.mip_sizes([8, 3]), [[4, 1]]);
+        assert!(bloom.mip_sizes([1, 100]).is_empty());
+    }
+}
@@ -0,0 +1,44 @@
+use crate::uniforms::post::SsaoUniform;
+use nannou::glam::Mat4;
+use serde::{Deserialize, Serialize};
+
+/// Screen space ambient occlusion: darkens creases and corners by how much of a hemisphere around
+/// each pixel lies behind the depth buffer. Normals are reconstructed from the depth buffer, and
+/// the occlusion is blurred before it darkens the frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ssao {
+    // of the hemisphere sampled around each pixel, in world units
+    pub radius: f32,
+    // keeps surfaces from occluding themselves, in world units
+    pub bias: f32,
+    // the power the ambient occlusion is raised to; higher darkens it
+    pub intensity: f32,
+    // per pixel, up to `Ssao::MAX_SAMPLES`
+    pub sample_count: u32,
+}
+
+impl Default for Ssao {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.5,
+            sample_count: 16,
+        }
+    }
+}
+
+impl Ssao {
+    pub const MAX_SAMPLES: u32 = 64;
+
+    pub(super) fn uniform(&self, projection: Mat4) -> SsaoUniform {
+        SsaoUniform::new(
+            projection,
+            self.radius,
+            self.bias,
+            self.intensity,
+            self.sample_count.clamp(1, Self::MAX_SAMPLES),
+        )
+    }
+}
@@ -0,0 +1,30 @@
+use crate::uniforms::post::VignetteUniform;
+use serde::{Deserialize, Serialize};
+
+/// Darkens the frame towards its corners.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Vignette {
+    // how dark the corners get, from 0.0 to 1.0
+    pub intensity: f32,
+    // where the darkening starts, from the center, where 1.0 reaches the corners
+    pub radius: f32,
+    // over which the darkening fades in, past `radius`
+    pub smoothness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.4,
+            radius: 0.5,
+            smoothness: 0.5,
+        }
+    }
+}
+
+impl Vignette {
+    pub(super) fn uniform(&self) -> VignetteUniform {
+        VignetteUniform::new(self.intensity, self.radius, self.smoothness)
+    }
+}
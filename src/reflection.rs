@@ -0,0 +1,808 @@
+use crevice::std140::AsStd140;
+use naga::valid::{Capabilities, ModuleInfo, ValidationFlags, Validator};
+use naga::{Handle, ImageClass, ImageDimension, ScalarKind, StorageClass, TypeInner};
+use nannou::wgpu;
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// The entries of a bind group layout, kept so that shaders can be checked against them before a
+/// pipeline using the layout is built.
+///
+/// Mirrors `wgpu::BindGroupLayoutBuilder`, except that uniform buffers take the Rust type they
+/// hold, whose std140 size becomes the minimum binding size of the entry.
+#[derive(Debug, Clone, Default)]
+pub struct LayoutEntries {
+    // by binding
+    entries: Vec<(wgpu::ShaderStage, wgpu::BindingType)>,
+}
+
+impl LayoutEntries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn binding(mut self, visibility: wgpu::ShaderStage, ty: wgpu::BindingType) -> Self {
+        self.entries.push((visibility, ty));
+        self
+    }
+
+    pub fn uniform_buffer<T: AsStd140>(
+        self,
+        visibility: wgpu::ShaderStage,
+        has_dynamic_offset: bool,
+    ) -> Self {
+        self.binding(
+            visibility,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset,
+                min_binding_size: wgpu::BufferSize::new(T::std140_size_static() as u64),
+            },
+        )
+    }
+
+    pub fn storage_buffer(
+        self,
+        visibility: wgpu::ShaderStage,
+        has_dynamic_offset: bool,
+        read_only: bool,
+    ) -> Self {
+        self.binding(
+            visibility,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset,
+                min_binding_size: None,
+            },
+        )
+    }
+
+    pub fn sampler(self, visibility: wgpu::ShaderStage, filtering: bool) -> Self {
+        self.binding(
+            visibility,
+            wgpu::BindingType::Sampler {
+                filtering,
+                comparison: false,
+            },
+        )
+    }
+
+    pub fn comparison_sampler(self, visibility: wgpu::ShaderStage, filtering: bool) -> Self {
+        self.binding(
+            visibility,
+            wgpu::BindingType::Sampler {
+                filtering,
+                comparison: true,
+            },
+        )
+    }
+
+    pub fn texture(
+        self,
+        visibility: wgpu::ShaderStage,
+        multisampled: bool,
+        view_dimension: wgpu::TextureViewDimension,
+        sample_type: wgpu::TextureSampleType,
+    ) -> Self {
+        self.binding(
+            visibility,
+            wgpu::BindingType::Texture {
+                multisampled,
+                view_dimension,
+                sample_type,
+            },
+        )
+    }
+
+    pub fn entries(&self) -> &[(wgpu::ShaderStage, wgpu::BindingType)] {
+        &self.entries
+    }
+
+    pub fn build(&self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        self.entries
+            .iter()
+            .fold(
+                wgpu::BindGroupLayoutBuilder::new(),
+                |builder, (visibility, ty)| builder.binding(*visibility, *ty),
+            )
+            .build(device)
+    }
+}
+
+/// The vertex buffers and bind group layouts a render pipeline is built with.
+pub struct PipelineInterface<'a> {
+    pub vertex_buffers: &'a [wgpu::VertexBufferLayout<'a>],
+    // by group
+    pub bind_groups: &'a [LayoutEntries],
+}
+
+/// Ways in which a shader disagrees with the Rust side of its pipeline.
+#[derive(Debug, Error)]
+pub enum ReflectionError {
+    #[error("{shader} is invalid: {message}")]
+    Invalid { shader: String, message: String },
+    #[error("{shader} has no {stage:?} entry point")]
+    MissingEntryPoint {
+        shader: String,
+        stage: naga::ShaderStage,
+    },
+    #[error("{shader} reads vertex input {location}, which no vertex buffer provides")]
+    MissingLocation { shader: String, location: u32 },
+    #[error("more than one vertex attribute of {shader}'s pipeline has location {location}")]
+    DuplicateLocation { shader: String, location: u32 },
+    #[error(
+        "{shader} reads vertex input {location} as {shader_type}, which doesn't match its {format:?} attribute"
+    )]
+    FormatMismatch {
+        shader: String,
+        location: u32,
+        format: wgpu::VertexFormat,
+        shader_type: String,
+    },
+    #[error(
+        "{shader} binds `{name}` at group {group}, binding {binding}, which isn't in the layout"
+    )]
+    MissingBinding {
+        shader: String,
+        name: String,
+        group: u32,
+        binding: u32,
+    },
+    #[error("`{name}` at group {group}, binding {binding} of {shader} doesn't match its layout entry {entry:?}")]
+    BindingTypeMismatch {
+        shader: String,
+        name: String,
+        group: u32,
+        binding: u32,
+        entry: wgpu::BindingType,
+    },
+    #[error("`{name}` at group {group}, binding {binding} of {shader} is used by {used_by:?}, but only visible to {visibility:?}")]
+    BindingVisibility {
+        shader: String,
+        name: String,
+        group: u32,
+        binding: u32,
+        used_by: wgpu::ShaderStage,
+        visibility: wgpu::ShaderStage,
+    },
+    #[error("`{name}` at group {group}, binding {binding} of {shader} is {shader_size} bytes, but its Rust type is {rust_size} bytes in std140")]
+    UniformSizeMismatch {
+        shader: String,
+        name: String,
+        group: u32,
+        binding: u32,
+        shader_size: u32,
+        rust_size: u64,
+    },
+}
+
+/// Check that the parsed `module` of a pipeline agrees with its vertex buffers and bind group
+/// layouts: every vertex input has an attribute of a matching format, and every resource binding
+/// has a layout entry of a matching type, size and visibility. Compute shaders, having no vertex
+/// inputs, are only checked against their layouts. `shader` names it in errors.
+pub fn validate_module(
+    shader: &str,
+    module: &naga::Module,
+    interface: &PipelineInterface,
+) -> Result<(), ReflectionError> {
+    let info = Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(module)
+        .map_err(|err| ReflectionError::Invalid {
+            shader: shader.to_string(),
+            message: err.to_string(),
+        })?;
+    // compute shaders have no vertex stage to feed
+    let is_compute = module
+        .entry_points
+        .iter()
+        .all(|entry_point| entry_point.stage == naga::ShaderStage::Compute);
+    if !is_compute {
+        validate_vertex_inputs(shader, module, interface.vertex_buffers)?;
+    }
+    validate_bindings(shader, module, &info, interface.bind_groups)
+}
+
+fn validate_vertex_inputs(
+    shader: &str,
+    module: &naga::Module,
+    vertex_buffers: &[wgpu::VertexBufferLayout],
+) -> Result<(), ReflectionError> {
+    let entry_point = module
+        .entry_points
+        .iter()
+        .find(|entry_point| entry_point.stage == naga::ShaderStage::Vertex)
+        .ok_or_else(|| ReflectionError::MissingEntryPoint {
+            shader: shader.to_string(),
+            stage: naga::ShaderStage::Vertex,
+        })?;
+
+    let mut formats = BTreeMap::new();
+    for attribute in vertex_buffers.iter().flat_map(|buffer| buffer.attributes) {
+        if formats
+            .insert(attribute.shader_location, attribute.format)
+            .is_some()
+        {
+            return Err(ReflectionError::DuplicateLocation {
+                shader: shader.to_string(),
+                location: attribute.shader_location,
+            });
+        }
+    }
+
+    for (location, ty) in vertex_inputs(module, &entry_point.function) {
+        let format = *formats
+            .get(&location)
+            .ok_or_else(|| ReflectionError::MissingLocation {
+                shader: shader.to_string(),
+                location,
+            })?;
+        let (kind, width, components) = format_components(format);
+        let matches = match module.types[ty].inner {
+            TypeInner::Scalar { kind: k, width: w } => (k, w, 1) == (kind, width, components),
+            TypeInner::Vector {
+                size,
+                kind: k,
+                width: w,
+            } => (k, w, size as u8) == (kind, width, components),
+            _ => false,
+        };
+        if !matches {
+            return Err(ReflectionError::FormatMismatch {
+                shader: shader.to_string(),
+                location,
+                format,
+                shader_type: type_name(module, ty),
+            });
+        }
+    }
+    Ok(())
+}
+
+// the location and type of every input of `function`, including the members of struct arguments
+fn vertex_inputs(
+    module: &naga::Module,
+    function: &naga::Function,
+) -> Vec<(u32, Handle<naga::Type>)> {
+    let mut inputs = Vec::new();
+    for argument in function.arguments.iter() {
+        match (&argument.binding, &module.types[argument.ty].inner) {
+            (Some(naga::Binding::Location { location, .. }), _) => {
+                inputs.push((*location, argument.ty))
+            }
+            (None, TypeInner::Struct { members, .. }) => {
+                inputs.extend(members.iter().filter_map(|member| match member.binding {
+                    Some(naga::Binding::Location { location, .. }) => Some((location, member.ty)),
+                    _ => None,
+                }))
+            }
+            _ => (),
+        }
+    }
+    inputs
+}
+
+// the scalar kind, byte width and component count a vertex format reads as in a shader
+fn format_components(format: wgpu::VertexFormat) -> (ScalarKind, u8, u8) {
+    use wgpu::VertexFormat as F;
+    match format {
+        F::Uint8x2 | F::Uint16x2 | F::Uint32x2 => (ScalarKind::Uint, 4, 2),
+        F::Uint8x4 | F::Uint16x4 | F::Uint32x4 => (ScalarKind::Uint, 4, 4),
+        F::Uint32 => (ScalarKind::Uint, 4, 1),
+        F::Uint32x3 => (ScalarKind::Uint, 4, 3),
+        F::Sint8x2 | F::Sint16x2 | F::Sint32x2 => (ScalarKind::Sint, 4, 2),
+        F::Sint8x4 | F::Sint16x4 | F::Sint32x4 => (ScalarKind::Sint, 4, 4),
+        F::Sint32 => (ScalarKind::Sint, 4, 1),
+        F::Sint32x3 => (ScalarKind::Sint, 4, 3),
+        F::Unorm8x2 | F::Snorm8x2 | F::Unorm16x2 | F::Snorm16x2 | F::Float16x2 | F::Float32x2 => {
+            (ScalarKind::Float, 4, 2)
+        }
+        F::Unorm8x4 | F::Snorm8x4 | F::Unorm16x4 | F::Snorm16x4 | F::Float16x4 | F::Float32x4 => {
+            (ScalarKind::Float, 4, 4)
+        }
+        F::Float32 => (ScalarKind::Float, 4, 1),
+        F::Float32x3 => (ScalarKind::Float, 4, 3),
+        F::Float64 => (ScalarKind::Float, 8, 1),
+        F::Float64x2 => (ScalarKind::Float, 8, 2),
+        F::Float64x3 => (ScalarKind::Float, 8, 3),
+        F::Float64x4 => (ScalarKind::Float, 8, 4),
+    }
+}
+
+// as written in WGSL, for the types of vertex inputs
+fn type_name(module: &naga::Module, ty: Handle<naga::Type>) -> String {
+    let scalar = |kind, width| match (kind, width) {
+        (ScalarKind::Float, 8) => "f64",
+        (ScalarKind::Float, _) => "f32",
+        (ScalarKind::Sint, _) => "i32",
+        (ScalarKind::Uint, _) => "u32",
+        (ScalarKind::Bool, _) => "bool",
+    };
+    match module.types[ty].inner {
+        TypeInner::Scalar { kind, width } => scalar(kind, width).to_string(),
+        TypeInner::Vector { size, kind, width } => {
+            format!("vec{}<{}>", size as u8, scalar(kind, width))
+        }
+        ref inner => format!("{:?}", inner),
+    }
+}
+
+fn validate_bindings(
+    shader: &str,
+    module: &naga::Module,
+    info: &ModuleInfo,
+    bind_groups: &[LayoutEntries],
+) -> Result<(), ReflectionError> {
+    let used_by = global_stages(module, info);
+    for (handle, global) in module.global_variables.iter() {
+        let resource = match &global.binding {
+            Some(resource) => resource,
+            None => continue,
+        };
+        let (group, binding) = (resource.group, resource.binding);
+        let name = global.name.clone().unwrap_or_default();
+        let (visibility, entry) = *bind_groups
+            .get(group as usize)
+            .and_then(|entries| entries.entries().get(binding as usize))
+            .ok_or_else(|| ReflectionError::MissingBinding {
+                shader: shader.to_string(),
+                name: name.clone(),
+                group,
+                binding,
+            })?;
+
+        let used_by = used_by
+            .get(&handle)
+            .copied()
+            .unwrap_or_else(wgpu::ShaderStage::empty);
+        if !visibility.contains(used_by) {
+            return Err(ReflectionError::BindingVisibility {
+                shader: shader.to_string(),
+                name,
+                group,
+                binding,
+                used_by,
+                visibility,
+            });
+        }
+
+        let inner = &module.types[global.ty].inner;
+        let matches = match (global.class, inner, entry) {
+            (
+                StorageClass::Uniform,
+                _,
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    min_binding_size,
+                    ..
+                },
+            ) => {
+                let shader_size = inner.span(&module.constants);
+                // std140 rounds the size of a struct up to its 16 byte alignment
+                let padded_size = (shader_size as u64 + 15) & !15;
+                match min_binding_size {
+                    Some(rust_size) if rust_size.get() != padded_size => {
+                        return Err(ReflectionError::UniformSizeMismatch {
+                            shader: shader.to_string(),
+                            name,
+                            group,
+                            binding,
+                            shader_size,
+                            rust_size: rust_size.get(),
+                        })
+                    }
+                    _ => true,
+                }
+            }
+            (
+                StorageClass::Storage,
+                _,
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only },
+                    ..
+                },
+            ) => !read_only || !global.storage_access.contains(naga::StorageAccess::STORE),
+            (
+                StorageClass::Handle,
+                TypeInner::Image {
+                    dim,
+                    arrayed,
+                    class,
+                },
+                wgpu::BindingType::Texture {
+                    multisampled,
+                    view_dimension,
+                    sample_type,
+                },
+            ) => {
+                let class_matches = match (class, sample_type) {
+                    (ImageClass::Depth, wgpu::TextureSampleType::Depth) => !multisampled,
+                    (ImageClass::Sampled { kind, multi }, sample_type) => {
+                        let sample_kind = match sample_type {
+                            wgpu::TextureSampleType::Float { .. } => ScalarKind::Float,
+                            wgpu::TextureSampleType::Sint => ScalarKind::Sint,
+                            wgpu::TextureSampleType::Uint => ScalarKind::Uint,
+                            wgpu::TextureSampleType::Depth => ScalarKind::Float,
+                        };
+                        *kind == sample_kind && *multi == multisampled
+                    }
+                    _ => false,
+                };
+                class_matches && view_dimension == image_view_dimension(*dim, *arrayed)
+            }
+            (
+                StorageClass::Handle,
+                TypeInner::Image {
+                    dim,
+                    arrayed,
+                    class: ImageClass::Storage(_),
+                },
+                wgpu::BindingType::StorageTexture { view_dimension, .. },
+            ) => view_dimension == image_view_dimension(*dim, *arrayed),
+            (
+                StorageClass::Handle,
+                TypeInner::Sampler { comparison },
+                wgpu::BindingType::Sampler {
+                    comparison: entry_comparison,
+                    ..
+                },
+            ) => *comparison == entry_comparison,
+            _ => false,
+        };
+        if !matches {
+            return Err(ReflectionError::BindingTypeMismatch {
+                shader: shader.to_string(),
+                name,
+                group,
+                binding,
+                entry,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn image_view_dimension(dim: ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+// the stages whose entry points use each global, directly or through the functions they call
+fn global_stages(
+    module: &naga::Module,
+    info: &ModuleInfo,
+) -> HashMap<Handle<naga::GlobalVariable>, wgpu::ShaderStage> {
+    let mut stages = HashMap::new();
+    for (index, entry_point) in module.entry_points.iter().enumerate() {
+        let stage = match entry_point.stage {
+            naga::ShaderStage::Vertex => wgpu::ShaderStage::VERTEX,
+            naga::ShaderStage::Fragment => wgpu::ShaderStage::FRAGMENT,
+            naga::ShaderStage::Compute => wgpu::ShaderStage::COMPUTE,
+        };
+        let uses = info.get_entry_point(index);
+        for (global, _) in module.global_variables.iter() {
+            if !uses[global].is_empty() {
+                *stages
+                    .entry(global)
+                    .or_insert_with(wgpu::ShaderStage::empty) |= stage;
+            }
+        }
+    }
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::{ShaderFeatures, ShaderLibrary};
+    use crate::uniforms::instance_input::world_instance::WorldInstance;
+    use crate::uniforms::instance_input::GpuInstance;
+    use crate::uniforms::vertex_input::gltf_mesh_vertex::GltfMeshVertex;
+    use crate::uniforms::vertex_input::GpuVertex;
+    use mint::{ColumnMatrix4, Vector4};
+
+    const SHADER: &str = r#"
+[[block]] struct Globals {
+	transform: mat4x4<f32>;
+	tint: vec4<f32>;
+};
+
+[[group(0), binding(0)]] var<uniform> globals: Globals;
+[[group(0), binding(1)]] var color_texture: texture_2d<f32>;
+[[group(0), binding(2)]] var color_sampler: sampler;
+
+struct VertexOutput {
+	[[builtin(position)]] position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main([[location(0)]] position: vec3<f32>, [[location(1)]] uv: vec2<f32>) -> VertexOutput {
+	var out: VertexOutput;
+	out.position = globals.transform * vec4<f32>(position, 1.0);
+	out.uv = uv;
+	return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	return textureSample(color_texture, color_sampler, in.uv) * globals.tint;
+}
+"#;
+
+    #[derive(AsStd140)]
+    struct Globals {
+        transform: ColumnMatrix4<f32>,
+        tint: Vector4<f32>,
+    }
+
+    // lacks `tint`
+    #[derive(AsStd140)]
+    struct Transform {
+        transform: ColumnMatrix4<f32>,
+    }
+
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+
+    fn vertex_buffer(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: 20,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes,
+        }
+    }
+
+    fn texture_entries(entries: LayoutEntries) -> LayoutEntries {
+        entries
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .sampler(wgpu::ShaderStage::FRAGMENT, true)
+    }
+
+    fn globals_entries() -> LayoutEntries {
+        texture_entries(
+            LayoutEntries::new()
+                .uniform_buffer::<Globals>(wgpu::ShaderStage::VERTEX_FRAGMENT, false),
+        )
+    }
+
+    fn check(
+        vertex_buffers: &[wgpu::VertexBufferLayout],
+        bind_groups: &[LayoutEntries],
+    ) -> Result<(), ReflectionError> {
+        let module = naga::front::wgsl::parse_str(SHADER).unwrap_or_else(|err| panic!("{}", err));
+        validate_module(
+            "test.wgsl",
+            &module,
+            &PipelineInterface {
+                vertex_buffers,
+                bind_groups,
+            },
+        )
+    }
+
+    #[test]
+    fn basic_shader_matches_its_pipeline() {
+        let library = ShaderLibrary::builtin();
+        for bits in 0..=ShaderFeatures::all().bits() {
+            let features = ShaderFeatures::from_bits_truncate(bits);
+            let shader = library
+                .process("basic.wgsl", &features.defines())
+                .unwrap_or_else(|err| panic!("{}", err));
+            let module = shader
+                .parse()
+                .unwrap_or_else(|err| panic!("{:?}: {}", features, err));
+            validate_module(
+                "basic.wgsl",
+                &module,
+                &PipelineInterface {
+                    vertex_buffers: &[GltfMeshVertex::desc(), WorldInstance::desc()],
+                    bind_groups: &crate::basic_bind_group_entries(),
+                },
+            )
+            .unwrap_or_else(|err| panic!("{:?}: {}", features, err));
+        }
+    }
+
+    #[test]
+    fn matching_pipeline_passes() {
+        let result = check(&[vertex_buffer(&ATTRIBUTES)], &[globals_entries()]);
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn reports_missing_locations() {
+        let result = check(&[vertex_buffer(&ATTRIBUTES[..1])], &[globals_entries()]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::MissingLocation { location: 1, .. })
+            ),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_locations() {
+        let result = check(
+            &[vertex_buffer(&ATTRIBUTES), vertex_buffer(&ATTRIBUTES[1..])],
+            &[globals_entries()],
+        );
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::DuplicateLocation { location: 1, .. })
+            ),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn reports_format_mismatches() {
+        let attributes = wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x2];
+        let result = check(&[vertex_buffer(&attributes)], &[globals_entries()]);
+        assert!(
+            matches!(
+                &result,
+                Err(ReflectionError::FormatMismatch {
+                    location: 0,
+                    format: wgpu::VertexFormat::Float32x4,
+                    shader_type,
+                    ..
+                }) if shader_type == "vec3<f32>"
+            ),
+            "{:?}",
+            result
+        );
+
+        let attributes = wgpu::vertex_attr_array![0 => Float32x3, 1 => Uint32x2];
+        let result = check(&[vertex_buffer(&attributes)], &[globals_entries()]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::FormatMismatch { location: 1, .. })
+            ),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn reports_missing_bindings() {
+        let entries = LayoutEntries::new()
+            .uniform_buffer::<Globals>(wgpu::ShaderStage::VERTEX_FRAGMENT, false);
+        let result = check(&[vertex_buffer(&ATTRIBUTES)], &[entries]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::MissingBinding {
+                    group: 0,
+                    binding: 1,
+                    ..
+                })
+            ),
+            "{:?}",
+            result
+        );
+
+        let result = check(&[vertex_buffer(&ATTRIBUTES)], &[]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::MissingBinding { group: 0, .. })
+            ),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn reports_binding_type_mismatches() {
+        let entries = texture_entries(LayoutEntries::new().storage_buffer(
+            wgpu::ShaderStage::VERTEX_FRAGMENT,
+            false,
+            true,
+        ));
+        let result = check(&[vertex_buffer(&ATTRIBUTES)], &[entries]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::BindingTypeMismatch { binding: 0, .. })
+            ),
+            "{:?}",
+            result
+        );
+
+        let entries = LayoutEntries::new()
+            .uniform_buffer::<Globals>(wgpu::ShaderStage::VERTEX_FRAGMENT, false)
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2Array,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .sampler(wgpu::ShaderStage::FRAGMENT, true);
+        let result = check(&[vertex_buffer(&ATTRIBUTES)], &[entries]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::BindingTypeMismatch { binding: 1, .. })
+            ),
+            "{:?}",
+            result
+        );
+
+        let entries = LayoutEntries::new()
+            .uniform_buffer::<Globals>(wgpu::ShaderStage::VERTEX_FRAGMENT, false)
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .comparison_sampler(wgpu::ShaderStage::FRAGMENT, true);
+        let result = check(&[vertex_buffer(&ATTRIBUTES)], &[entries]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::BindingTypeMismatch { binding: 2, .. })
+            ),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn reports_bindings_invisible_to_their_stage() {
+        // `globals` is read by both stages
+        let entries = texture_entries(
+            LayoutEntries::new().uniform_buffer::<Globals>(wgpu::ShaderStage::VERTEX, false),
+        );
+        let result = check(&[vertex_buffer(&ATTRIBUTES)], &[entries]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::BindingVisibility {
+                    binding: 0,
+                    used_by: wgpu::ShaderStage::VERTEX_FRAGMENT,
+                    ..
+                })
+            ),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn reports_std140_size_mismatches() {
+        let entries = texture_entries(
+            LayoutEntries::new()
+                .uniform_buffer::<Transform>(wgpu::ShaderStage::VERTEX_FRAGMENT, false),
+        );
+        let result = check(&[vertex_buffer(&ATTRIBUTES)], &[entries]);
+        assert!(
+            matches!(
+                result,
+                Err(ReflectionError::UniformSizeMismatch {
+                    shader_size: 80,
+                    rust_size: 64,
+                    ..
+                })
+            ),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,645 @@
+pub mod transient;
+
+pub use transient::TransientTextures;
+
+use nannou::wgpu;
+use nannou::Frame;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use thiserror::Error;
+use transient::Lifetime;
+
+/// The size, format and sample count of a texture in a `RenderGraph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureDesc {
+    pub size: [u32; 2],
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+}
+
+impl TextureDesc {
+    pub fn of(texture: &wgpu::Texture) -> Self {
+        Self {
+            size: texture.size(),
+            format: texture.format(),
+            sample_count: texture.sample_count(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureHandle(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferHandle(usize);
+
+#[derive(Debug, Error)]
+pub enum RenderGraphError {
+    #[error("render graph passes {passes:?} depend on each other in a cycle")]
+    Cycle { passes: Vec<String> },
+    #[error("pass {pass:?} reads transient texture {texture:?} before any pass writes it")]
+    UninitializedTexture { pass: String, texture: String },
+    #[error("the attachments of pass {pass:?} differ in size")]
+    AttachmentSizeMismatch { pass: String },
+}
+
+/// The passes of one frame, along with the textures and buffers they read and write.
+///
+/// Passes run in an order that satisfies their dependencies rather than the order they are added
+/// in: a pass reading a resource runs after every pass writing it, and passes writing the same
+/// resource run in the order they were added. Passes without dependencies between them keep the
+/// order they were added in.
+///
+/// Textures are either imported, such as the window's `Frame` or an offscreen target that outlives
+/// the frame, or transient. Transient textures are allocated from `TransientTextures` when the
+/// graph is executed, and share memory with other transient textures of the same description
+/// whenever their lifetimes don't overlap.
+pub struct RenderGraph<'a> {
+    transients: &'a mut TransientTextures,
+    resources: Vec<Resource<'a>>,
+    passes: Vec<Pass<'a>>,
+}
+
+struct Resource<'a> {
+    name: String,
+    kind: ResourceKind<'a>,
+}
+
+enum ResourceKind<'a> {
+    ImportedTexture {
+        view: &'a wgpu::TextureView,
+        desc: TextureDesc,
+    },
+    TransientTexture {
+        desc: TextureDesc,
+    },
+    // bound by the passes themselves; the graph only orders its readers and writers
+    Buffer,
+}
+
+impl<'a> Resource<'a> {
+    fn texture_desc(&self) -> Option<TextureDesc> {
+        match self.kind {
+            ResourceKind::ImportedTexture { desc, .. }
+            | ResourceKind::TransientTexture { desc } => Some(desc),
+            ResourceKind::Buffer => None,
+        }
+    }
+
+    fn is_transient(&self) -> bool {
+        matches!(self.kind, ResourceKind::TransientTexture { .. })
+    }
+}
+
+struct Pass<'a> {
+    name: String,
+    // indices into `RenderGraph::resources`
+    reads: Vec<usize>,
+    writes: Vec<usize>,
+    color_attachments: Vec<ColorAttachment>,
+    depth_attachment: Option<DepthAttachment>,
+    work: Work<'a>,
+}
+
+struct ColorAttachment {
+    texture: TextureHandle,
+    resolve_target: Option<TextureHandle>,
+    load: wgpu::LoadOp<wgpu::Color>,
+}
+
+struct DepthAttachment {
+    texture: TextureHandle,
+    load: wgpu::LoadOp<f32>,
+}
+
+// The `&'p PassResources<'a>` argument implies `'a: 'p`, which lets the render functions record
+// anything borrowed for the lifetime of the graph into the render pass.
+type RenderFn<'a> = Box<dyn for<'p> FnOnce(&mut wgpu::RenderPass<'p>, &'p PassResources<'a>) + 'a>;
+type EncodeFn<'a> = Box<dyn FnOnce(&mut wgpu::CommandEncoder, &PassResources<'a>) + 'a>;
+
+enum Work<'a> {
+    Render(RenderFn<'a>),
+    Encode(EncodeFn<'a>),
+}
+
+/// The textures of a `RenderGraph`, as handed to its passes.
+pub struct PassResources<'a> {
+    // by resource; `None` for transient textures no pass uses
+    texture_views: Vec<Option<&'a wgpu::TextureView>>,
+    transient_generation: u64,
+}
+
+impl<'a> PassResources<'a> {
+    pub fn texture_view(&self, texture: TextureHandle) -> &'a wgpu::TextureView {
+        self.texture_views[texture.0].expect("the texture is not used by any pass")
+    }
+
+    /// The `TransientTextures::generation` of this frame, for passes keeping bind groups of
+    /// transient views from one frame to the next.
+    pub fn transient_generation(&self) -> u64 {
+        self.transient_generation
+    }
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new(transients: &'a mut TransientTextures) -> Self {
+        Self {
+            transients,
+            resources: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    fn add_resource(&mut self, name: &str, kind: ResourceKind<'a>) -> usize {
+        self.resources.push(Resource {
+            name: name.to_string(),
+            kind,
+        });
+        self.resources.len() - 1
+    }
+
+    /// A texture that outlives the graph, such as an offscreen render target.
+    pub fn import_texture(
+        &mut self,
+        name: &str,
+        view: &'a wgpu::TextureView,
+        desc: TextureDesc,
+    ) -> TextureHandle {
+        TextureHandle(self.add_resource(name, ResourceKind::ImportedTexture { view, desc }))
+    }
+
+    /// The texture of the window's frame. nannou resolves it, if it is multisampled, and draws it
+    /// to the swap chain after `view` returns.
+    pub fn import_frame(&mut self, frame: &'a Frame) -> TextureHandle {
+        let desc = TextureDesc {
+            size: frame.texture_size(),
+            format: Frame::TEXTURE_FORMAT,
+            sample_count: frame.texture_msaa_samples(),
+        };
+        self.import_texture("frame", frame.texture_view(), desc)
+    }
+
+    /// A texture that only lives for the frame. Its contents are undefined until a pass writes it.
+    ///
+    /// Transient textures can only be rendered to and sampled, not copied into or out of; copy
+    /// through imported textures instead.
+    pub fn create_texture(&mut self, name: &str, desc: TextureDesc) -> TextureHandle {
+        TextureHandle(self.add_resource(name, ResourceKind::TransientTexture { desc }))
+    }
+
+    /// A buffer outside the graph, which passes declare their reads and writes of so that they run
+    /// in order.
+    pub fn import_buffer(&mut self, name: &str) -> BufferHandle {
+        BufferHandle(self.add_resource(name, ResourceKind::Buffer))
+    }
+
+    pub fn texture_desc(&self, texture: TextureHandle) -> TextureDesc {
+        self.resources[texture.0]
+            .texture_desc()
+            .expect("handle of a buffer")
+    }
+
+    /// Start declaring a pass. The pass is added once `render` or `encode` is called.
+    pub fn add_pass<'g>(&'g mut self, name: &str) -> PassBuilder<'g, 'a> {
+        PassBuilder {
+            graph: self,
+            name: name.to_string(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            color_attachments: Vec::new(),
+            depth_attachment: None,
+        }
+    }
+
+    /// Order the passes, allocate the transient textures and record every pass into `encoder`.
+    pub fn execute(
+        self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), RenderGraphError> {
+        let RenderGraph {
+            transients,
+            resources,
+            passes,
+        } = self;
+
+        for pass in passes.iter() {
+            let mut sizes = pass
+                .color_attachments
+                .iter()
+                .flat_map(|attachment| {
+                    std::iter::once(attachment.texture).chain(attachment.resolve_target)
+                })
+                .chain(pass.depth_attachment.as_ref().map(|depth| depth.texture))
+                .map(|texture| resources[texture.0].texture_desc().unwrap().size);
+            if let Some(size) = sizes.next() {
+                if sizes.any(|other| other != size) {
+                    return Err(RenderGraphError::AttachmentSizeMismatch {
+                        pass: pass.name.clone(),
+                    });
+                }
+            }
+        }
+
+        let order = schedule(&passes, resources.len())?;
+
+        let uses = resource_uses(&passes, &resources, &order)?;
+
+        let transient_resources: Vec<usize> = (0..resources.len())
+            .filter(|&resource| resources[resource].is_transient() && uses[resource].is_some())
+            .collect();
+        let lifetimes: Vec<Lifetime> = transient_resources
+            .iter()
+            .map(|&resource| {
+                let (first, last) = uses[resource].unwrap();
+                Lifetime {
+                    desc: resources[resource].texture_desc().unwrap(),
+                    first,
+                    last,
+                }
+            })
+            .collect();
+        let assignment = transients.allocate(device, &lifetimes);
+        let transients: &'a TransientTextures = transients;
+
+        let mut pass_resources = PassResources {
+            texture_views: vec![None; resources.len()],
+            transient_generation: transients.generation(),
+        };
+        for (index, resource) in resources.iter().enumerate() {
+            match resource.kind {
+                ResourceKind::ImportedTexture { view, .. } => {
+                    pass_resources.texture_views[index] = Some(view)
+                }
+                ResourceKind::TransientTexture { .. } | ResourceKind::Buffer => (),
+            }
+        }
+        for (&resource, &texture) in transient_resources.iter().zip(assignment.iter()) {
+            pass_resources.texture_views[resource] = Some(transients.view(texture));
+        }
+
+        let mut passes: Vec<Option<Pass<'a>>> = passes.into_iter().map(Some).collect();
+        for (position, &index) in order.iter().enumerate() {
+            let pass = passes[index].take().unwrap();
+            // transient attachments no later pass reads needn't be stored
+            let store = |texture: TextureHandle| {
+                !resources[texture.0].is_transient()
+                    || uses[texture.0].map_or(false, |(_, last)| last > position)
+            };
+            match pass.work {
+                Work::Render(render) => {
+                    let color_attachments: Vec<wgpu::RenderPassColorAttachment> = pass
+                        .color_attachments
+                        .iter()
+                        .map(|attachment| wgpu::RenderPassColorAttachment {
+                            view: pass_resources.texture_view(attachment.texture),
+                            resolve_target: attachment
+                                .resolve_target
+                                .map(|target| &**pass_resources.texture_view(target)),
+                            ops: wgpu::Operations {
+                                load: attachment.load,
+                                store: store(attachment.texture),
+                            },
+                        })
+                        .collect();
+                    let depth_stencil_attachment =
+                        pass.depth_attachment.as_ref().map(|attachment| {
+                            wgpu::RenderPassDepthStencilAttachment {
+                                view: pass_resources.texture_view(attachment.texture),
+                                depth_ops: Some(wgpu::Operations {
+                                    load: attachment.load,
+                                    store: store(attachment.texture),
+                                }),
+                                stencil_ops: None,
+                            }
+                        });
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some(&pass.name),
+                        color_attachments: &color_attachments,
+                        depth_stencil_attachment,
+                    });
+                    render(&mut render_pass, &pass_resources);
+                }
+                Work::Encode(encode) => encode(encoder, &pass_resources),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Pass<'a> {
+    // whether an attachment of the pass loads the previous contents of `resource`
+    fn loads(&self, resource: usize) -> bool {
+        let color = self.color_attachments.iter().any(|attachment| {
+            attachment.texture.0 == resource && matches!(attachment.load, wgpu::LoadOp::Load)
+        });
+        let depth = self.depth_attachment.as_ref().map_or(false, |attachment| {
+            attachment.texture.0 == resource && matches!(attachment.load, wgpu::LoadOp::Load)
+        });
+        color || depth
+    }
+}
+
+// The positions in `order` of the first and last pass using each resource. Transient textures
+// start out undefined, so the first pass using one must write it without loading it.
+fn resource_uses(
+    passes: &[Pass],
+    resources: &[Resource],
+    order: &[usize],
+) -> Result<Vec<Option<(usize, usize)>>, RenderGraphError> {
+    let mut uses: Vec<Option<(usize, usize)>> = vec![None; resources.len()];
+    for (position, &index) in order.iter().enumerate() {
+        let pass = &passes[index];
+        for &resource in pass.reads.iter().chain(pass.writes.iter()) {
+            let first = uses[resource].map_or(position, |(first, _)| first);
+            uses[resource] = Some((first, position));
+            if first == position
+                && resources[resource].is_transient()
+                && (!pass.writes.contains(&resource) || pass.loads(resource))
+            {
+                return Err(RenderGraphError::UninitializedTexture {
+                    pass: pass.name.clone(),
+                    texture: resources[resource].name.clone(),
+                });
+            }
+        }
+    }
+    Ok(uses)
+}
+
+// Orders the passes so that every writer of a resource runs before its readers, and the writers
+// of a resource run in the order they were added. Ties go to the pass added first.
+fn schedule(passes: &[Pass], resource_count: usize) -> Result<Vec<usize>, RenderGraphError> {
+    let mut writers: Vec<Vec<usize>> = vec![Vec::new(); resource_count];
+    let mut readers: Vec<Vec<usize>> = vec![Vec::new(); resource_count];
+    for (index, pass) in passes.iter().enumerate() {
+        for &resource in pass.writes.iter() {
+            writers[resource].push(index);
+        }
+        for &resource in pass.reads.iter() {
+            if !pass.writes.contains(&resource) {
+                readers[resource].push(index);
+            }
+        }
+    }
+
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); passes.len()];
+    let mut dependency_count = vec![0; passes.len()];
+    for (writers, readers) in writers.iter().zip(readers.iter()) {
+        let edges = writers.windows(2).map(|pair| (pair[0], pair[1])).chain(
+            writers
+                .last()
+                .into_iter()
+                .flat_map(|&last| readers.iter().map(move |&reader| (last, reader))),
+        );
+        for (from, to) in edges {
+            dependents[from].push(to);
+            dependency_count[to] += 1;
+        }
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..passes.len())
+        .filter(|&index| dependency_count[index] == 0)
+        .map(Reverse)
+        .collect();
+    let mut order = Vec::with_capacity(passes.len());
+    while let Some(Reverse(index)) = ready.pop() {
+        order.push(index);
+        for &dependent in dependents[index].iter() {
+            dependency_count[dependent] -= 1;
+            if dependency_count[dependent] == 0 {
+                ready.push(Reverse(dependent));
+            }
+        }
+    }
+
+    if order.len() < passes.len() {
+        return Err(RenderGraphError::Cycle {
+            passes: (0..passes.len())
+                .filter(|&index| dependency_count[index] > 0)
+                .map(|index| passes[index].name.clone())
+                .collect(),
+        });
+    }
+    Ok(order)
+}
+
+/// Declares the resources of one pass of a `RenderGraph`.
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    name: String,
+    reads: Vec<usize>,
+    writes: Vec<usize>,
+    color_attachments: Vec<ColorAttachment>,
+    depth_attachment: Option<DepthAttachment>,
+}
+
+impl<'g, 'a> PassBuilder<'g, 'a> {
+    fn read(mut self, resource: usize) -> Self {
+        if !self.reads.contains(&resource) {
+            self.reads.push(resource);
+        }
+        self
+    }
+
+    fn write(mut self, resource: usize) -> Self {
+        if !self.writes.contains(&resource) {
+            self.writes.push(resource);
+        }
+        self
+    }
+
+    /// The pass samples `texture`, or copies from it if it is imported.
+    pub fn read_texture(self, texture: TextureHandle) -> Self {
+        self.read(texture.0)
+    }
+
+    /// The pass writes `texture` other than as an attachment, such as by copying into an imported
+    /// texture.
+    pub fn write_texture(self, texture: TextureHandle) -> Self {
+        self.write(texture.0)
+    }
+
+    pub fn read_buffer(self, buffer: BufferHandle) -> Self {
+        self.read(buffer.0)
+    }
+
+    pub fn write_buffer(self, buffer: BufferHandle) -> Self {
+        self.write(buffer.0)
+    }
+
+    pub fn color_attachment(self, texture: TextureHandle, load: wgpu::LoadOp<wgpu::Color>) -> Self {
+        self.add_color_attachment(texture, None, load)
+    }
+
+    /// A multisampled color attachment, resolved into `resolve_target` at the end of the pass.
+    pub fn resolved_color_attachment(
+        self,
+        texture: TextureHandle,
+        resolve_target: TextureHandle,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> Self {
+        self.add_color_attachment(texture, Some(resolve_target), load)
+            .write(resolve_target.0)
+    }
+
+    fn add_color_attachment(
+        mut self,
+        texture: TextureHandle,
+        resolve_target: Option<TextureHandle>,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> Self {
+        self.color_attachments.push(ColorAttachment {
+            texture,
+            resolve_target,
+            load,
+        });
+        self.write(texture.0)
+    }
+
+    pub fn depth_attachment(mut self, texture: TextureHandle, load: wgpu::LoadOp<f32>) -> Self {
+        self.depth_attachment = Some(DepthAttachment { texture, load });
+        self.write(texture.0)
+    }
+
+    /// Add the pass, recording `render` into a render pass over the declared attachments.
+    pub fn render<F>(self, render: F)
+    where
+        F: for<'p> FnOnce(&mut wgpu::RenderPass<'p>, &'p PassResources<'a>) + 'a,
+    {
+        self.finish(Work::Render(Box::new(render)));
+    }
+
+    /// Add the pass, recording `encode` straight into the command encoder, for passes that copy
+    /// or begin render passes of their own. Attachments declared on the pass are ignored, other
+    /// than as writes.
+    pub fn encode<F>(self, encode: F)
+    where
+        F: FnOnce(&mut wgpu::CommandEncoder, &PassResources<'a>) + 'a,
+    {
+        self.finish(Work::Encode(Box::new(encode)));
+    }
+
+    fn finish(self, work: Work<'a>) {
+        self.graph.passes.push(Pass {
+            name: self.name,
+            reads: self.reads,
+            writes: self.writes,
+            color_attachments: self.color_attachments,
+            depth_attachment: self.depth_attachment,
+            work,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pass(name: &str, reads: &[usize], writes: &[usize]) -> Pass<'static> {
+        Pass {
+            name: name.to_string(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            color_attachments: Vec::new(),
+            depth_attachment: None,
+            work: Work::Encode(Box::new(|_, _| ())),
+        }
+    }
+
+    fn transient(name: &str) -> Resource<'static> {
+        Resource {
+            name: name.to_string(),
+            kind: ResourceKind::TransientTexture {
+                desc: TextureDesc {
+                    size: [4, 4],
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    sample_count: 1,
+                },
+            },
+        }
+    }
+
+    fn scheduled(passes: &[Pass], resource_count: usize) -> Vec<usize> {
+        schedule(passes, resource_count).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    #[test]
+    fn readers_run_after_the_last_writer() {
+        let passes = [
+            pass("composite", &[0, 1], &[2]),
+            pass("shadow", &[], &[0]),
+            pass("scene", &[0], &[1]),
+            pass("overlay", &[1], &[1]),
+        ];
+        assert_eq!(scheduled(&passes, 3), vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn writers_keep_the_order_they_were_added_in() {
+        let passes = [
+            pass("clear", &[], &[0]),
+            pass("draw", &[], &[0]),
+            pass("blit", &[], &[0]),
+        ];
+        assert_eq!(scheduled(&passes, 1), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn independent_passes_keep_the_order_they_were_added_in() {
+        let passes = [
+            pass("a", &[0], &[1]),
+            pass("b", &[], &[2]),
+            pass("c", &[], &[0]),
+            pass("d", &[2], &[3]),
+        ];
+        // `a` waits for `c` and `d` for `b`, so `b` runs first
+        assert_eq!(scheduled(&passes, 4), vec![1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let passes = [
+            pass("source", &[], &[2]),
+            pass("a", &[0, 2], &[1]),
+            pass("b", &[1], &[0]),
+        ];
+        match schedule(&passes, 3) {
+            Err(RenderGraphError::Cycle { passes }) => assert_eq!(passes, vec!["a", "b"]),
+            other => panic!("expected a cycle, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn transient_textures_must_be_written_before_they_are_read() {
+        let resources = [transient("hdr")];
+        let passes = [pass("tonemap", &[0], &[]), pass("scene", &[], &[0])];
+        let order = [0, 1];
+        match resource_uses(&passes, &resources, &order) {
+            Err(RenderGraphError::UninitializedTexture { pass, texture }) => {
+                assert_eq!((pass.as_str(), texture.as_str()), ("tonemap", "hdr"));
+            }
+            other => panic!("expected an uninitialized texture, got {:?}", other.ok()),
+        }
+
+        let order = scheduled(&passes, resources.len());
+        let uses =
+            resource_uses(&passes, &resources, &order).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(uses, vec![Some((0, 1))]);
+    }
+
+    #[test]
+    fn transient_textures_are_not_loaded_by_their_first_writer() {
+        let resources = [transient("hdr")];
+        let mut scene = pass("scene", &[], &[0]);
+        scene.color_attachments.push(ColorAttachment {
+            texture: TextureHandle(0),
+            resolve_target: None,
+            load: wgpu::LoadOp::Load,
+        });
+        assert!(matches!(
+            resource_uses(&[scene], &resources, &[0]),
+            Err(RenderGraphError::UninitializedTexture { .. })
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+use super::TextureDesc;
+use nannou::wgpu;
+
+/// The textures behind the transient attachments of every `RenderGraph`, kept from one frame to
+/// the next so that a graph which doesn't change from frame to frame allocates nothing.
+#[derive(Default)]
+pub struct TransientTextures {
+    textures: Vec<PhysicalTexture>,
+    // those of the last `allocate`, and how many times they changed
+    lifetimes: Vec<Lifetime>,
+    generation: u64,
+}
+
+struct PhysicalTexture {
+    desc: TextureDesc,
+    // kept alive for `view`
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl PhysicalTexture {
+    fn new(device: &wgpu::Device, desc: &TextureDesc) -> Self {
+        let texture = wgpu::TextureBuilder::new()
+            .size(desc.size)
+            .format(desc.format)
+            .sample_count(desc.sample_count)
+            // `RenderGraph::create_texture` documents that transients aren't copied
+            .usage(wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+            .build(device);
+        let view = texture.view().build();
+        Self {
+            desc: *desc,
+            _texture: texture,
+            view,
+        }
+    }
+}
+
+/// The passes a transient texture is used by, as positions in the order the graph runs them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Lifetime {
+    pub desc: TextureDesc,
+    pub first: usize,
+    pub last: usize,
+}
+
+impl TransientTextures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Assigns every lifetime a texture, returning its index for `view`. Lifetimes with the same
+    // description share a texture when they don't overlap.
+    pub(super) fn allocate(&mut self, device: &wgpu::Device, lifetimes: &[Lifetime]) -> Vec<usize> {
+        if lifetimes != self.lifetimes.as_slice() {
+            self.lifetimes = lifetimes.to_vec();
+            self.generation += 1;
+        }
+        let (slots, assignment) = assign_slots(lifetimes);
+
+        // reuse the textures of the last frame where the descriptions match, dropping the rest
+        let mut previous: Vec<Option<PhysicalTexture>> =
+            self.textures.drain(..).map(Some).collect();
+        self.textures = slots
+            .iter()
+            .map(|desc| {
+                previous
+                    .iter_mut()
+                    .find(|texture| matches!(texture, Some(texture) if texture.desc == *desc))
+                    .and_then(Option::take)
+                    .unwrap_or_else(|| PhysicalTexture::new(device, desc))
+            })
+            .collect();
+        assignment
+    }
+
+    pub(super) fn view(&self, index: usize) -> &wgpu::TextureView {
+        &self.textures[index].view
+    }
+
+    /// Counts the frames whose graph used its transient textures differently from the frame
+    /// before. Until it changes, every transient texture gets the same view as last frame.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+// Assigns every lifetime a slot, returning the description of each slot and the slot of each
+// lifetime.
+fn assign_slots(lifetimes: &[Lifetime]) -> (Vec<TextureDesc>, Vec<usize>) {
+    let mut order: Vec<usize> = (0..lifetimes.len()).collect();
+    order.sort_by_key(|&i| lifetimes[i].first);
+
+    // the textures needed this frame, with the last pass using each so far
+    let mut slots: Vec<(TextureDesc, usize)> = Vec::new();
+    let mut assignment = vec![0; lifetimes.len()];
+    for i in order {
+        let lifetime = &lifetimes[i];
+        let free = slots
+            .iter()
+            .position(|(desc, last)| *desc == lifetime.desc && *last < lifetime.first);
+        assignment[i] = match free {
+            Some(slot) => {
+                slots[slot].1 = lifetime.last;
+                slot
+            }
+            None => {
+                slots.push((lifetime.desc, lifetime.last));
+                slots.len() - 1
+            }
+        };
+    }
+    let descs = slots.into_iter().map(|(desc, _)| desc).collect();
+    (descs, assignment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(format: wgpu::TextureFormat) -> TextureDesc {
+        TextureDesc {
+            size: [4, 4],
+            format,
+            sample_count: 1,
+        }
+    }
+
+    fn lifetime(desc: TextureDesc, first: usize, last: usize) -> Lifetime {
+        Lifetime { desc, first, last }
+    }
+
+    #[test]
+    fn disjoint_lifetimes_share_a_slot() {
+        let hdr = desc(wgpu::TextureFormat::Rgba16Float);
+        let lifetimes = [
+            lifetime(hdr, 3, 4),
+            lifetime(hdr, 0, 1),
+            lifetime(hdr, 2, 2),
+        ];
+        let (slots, assignment) = assign_slots(&lifetimes);
+        assert_eq!(slots, vec![hdr]);
+        assert_eq!(assignment, vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn overlapping_lifetimes_get_their_own_slots() {
+        let hdr = desc(wgpu::TextureFormat::Rgba16Float);
+        // a lifetime ending at the pass another starts at overlaps it
+        let lifetimes = [
+            lifetime(hdr, 0, 2),
+            lifetime(hdr, 1, 3),
+            lifetime(hdr, 2, 2),
+            lifetime(hdr, 3, 3),
+        ];
+        let (slots, assignment) = assign_slots(&lifetimes);
+        assert_eq!(slots, vec![hdr, hdr, hdr]);
+        assert_eq!(assignment, vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn differing_descriptions_get_their_own_slots() {
+        let hdr = desc(wgpu::TextureFormat::Rgba16Float);
+        let depth = desc(wgpu::TextureFormat::Depth32Float);
+        let multisampled = TextureDesc {
+            sample_count: 4,
+            ..hdr
+        };
+        let lifetimes = [
+            lifetime(hdr, 0, 0),
+            lifetime(depth, 1, 1),
+            lifetime(multisampled, 2, 2),
+            lifetime(hdr, 3, 3),
+        ];
+        let (slots, assignment) = assign_slots(&lifetimes);
+        assert_eq!(slots, vec![hdr, depth, multisampled]);
+        assert_eq!(assignment, vec![0, 1, 2, 0]);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::transform::{Transform, Transformable};
+use nannou::glam::Mat4;
+use thiserror::Error;
+
+/// A handle to a node within a `SceneGraph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
+struct SceneNode {
+    // relative to the parent node (or to world space for root nodes)
+    local: Transform,
+    // cached `parent.world_matrix * local.mat4x4()`, valid while `dirty` is false
+    world_matrix: Mat4,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    dirty: bool,
+}
+
+/// A hierarchy of transforms. Every node stores its local transform and caches its world matrix,
+/// which is recomputed by `update_world_matrices` whenever the node or one of its ancestors moved.
+#[derive(Debug, Clone, Default)]
+pub struct SceneGraph {
+    nodes: Vec<SceneNode>,
+}
+
+/// Errors that might occur while restructuring a `SceneGraph`.
+#[derive(Debug, Error)]
+pub enum SceneGraphError {
+    #[error("cannot parent node {node:?} to {parent:?}, as {parent:?} is one of its descendants")]
+    Cycle { node: NodeId, parent: NodeId },
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, local: Transform, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(SceneNode {
+            local,
+            world_matrix: Mat4::IDENTITY,
+            parent,
+            children: Vec::new(),
+            dirty: true,
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+        id
+    }
+
+    #[allow(dead_code)]
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    #[allow(dead_code)]
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id.0].children
+    }
+
+    pub fn local_transform(&self, id: NodeId) -> &Transform {
+        &self.nodes[id.0].local
+    }
+
+    // marks the node dirty, so its subtree is recomputed on the next update
+    pub fn local_transform_mut(&mut self, id: NodeId) -> &mut Transform {
+        let node = &mut self.nodes[id.0];
+        node.dirty = true;
+        &mut node.local
+    }
+
+    /// The world matrix of the node as of the last call to `update_world_matrices`.
+    pub fn world_matrix(&self, id: NodeId) -> Mat4 {
+        self.nodes[id.0].world_matrix
+    }
+
+    /// Recompute the world matrix of every node that moved, or whose ancestors moved, since the
+    /// last update. Parents are always visited before their children.
+    pub fn update_world_matrices(&mut self) {
+        let mut stack: Vec<(NodeId, bool)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(i, _)| (NodeId(i), false))
+            .collect();
+
+        while let Some((id, parent_changed)) = stack.pop() {
+            let node = &self.nodes[id.0];
+            let changed = parent_changed || node.dirty;
+            if changed {
+                let parent_matrix = node
+                    .parent
+                    .map_or(Mat4::IDENTITY, |parent| self.nodes[parent.0].world_matrix);
+                let world_matrix = parent_matrix * node.local.mat4x4();
+                let node = &mut self.nodes[id.0];
+                node.world_matrix = world_matrix;
+                node.dirty = false;
+            }
+            stack.extend(
+                self.nodes[id.0]
+                    .children
+                    .iter()
+                    .map(|&child| (child, changed)),
+            );
+        }
+    }
+
+    /// Move `id` (and its subtree) under `parent`, or make it a root node if `parent` is `None`.
+    ///
+    /// The local transform is recomputed so that the node keeps its current world transform. Note
+    /// that a `Transform` cannot represent shear, so this is only exact when the parent chain does
+    /// not combine rotation with non-uniform scale.
+    #[allow(dead_code)]
+    pub fn set_parent(
+        &mut self,
+        id: NodeId,
+        parent: Option<NodeId>,
+    ) -> Result<(), SceneGraphError> {
+        if let Some(new_parent) = parent {
+            let mut ancestor = Some(new_parent);
+            while let Some(a) = ancestor {
+                if a == id {
+                    return Err(SceneGraphError::Cycle {
+                        node: id,
+                        parent: new_parent,
+                    });
+                }
+                ancestor = self.nodes[a.0].parent;
+            }
+        }
+
+        let world_matrix = self.compute_world_matrix(id);
+        let parent_matrix = parent.map_or(Mat4::IDENTITY, |p| self.compute_world_matrix(p));
+
+        if let Some(old_parent) = self.nodes[id.0].parent {
+            self.nodes[old_parent.0]
+                .children
+                .retain(|&child| child != id);
+        }
+        if let Some(new_parent) = parent {
+            self.nodes[new_parent.0].children.push(id);
+        }
+
+        let node = &mut self.nodes[id.0];
+        node.parent = parent;
+        node.local = Transform::from(parent_matrix.inverse() * world_matrix);
+        node.dirty = true;
+        Ok(())
+    }
+
+    // walks up the hierarchy, so unlike `world_matrix` this does not depend on the cache
+    fn compute_world_matrix(&self, id: NodeId) -> Mat4 {
+        let node = &self.nodes[id.0];
+        let local_matrix = node.local.mat4x4();
+        match node.parent {
+            Some(parent) => self.compute_world_matrix(parent) * local_matrix,
+            None => local_matrix,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::glam::{Quat, Vec3};
+
+    fn translation(x: f32, y: f32, z: f32) -> Transform {
+        Transform::new(Vec3::new(x, y, z), Quat::IDENTITY, Vec3::ONE)
+    }
+
+    fn world_translation(graph: &SceneGraph, id: NodeId) -> Vec3 {
+        graph.world_matrix(id).w_axis.truncate()
+    }
+
+    #[test]
+    fn moving_a_parent_updates_its_descendants() {
+        let mut graph = SceneGraph::new();
+        let root = graph.add_node(translation(1.0, 0.0, 0.0), None);
+        let child = graph.add_node(translation(0.0, 2.0, 0.0), Some(root));
+        let grandchild = graph.add_node(translation(0.0, 0.0, 3.0), Some(child));
+        graph.update_world_matrices();
+        assert!(world_translation(&graph, grandchild).abs_diff_eq(Vec3::new(1.0, 2.0, 3.0), 1e-5));
+
+        // only the root is dirty, yet the whole subtree follows it
+        graph.local_transform_mut(root).translation = Vec3::new(-1.0, 0.0, 0.0);
+        graph.update_world_matrices();
+        assert!(world_translation(&graph, child).abs_diff_eq(Vec3::new(-1.0, 2.0, 0.0), 1e-5));
+        assert!(world_translation(&graph, grandchild).abs_diff_eq(Vec3::new(-1.0, 2.0, 3.0), 1e-5));
+    }
+
+    #[test]
+    fn set_parent_keeps_the_world_transform() {
+        let mut graph = SceneGraph::new();
+        let parent = graph.add_node(
+            Transform::new(
+                Vec3::new(4.0, 0.0, 0.0),
+                Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+                Vec3::splat(2.0),
+            ),
+            None,
+        );
+        let node = graph.add_node(translation(1.0, 2.0, 3.0), None);
+        graph.update_world_matrices();
+        let before = graph.world_matrix(node);
+
+        graph
+            .set_parent(node, Some(parent))
+            .unwrap_or_else(|err| panic!("{}", err));
+        graph.update_world_matrices();
+        assert_eq!(graph.parent(node), Some(parent));
+        assert_eq!(graph.children(parent), &[node]);
+        assert!(graph.world_matrix(node).abs_diff_eq(before, 1e-5));
+
+        graph
+            .set_parent(node, None)
+            .unwrap_or_else(|err| panic!("{}", err));
+        graph.update_world_matrices();
+        assert!(graph.children(parent).is_empty());
+        assert!(graph.world_matrix(node).abs_diff_eq(before, 1e-5));
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut graph = SceneGraph::new();
+        let root = graph.add_node(Transform::default(), None);
+        let child = graph.add_node(Transform::default(), Some(root));
+        let grandchild = graph.add_node(Transform::default(), Some(child));
+
+        for &new_parent in &[root, grandchild] {
+            match graph.set_parent(root, Some(new_parent)) {
+                Err(SceneGraphError::Cycle { node, parent }) => {
+                    assert_eq!(node, root);
+                    assert_eq!(parent, new_parent);
+                }
+                Ok(()) => panic!("parenting {:?} to {:?} succeeded", root, new_parent),
+            }
+        }
+        assert_eq!(graph.parent(root), None);
+    }
+}
@@ -0,0 +1,127 @@
+// The background, drawn at the far plane wherever the opaque geometry left the depth cleared
+
+#include "cubemap.wgsl"
+
+let BACKGROUND_SKYBOX: u32 = 0u;
+let BACKGROUND_PANORAMA: u32 = 1u;
+let BACKGROUND_GRADIENT: u32 = 2u;
+let BACKGROUND_PHYSICAL_SKY: u32 = 3u;
+
+// the angular radius of the sun of a physical sky, in radians
+let SUN_ANGULAR_RADIUS: f32 = 0.01;
+// how much brighter the sun is than the sky around it
+let SUN_BRIGHTNESS: f32 = 20.0;
+
+[[block]] struct BackgroundUniform {
+	inverse_view_projection: mat4x4<f32>;
+	// world space directions to the directions the environment maps are sampled with
+	rotation: mat4x4<f32>;
+	zenith: vec4<f32>;
+	horizon: vec4<f32>;
+	nadir: vec4<f32>;
+	// the Perez coefficients of the physical sky, for (x, y, Y)
+	perez_a: vec4<f32>;
+	perez_b: vec4<f32>;
+	perez_c: vec4<f32>;
+	perez_d: vec4<f32>;
+	perez_e: vec4<f32>;
+	sky_zenith: vec4<f32>;
+	sun_direction: vec4<f32>;
+	mode: u32;
+	intensity: f32;
+	lod: f32;
+};
+
+[[group(0), binding(0)]] var<uniform> background: BackgroundUniform;
+[[group(0), binding(1)]] var background_sampler: sampler;
+[[group(0), binding(2)]] var skybox: texture_cube<f32>;
+[[group(0), binding(3)]] var panorama: texture_2d<f32>;
+
+struct VertexOutput {
+	[[builtin(position)]] position: vec4<f32>;
+	[[location(0)]] ndc: vec2<f32>;
+};
+
+// a single triangle covering the screen at the far plane
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+	let x = f32(i32(vertex_index & 1u) * 4 - 1);
+	let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+	var out: VertexOutput;
+	out.position = vec4<f32>(x, y, 1.0, 1.0);
+	out.ndc = vec2<f32>(x, y);
+	return out;
+}
+
+// the world space direction of the view ray through `ndc`, from the points it passes through on
+// the near and far planes. Multiplying each by the other's w rather than dividing by its own keeps
+// the direction finite when the far plane is at infinity, and parallel for orthographic cameras
+fn view_ray(ndc: vec2<f32>) -> vec3<f32> {
+	let near = background.inverse_view_projection * vec4<f32>(ndc, 0.0, 1.0);
+	let far = background.inverse_view_projection * vec4<f32>(ndc, 1.0, 1.0);
+	return normalize(far.xyz * near.w - near.xyz * far.w);
+}
+
+fn gradient(direction: vec3<f32>) -> vec3<f32> {
+	let height = direction.y;
+	if (height >= 0.0) {
+		return mix(background.horizon.rgb, background.zenith.rgb, vec3<f32>(height));
+	}
+	return mix(background.horizon.rgb, background.nadir.rgb, vec3<f32>(-height));
+}
+
+// the Perez sky luminance distribution at `cos_theta` from the zenith and `gamma` from the sun,
+// for each of (x, y, Y)
+fn perez(cos_theta: f32, gamma: f32) -> vec3<f32> {
+	let cos_gamma = cos(gamma);
+	return (vec3<f32>(1.0) + background.perez_a.xyz * exp(background.perez_b.xyz / cos_theta))
+		* (vec3<f32>(1.0) + background.perez_c.xyz * exp(background.perez_d.xyz * gamma)
+			+ background.perez_e.xyz * cos_gamma * cos_gamma);
+}
+
+// Preetham et al. 1999, "A Practical Analytic Model for Daylight": the clear sky, scaled from its
+// luminance in kcd/m² by the intensity. Below the horizon, the sky at the horizon
+fn physical_sky(direction: vec3<f32>) -> vec3<f32> {
+	let sun = background.sun_direction.xyz;
+	let cos_theta = max(direction.y, 0.01);
+	let gamma = acos(clamp(dot(direction, sun), -1.0, 1.0));
+	let sun_gamma = acos(clamp(sun.y, -1.0, 1.0));
+	let xyY = background.sky_zenith.xyz * perez(cos_theta, gamma) / perez(1.0, sun_gamma);
+
+	// to linear sRGB, by way of XYZ
+	let luminance = xyY.z;
+	let x = xyY.x * luminance / xyY.y;
+	let z = (1.0 - xyY.x - xyY.y) * luminance / xyY.y;
+	var color: vec3<f32> = max(vec3<f32>(
+		3.2406 * x - 1.5372 * luminance - 0.4986 * z,
+		-0.9689 * x + 1.8758 * luminance + 0.0415 * z,
+		0.0557 * x - 0.2040 * luminance + 1.0570 * z,
+	), vec3<f32>(0.0));
+	if (direction.y > 0.0) {
+		// a disk with a slightly soft edge
+		color = color * (1.0 + SUN_BRIGHTNESS * (1.0 - smoothStep(SUN_ANGULAR_RADIUS * 0.8, SUN_ANGULAR_RADIUS, gamma)));
+	}
+	return color;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let direction = view_ray(in.ndc);
+	let rotation = mat3x3<f32>(
+		background.rotation[0].xyz,
+		background.rotation[1].xyz,
+		background.rotation[2].xyz,
+	);
+
+	var color: vec3<f32>;
+	if (background.mode == BACKGROUND_SKYBOX) {
+		color = textureSampleLevel(skybox, background_sampler, rotation * direction, background.lod).rgb;
+	} elseif (background.mode == BACKGROUND_PANORAMA) {
+		color = textureSampleLevel(panorama, background_sampler, equirect_uv(rotation * direction), 0.0).rgb;
+	} elseif (background.mode == BACKGROUND_GRADIENT) {
+		color = gradient(direction);
+	} else {
+		color = physical_sky(direction);
+	}
+	return vec4<f32>(color * background.intensity, 1.0);
+}
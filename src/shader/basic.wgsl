@@ -0,0 +1,199 @@
+// The forward pass. Processed with any of the `ShaderFeatures` defined:
+// SKINNED, NORMAL_MAPPED, SHADOWED and INSTANCED.
+
+#include "scene.wgsl"
+#include "material.wgsl"
+#include "lights.wgsl"
+#include "environment.wgsl"
+#include "shadows.wgsl"
+
+struct VertexInput {
+  [[location(0)]] position: vec4<f32>;
+  [[location(1)]] normal: vec3<f32>;
+  [[location(2)]] tangent: vec3<f32>;
+  [[location(3)]] bitangent: vec3<f32>;
+  [[location(4)]] color: vec4<f32>;
+  [[location(5)]] tex_coords_0: vec2<f32>;
+  [[location(6)]] tex_coords_1: vec2<f32>;
+  [[location(7)]] tex_coords_2: vec2<f32>;
+  [[location(8)]] skin_weight: vec4<f32>;
+  [[location(9)]] skin_index: vec4<u32>;
+};
+#ifdef INSTANCED
+struct InstanceInput {
+    [[location(10)]] model_matrix_0: vec4<f32>;
+    [[location(11)]] model_matrix_1: vec4<f32>;
+    [[location(12)]] model_matrix_2: vec4<f32>;
+    [[location(13)]] model_matrix_3: vec4<f32>;
+    [[location(14)]] normal_matrix_0: vec3<f32>;
+    [[location(15)]] normal_matrix_1: vec3<f32>;
+    [[location(16)]] normal_matrix_2: vec3<f32>;
+};
+#endif
+
+struct VertexOutput {
+    [[builtin(position)]] homogenous_clip_space_coords: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] world_tangent: vec3<f32>;
+    [[location(3)]] world_bitangent: vec3<f32>;
+    [[location(4)]] color: vec4<f32>;
+    [[location(5)]] tex_coords_0: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main(
+  vertex: VertexInput,
+#ifdef INSTANCED
+	instance: InstanceInput,
+#endif
+	[[builtin(vertex_index)]] vertex_index: u32,
+) -> VertexOutput {
+
+#ifdef INSTANCED
+	// the instances are in world space already
+	var model_matrix: mat4x4<f32> = mat4x4<f32>(
+		instance.model_matrix_0,
+		instance.model_matrix_1,
+		instance.model_matrix_2,
+		instance.model_matrix_3,
+	);
+	var normal_matrix: mat3x3<f32> = mat3x3<f32>(
+		instance.normal_matrix_0,
+		instance.normal_matrix_1,
+		instance.normal_matrix_2,
+	);
+#else
+	var model_matrix: mat4x4<f32> = model.model_matrix;
+	// only valid for uniform scale, otherwise the inverse transpose is needed
+	var normal_matrix: mat3x3<f32> = mat3x3<f32>(
+		model_matrix[0].xyz,
+		model_matrix[1].xyz,
+		model_matrix[2].xyz,
+	);
+#endif
+#ifdef SKINNED
+	// skinned meshes are placed by their joints alone
+	model_matrix = skin_matrix(vertex.skin_weight, vertex.skin_index);
+	// only valid for uniform scale, otherwise the inverse transpose is needed
+	normal_matrix = mat3x3<f32>(
+		model_matrix[0].xyz,
+		model_matrix[1].xyz,
+		model_matrix[2].xyz,
+	);
+#endif
+
+	// morph targets apply before skinning
+	let morph = morph_delta(vertex_index);
+	let world_position = model_matrix * (vertex.position + morph.position);
+
+	var out: VertexOutput;
+	out.homogenous_clip_space_coords = camera.projection_matrix * camera.view_matrix * world_position;
+	out.world_position = world_position.xyz;
+	out.world_normal = normal_matrix * (vertex.normal + morph.normal.xyz);
+	out.world_tangent = normal_matrix * (vertex.tangent + morph.tangent.xyz);
+	out.world_bitangent = normal_matrix * vertex.bitangent;
+	out.color = vertex.color;
+	out.tex_coords_0 = vertex.tex_coords_0;
+	return out;
+}
+
+// Fragment shader
+
+fn view_depth(world_position: vec3<f32>) -> f32 {
+	return abs((camera.view_matrix * vec4<f32>(world_position, 1.0)).z);
+}
+
+fn shade_lights(surface: Surface, in: VertexOutput) -> vec3<f32> {
+	let world_position = in.world_position;
+	let geometric_normal = normalize(in.world_normal);
+	let depth = view_depth(world_position);
+	let v = normalize(camera.position.xyz - world_position);
+	var color: vec3<f32> = vec3<f32>(0.0);
+
+	var i: u32 = 0u;
+	loop {
+		if (i >= light_counts.directional) {
+			break;
+		}
+		let light = directional_lights.data[i];
+		let l = normalize(light.model_matrix[2].xyz);
+#ifdef SHADOWED
+		let visibility = directional_shadow(light, world_position, geometric_normal, depth);
+#else
+		let visibility = 1.0;
+#endif
+		color = color + shade(surface, v, l, light.color.rgb * light.color.a * visibility);
+		continuing {
+			i = i + 1u;
+		}
+	}
+
+	i = 0u;
+	loop {
+		if (i >= light_counts.point) {
+			break;
+		}
+		let light = point_lights.data[i];
+		let to_light = light.position - world_position;
+		let attenuation = range_attenuation(length(to_light), light.range);
+#ifdef SHADOWED
+		let visibility = point_shadow(light, world_position, geometric_normal);
+#else
+		let visibility = 1.0;
+#endif
+		let radiance = light.color * light.intensity * attenuation * visibility;
+		color = color + shade(surface, v, normalize(to_light), radiance);
+		continuing {
+			i = i + 1u;
+		}
+	}
+
+	i = 0u;
+	loop {
+		if (i >= light_counts.spot) {
+			break;
+		}
+		let light = spot_lights.data[i];
+		let to_light = light.position - world_position;
+		let l = normalize(to_light);
+		let attenuation = range_attenuation(length(to_light), light.range) * spot_attenuation(light, l);
+		let radiance = light.color * light.intensity * attenuation;
+		color = color + shade(surface, v, l, radiance);
+		continuing {
+			i = i + 1u;
+		}
+	}
+
+	return color;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let surface = sample_surface(in.tex_coords_0, in.color, in.world_normal, in.world_tangent, in.world_bitangent);
+	if (surface.base_color.a < material.alpha_cutoff) {
+		discard;
+	}
+
+	let direct = shade_lights(surface, in);
+	let v = normalize(camera.position.xyz - in.world_position);
+	let indirect = shade_environment(surface, v) * surface.occlusion;
+	var color: vec3<f32> = direct + indirect + surface.emissive;
+
+#ifdef SHADOWED
+	// tint by the cascade of the first shadow slot: red, green, blue, yellow
+	if (shadow.debug_cascades != 0u && shadow.light_count > 0u) {
+		var tints: array<vec3<f32>, 4> = array<vec3<f32>, 4>(
+			vec3<f32>(1.0, 0.2, 0.2),
+			vec3<f32>(0.2, 1.0, 0.2),
+			vec3<f32>(0.2, 0.2, 1.0),
+			vec3<f32>(1.0, 1.0, 0.2),
+		);
+		let c = cascade_index(0u, view_depth(in.world_position));
+		if (c < shadow.cascade_count) {
+			color = mix(color, tints[c % 4u], vec3<f32>(0.4));
+		}
+	}
+#endif
+	return vec4<f32>(color, surface.base_color.a);
+}
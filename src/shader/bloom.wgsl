@@ -0,0 +1,9 @@
+// The uniform of the bloom's upsampling and compositing passes
+
+[[block]] struct BloomUniform {
+	// of the tent filter upsampling each mip, in texture coordinates
+	filter_radius: f32;
+	intensity: f32;
+};
+
+[[group(1), binding(0)]] var<uniform> bloom: BloomUniform;
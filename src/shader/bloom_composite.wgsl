@@ -0,0 +1,14 @@
+// Mixes the bloom into the frame
+
+#include "post.wgsl"
+#include "bloom.wgsl"
+
+// the sum of the mips, at half the frame's size
+[[group(2), binding(0)]] var blurred: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let color = textureLoad(source, vec2<i32>(in.position.xy), 0);
+	let glow = textureSampleLevel(blurred, source_sampler, in.uv, 0.0).rgb;
+	return vec4<f32>(mix(color.rgb, glow, vec3<f32>(bloom.intensity)), color.a);
+}
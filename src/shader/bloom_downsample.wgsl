@@ -0,0 +1,53 @@
+// Downsamples the frame, or the last mip of the bloom, into the next mip with the 13 tap filter of
+// Jimenez 2014, "Next Generation Post Processing in Call of Duty: Advanced Warfare". With
+// `KARIS_AVERAGE`, for the first mip, each of the five boxes of the filter is weighted by the
+// inverse of its luma, so that single bright pixels don't flicker as they move
+
+#include "post.wgsl"
+#include "color.wgsl"
+
+fn karis_weight(color: vec3<f32>) -> f32 {
+	return 1.0 / (1.0 + luminance(color));
+}
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let texel = texel_size();
+	let uv = in.uv;
+	// a b c
+	//  j k
+	// d e f
+	//  l m
+	// g h i
+	let a = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(-2.0, -2.0), 0.0).rgb;
+	let b = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(0.0, -2.0), 0.0).rgb;
+	let c = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(2.0, -2.0), 0.0).rgb;
+	let d = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(-2.0, 0.0), 0.0).rgb;
+	let e = textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+	let f = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(2.0, 0.0), 0.0).rgb;
+	let g = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(-2.0, 2.0), 0.0).rgb;
+	let h = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(0.0, 2.0), 0.0).rgb;
+	let i = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(2.0, 2.0), 0.0).rgb;
+	let j = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(-1.0, -1.0), 0.0).rgb;
+	let k = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(1.0, -1.0), 0.0).rgb;
+	let l = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(-1.0, 1.0), 0.0).rgb;
+	let m = textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(1.0, 1.0), 0.0).rgb;
+
+#ifdef KARIS_AVERAGE
+	let box_0 = (a + b + d + e) * 0.25;
+	let box_1 = (b + c + e + f) * 0.25;
+	let box_2 = (d + e + g + h) * 0.25;
+	let box_3 = (e + f + h + i) * 0.25;
+	let box_4 = (j + k + l + m) * 0.25;
+	let weight_0 = 0.125 * karis_weight(box_0);
+	let weight_1 = 0.125 * karis_weight(box_1);
+	let weight_2 = 0.125 * karis_weight(box_2);
+	let weight_3 = 0.125 * karis_weight(box_3);
+	let weight_4 = 0.5 * karis_weight(box_4);
+	let color = (box_0 * weight_0 + box_1 * weight_1 + box_2 * weight_2 + box_3 * weight_3 + box_4 * weight_4)
+		/ (weight_0 + weight_1 + weight_2 + weight_3 + weight_4);
+#else
+	let color = e * 0.125 + (a + c + g + i) * 0.03125 + (b + d + f + h) * 0.0625 + (j + k + l + m) * 0.125;
+#endif
+	return vec4<f32>(max(color, vec3<f32>(0.0)), 1.0);
+}
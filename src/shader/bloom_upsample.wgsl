@@ -0,0 +1,32 @@
+// Upsamples a mip of the bloom with a 3x3 tent filter and adds the downsampled mip of the target's
+// size, summing the blurred mips back up the chain
+
+#include "post.wgsl"
+#include "bloom.wgsl"
+
+[[group(2), binding(0)]] var downsampled: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	// narrower across than down in texture coordinates, so that the blur is round on screen
+	let size = vec2<f32>(textureDimensions(downsampled));
+	let radius = vec2<f32>(bloom.filter_radius * size.y / size.x, bloom.filter_radius);
+	let uv = in.uv;
+
+	// 1 2 1
+	// 2 4 2
+	// 1 2 1
+	let a = textureSampleLevel(source, source_sampler, uv + radius * vec2<f32>(-1.0, -1.0), 0.0).rgb;
+	let b = textureSampleLevel(source, source_sampler, uv + radius * vec2<f32>(0.0, -1.0), 0.0).rgb;
+	let c = textureSampleLevel(source, source_sampler, uv + radius * vec2<f32>(1.0, -1.0), 0.0).rgb;
+	let d = textureSampleLevel(source, source_sampler, uv + radius * vec2<f32>(-1.0, 0.0), 0.0).rgb;
+	let e = textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+	let f = textureSampleLevel(source, source_sampler, uv + radius * vec2<f32>(1.0, 0.0), 0.0).rgb;
+	let g = textureSampleLevel(source, source_sampler, uv + radius * vec2<f32>(-1.0, 1.0), 0.0).rgb;
+	let h = textureSampleLevel(source, source_sampler, uv + radius * vec2<f32>(0.0, 1.0), 0.0).rgb;
+	let i = textureSampleLevel(source, source_sampler, uv + radius * vec2<f32>(1.0, 1.0), 0.0).rgb;
+	let blurred = (e * 4.0 + (b + d + f + h) * 2.0 + (a + c + g + i)) * 0.0625;
+
+	let color = blurred + textureLoad(downsampled, vec2<i32>(in.position.xy), 0).rgb;
+	return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,26 @@
+// The terms of the Cook-Torrance BRDF, as in the glTF spec appendix B:
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#appendix-b-brdf-implementation
+
+#include "constants.wgsl"
+
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+	let alpha_2 = alpha * alpha;
+	let d = n_dot_h * n_dot_h * (alpha_2 - 1.0) + 1.0;
+	return alpha_2 / (PI * d * d);
+}
+
+// height-correlated Smith visibility, which includes the 1 / (4 n.l n.v) denominator
+fn visibility_smith_ggx(n_dot_l: f32, n_dot_v: f32, alpha: f32) -> f32 {
+	let alpha_2 = alpha * alpha;
+	let ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha_2) + alpha_2);
+	let ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha_2) + alpha_2);
+	let ggx = ggx_v + ggx_l;
+	if (ggx > 0.0) {
+		return 0.5 / ggx;
+	}
+	return 0.0;
+}
+
+fn fresnel_schlick(f0: vec3<f32>, v_dot_h: f32) -> vec3<f32> {
+	return f0 + (vec3<f32>(1.0) - f0) * pow(clamp(1.0 - v_dot_h, 0.0, 1.0), 5.0);
+}
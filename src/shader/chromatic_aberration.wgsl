@@ -0,0 +1,20 @@
+// Splits red and blue apart towards the edges of the frame, sampling red further in and blue
+// further out
+
+#include "post.wgsl"
+
+[[block]] struct ChromaticAberrationUniform {
+	// how far red and blue are pulled apart at the edges, in texture coordinates
+	intensity: f32;
+};
+
+[[group(1), binding(0)]] var<uniform> chromatic_aberration: ChromaticAberrationUniform;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let center = textureLoad(source, vec2<i32>(in.position.xy), 0);
+	let offset = (in.uv * 2.0 - vec2<f32>(1.0)) * chromatic_aberration.intensity;
+	let red = textureSampleLevel(source, source_sampler, in.uv - offset, 0.0).r;
+	let blue = textureSampleLevel(source, source_sampler, in.uv + offset, 0.0).b;
+	return vec4<f32>(red, center.g, blue, center.a);
+}
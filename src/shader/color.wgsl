@@ -0,0 +1,6 @@
+// Color science shared between shaders
+
+// the relative luminance of a linear sRGB color
+fn luminance(color: vec3<f32>) -> f32 {
+	return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
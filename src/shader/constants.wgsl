@@ -0,0 +1,3 @@
+// Constants shared between shaders
+
+let PI: f32 = 3.14159265359;
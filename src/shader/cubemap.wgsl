@@ -0,0 +1,33 @@
+// Directions through the faces of cube maps and the texels of equirectangular panoramas
+
+#include "constants.wgsl"
+
+// the direction through `uv` of cube face `face`, in the order +X, -X, +Y, -Y, +Z, -Z, with `uv`
+// from (0, 0) in the top left corner of the face to (1, 1) in the bottom right
+fn cube_face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+	let s = uv.x * 2.0 - 1.0;
+	let t = uv.y * 2.0 - 1.0;
+	var direction: vec3<f32>;
+	if (face == 0u) {
+		direction = vec3<f32>(1.0, -t, -s);
+	} elseif (face == 1u) {
+		direction = vec3<f32>(-1.0, -t, s);
+	} elseif (face == 2u) {
+		direction = vec3<f32>(s, 1.0, t);
+	} elseif (face == 3u) {
+		direction = vec3<f32>(s, -1.0, -t);
+	} elseif (face == 4u) {
+		direction = vec3<f32>(s, -t, 1.0);
+	} else {
+		direction = vec3<f32>(-s, -t, -1.0);
+	}
+	return normalize(direction);
+}
+
+// the texture coordinates of unit vector `direction` in an equirectangular panorama, with +Y at
+// the top and -Z in the middle
+fn equirect_uv(direction: vec3<f32>) -> vec2<f32> {
+	let longitude = atan2(direction.x, -direction.z);
+	let latitude = acos(clamp(direction.y, -1.0, 1.0));
+	return vec2<f32>(longitude / (2.0 * PI) + 0.5, latitude / PI);
+}
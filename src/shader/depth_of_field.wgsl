@@ -0,0 +1,71 @@
+// Depth of field, after Dennis Gustafsson 2018, "Bokeh depth of field in a single pass": gathers a
+// disk of samples as large as the largest circle of confusion around each pixel, each of which
+// covers the pixel if its own circle of confusion reaches it. Those behind the pixel are limited to
+// twice its circle of confusion, so that what is in focus doesn't smear over what is behind it
+
+#include "post.wgsl"
+
+// spreads the samples evenly over the disk
+let GOLDEN_ANGLE: f32 = 2.39996323;
+
+[[block]] struct DepthOfFieldUniform {
+	inverse_projection: mat4x4<f32>;
+	focus_distance: f32;
+	// the blur of what is infinitely far behind the focus, as a fraction of `max_radius`
+	aperture: f32;
+	// the largest circle of confusion, in pixels
+	max_radius: f32;
+	sample_count: u32;
+};
+
+[[group(1), binding(0)]] var<uniform> depth_of_field: DepthOfFieldUniform;
+[[group(2), binding(0)]] var depth: texture_2d<f32>;
+
+// how far in front of the camera the surface at `uv` is
+fn distance_at(uv: vec2<f32>) -> f32 {
+	let size = textureDimensions(depth);
+	let pixel = clamp_pixel(vec2<i32>(uv * vec2<f32>(size)), size);
+	return abs(view_position(depth_of_field.inverse_projection, uv, textureLoad(depth, pixel, 0).x).z);
+}
+
+// the radius of the circle of confusion of a surface `view_distance` in front of the camera, in pixels
+fn circle_of_confusion(view_distance: f32) -> f32 {
+	let blur = depth_of_field.aperture * abs(1.0 - depth_of_field.focus_distance / view_distance);
+	return clamp(blur, 0.0, 1.0) * depth_of_field.max_radius;
+}
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let center = textureLoad(source, vec2<i32>(in.position.xy), 0);
+	let center_distance = distance_at(in.uv);
+	let center_radius = circle_of_confusion(center_distance);
+	let texel = texel_size();
+
+	var color: vec3<f32> = center.rgb;
+	var total: f32 = 1.0;
+	let count = f32(depth_of_field.sample_count);
+	var i: u32 = 0u;
+	loop {
+		if (i >= depth_of_field.sample_count) {
+			break;
+		}
+		let radius = sqrt((f32(i) + 0.5) / count) * depth_of_field.max_radius;
+		let angle = f32(i) * GOLDEN_ANGLE;
+		let uv = in.uv + vec2<f32>(cos(angle), sin(angle)) * radius * texel;
+		let sample_distance = distance_at(uv);
+		var sample_radius: f32 = circle_of_confusion(sample_distance);
+		if (sample_distance > center_distance) {
+			sample_radius = min(sample_radius, center_radius * 2.0);
+		}
+
+		// samples that don't cover the pixel add the average so far, so as not to dim it
+		let coverage = smoothStep(radius - 0.5, radius + 0.5, sample_radius);
+		let sample_color = textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+		color = color + mix(color / total, sample_color, vec3<f32>(coverage));
+		total = total + 1.0;
+		continuing {
+			i = i + 1u;
+		}
+	}
+	return vec4<f32>(color / total, center.a);
+}
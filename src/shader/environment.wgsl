@@ -0,0 +1,41 @@
+// `[[group(2)]]`, continued: image-based lighting by the maps baked from the environment, with
+// the split-sum approximation of the specular BRDF (Karis 2013, "Real Shading in Unreal Engine 4")
+
+#include "lights.wgsl"
+
+[[block]] struct EnvironmentUniform {
+	// world space directions to the directions the maps are sampled with
+	rotation: mat4x4<f32>;
+	intensity: f32;
+	// the mip level of the prefiltered map for a roughness of 1.0
+	max_prefiltered_lod: f32;
+};
+
+[[group(2), binding(4)]] var<uniform> environment: EnvironmentUniform;
+[[group(2), binding(5)]] var environment_sampler: sampler;
+[[group(2), binding(6)]] var irradiance_map: texture_cube<f32>;
+[[group(2), binding(7)]] var prefiltered_map: texture_cube<f32>;
+// the scale and bias to F0, by n.v across and roughness down
+[[group(2), binding(8)]] var brdf_lut: texture_2d<f32>;
+
+// the outgoing radiance towards `v` of the environment reflected by `surface`
+fn shade_environment(surface: Surface, v: vec3<f32>) -> vec3<f32> {
+	let n = surface.normal;
+	let n_dot_v = clamp(abs(dot(n, v)), 0.0001, 1.0);
+	let rotation = mat3x3<f32>(
+		environment.rotation[0].xyz,
+		environment.rotation[1].xyz,
+		environment.rotation[2].xyz,
+	);
+
+	let f0 = mix(vec3<f32>(0.04), surface.base_color.rgb, vec3<f32>(surface.metallic));
+	let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2<f32>(n_dot_v, surface.roughness), 0.0).rg;
+	let reflectance = f0 * brdf.x + vec3<f32>(brdf.y);
+	let lod = surface.roughness * environment.max_prefiltered_lod;
+	let specular = textureSampleLevel(prefiltered_map, environment_sampler, rotation * reflect(-v, n), lod).rgb * reflectance;
+
+	// what isn't reflected is diffused, by dielectrics only
+	let irradiance = textureSampleLevel(irradiance_map, environment_sampler, rotation * n, 0.0).rgb;
+	let diffuse = (vec3<f32>(1.0) - reflectance) * (1.0 - surface.metallic) * surface.base_color.rgb * irradiance;
+	return (diffuse + specular) * environment.intensity;
+}
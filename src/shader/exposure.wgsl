@@ -0,0 +1,52 @@
+// Auto exposure from a histogram of the log luminance of the frame, in two compute passes: the
+// histogram of each tile of the frame, then their sum and its average
+
+#include "color.wgsl"
+
+// bin 0 holds the black pixels, which have no log luminance; bins 1 and up split the log
+// luminance range evenly
+let BIN_COUNT: u32 = 256u;
+// each workgroup of the histogram pass reads every fourth pixel of its tile along each axis
+let TILE_SIZE: u32 = 64u;
+let TILE_STRIDE: u32 = 4u;
+
+[[block]] struct AutoExposureUniform {
+	min_log_luminance: f32;
+	log_luminance_range: f32;
+	// fractions of the pixels, darkest first, between which the luminance is averaged
+	low_percentile: f32;
+	high_percentile: f32;
+	// stops added to the exposure mapping the average luminance to middle grey
+	compensation: f32;
+	// how far the exposure moves towards its target this frame, from 0.0 to 1.0
+	adaptation: f32;
+	tile_columns: u32;
+	tile_count: u32;
+};
+
+// the histogram of every tile, one after the other
+[[block]] struct Tiles {
+	counts: [[stride(4)]] array<u32>;
+};
+
+[[block]] struct Exposure {
+	stops: f32;
+};
+
+[[group(0), binding(0)]] var<uniform> settings: AutoExposureUniform;
+[[group(0), binding(1)]] var source: texture_2d<f32>;
+[[group(0), binding(2)]] var<storage> tiles: [[access(read_write)]] Tiles;
+[[group(0), binding(3)]] var<storage> exposure: [[access(read_write)]] Exposure;
+
+fn luminance_bin(pixel_luminance: f32) -> u32 {
+	if (pixel_luminance < exp2(settings.min_log_luminance)) {
+		return 0u;
+	}
+	let t = clamp((log2(pixel_luminance) - settings.min_log_luminance) / settings.log_luminance_range, 0.0, 1.0);
+	return 1u + u32(t * f32(BIN_COUNT - 2u));
+}
+
+// the log luminance in the middle of a fractional bin
+fn bin_log_luminance(bin: f32) -> f32 {
+	return (bin - 0.5) / f32(BIN_COUNT - 2u) * settings.log_luminance_range + settings.min_log_luminance;
+}
@@ -0,0 +1,72 @@
+// Auto exposure, pass 2 of 2: sum the histograms of the tiles, average the log luminance between
+// the percentiles and adapt the exposure towards the one mapping it to middle grey
+
+#include "exposure.wgsl"
+
+let MIDDLE_GREY: f32 = 0.18;
+
+var<workgroup> histogram: array<u32, 256>;
+
+[[stage(compute), workgroup_size(256)]]
+fn main([[builtin(local_invocation_index)]] bin: u32) {
+	var count: u32 = 0u;
+	var tile: u32 = 0u;
+	loop {
+		if (tile >= settings.tile_count) {
+			break;
+		}
+		count = count + tiles.counts[tile * BIN_COUNT + bin];
+		continuing {
+			tile = tile + 1u;
+		}
+	}
+	histogram[bin] = count;
+	workgroupBarrier();
+	if (bin != 0u) {
+		return;
+	}
+
+	var total: u32 = 0u;
+	var i: u32 = 1u;
+	loop {
+		if (i >= BIN_COUNT) {
+			break;
+		}
+		total = total + histogram[i];
+		continuing {
+			i = i + 1u;
+		}
+	}
+	// a black frame keeps the exposure it had
+	if (total == 0u) {
+		return;
+	}
+
+	// weigh each bin by how much of it lies between the percentiles
+	let low = f32(total) * settings.low_percentile;
+	let high = f32(total) * settings.high_percentile;
+	var below: f32 = 0.0;
+	var weighted_bins: f32 = 0.0;
+	var weight: f32 = 0.0;
+	i = 1u;
+	loop {
+		if (i >= BIN_COUNT) {
+			break;
+		}
+		let bin_count = f32(histogram[i]);
+		let kept = clamp(below + bin_count, low, high) - clamp(below, low, high);
+		weighted_bins = weighted_bins + kept * f32(i);
+		weight = weight + kept;
+		below = below + bin_count;
+		continuing {
+			i = i + 1u;
+		}
+	}
+	if (weight <= 0.0) {
+		return;
+	}
+
+	let average = bin_log_luminance(weighted_bins / weight);
+	let target = log2(MIDDLE_GREY) - average + settings.compensation;
+	exposure.stops = mix(exposure.stops, target, settings.adaptation);
+}
@@ -0,0 +1,41 @@
+// Auto exposure, pass 1 of 2: the histogram of each tile of the frame
+
+#include "exposure.wgsl"
+
+// the bin of each pixel read by the workgroup
+var<workgroup> pixel_bins: array<u32, 256>;
+
+[[stage(compute), workgroup_size(16, 16)]]
+fn main(
+	[[builtin(local_invocation_id)]] local_id: vec3<u32>,
+	[[builtin(local_invocation_index)]] local_index: u32,
+	[[builtin(workgroup_id)]] workgroup_id: vec3<u32>,
+) {
+	let size = textureDimensions(source);
+	let x = workgroup_id.x * TILE_SIZE + local_id.x * TILE_STRIDE;
+	let y = workgroup_id.y * TILE_SIZE + local_id.y * TILE_STRIDE;
+	// pixels off the edge of the frame land in no bin
+	var bin: u32 = BIN_COUNT;
+	if (i32(x) < size.x && i32(y) < size.y) {
+		bin = luminance_bin(luminance(textureLoad(source, vec2<i32>(i32(x), i32(y)), 0).rgb));
+	}
+	pixel_bins[local_index] = bin;
+	workgroupBarrier();
+
+	// without atomics, each invocation counts the pixels in one bin
+	var count: u32 = 0u;
+	var pixel: u32 = 0u;
+	loop {
+		if (pixel >= 256u) {
+			break;
+		}
+		if (pixel_bins[pixel] == local_index) {
+			count = count + 1u;
+		}
+		continuing {
+			pixel = pixel + 1u;
+		}
+	}
+	let tile = workgroup_id.y * settings.tile_columns + workgroup_id.x;
+	tiles.counts[tile * BIN_COUNT + local_index] = count;
+}
@@ -0,0 +1,18 @@
+// A vertex stage covering the whole target with a single triangle, for passes shading every pixel
+
+struct FullscreenOutput {
+	[[builtin(position)]] position: vec4<f32>;
+	// from (0, 0) in the top left corner of the target to (1, 1) in the bottom right
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] vertex_index: u32) -> FullscreenOutput {
+	// (-1, -1), (3, -1) and (-1, 3) for the first three vertices
+	let x = f32(i32(vertex_index & 1u) * 4 - 1);
+	let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+	var out: FullscreenOutput;
+	out.position = vec4<f32>(x, y, 0.0, 1.0);
+	out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+	return out;
+}
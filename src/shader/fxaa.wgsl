@@ -0,0 +1,55 @@
+// Fast approximate anti-aliasing, after Timothy Lottes' FXAA: finds the direction of the edge
+// through each pixel from the luma of its corners and blurs along it, unless that overshoots the
+// range of luma around the pixel. Luma is taken from the HDR colors compressed into the
+// displayable range, as the tonemapping will
+
+#include "post.wgsl"
+#include "color.wgsl"
+
+[[block]] struct FxaaUniform {
+	span_max: f32;
+	reduce_mul: f32;
+	reduce_min: f32;
+};
+
+[[group(1), binding(0)]] var<uniform> fxaa: FxaaUniform;
+
+fn luma(color: vec3<f32>) -> f32 {
+	let y = luminance(color);
+	return y / (1.0 + y);
+}
+
+fn sample_color(uv: vec2<f32>) -> vec3<f32> {
+	return textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+}
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let texel = texel_size();
+	let uv = in.uv;
+	let center = textureLoad(source, vec2<i32>(in.position.xy), 0);
+	let luma_nw = luma(sample_color(uv + texel * vec2<f32>(-1.0, -1.0)));
+	let luma_ne = luma(sample_color(uv + texel * vec2<f32>(1.0, -1.0)));
+	let luma_sw = luma(sample_color(uv + texel * vec2<f32>(-1.0, 1.0)));
+	let luma_se = luma(sample_color(uv + texel * vec2<f32>(1.0, 1.0)));
+	let luma_m = luma(center.rgb);
+	let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+	let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+	// across the gradient of the luma, along the edge
+	var direction: vec2<f32> = vec2<f32>(
+		(luma_sw + luma_se) - (luma_nw + luma_ne),
+		(luma_nw + luma_sw) - (luma_ne + luma_se),
+	);
+	let direction_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * fxaa.reduce_mul, fxaa.reduce_min);
+	let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+	direction = clamp(direction * scale, vec2<f32>(-fxaa.span_max), vec2<f32>(fxaa.span_max)) * texel;
+
+	let color_a = (sample_color(uv + direction * (1.0 / 3.0 - 0.5)) + sample_color(uv + direction * (2.0 / 3.0 - 0.5))) * 0.5;
+	let color_b = color_a * 0.5 + (sample_color(uv - direction * 0.5) + sample_color(uv + direction * 0.5)) * 0.25;
+	let luma_b = luma(color_b);
+	if (luma_b < luma_min || luma_b > luma_max) {
+		return vec4<f32>(color_a, center.a);
+	}
+	return vec4<f32>(color_b, center.a);
+}
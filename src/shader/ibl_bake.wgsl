@@ -0,0 +1,18 @@
+// `[[group(0)]]` of the passes baking the environment maps, which draw a face of a mip level of a
+// cube map at a time: which face, and the map they are baked from at binding 2
+
+#include "fullscreen.wgsl"
+#include "cubemap.wgsl"
+
+[[block]] struct BakeUniform {
+	// in the order +X, -X, +Y, -Y, +Z, -Z
+	face: u32;
+	// that the prefiltered mip level is filtered for
+	roughness: f32;
+	// texels along an edge of the top mip level of the cube being sampled
+	source_size: f32;
+	sample_count: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> bake: BakeUniform;
+[[group(0), binding(1)]] var source_sampler: sampler;
@@ -0,0 +1,39 @@
+// Bakes the split-sum BRDF lookup table: the scale (red) and bias (green) to F0 of the specular
+// reflectance under uniform white light, by n.v across and roughness down (Karis 2013)
+
+#include "fullscreen.wgsl"
+#include "ibl_sampling.wgsl"
+
+let SAMPLE_COUNT: u32 = 1024u;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let n_dot_v = max(in.uv.x, 0.0001);
+	let alpha = max(in.uv.y * in.uv.y, 0.002);
+	// the normal is +Z
+	let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+	var scale: f32 = 0.0;
+	var bias: f32 = 0.0;
+	var i: u32 = 0u;
+	loop {
+		if (i >= SAMPLE_COUNT) {
+			break;
+		}
+		let h = sample_ggx(hammersley(i, SAMPLE_COUNT), alpha);
+		let l = reflect(-v, h);
+		let n_dot_l = clamp(l.z, 0.0, 1.0);
+		if (n_dot_l > 0.0) {
+			let n_dot_h = clamp(h.z, 0.0, 1.0);
+			let v_dot_h = clamp(dot(v, h), 0.0, 1.0);
+			// the BRDF times n.l over the density of `l`, in which the distributions cancel out
+			let weight = visibility_smith_ggx(n_dot_l, n_dot_v, alpha) * 4.0 * v_dot_h * n_dot_l / n_dot_h;
+			let fresnel = pow(1.0 - v_dot_h, 5.0);
+			scale = scale + (1.0 - fresnel) * weight;
+			bias = bias + fresnel * weight;
+		}
+		continuing {
+			i = i + 1u;
+		}
+	}
+	return vec4<f32>(scale / f32(SAMPLE_COUNT), bias / f32(SAMPLE_COUNT), 0.0, 1.0);
+}
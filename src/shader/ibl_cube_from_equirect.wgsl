@@ -0,0 +1,11 @@
+// Bakes a face of the top mip level of the environment cube map from an equirectangular panorama
+
+#include "ibl_bake.wgsl"
+
+[[group(0), binding(2)]] var panorama: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let uv = equirect_uv(cube_face_direction(bake.face, in.uv));
+	return vec4<f32>(textureSampleLevel(panorama, source_sampler, uv, 0.0).rgb, 1.0);
+}
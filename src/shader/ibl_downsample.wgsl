@@ -0,0 +1,13 @@
+// Bakes a face of a mip level of the environment cube map from the level above it
+
+#include "ibl_bake.wgsl"
+
+// a view of the level above alone; a bilinear sample at the center of a texel of this level
+// averages the four texels above it
+[[group(0), binding(2)]] var source: texture_cube<f32>;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let direction = cube_face_direction(bake.face, in.uv);
+	return vec4<f32>(textureSampleLevel(source, source_sampler, direction, 0.0).rgb, 1.0);
+}
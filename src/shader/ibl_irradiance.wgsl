@@ -0,0 +1,27 @@
+// Bakes a face of the irradiance map: the environment averaged over the hemisphere around each
+// direction, weighted by cosine, which is the radiance of a white Lambertian surface facing it
+
+#include "ibl_bake.wgsl"
+#include "ibl_sampling.wgsl"
+
+[[group(0), binding(2)]] var environment_map: texture_cube<f32>;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let n = cube_face_direction(bake.face, in.uv);
+	let frame = tangent_frame(n);
+	var irradiance: vec3<f32> = vec3<f32>(0.0);
+	var i: u32 = 0u;
+	loop {
+		if (i >= bake.sample_count) {
+			break;
+		}
+		let l = sample_cosine(hammersley(i, bake.sample_count));
+		let lod = sample_lod(l.z / PI, bake.sample_count, bake.source_size);
+		irradiance = irradiance + textureSampleLevel(environment_map, source_sampler, frame * l, lod).rgb;
+		continuing {
+			i = i + 1u;
+		}
+	}
+	return vec4<f32>(irradiance / f32(bake.sample_count), 1.0);
+}
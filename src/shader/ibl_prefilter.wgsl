@@ -0,0 +1,42 @@
+// Bakes a face of a mip level of the prefiltered specular map: the environment reflected by the
+// GGX lobe of the level's roughness, taking the view to be along the normal (Karis 2013, "Real
+// Shading in Unreal Engine 4")
+
+#include "ibl_bake.wgsl"
+#include "ibl_sampling.wgsl"
+
+[[group(0), binding(2)]] var environment_map: texture_cube<f32>;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let n = cube_face_direction(bake.face, in.uv);
+	// a mirror reflects the environment as it is
+	if (bake.roughness <= 0.0) {
+		return vec4<f32>(textureSampleLevel(environment_map, source_sampler, n, 0.0).rgb, 1.0);
+	}
+
+	let alpha = bake.roughness * bake.roughness;
+	let frame = tangent_frame(n);
+	var color: vec3<f32> = vec3<f32>(0.0);
+	var weight: f32 = 0.0;
+	var i: u32 = 0u;
+	loop {
+		if (i >= bake.sample_count) {
+			break;
+		}
+		let h = frame * sample_ggx(hammersley(i, bake.sample_count), alpha);
+		let l = reflect(-n, h);
+		let n_dot_l = dot(n, l);
+		if (n_dot_l > 0.0) {
+			// with the view along the normal, the density of `l` is D(h) n.h / (4 v.h) = D(h) / 4
+			let pdf = distribution_ggx(clamp(dot(n, h), 0.0, 1.0), alpha) * 0.25;
+			let lod = sample_lod(pdf, bake.sample_count, bake.source_size);
+			color = color + textureSampleLevel(environment_map, source_sampler, l, lod).rgb * n_dot_l;
+			weight = weight + n_dot_l;
+		}
+		continuing {
+			i = i + 1u;
+		}
+	}
+	return vec4<f32>(color / max(weight, 0.0001), 1.0);
+}
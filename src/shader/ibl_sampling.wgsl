@@ -0,0 +1,43 @@
+// Quasi-random directions for integrating the environment around a normal, importance sampled by
+// the BRDF
+
+#include "brdf.wgsl"
+
+// the `i`th of `n` points of the Hammersley set, in [0, 1)
+fn hammersley(i: u32, n: u32) -> vec2<f32> {
+	return vec2<f32>(f32(i) / f32(n), f32(reverseBits(i)) / 4294967296.0);
+}
+
+// an orthonormal basis with `n` as its Z axis
+fn tangent_frame(n: vec3<f32>) -> mat3x3<f32> {
+	var up: vec3<f32> = vec3<f32>(0.0, 1.0, 0.0);
+	if (abs(n.y) > 0.999) {
+		up = vec3<f32>(1.0, 0.0, 0.0);
+	}
+	let tangent = normalize(cross(up, n));
+	return mat3x3<f32>(tangent, cross(n, tangent), n);
+}
+
+// a direction around +Z, with a density proportional to its cosine
+fn sample_cosine(xi: vec2<f32>) -> vec3<f32> {
+	let phi = 2.0 * PI * xi.x;
+	let r = sqrt(xi.y);
+	return vec3<f32>(r * cos(phi), r * sin(phi), sqrt(1.0 - xi.y));
+}
+
+// a half vector around +Z, with a density proportional to the GGX distribution of `alpha`
+fn sample_ggx(xi: vec2<f32>, alpha: f32) -> vec3<f32> {
+	let phi = 2.0 * PI * xi.x;
+	let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+	let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+	return vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+}
+
+// the mip level of a cube map with `size` texels along an edge whose texels cover about as much
+// of the sphere as each of `sample_count` samples drawn with density `pdf`, so that few samples
+// don't alias (GPU Gems 3, chapter 20)
+fn sample_lod(pdf: f32, sample_count: u32, size: f32) -> f32 {
+	let sample_solid_angle = 1.0 / (f32(sample_count) * pdf + 0.0001);
+	let texel_solid_angle = 4.0 * PI / (6.0 * size * size);
+	return max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+}
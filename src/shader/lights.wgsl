@@ -0,0 +1,96 @@
+// `[[group(2)]]`: the punctual lights, and how they shade a surface
+
+#include "brdf.wgsl"
+#include "material.wgsl"
+
+// directional and spot lights shine along the -Z axis of their transform
+struct DirectionalLight {
+	model_matrix: mat4x4<f32>;
+	// rgb color, with the intensity in the alpha channel
+	color: vec4<f32>;
+	// the slot of the light in the cascaded shadow map, or -1 if it casts no shadows
+	shadow_index: i32;
+	// world units
+	depth_bias: f32;
+	// shadow map texels
+	normal_bias: f32;
+};
+struct PointLight {
+	position: vec3<f32>;
+	// 0.0 for an unlimited range
+	range: f32;
+	color: vec3<f32>;
+	intensity: f32;
+	// the cube of the light in the point shadow map, or -1 if it casts no shadows this frame
+	shadow_index: i32;
+	// world units
+	depth_bias: f32;
+	normal_bias: f32;
+	// the distance encoded as a depth of 1.0 in the shadow cube
+	shadow_far: f32;
+};
+struct SpotLight {
+	position: vec3<f32>;
+	range: f32;
+	direction: vec3<f32>;
+	intensity: f32;
+	color: vec3<f32>;
+	inner_cone_cos: f32;
+	outer_cone_cos: f32;
+};
+
+[[block]] struct LightCounts {
+	directional: u32;
+	point: u32;
+	spot: u32;
+};
+[[block]] struct DirectionalLights {
+	data: [[stride(96)]] array<DirectionalLight>;
+};
+[[block]] struct PointLights {
+	data: [[stride(48)]] array<PointLight>;
+};
+[[block]] struct SpotLights {
+	data: [[stride(64)]] array<SpotLight>;
+};
+
+[[group(2), binding(0)]] var<uniform> light_counts: LightCounts;
+[[group(2), binding(1)]] var<storage> directional_lights: [[access(read)]] DirectionalLights;
+[[group(2), binding(2)]] var<storage> point_lights: [[access(read)]] PointLights;
+[[group(2), binding(3)]] var<storage> spot_lights: [[access(read)]] SpotLights;
+
+// the outgoing radiance towards `v` for light arriving from direction `l` with `radiance`
+fn shade(surface: Surface, v: vec3<f32>, l: vec3<f32>, radiance: vec3<f32>) -> vec3<f32> {
+	let n = surface.normal;
+	let h = normalize(l + v);
+	let n_dot_l = clamp(dot(n, l), 0.0, 1.0);
+	if (n_dot_l <= 0.0) {
+		return vec3<f32>(0.0);
+	}
+	let n_dot_v = clamp(abs(dot(n, v)), 0.0001, 1.0);
+	let n_dot_h = clamp(dot(n, h), 0.0, 1.0);
+	let v_dot_h = clamp(dot(v, h), 0.0, 1.0);
+
+	let alpha = max(surface.roughness * surface.roughness, 0.002);
+	let f0 = mix(vec3<f32>(0.04), surface.base_color.rgb, vec3<f32>(surface.metallic));
+	let f = fresnel_schlick(f0, v_dot_h);
+
+	let diffuse = (vec3<f32>(1.0) - f) * (1.0 - surface.metallic) * surface.base_color.rgb / PI;
+	let specular = f * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_l, n_dot_v, alpha);
+	return (diffuse + specular) * radiance * n_dot_l;
+}
+
+// KHR_lights_punctual: inverse square falloff, smoothly windowed to reach zero at `range`
+fn range_attenuation(light_distance: f32, range: f32) -> f32 {
+	let inverse_square = 1.0 / max(light_distance * light_distance, 0.0001);
+	if (range <= 0.0) {
+		return inverse_square;
+	}
+	let ratio = light_distance / range;
+	return clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0) * inverse_square;
+}
+
+fn spot_attenuation(light: SpotLight, l: vec3<f32>) -> f32 {
+	let cd = dot(light.direction, -l);
+	return smoothStep(light.outer_cone_cos, light.inner_cone_cos, cd);
+}
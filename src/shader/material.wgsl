@@ -0,0 +1,67 @@
+// `[[group(1)]]`: the glTF metallic-roughness material of the mesh
+
+[[block]] struct MaterialUniform {
+	base_color_factor: vec4<f32>;
+	emissive_factor: vec3<f32>;
+	metallic_factor: f32;
+	roughness_factor: f32;
+	normal_scale: f32;
+	occlusion_strength: f32;
+	alpha_cutoff: f32;
+};
+
+[[group(1), binding(0)]] var<uniform> material: MaterialUniform;
+[[group(1), binding(1)]] var material_sampler: sampler;
+[[group(1), binding(2)]] var base_color_texture: texture_2d<f32>;
+[[group(1), binding(3)]] var metallic_roughness_texture: texture_2d<f32>;
+[[group(1), binding(4)]] var normal_texture: texture_2d<f32>;
+[[group(1), binding(5)]] var occlusion_texture: texture_2d<f32>;
+[[group(1), binding(6)]] var emissive_texture: texture_2d<f32>;
+
+// the material inputs of the glTF metallic-roughness model at a single fragment
+struct Surface {
+	base_color: vec4<f32>;
+	normal: vec3<f32>;
+	metallic: f32;
+	roughness: f32;
+	occlusion: f32;
+	emissive: vec3<f32>;
+};
+
+// `color` is the vertex color, and the vectors are in world space
+fn sample_surface(
+	uv: vec2<f32>,
+	color: vec4<f32>,
+	normal: vec3<f32>,
+	tangent: vec3<f32>,
+	bitangent: vec3<f32>,
+) -> Surface {
+	var surface: Surface;
+
+	surface.base_color = material.base_color_factor * color * textureSample(base_color_texture, material_sampler, uv);
+
+	// roughness is stored in the green channel, metalness in the blue channel
+	let metallic_roughness = textureSample(metallic_roughness_texture, material_sampler, uv);
+	surface.metallic = material.metallic_factor * metallic_roughness.b;
+	surface.roughness = material.roughness_factor * metallic_roughness.g;
+
+#ifdef NORMAL_MAPPED
+	// tangent-space normal mapping
+	let scale = material.normal_scale;
+	let tangent_normal = (textureSample(normal_texture, material_sampler, uv).xyz * 2.0 - vec3<f32>(1.0, 1.0, 1.0)) * vec3<f32>(scale, scale, 1.0);
+	let tbn = mat3x3<f32>(
+		normalize(tangent),
+		normalize(bitangent),
+		normalize(normal),
+	);
+	surface.normal = normalize(tbn * tangent_normal);
+#else
+	surface.normal = normalize(normal);
+#endif
+
+	let occlusion = textureSample(occlusion_texture, material_sampler, uv).r;
+	surface.occlusion = 1.0 + material.occlusion_strength * (occlusion - 1.0);
+
+	surface.emissive = material.emissive_factor * textureSample(emissive_texture, material_sampler, uv).rgb;
+	return surface;
+}
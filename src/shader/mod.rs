@@ -0,0 +1,227 @@
+pub mod preprocessor;
+pub mod watcher;
+
+pub use preprocessor::{PreprocessError, ProcessedShader, ShaderLibrary, SourceLocation};
+pub use watcher::ShaderWatcher;
+
+use crate::reflection::ReflectionError;
+use bitflags::bitflags;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+// the shaders `ShaderLibrary::builtin` embeds, by the names they include each other by
+const BUILTIN_SHADERS: &[(&str, &str)] = &[
+    ("basic.wgsl", include_str!("basic.wgsl")),
+    ("scene.wgsl", include_str!("scene.wgsl")),
+    ("material.wgsl", include_str!("material.wgsl")),
+    ("lights.wgsl", include_str!("lights.wgsl")),
+    ("shadows.wgsl", include_str!("shadows.wgsl")),
+    ("shadow_depth.wgsl", include_str!("shadow_depth.wgsl")),
+    (
+        "shadow_point_depth.wgsl",
+        include_str!("shadow_point_depth.wgsl"),
+    ),
+    ("constants.wgsl", include_str!("constants.wgsl")),
+    ("brdf.wgsl", include_str!("brdf.wgsl")),
+    ("environment.wgsl", include_str!("environment.wgsl")),
+    ("background.wgsl", include_str!("background.wgsl")),
+    ("color.wgsl", include_str!("color.wgsl")),
+    ("tonemap.wgsl", include_str!("tonemap.wgsl")),
+    ("exposure.wgsl", include_str!("exposure.wgsl")),
+    (
+        "exposure_histogram.wgsl",
+        include_str!("exposure_histogram.wgsl"),
+    ),
+    (
+        "exposure_average.wgsl",
+        include_str!("exposure_average.wgsl"),
+    ),
+    ("post.wgsl", include_str!("post.wgsl")),
+    ("post_copy.wgsl", include_str!("post_copy.wgsl")),
+    (
+        "post_depth_resolve.wgsl",
+        include_str!("post_depth_resolve.wgsl"),
+    ),
+    ("bloom.wgsl", include_str!("bloom.wgsl")),
+    (
+        "bloom_downsample.wgsl",
+        include_str!("bloom_downsample.wgsl"),
+    ),
+    ("bloom_upsample.wgsl", include_str!("bloom_upsample.wgsl")),
+    ("bloom_composite.wgsl", include_str!("bloom_composite.wgsl")),
+    ("fxaa.wgsl", include_str!("fxaa.wgsl")),
+    ("ssao.wgsl", include_str!("ssao.wgsl")),
+    ("ssao_occlusion.wgsl", include_str!("ssao_occlusion.wgsl")),
+    ("ssao_apply.wgsl", include_str!("ssao_apply.wgsl")),
+    ("depth_of_field.wgsl", include_str!("depth_of_field.wgsl")),
+    ("vignette.wgsl", include_str!("vignette.wgsl")),
+    (
+        "chromatic_aberration.wgsl",
+        include_str!("chromatic_aberration.wgsl"),
+    ),
+    ("fullscreen.wgsl", include_str!("fullscreen.wgsl")),
+    ("cubemap.wgsl", include_str!("cubemap.wgsl")),
+    ("ibl_bake.wgsl", include_str!("ibl_bake.wgsl")),
+    ("ibl_sampling.wgsl", include_str!("ibl_sampling.wgsl")),
+    (
+        "ibl_cube_from_equirect.wgsl",
+        include_str!("ibl_cube_from_equirect.wgsl"),
+    ),
+    ("ibl_downsample.wgsl", include_str!("ibl_downsample.wgsl")),
+    ("ibl_irradiance.wgsl", include_str!("ibl_irradiance.wgsl")),
+    ("ibl_prefilter.wgsl", include_str!("ibl_prefilter.wgsl")),
+    ("ibl_brdf_lut.wgsl", include_str!("ibl_brdf_lut.wgsl")),
+];
+
+impl ShaderLibrary {
+    /// The engine's shaders, as they were when it was compiled.
+    pub fn builtin() -> Self {
+        let mut library = Self::new();
+        for (name, source) in BUILTIN_SHADERS {
+            library.insert(*name, *source);
+        }
+        library
+    }
+}
+
+bitflags! {
+    /// The variants of the forward shader. Each feature `#define`s its name in `basic.wgsl`.
+    pub struct ShaderFeatures: u32 {
+        // joint matrices deform the vertices
+        const SKINNED = 1 << 0;
+        // the material has a normal texture
+        const NORMAL_MAPPED = 1 << 1;
+        // lights sample the shadow maps
+        const SHADOWED = 1 << 2;
+        // the world matrices come from the instance buffer rather than the model uniforms
+        const INSTANCED = 1 << 3;
+    }
+}
+
+impl ShaderFeatures {
+    const NAMES: [(ShaderFeatures, &'static str); 4] = [
+        (ShaderFeatures::SKINNED, "SKINNED"),
+        (ShaderFeatures::NORMAL_MAPPED, "NORMAL_MAPPED"),
+        (ShaderFeatures::SHADOWED, "SHADOWED"),
+        (ShaderFeatures::INSTANCED, "INSTANCED"),
+    ];
+
+    /// The defines to process a shader with for these features.
+    pub fn defines(self) -> BTreeMap<String, String> {
+        Self::NAMES
+            .iter()
+            .filter(|(feature, _)| self.contains(*feature))
+            .map(|(_, name)| (name.to_string(), String::new()))
+            .collect()
+    }
+}
+
+/// Whatever is built from each permutation of a shader, built once per feature set.
+#[derive(Debug)]
+pub struct PermutationCache<T> {
+    permutations: HashMap<ShaderFeatures, T>,
+}
+
+impl<T> Default for PermutationCache<T> {
+    fn default() -> Self {
+        Self {
+            permutations: HashMap::new(),
+        }
+    }
+}
+
+impl<T> PermutationCache<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, features: ShaderFeatures) -> Option<&T> {
+        self.permutations.get(&features)
+    }
+
+    /// Cache `permutation` for `features`, replacing any cached already.
+    pub fn insert(&mut self, features: ShaderFeatures, permutation: T) {
+        self.permutations.insert(features, permutation);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ShaderFeatures, &T)> {
+        self.permutations
+            .iter()
+            .map(|(features, permutation)| (*features, permutation))
+    }
+}
+
+/// Ways in which turning a shader of a `ShaderLibrary` into a pipeline can fail.
+#[derive(Debug, Error)]
+pub enum ShaderError {
+    #[error(transparent)]
+    Preprocess(#[from] PreprocessError),
+    #[error("{location}:{column}: {message}\n  | {snippet}")]
+    Parse {
+        location: SourceLocation,
+        column: usize,
+        message: String,
+        // the offending line, as parsed
+        snippet: String,
+    },
+    #[error(transparent)]
+    Reflection(#[from] ReflectionError),
+    #[error("failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+}
+
+impl ProcessedShader {
+    /// Parse the assembled WGSL, reporting errors at the file and line they came from.
+    pub fn parse(&self) -> Result<naga::Module, ShaderError> {
+        naga::front::wgsl::parse_str(self.source()).map_err(|err| {
+            let (line, column) = err.location(self.source());
+            let location = self
+                .location(line)
+                .cloned()
+                .unwrap_or_else(|| SourceLocation {
+                    file: self.name().to_string(),
+                    line,
+                });
+            ShaderError::Parse {
+                location,
+                column,
+                message: err.to_string(),
+                snippet: self
+                    .source()
+                    .lines()
+                    .nth(line.saturating_sub(1))
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_parse_errors_to_their_file() {
+        let mut library = ShaderLibrary::new();
+        library.insert("main.wgsl", "#include \"broken.wgsl\"\nfn main() {}\n");
+        library.insert("broken.wgsl", "// a comment\nlet x: f32 = ;\n");
+        let result = library
+            .process("main.wgsl", &ShaderFeatures::empty().defines())
+            .unwrap()
+            .parse();
+        match result {
+            Err(ShaderError::Parse {
+                location, snippet, ..
+            }) => {
+                assert_eq!(location.file, "broken.wgsl");
+                assert_eq!(location.line, 2);
+                assert_eq!(snippet, "let x: f32 = ;");
+            }
+            result => panic!("{:?}", result.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+// The bindings and helpers shared by the post-process effects. Each reads the frame so far through
+// `[[group(0)]]`, its uniform through `[[group(1)]]` and any other texture through `[[group(2)]]`
+
+#include "fullscreen.wgsl"
+
+[[group(0), binding(0)]] var source: texture_2d<f32>;
+[[group(0), binding(1)]] var source_sampler: sampler;
+
+// the size of a pixel of the frame, in texture coordinates
+fn texel_size() -> vec2<f32> {
+	return vec2<f32>(1.0) / vec2<f32>(textureDimensions(source));
+}
+
+// the view space position of what the depth buffer holds `depth` for at `uv`
+fn view_position(inverse_projection: mat4x4<f32>, uv: vec2<f32>, depth: f32) -> vec3<f32> {
+	let position = inverse_projection * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+	return position.xyz / position.w;
+}
+
+// `pixel` of a texture of `size` pixels, moved onto its nearest edge if it is outside it
+fn clamp_pixel(pixel: vec2<i32>, size: vec2<i32>) -> vec2<i32> {
+	return clamp(pixel, vec2<i32>(0), size - vec2<i32>(1));
+}
@@ -0,0 +1,8 @@
+// Copies the frame through unchanged, when no effect is enabled
+
+#include "post.wgsl"
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	return textureLoad(source, vec2<i32>(in.position.xy), 0);
+}
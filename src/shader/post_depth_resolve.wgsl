@@ -0,0 +1,17 @@
+// Copies the depth buffer of the forward pass into a single-sampled float target the effects can
+// read, taking the first sample of each pixel when it is multisampled
+
+#include "fullscreen.wgsl"
+
+#ifdef MULTISAMPLED
+[[group(0), binding(0)]] var forward_depth: texture_multisampled_2d<f32>;
+#else
+[[group(0), binding(0)]] var forward_depth: texture_2d<f32>;
+#endif
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	// the last argument is the sample when multisampled, and the mip level otherwise
+	let depth = textureLoad(forward_depth, vec2<i32>(in.position.xy), 0).x;
+	return vec4<f32>(depth, 0.0, 0.0, 0.0);
+}
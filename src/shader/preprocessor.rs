@@ -0,0 +1,458 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use thiserror::Error;
+
+/// A line of one of the files a shader was assembled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    // 1-based
+    pub line: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PreprocessError {
+    #[error("no shader named \"{0}\"")]
+    MissingShader(String),
+    #[error("{location}: no shader named \"{name}\" to include")]
+    MissingInclude {
+        location: SourceLocation,
+        name: String,
+    },
+    #[error("{location}: malformed `#{directive}`")]
+    Malformed {
+        location: SourceLocation,
+        directive: String,
+    },
+    #[error("{location}: unknown directive `#{directive}`")]
+    UnknownDirective {
+        location: SourceLocation,
+        directive: String,
+    },
+    #[error("{location}: `#{directive}` without an `#ifdef` or `#ifndef` before it")]
+    Unmatched {
+        location: SourceLocation,
+        directive: String,
+    },
+    #[error("{location}: `#{directive}` is never closed by an `#endif`")]
+    Unterminated {
+        location: SourceLocation,
+        directive: String,
+    },
+}
+
+/// WGSL sources by name, the names being what `#include` refers to them by.
+///
+/// On top of WGSL, sources may use these directives, each on a line of its own:
+/// - `#include "name.wgsl"` pastes in the source of that name, unless it was already included,
+/// - `#define NAME` and `#define NAME value`, where `value` replaces every later `NAME` token,
+/// - `#undef NAME`,
+/// - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`, which may nest.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderLibrary {
+    sources: HashMap<String, Cow<'static, str>>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the source of `name`, or replace it if the library has one already.
+    pub fn insert(&mut self, name: impl Into<String>, source: impl Into<Cow<'static, str>>) {
+        self.sources.insert(name.into(), source.into());
+    }
+
+    /// Assemble the shader `name` with `defines` defined, as if by a `#define` before its first
+    /// line.
+    pub fn process(
+        &self,
+        name: &str,
+        defines: &BTreeMap<String, String>,
+    ) -> Result<ProcessedShader, PreprocessError> {
+        let (name, _) = self
+            .sources
+            .get_key_value(name)
+            .ok_or_else(|| PreprocessError::MissingShader(name.to_string()))?;
+        let mut preprocessor = Preprocessor {
+            library: self,
+            defines: defines
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            included: Vec::new(),
+            source: String::new(),
+            lines: Vec::new(),
+        };
+        preprocessor.include(name)?;
+        Ok(ProcessedShader {
+            name: name.clone(),
+            files: preprocessor
+                .included
+                .iter()
+                .map(|file| file.to_string())
+                .collect(),
+            source: preprocessor.source,
+            lines: preprocessor.lines,
+        })
+    }
+}
+
+/// The WGSL assembled from a shader and everything it includes, along with where each of its
+/// lines came from.
+#[derive(Debug, Clone)]
+pub struct ProcessedShader {
+    name: String,
+    // `name` and everything it included, in the order they were included
+    files: Vec<String>,
+    source: String,
+    // of each line of `source`
+    lines: Vec<SourceLocation>,
+}
+
+impl ProcessedShader {
+    /// The name of the shader in its `ShaderLibrary`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The shader and every shader it included, so that it can be processed again when any of
+    /// them changes.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Where the 1-based `line` of `source` came from.
+    pub fn location(&self, line: usize) -> Option<&SourceLocation> {
+        line.checked_sub(1).and_then(|index| self.lines.get(index))
+    }
+}
+
+struct Preprocessor<'a> {
+    library: &'a ShaderLibrary,
+    // to an empty string for those without a value
+    defines: HashMap<String, String>,
+    // in order
+    included: Vec<&'a str>,
+    source: String,
+    lines: Vec<SourceLocation>,
+}
+
+// an `#ifdef` or `#ifndef` block
+struct Conditional {
+    // where it was opened
+    location: SourceLocation,
+    // whether the lines around the block are kept
+    enclosing: bool,
+    // of the `#ifdef` or `#ifndef`
+    condition: bool,
+    in_else: bool,
+}
+
+impl Conditional {
+    fn active(&self) -> bool {
+        self.enclosing && self.condition != self.in_else
+    }
+}
+
+impl<'a> Preprocessor<'a> {
+    fn include(&mut self, name: &'a str) -> Result<(), PreprocessError> {
+        if self.included.contains(&name) {
+            return Ok(());
+        }
+        self.included.push(name);
+        let source = self.library.sources[name].as_ref();
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let location = SourceLocation {
+                file: name.to_string(),
+                line: index + 1,
+            };
+            let active = conditionals.last().map_or(true, Conditional::active);
+            let directive = match line.trim_start().strip_prefix('#') {
+                Some(directive) => directive,
+                None => {
+                    if active {
+                        let line = self.substitute(line);
+                        self.source.push_str(&line);
+                        self.source.push('\n');
+                        self.lines.push(location);
+                    }
+                    continue;
+                }
+            };
+
+            let (directive, argument) = match directive.find(char::is_whitespace) {
+                Some(end) => (&directive[..end], directive[end..].trim()),
+                None => (directive, ""),
+            };
+            let malformed = |location: SourceLocation| PreprocessError::Malformed {
+                location,
+                directive: directive.to_string(),
+            };
+            match directive {
+                "include" => {
+                    if !active {
+                        continue;
+                    }
+                    let included = argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .ok_or_else(|| malformed(location.clone()))?;
+                    let (included, _) =
+                        self.library
+                            .sources
+                            .get_key_value(included)
+                            .ok_or_else(|| PreprocessError::MissingInclude {
+                                location,
+                                name: included.to_string(),
+                            })?;
+                    self.include(included)?;
+                }
+                "define" => {
+                    let (name, value) = match argument.find(char::is_whitespace) {
+                        Some(end) => (&argument[..end], argument[end..].trim()),
+                        None => (argument, ""),
+                    };
+                    if !is_identifier(name) {
+                        return Err(malformed(location));
+                    }
+                    if active {
+                        self.defines.insert(name.to_string(), value.to_string());
+                    }
+                }
+                "undef" => {
+                    if !is_identifier(argument) {
+                        return Err(malformed(location));
+                    }
+                    if active {
+                        self.defines.remove(argument);
+                    }
+                }
+                "ifdef" | "ifndef" => {
+                    if !is_identifier(argument) {
+                        return Err(malformed(location));
+                    }
+                    let defined = self.defines.contains_key(argument);
+                    conditionals.push(Conditional {
+                        location,
+                        enclosing: active,
+                        condition: defined == (directive == "ifdef"),
+                        in_else: false,
+                    });
+                }
+                "else" => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.in_else => conditional.in_else = true,
+                    _ => {
+                        return Err(PreprocessError::Unmatched {
+                            location,
+                            directive: directive.to_string(),
+                        })
+                    }
+                },
+                "endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(PreprocessError::Unmatched {
+                            location,
+                            directive: directive.to_string(),
+                        });
+                    }
+                }
+                _ => {
+                    return Err(PreprocessError::UnknownDirective {
+                        location,
+                        directive: directive.to_string(),
+                    })
+                }
+            }
+        }
+
+        match conditionals.pop() {
+            Some(conditional) => Err(PreprocessError::Unterminated {
+                location: conditional.location,
+                directive: if conditional.condition {
+                    "ifdef".to_string()
+                } else {
+                    "ifndef".to_string()
+                },
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // `line` with every identifier naming a define with a value replaced by that value
+    fn substitute<'l>(&self, line: &'l str) -> Cow<'l, str> {
+        if self.defines.values().all(String::is_empty) {
+            return Cow::Borrowed(line);
+        }
+        let bytes = line.as_bytes();
+        let mut substituted = String::with_capacity(line.len());
+        // the end of the last substitution
+        let mut copied = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let starts_identifier = (bytes[i].is_ascii_alphabetic() || bytes[i] == b'_')
+                && (i == 0 || !is_identifier_byte(bytes[i - 1]));
+            if !starts_identifier {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < bytes.len() && is_identifier_byte(bytes[i]) {
+                i += 1;
+            }
+            match self.defines.get(&line[start..i]) {
+                Some(value) if !value.is_empty() => {
+                    substituted.push_str(&line[copied..start]);
+                    substituted.push_str(value);
+                    copied = i;
+                }
+                _ => (),
+            }
+        }
+        if copied == 0 {
+            return Cow::Borrowed(line);
+        }
+        substituted.push_str(&line[copied..]);
+        Cow::Owned(substituted)
+    }
+}
+
+fn is_identifier_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.bytes()
+        .next()
+        .map_or(false, |first| !first.is_ascii_digit())
+        && name.bytes().all(is_identifier_byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(sources: &[(&'static str, &'static str)]) -> ShaderLibrary {
+        let mut library = ShaderLibrary::new();
+        for (name, source) in sources {
+            library.insert(*name, *source);
+        }
+        library
+    }
+
+    fn defines(names: &[&str]) -> BTreeMap<String, String> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), String::new()))
+            .collect()
+    }
+
+    fn location(file: &str, line: usize) -> SourceLocation {
+        SourceLocation {
+            file: file.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn includes_each_file_once_and_maps_its_lines() {
+        let library = library(&[
+            (
+                "main.wgsl",
+                "#include \"a.wgsl\"\n#include \"b.wgsl\"\nmain",
+            ),
+            ("a.wgsl", "a"),
+            ("b.wgsl", "#include \"a.wgsl\"\n\nb"),
+        ]);
+        let shader = library.process("main.wgsl", &defines(&[])).unwrap();
+        assert_eq!(shader.source(), "a\n\nb\nmain\n");
+        assert_eq!(shader.files(), ["main.wgsl", "a.wgsl", "b.wgsl"]);
+        assert_eq!(shader.location(1), Some(&location("a.wgsl", 1)));
+        assert_eq!(shader.location(3), Some(&location("b.wgsl", 3)));
+        assert_eq!(shader.location(4), Some(&location("main.wgsl", 3)));
+        assert_eq!(shader.location(5), None);
+    }
+
+    #[test]
+    fn keeps_the_branches_of_defined_names() {
+        let source = "#ifdef A\na\n#ifndef B\nnot b\n#else\nb\n#endif\n#else\nnot a\n#endif";
+        let library = library(&[("main.wgsl", source)]);
+        let process = |names: &[&str]| {
+            library
+                .process("main.wgsl", &defines(names))
+                .unwrap()
+                .source()
+                .to_string()
+        };
+        assert_eq!(process(&[]), "not a\n");
+        assert_eq!(process(&["A"]), "a\nnot b\n");
+        assert_eq!(process(&["A", "B"]), "a\nb\n");
+        assert_eq!(process(&["B"]), "not a\n");
+    }
+
+    #[test]
+    fn replaces_defined_values_by_whole_identifiers() {
+        let source = "#define N 4\n#define EMPTY\nlet n = N + N_2 + EMPTY;\n#undef N\nN";
+        let library = library(&[("main.wgsl", source)]);
+        let shader = library.process("main.wgsl", &defines(&[])).unwrap();
+        assert_eq!(shader.source(), "let n = 4 + N_2 + EMPTY;\nN\n");
+        assert_eq!(shader.location(2), Some(&location("main.wgsl", 5)));
+    }
+
+    #[test]
+    fn reports_where_errors_are() {
+        let library = library(&[
+            ("main.wgsl", "\n#include \"other.wgsl\""),
+            (
+                "other.wgsl",
+                "#ifdef A\n#include \"missing.wgsl\"\n#endif\n#ifndef A\n",
+            ),
+            ("unmatched.wgsl", "#endif"),
+            ("unknown.wgsl", "#pragma once"),
+        ]);
+        let result = library.process("main.wgsl", &defines(&["A"]));
+        assert!(
+            matches!(
+                &result,
+                Err(PreprocessError::MissingInclude { location: l, name })
+                    if *l == location("other.wgsl", 2) && name == "missing.wgsl"
+            ),
+            "{:?}",
+            result
+        );
+        let result = library.process("main.wgsl", &defines(&[]));
+        assert!(
+            matches!(
+                &result,
+                Err(PreprocessError::Unterminated { location: l, .. })
+                    if *l == location("other.wgsl", 4)
+            ),
+            "{:?}",
+            result
+        );
+        assert!(matches!(
+            library.process("unmatched.wgsl", &defines(&[])),
+            Err(PreprocessError::Unmatched { .. })
+        ));
+        assert!(matches!(
+            library.process("unknown.wgsl", &defines(&[])),
+            Err(PreprocessError::UnknownDirective { .. })
+        ));
+        assert!(matches!(
+            library.process("missing.wgsl", &defines(&[])),
+            Err(PreprocessError::MissingShader(_))
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+// `[[group(0)]]`: the camera and model uniforms, and what deforms skinned and morphed meshes.
+// With SHADOW_CASTER defined, the bindings of the shadow depth passes instead.
+
+[[block]] struct CameraUniform {
+	view_matrix: mat4x4<f32>;
+	projection_matrix: mat4x4<f32>;
+	position: vec4<f32>;
+};
+
+[[block]] struct ModelUniform {
+  // unused by instanced draws: the instances carry the world matrices
+  model_matrix : mat4x4<f32>;
+  // the first joint matrix of the skin, or -1 for meshes without one
+  joint_offset: i32;
+  // the first delta of the morph targets, or -1 for meshes without any
+  morph_delta_offset: i32;
+  morph_weight_offset: u32;
+  morph_target_count: u32;
+  morph_vertex_count: u32;
+};
+
+[[block]] struct Joints {
+	matrices: [[stride(64)]] array<mat4x4<f32>>;
+};
+
+struct MorphDelta {
+	position: vec4<f32>;
+	normal: vec4<f32>;
+	tangent: vec4<f32>;
+};
+[[block]] struct MorphDeltas {
+	data: [[stride(48)]] array<MorphDelta>;
+};
+[[block]] struct MorphWeights {
+	data: [[stride(4)]] array<f32>;
+};
+
+#ifdef SHADOW_CASTER
+// the shadow depth passes bind their caster at binding 0, and the joints and morph targets of
+// every mesh in a group of their own
+[[group(0), binding(1)]] var<uniform> model : ModelUniform;
+[[group(1), binding(0)]] var<storage> joints: [[access(read)]] Joints;
+[[group(1), binding(1)]] var<storage> morph_deltas: [[access(read)]] MorphDeltas;
+[[group(1), binding(2)]] var<storage> morph_weights: [[access(read)]] MorphWeights;
+#else
+[[group(0), binding(0)]] var<uniform> camera: CameraUniform;
+[[group(0), binding(1)]] var<uniform> model : ModelUniform;
+[[group(0), binding(2)]] var<storage> joints: [[access(read)]] Joints;
+[[group(0), binding(3)]] var<storage> morph_deltas: [[access(read)]] MorphDeltas;
+[[group(0), binding(4)]] var<storage> morph_weights: [[access(read)]] MorphWeights;
+#endif
+
+#ifdef SKINNED
+// linear blend skinning: the weighted sum of up to four joint matrices
+fn skin_matrix(weights: vec4<f32>, indices: vec4<u32>) -> mat4x4<f32> {
+	let offset = u32(model.joint_offset);
+	return joints.matrices[offset + indices.x] * weights.x
+		+ joints.matrices[offset + indices.y] * weights.y
+		+ joints.matrices[offset + indices.z] * weights.z
+		+ joints.matrices[offset + indices.w] * weights.w;
+}
+#endif
+
+// the offset of vertex `index` in every morph target, scaled by the target weights
+fn morph_delta(index: u32) -> MorphDelta {
+	var position: vec4<f32> = vec4<f32>(0.0);
+	var normal: vec4<f32> = vec4<f32>(0.0);
+	var tangent: vec4<f32> = vec4<f32>(0.0);
+	if (model.morph_delta_offset >= 0) {
+		var target: u32 = 0u;
+		loop {
+			if (target >= model.morph_target_count) {
+				break;
+			}
+			let weight = morph_weights.data[model.morph_weight_offset + target];
+			let delta = morph_deltas.data[u32(model.morph_delta_offset) + target * model.morph_vertex_count + index];
+			position = position + delta.position * weight;
+			normal = normal + delta.normal * weight;
+			tangent = tangent + delta.tangent * weight;
+			continuing {
+				target = target + 1u;
+			}
+		}
+	}
+	return MorphDelta(position, normal, tangent);
+}
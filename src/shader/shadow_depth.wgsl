@@ -0,0 +1,32 @@
+// depth-only pass, rendering shadow casters into one layer of a shadow map. Every caster shares
+// one pipeline, which picks between skinning and the model matrix per draw.
+
+#define SHADOW_CASTER
+#define SKINNED
+#include "scene.wgsl"
+
+[[block]] struct ShadowCasterUniform {
+	view_projection: mat4x4<f32>;
+};
+
+// both bound with dynamic offsets: one slot per shadow map layer and one per entity, the model
+// uniform being declared by `scene.wgsl`
+[[group(0), binding(0)]] var<uniform> caster: ShadowCasterUniform;
+
+// skinned meshes are placed by their joints alone
+fn world_matrix(weights: vec4<f32>, indices: vec4<u32>) -> mat4x4<f32> {
+	if (model.joint_offset >= 0) {
+		return skin_matrix(weights, indices);
+	}
+	return model.model_matrix;
+}
+
+[[stage(vertex)]]
+fn main(
+	[[location(0)]] position: vec4<f32>,
+	[[location(8)]] skin_weight: vec4<f32>,
+	[[location(9)]] skin_index: vec4<u32>,
+	[[builtin(vertex_index)]] vertex_index: u32,
+) -> [[builtin(position)]] vec4<f32> {
+	return caster.view_projection * world_matrix(skin_weight, skin_index) * (position + morph_delta(vertex_index).position);
+}
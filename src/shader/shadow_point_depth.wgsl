@@ -0,0 +1,49 @@
+// depth pass of a point light shadow cube face, storing the linear distance to the light. Every
+// caster shares one pipeline, which picks between skinning and the model matrix per draw.
+
+#define SHADOW_CASTER
+#define SKINNED
+#include "scene.wgsl"
+
+[[block]] struct PointShadowCasterUniform {
+	view_projection: mat4x4<f32>;
+	light_position: vec3<f32>;
+	// the distance mapped to a depth of 1.0
+	far: f32;
+};
+
+// both bound with dynamic offsets: one slot per cube face and one per entity, the model uniform
+// being declared by `scene.wgsl`
+[[group(0), binding(0)]] var<uniform> caster: PointShadowCasterUniform;
+
+// skinned meshes are placed by their joints alone
+fn world_matrix(weights: vec4<f32>, indices: vec4<u32>) -> mat4x4<f32> {
+	if (model.joint_offset >= 0) {
+		return skin_matrix(weights, indices);
+	}
+	return model.model_matrix;
+}
+
+struct VertexOutput {
+	[[builtin(position)]] clip_position: vec4<f32>;
+	[[location(0)]] world_position: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn main(
+	[[location(0)]] position: vec4<f32>,
+	[[location(8)]] skin_weight: vec4<f32>,
+	[[location(9)]] skin_index: vec4<u32>,
+	[[builtin(vertex_index)]] vertex_index: u32,
+) -> VertexOutput {
+	let world_position = world_matrix(skin_weight, skin_index) * (position + morph_delta(vertex_index).position);
+	var out: VertexOutput;
+	out.clip_position = caster.view_projection * world_position;
+	out.world_position = world_position.xyz;
+	return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[builtin(frag_depth)]] f32 {
+	return distance(in.world_position, caster.light_position) / caster.far;
+}
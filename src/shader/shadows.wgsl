@@ -0,0 +1,105 @@
+// `[[group(3)]]`: the cascaded shadow maps of the directional lights and the shadow cubes of the
+// point lights
+
+#include "lights.wgsl"
+
+[[block]] struct ShadowUniform {
+	// lights with a shadow index of `light_count` or higher are unshadowed
+	light_count: u32;
+	cascade_count: u32;
+	pcf_radius: u32;
+	debug_cascades: u32;
+};
+struct Cascade {
+	view_projection: mat4x4<f32>;
+	// the view-space depth at which the cascade ends
+	split_far: f32;
+	texel_size: f32;
+	depth_range: f32;
+};
+// `cascade_count` cascades per shadowed light, at the same index as their shadow map layer
+[[block]] struct Cascades {
+	data: [[stride(80)]] array<Cascade>;
+};
+
+[[group(3), binding(0)]] var<uniform> shadow: ShadowUniform;
+[[group(3), binding(1)]] var<storage> cascades: [[access(read)]] Cascades;
+[[group(3), binding(2)]] var shadow_map: texture_depth_2d_array;
+[[group(3), binding(3)]] var shadow_sampler: sampler_comparison;
+[[group(3), binding(4)]] var point_shadow_map: texture_depth_cube_array;
+
+// the cascade of the given shadow slot covering `view_depth`, or `cascade_count` if there is none
+fn cascade_index(shadow_index: u32, view_depth: f32) -> u32 {
+	var c: u32 = 0u;
+	loop {
+		if (c >= shadow.cascade_count) {
+			break;
+		}
+		if (view_depth < cascades.data[shadow_index * shadow.cascade_count + c].split_far) {
+			break;
+		}
+		continuing {
+			c = c + 1u;
+		}
+	}
+	return c;
+}
+
+// the fraction of the light reaching `world_position`, filtered over a PCF kernel
+fn directional_shadow(light: DirectionalLight, world_position: vec3<f32>, geometric_normal: vec3<f32>, view_depth: f32) -> f32 {
+	if (light.shadow_index < 0) {
+		return 1.0;
+	}
+	let shadow_index = u32(light.shadow_index);
+	if (shadow_index >= shadow.light_count) {
+		return 1.0;
+	}
+	let c = cascade_index(shadow_index, view_depth);
+	if (c >= shadow.cascade_count) {
+		return 1.0;
+	}
+	let layer = shadow_index * shadow.cascade_count + c;
+	let cascade = cascades.data[layer];
+
+	let position = world_position + geometric_normal * light.normal_bias * cascade.texel_size;
+	let clip = cascade.view_projection * vec4<f32>(position, 1.0);
+	let ndc = clip.xyz / clip.w;
+	let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+	let depth = ndc.z - light.depth_bias / cascade.depth_range;
+
+	let texel = 1.0 / f32(textureDimensions(shadow_map).x);
+	let r = i32(shadow.pcf_radius);
+	var lit: f32 = 0.0;
+	var x: i32 = -r;
+	loop {
+		if (x > r) {
+			break;
+		}
+		var y: i32 = -r;
+		loop {
+			if (y > r) {
+				break;
+			}
+			let offset = vec2<f32>(f32(x), f32(y)) * texel;
+			lit = lit + textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, i32(layer), depth);
+			continuing {
+				y = y + 1;
+			}
+		}
+		continuing {
+			x = x + 1;
+		}
+	}
+	let taps = f32((2 * r + 1) * (2 * r + 1));
+	return lit / taps;
+}
+
+// the shadow cubes store the distance to the light divided by `shadow_far`
+fn point_shadow(light: PointLight, world_position: vec3<f32>, geometric_normal: vec3<f32>) -> f32 {
+	if (light.shadow_index < 0) {
+		return 1.0;
+	}
+	let from_light = world_position + geometric_normal * light.normal_bias - light.position;
+	let depth = (length(from_light) - light.depth_bias) / light.shadow_far;
+	return textureSampleCompareLevel(point_shadow_map, shadow_sampler, from_light, light.shadow_index, depth);
+}
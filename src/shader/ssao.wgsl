@@ -0,0 +1,14 @@
+// The uniform of the ambient occlusion passes
+
+[[block]] struct SsaoUniform {
+	projection: mat4x4<f32>;
+	inverse_projection: mat4x4<f32>;
+	// of the hemisphere sampled around each pixel, in view space units
+	radius: f32;
+	bias: f32;
+	// the power the ambient occlusion is raised to
+	intensity: f32;
+	sample_count: u32;
+};
+
+[[group(1), binding(0)]] var<uniform> ssao: SsaoUniform;
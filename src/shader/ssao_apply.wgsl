@@ -0,0 +1,36 @@
+// Blurs the ambient occlusion over 4 by 4 pixels, evening out the noise turning its samples, and
+// darkens the frame by it
+
+#include "post.wgsl"
+#include "ssao.wgsl"
+
+[[group(2), binding(0)]] var occlusion: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let pixel = vec2<i32>(in.position.xy);
+	let size = textureDimensions(occlusion);
+	var sum: f32 = 0.0;
+	var y: i32 = -2;
+	loop {
+		if (y >= 2) {
+			break;
+		}
+		var x: i32 = -2;
+		loop {
+			if (x >= 2) {
+				break;
+			}
+			sum = sum + textureLoad(occlusion, clamp_pixel(pixel + vec2<i32>(x, y), size), 0).x;
+			continuing {
+				x = x + 1;
+			}
+		}
+		continuing {
+			y = y + 1;
+		}
+	}
+	let visibility = pow(sum / 16.0, ssao.intensity);
+	let color = textureLoad(source, pixel, 0);
+	return vec4<f32>(color.rgb * visibility, color.a);
+}
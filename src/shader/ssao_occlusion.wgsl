@@ -0,0 +1,102 @@
+// Screen space ambient occlusion: how much of a hemisphere of samples around the surface in each
+// pixel lies behind the depth buffer, oriented along the normal reconstructed from the depth buffer
+
+#include "post.wgsl"
+#include "ssao.wgsl"
+#include "constants.wgsl"
+
+// spreads the samples evenly around the hemisphere
+let GOLDEN_ANGLE: f32 = 2.39996323;
+
+[[group(2), binding(0)]] var depth: texture_2d<f32>;
+
+// the view space position of the surface at `pixel`, or the nearest pixel inside the frame
+fn position_at(pixel: vec2<i32>) -> vec3<f32> {
+	let size = textureDimensions(depth);
+	let clamped = clamp_pixel(pixel, size);
+	let uv = (vec2<f32>(clamped) + vec2<f32>(0.5)) / vec2<f32>(size);
+	return view_position(ssao.inverse_projection, uv, textureLoad(depth, clamped, 0).x);
+}
+
+// Jimenez 2014's interleaved gradient noise, turning the samples of each pixel differently so that
+// the blur afterwards evens them out
+fn interleaved_gradient_noise(pixel: vec2<f32>) -> f32 {
+	return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let pixel = vec2<i32>(in.position.xy);
+	// nothing occludes the background
+	if (textureLoad(depth, pixel, 0).x >= 1.0) {
+		return vec4<f32>(1.0);
+	}
+	let position = position_at(pixel);
+
+	// from the neighbours nearest in depth on each axis, so that the normal doesn't bend around the
+	// edges of objects
+	let left = position_at(pixel - vec2<i32>(1, 0));
+	let right = position_at(pixel + vec2<i32>(1, 0));
+	let up = position_at(pixel - vec2<i32>(0, 1));
+	let down = position_at(pixel + vec2<i32>(0, 1));
+	var dx: vec3<f32> = right - position;
+	if (abs(position.z - left.z) < abs(right.z - position.z)) {
+		dx = position - left;
+	}
+	var dy: vec3<f32> = down - position;
+	if (abs(position.z - up.z) < abs(down.z - position.z)) {
+		dy = position - up;
+	}
+	var normal: vec3<f32> = normalize(cross(dx, dy));
+	// towards the camera, whichever way view space looks
+	if (dot(normal, position) > 0.0) {
+		normal = -normal;
+	}
+
+	// a basis around the normal, after Duff et al. 2017, "Building an Orthonormal Basis, Revisited",
+	// turned by the noise
+	var side: f32 = 1.0;
+	if (normal.z < 0.0) {
+		side = -1.0;
+	}
+	let a = -1.0 / (side + normal.z);
+	let b = normal.x * normal.y * a;
+	let basis_x = vec3<f32>(1.0 + side * normal.x * normal.x * a, side * b, -side * normal.x);
+	let basis_y = vec3<f32>(b, side + normal.y * normal.y * a, -normal.y);
+	let angle = interleaved_gradient_noise(in.position.xy) * 2.0 * PI;
+	let tangent = basis_x * cos(angle) + basis_y * sin(angle);
+	let bitangent = cross(normal, tangent);
+
+	let size = textureDimensions(depth);
+	let count = f32(ssao.sample_count);
+	var occlusion: f32 = 0.0;
+	var i: u32 = 0u;
+	loop {
+		if (i >= ssao.sample_count) {
+			break;
+		}
+		// cosine weighted around the normal, and denser near the surface
+		let t = (f32(i) + 0.5) / count;
+		let phi = f32(i) * GOLDEN_ANGLE;
+		let sin_theta = sqrt(t);
+		let direction = tangent * (cos(phi) * sin_theta) + bitangent * (sin(phi) * sin_theta) + normal * sqrt(1.0 - t);
+		let sample_position = position + direction * (ssao.radius * mix(0.1, 1.0, t * t));
+
+		// the surface where the sample lands on screen
+		let clip = ssao.projection * vec4<f32>(sample_position, 1.0);
+		let ndc = clip.xy / clip.w;
+		let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+		let surface = position_at(vec2<i32>(uv * vec2<f32>(size)));
+
+		// occluded if the surface is nearer the camera, fading out for surfaces far in front of the
+		// hemisphere
+		let range = smoothStep(0.0, 1.0, ssao.radius / max(abs(position.z - surface.z), 0.0001));
+		if (abs(surface.z) <= abs(sample_position.z) - ssao.bias) {
+			occlusion = occlusion + range;
+		}
+		continuing {
+			i = i + 1u;
+		}
+	}
+	return vec4<f32>(1.0 - occlusion / count);
+}
@@ -0,0 +1,93 @@
+// Resolves the HDR frame into nannou's frame texture: scales it by the exposure, then maps it into
+// the displayable range with the selected tonemapper
+
+#include "fullscreen.wgsl"
+#include "color.wgsl"
+
+let TONEMAPPER_NONE: u32 = 0u;
+let TONEMAPPER_REINHARD: u32 = 1u;
+let TONEMAPPER_ACES_FILMIC: u32 = 2u;
+let TONEMAPPER_AGX: u32 = 3u;
+
+[[block]] struct TonemapUniform {
+	tonemapper: u32;
+};
+
+// written every frame, by the CPU for a manual exposure and by `exposure_average.wgsl` otherwise
+[[block]] struct Exposure {
+	stops: f32;
+};
+
+[[group(0), binding(0)]] var source: texture_2d<f32>;
+[[group(0), binding(1)]] var<uniform> tonemap: TonemapUniform;
+[[group(0), binding(2)]] var<storage> exposure: [[access(read)]] Exposure;
+
+// Reinhard et al. 2002, on the luminance alone so that saturated colors keep their hue
+fn tonemap_reinhard(color: vec3<f32>) -> vec3<f32> {
+	return color / (1.0 + luminance(color));
+}
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms, from and to linear
+// sRGB: https://github.com/TheRealMJP/BakingLab/blob/master/BakingLab/ACES.hlsl
+fn tonemap_aces_filmic(color: vec3<f32>) -> vec3<f32> {
+	let input = mat3x3<f32>(
+		vec3<f32>(0.59719, 0.07600, 0.02840),
+		vec3<f32>(0.35458, 0.90834, 0.13383),
+		vec3<f32>(0.04823, 0.01566, 0.83777),
+	);
+	let output = mat3x3<f32>(
+		vec3<f32>(1.60475, -0.10208, -0.00327),
+		vec3<f32>(-0.53108, 1.10813, -0.07276),
+		vec3<f32>(-0.07367, -0.00605, 1.07602),
+	);
+	let v = input * color;
+	let a = v * (v + vec3<f32>(0.0245786)) - vec3<f32>(0.000090537);
+	let b = v * (v * 0.983729 + vec3<f32>(0.4329510)) + vec3<f32>(0.238081);
+	return output * (a / b);
+}
+
+// Troy Sobotka's AgX, after Benjamin Wrensch's polynomial fit of its default contrast curve:
+// https://iolite-engine.com/blog_posts/minimal_agx_implementation
+fn tonemap_agx(color: vec3<f32>) -> vec3<f32> {
+	let inset = mat3x3<f32>(
+		vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+		vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+		vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+	);
+	let outset = mat3x3<f32>(
+		vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+		vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+		vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+	);
+	let min_ev = -12.47393;
+	let max_ev = 4.026069;
+
+	// into the log encoding the curve was fit in, which spans `min_ev` to `max_ev` stops around
+	// middle grey
+	let encoded = clamp(log2(max(inset * color, vec3<f32>(0.0000000001))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+	let x = (encoded - vec3<f32>(min_ev)) / (max_ev - min_ev);
+	let x2 = x * x;
+	let x4 = x2 * x2;
+	let curve = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - vec3<f32>(0.00232);
+
+	// the curve ends in display encoding, which nannou applies again on the way to the swap chain
+	return pow(max(outset * curve, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let hdr = textureLoad(source, vec2<i32>(in.position.xy), 0).rgb;
+	let color = hdr * exp2(exposure.stops);
+
+	var mapped: vec3<f32>;
+	if (tonemap.tonemapper == TONEMAPPER_REINHARD) {
+		mapped = tonemap_reinhard(color);
+	} elseif (tonemap.tonemapper == TONEMAPPER_ACES_FILMIC) {
+		mapped = tonemap_aces_filmic(color);
+	} elseif (tonemap.tonemapper == TONEMAPPER_AGX) {
+		mapped = tonemap_agx(color);
+	} else {
+		mapped = color;
+	}
+	return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
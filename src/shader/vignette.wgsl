@@ -0,0 +1,22 @@
+// Darkens the frame towards its corners
+
+#include "post.wgsl"
+
+[[block]] struct VignetteUniform {
+	intensity: f32;
+	// from the center, where 1.0 reaches the corners
+	radius: f32;
+	smoothness: f32;
+};
+
+[[group(1), binding(0)]] var<uniform> vignette: VignetteUniform;
+
+[[stage(fragment)]]
+fn main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+	let color = textureLoad(source, vec2<i32>(in.position.xy), 0);
+	// from 0.0 in the center to 1.0 in the corners
+	let from_center = length(in.uv * 2.0 - vec2<f32>(1.0)) * 0.70710678;
+	let edge = vignette.radius + max(vignette.smoothness, 0.0001);
+	let darkening = vignette.intensity * smoothStep(vignette.radius, edge, from_center);
+	return vec4<f32>(color.rgb * (1.0 - darkening), color.a);
+}
@@ -0,0 +1,90 @@
+use super::{ShaderError, ShaderLibrary};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Watches a directory of WGSL sources, loading those that change into a `ShaderLibrary`.
+///
+/// Polls the modification times of the `.wgsl` files in the directory, at most once per
+/// `interval`, which is plenty for the handful of shaders the engine has and needs no thread of
+/// its own.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    interval: Duration,
+    last_poll: Instant,
+    // of each file as of the last poll, by file name
+    modified: HashMap<String, SystemTime>,
+}
+
+impl ShaderWatcher {
+    /// Watch `dir`, taking its shaders as they are now to be those the library already has.
+    pub fn new(dir: impl Into<PathBuf>, interval: Duration) -> Result<Self, ShaderError> {
+        let dir = dir.into();
+        let modified = modification_times(&dir)?;
+        Ok(Self {
+            dir,
+            interval,
+            last_poll: Instant::now(),
+            modified,
+        })
+    }
+
+    /// The directory of the engine's own shaders, if the sources it was compiled from are still
+    /// around.
+    pub fn builtin_dir() -> Option<&'static Path> {
+        let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader"));
+        if dir.is_dir() {
+            Some(dir)
+        } else {
+            None
+        }
+    }
+
+    /// Load every file added or modified since the last poll into `library`, returning their
+    /// names. Does nothing until `interval` has passed since the last poll.
+    pub fn poll(&mut self, library: &mut ShaderLibrary) -> Result<Vec<String>, ShaderError> {
+        if self.last_poll.elapsed() < self.interval {
+            return Ok(Vec::new());
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        for (name, modified) in modification_times(&self.dir)? {
+            if self.modified.get(&name) == Some(&modified) {
+                continue;
+            }
+            let path = self.dir.join(&name);
+            let source =
+                fs::read_to_string(&path).map_err(|source| ShaderError::Io { path, source })?;
+            library.insert(name.clone(), source);
+            self.modified.insert(name.clone(), modified);
+            changed.push(name);
+        }
+        Ok(changed)
+    }
+}
+
+// of each `.wgsl` file in `dir`, by file name
+fn modification_times(dir: &Path) -> Result<HashMap<String, SystemTime>, ShaderError> {
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| ShaderError::Io { path, source }
+    };
+    let mut modified = HashMap::new();
+    for entry in fs::read_dir(dir).map_err(io_error(dir))? {
+        let path = entry.map_err(io_error(dir))?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("wgsl") {
+            continue;
+        }
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let time = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(io_error(&path))?;
+        modified.insert(name, time);
+    }
+    Ok(modified)
+}
@@ -0,0 +1,207 @@
+use crate::camera::projection::Projection;
+use crate::transform::Handedness;
+use nannou::glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+// https://developer.nvidia.com/gpugems/gpugems3/part-ii-light-and-shadows/chapter-10-parallel-split-shadow-maps-programmable-gpus
+
+/// A single slice of the view frustum and the light-space projection that covers it.
+#[derive(Debug, Clone, Copy)]
+pub struct Cascade {
+    // world space to shadow map clip space
+    pub view_projection: Mat4,
+    // the view-space depth at which the cascade ends
+    pub split_far: f32,
+    // world-space size of one shadow map texel
+    pub texel_size: f32,
+    // world-space depth covered by the orthographic projection
+    pub depth_range: f32,
+}
+
+/// The far distance of each of `count` cascades between `near` and `far`.
+///
+/// `lambda` blends between uniform (0.0) and logarithmic (1.0) splits; logarithmic splits give
+/// the cascades close to the camera more resolution.
+pub fn split_distances(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    // orthographic cameras may start at a depth of 0.0, where logarithmic splits are undefined
+    let log_near = near.max(f32::EPSILON);
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let logarithmic = log_near * (far / log_near).powf(p);
+            let uniform = near + (far - near) * p;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// Fit an orthographic shadow projection around the slice of the camera frustum between the
+/// view-space depths `near` and `far`.
+///
+/// The projection bounds the slice with a sphere, so its size doesn't change as the camera
+/// rotates, and is snapped to whole texels, so shadow edges don't shimmer as the camera moves.
+/// `caster_margin` extends the projection towards the light to catch casters outside the slice.
+pub fn fit_cascade(
+    camera_world: Mat4,
+    projection: &Projection,
+    near: f32,
+    far: f32,
+    light_direction: Vec3,
+    resolution: u32,
+    caster_margin: f32,
+) -> Cascade {
+    let corners: Vec<Vec3> = frustum_slice_corners(projection, near, far)
+        .iter()
+        .map(|&corner| camera_world.transform_point3(corner))
+        .collect();
+    let center = corners.iter().fold(Vec3::ZERO, |sum, &c| sum + c) / corners.len() as f32;
+    let radius = corners
+        .iter()
+        .map(|&c| c.distance(center))
+        .fold(0.0, f32::max);
+    // quantize, so floating point noise doesn't change the projection from frame to frame
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let light_direction = light_direction.normalize();
+    let up = if light_direction.y.abs() > 0.99 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    let eye = center - light_direction * (radius + caster_margin);
+    let view = Mat4::look_at_rh(eye, center, up);
+    let depth_range = 2.0 * radius + caster_margin;
+    let mut ortho = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, depth_range);
+
+    // move the projection by a sub-texel amount so the world origin lands on a texel corner
+    let half_resolution = resolution as f32 / 2.0;
+    let origin = (ortho * view * Vec4::W).xy() * half_resolution;
+    let offset = (origin.round() - origin) / half_resolution;
+    ortho.w_axis.x += offset.x;
+    ortho.w_axis.y += offset.y;
+
+    Cascade {
+        view_projection: ortho * view,
+        split_far: far,
+        texel_size: 2.0 * radius / resolution as f32,
+        depth_range,
+    }
+}
+
+// view space corners of the frustum between the depths `near` and `far`
+fn frustum_slice_corners(projection: &Projection, near: f32, far: f32) -> [Vec3; 8] {
+    // view space looks down -Z in a right-handed and +Z in a left-handed coordinate system
+    let forward = match projection.handedness() {
+        Handedness::Right => -1.0,
+        Handedness::Left => 1.0,
+    };
+    let mut corners = [Vec3::ZERO; 8];
+    for (i, &depth) in [near, far].iter().enumerate() {
+        let (left, right, bottom, top) = projection.bounds_at(depth);
+        for (j, &(x, y)) in [(left, bottom), (right, bottom), (right, top), (left, top)]
+            .iter()
+            .enumerate()
+        {
+            corners[i * 4 + j] = Vec3::new(x, y, forward * depth);
+        }
+    }
+    corners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::projection::{
+        CameraProjection, OrthographicProjection, OrthographicScaling, PerspectiveProjection,
+    };
+    use nannou::glam::Quat;
+
+    #[test]
+    fn splits_run_from_near_to_far() {
+        for &lambda in &[0.0, 0.5, 0.75, 1.0] {
+            let splits = split_distances(0.5, 200.0, 4, lambda);
+            assert_eq!(splits.len(), 4);
+            assert!(splits[0] > 0.5, "lambda {}: {:?}", lambda, splits);
+            assert!(
+                (splits[3] - 200.0).abs() < 1e-3,
+                "lambda {}: {:?}",
+                lambda,
+                splits
+            );
+            assert!(
+                splits.windows(2).all(|pair| pair[0] < pair[1]),
+                "lambda {}: {:?}",
+                lambda,
+                splits
+            );
+        }
+    }
+
+    #[test]
+    fn splits_start_at_a_zero_near_distance() {
+        let splits = split_distances(0.0, 40.0, 3, 1.0);
+        assert!(splits.iter().all(|split| split.is_finite() && *split > 0.0));
+        assert!((splits[2] - 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn logarithmic_splits_favour_the_near_cascades() {
+        let uniform = split_distances(1.0, 100.0, 3, 0.0);
+        let logarithmic = split_distances(1.0, 100.0, 3, 1.0);
+        assert!((uniform[0] - 34.0).abs() < 1e-3);
+        assert!(logarithmic[0] < uniform[0] && logarithmic[1] < uniform[1]);
+    }
+
+    fn assert_cascades_bound_their_slices(projection: &Projection) {
+        let camera_world = Mat4::from_rotation_translation(
+            Quat::from_rotation_y(0.7) * Quat::from_rotation_x(-0.3),
+            Vec3::new(3.0, 2.0, -5.0),
+        );
+        let light_direction = Vec3::new(0.3, -1.0, 0.2);
+        let splits = split_distances(projection.near(), projection.far(), 3, 0.75);
+        let mut split_near = projection.near();
+        for &split_far in &splits {
+            let cascade = fit_cascade(
+                camera_world,
+                projection,
+                split_near,
+                split_far,
+                light_direction,
+                1024,
+                5.0,
+            );
+            assert_eq!(cascade.split_far, split_far);
+            for &corner in frustum_slice_corners(projection, split_near, split_far).iter() {
+                let world = camera_world.transform_point3(corner);
+                let clip = cascade.view_projection.project_point3(world);
+                assert!(
+                    clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0 && (0.0..=1.0).contains(&clip.z),
+                    "corner {:?} of the slice {}..{} lands at {:?}",
+                    world,
+                    split_near,
+                    split_far,
+                    clip
+                );
+            }
+            split_near = split_far;
+        }
+    }
+
+    #[test]
+    fn cascades_bound_perspective_slices() {
+        let mut projection = PerspectiveProjection::new(Handedness::Right, 1.0, 1.0, 0.5, 60.0);
+        projection.update(1600, 900);
+        assert_cascades_bound_their_slices(&Projection::Perspective(projection));
+    }
+
+    #[test]
+    fn cascades_bound_orthographic_slices() {
+        let projection = OrthographicProjection::new(
+            Handedness::Left,
+            OrthographicScaling::Height(8.0),
+            1.5,
+            0.0,
+            40.0,
+        );
+        assert_cascades_bound_their_slices(&Projection::Orthographic(projection));
+    }
+}
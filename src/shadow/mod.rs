@@ -0,0 +1,721 @@
+pub mod cascade;
+pub mod point;
+
+use crate::camera::projection::Projection;
+use crate::camera::BasicCamera;
+use crate::light::{DirectionalLight, Lights};
+use crate::mesh::GpuMesh;
+use crate::morph::MorphResources;
+use crate::reflection::LayoutEntries;
+use crate::render_graph::{RenderGraph, TextureDesc, TextureHandle};
+use crate::shader::{ShaderError, ShaderLibrary};
+use crate::skin::SkinResources;
+use crate::transform::Transformable;
+use crate::uniforms::model::ModelUniform;
+use crate::uniforms::shadow::{
+    CascadeUniform, PointShadowCasterUniform, ShadowCasterUniform, ShadowUniform,
+};
+use crate::uniforms::vertex_input::gltf_mesh_vertex::GltfMeshVertex;
+use crate::BasicEntity;
+use cascade::{fit_cascade, split_distances};
+use crevice::std140::{AsStd140, Std140};
+use nannou::wgpu;
+use nannou::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use point::{point_shadow_slots, PointShadowMaps, PointShadowSettings};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+// https://docs.microsoft.com/en-us/windows/win32/dxtecharticles/cascaded-shadow-maps
+
+/// Per-light shadow settings of a `DirectionalLight`.
+#[derive(Debug, Clone)]
+pub struct DirectionalShadow {
+    // subtracted from the fragment depth, in world units
+    pub depth_bias: f32,
+    // offsets the sampled position along the surface normal, in shadow map texels
+    pub normal_bias: f32,
+}
+
+impl Default for DirectionalShadow {
+    fn default() -> Self {
+        Self {
+            depth_bias: 0.05,
+            normal_bias: 1.0,
+        }
+    }
+}
+
+/// Settings shared by the cascaded shadow maps of every directional light.
+#[derive(Debug, Clone)]
+pub struct CascadeSettings {
+    // shadowed lights beyond this count are rendered unshadowed
+    pub max_lights: u32,
+    pub cascade_count: u32,
+    // width and height of each cascade's shadow map
+    pub resolution: u32,
+    // blends between uniform (0.0) and logarithmic (1.0) cascade splits
+    pub split_lambda: f32,
+    // shadows end at this view distance, or at the camera's far plane if that's closer
+    pub max_distance: f32,
+    // how far towards the light, beyond the view frustum, shadow casters are rendered
+    pub caster_margin: f32,
+    // the PCF kernel covers `(2 * pcf_radius + 1)^2` texels
+    pub pcf_radius: u32,
+    // tints every fragment by the cascade it samples its shadow from
+    pub debug_cascades: bool,
+}
+
+impl Default for CascadeSettings {
+    fn default() -> Self {
+        Self {
+            max_lights: 1,
+            cascade_count: 4,
+            resolution: 2048,
+            split_lambda: 0.75,
+            max_distance: 100.0,
+            caster_margin: 50.0,
+            pcf_radius: 1,
+            debug_cascades: false,
+        }
+    }
+}
+
+/// The shadow slot of each directional light: the index of the light among those that cast
+/// shadows, or `None` if it doesn't.
+pub fn shadow_slots(lights: &[DirectionalLight]) -> impl Iterator<Item = Option<u32>> + '_ {
+    lights.iter().scan(0, |next_slot, light| {
+        Some(light.shadow.as_ref().map(|_| {
+            *next_slot += 1;
+            *next_slot - 1
+        }))
+    })
+}
+
+// dynamically offset uniforms must be aligned to 256 bytes
+const SLOT_SIZE: wgpu::BufferAddress = wgpu::BIND_BUFFER_ALIGNMENT;
+
+/// Cascaded shadow maps for directional lights, and shadow cubes for point lights.
+///
+/// Every shadowed directional light renders `cascade_count` depth-only passes, one per layer of a
+/// shadow map texture array, and every shadowed point light renders the six faces of a cube in a
+/// cube map array. The forward pass samples both at `[[group(3)]]`.
+pub struct ShadowResources {
+    pub settings: CascadeSettings,
+    pub point_settings: PointShadowSettings,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    depth_pipeline: wgpu::RenderPipeline,
+    caster_bind_group_layout: wgpu::BindGroupLayout,
+    point_depth_pipeline: wgpu::RenderPipeline,
+    point_caster_bind_group_layout: wgpu::BindGroupLayout,
+    // in entities
+    model_capacity: usize,
+    model_buffer: wgpu::Buffer,
+    maps: ShadowMaps,
+    point_maps: PointShadowMaps,
+    bind_group: wgpu::BindGroup,
+    caster_bind_group: wgpu::BindGroup,
+    point_caster_bind_group: wgpu::BindGroup,
+    // `[[group(1)]]` of both depth passes
+    deform_bind_group: wgpu::BindGroup,
+    // updated every frame
+    layer_count: u32,
+    point_layer_count: u32,
+    point_shadow_slots: Vec<Option<u32>>,
+}
+
+// everything sized by `max_lights`, `cascade_count` and `resolution`
+struct ShadowMaps {
+    max_lights: u32,
+    cascade_count: u32,
+    resolution: u32,
+    shadow_map: wgpu::Texture,
+    layer_views: Vec<wgpu::TextureView>,
+    // every layer, as sampled by the forward pass
+    view: wgpu::TextureView,
+    cascades_buffer: wgpu::Buffer,
+    caster_buffer: wgpu::Buffer,
+}
+
+impl ShadowMaps {
+    fn new(device: &wgpu::Device, settings: &CascadeSettings) -> Self {
+        let layer_count = (settings.max_lights * settings.cascade_count).max(1);
+        let shadow_map = wgpu::TextureBuilder::new()
+            .size([settings.resolution, settings.resolution])
+            .depth(layer_count)
+            .dimension(wgpu::TextureDimension::D2)
+            .format(ShadowResources::DEPTH_FORMAT)
+            .usage(wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+            .build(device);
+        let layer_views = (0..layer_count)
+            .map(|layer| shadow_map.view().layer(layer).build())
+            .collect();
+        let view = shadow_map
+            .view()
+            .dimension(wgpu::TextureViewDimension::D2Array)
+            .build();
+        let cascades_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shadow_cascades_buffer"),
+            size: (layer_count as usize * CascadeUniform::std140_size_static())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let caster_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shadow_caster_buffer"),
+            size: layer_count as wgpu::BufferAddress * SLOT_SIZE,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            max_lights: settings.max_lights,
+            cascade_count: settings.cascade_count,
+            resolution: settings.resolution,
+            shadow_map,
+            layer_views,
+            view,
+            cascades_buffer,
+            caster_buffer,
+        }
+    }
+
+    fn matches(&self, settings: &CascadeSettings) -> bool {
+        self.max_lights == settings.max_lights
+            && self.cascade_count == settings.cascade_count
+            && self.resolution == settings.resolution
+    }
+}
+
+impl ShadowResources {
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    /// The entries of `bind_group_layout`.
+    pub fn layout_entries() -> LayoutEntries {
+        LayoutEntries::new()
+            .uniform_buffer::<ShadowUniform>(wgpu::ShaderStage::FRAGMENT, false)
+            .storage_buffer(wgpu::ShaderStage::FRAGMENT, false, true)
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2Array,
+                wgpu::TextureSampleType::Depth,
+            )
+            .comparison_sampler(wgpu::ShaderStage::FRAGMENT, true)
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::CubeArray,
+                wgpu::TextureSampleType::Depth,
+            )
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+        settings: CascadeSettings,
+        point_settings: PointShadowSettings,
+        skins: &SkinResources,
+        morphs: &MorphResources,
+    ) -> Result<Self, ShaderError> {
+        let bind_group_layout = Self::layout_entries().build(device);
+
+        // bilinear filtering of the comparison results gives a free 2x2 PCF per tap
+        let sampler = wgpu::SamplerBuilder::new()
+            .address_mode(wgpu::AddressMode::ClampToEdge)
+            .mag_filter(wgpu::FilterMode::Linear)
+            .min_filter(wgpu::FilterMode::Linear)
+            .compare(Some(wgpu::CompareFunction::LessEqual))
+            .build(device);
+
+        let shadow_uniform = ShadowUniform::new(0, 0, 0, false).as_std140();
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("shadow_uniform_buffer"),
+            contents: shadow_uniform.as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        // the joints and morph targets that deform skinned and morphed casters
+        let deform_bind_group_layout = wgpu::BindGroupLayoutBuilder::new()
+            .storage_buffer(wgpu::ShaderStage::VERTEX, false, true)
+            .storage_buffer(wgpu::ShaderStage::VERTEX, false, true)
+            .storage_buffer(wgpu::ShaderStage::VERTEX, false, true)
+            .build(device);
+        let deform_bind_group = wgpu::BindGroupBuilder::new()
+            .buffer_bytes(skins.joints_buffer(), 0, None)
+            .buffer_bytes(morphs.deltas_buffer(), 0, None)
+            .buffer_bytes(morphs.weights_buffer(), 0, None)
+            .build(device, &deform_bind_group_layout);
+
+        let caster_bind_group_layout = wgpu::BindGroupLayoutBuilder::new()
+            .uniform_buffer(wgpu::ShaderStage::VERTEX, true)
+            .uniform_buffer(wgpu::ShaderStage::VERTEX, true)
+            .build(device);
+        let depth_pipeline = build_depth_pipeline(
+            device,
+            shaders,
+            &caster_bind_group_layout,
+            &deform_bind_group_layout,
+        )?;
+        // the fragment shader writes the distance to the light
+        let point_caster_bind_group_layout = wgpu::BindGroupLayoutBuilder::new()
+            .uniform_buffer(wgpu::ShaderStage::VERTEX_FRAGMENT, true)
+            .uniform_buffer(wgpu::ShaderStage::VERTEX, true)
+            .build(device);
+        let point_depth_pipeline = build_point_depth_pipeline(
+            device,
+            shaders,
+            &point_caster_bind_group_layout,
+            &deform_bind_group_layout,
+        )?;
+
+        let model_capacity = 1;
+        let model_buffer = create_model_buffer(device, model_capacity);
+        let maps = ShadowMaps::new(device, &settings);
+        let point_maps = PointShadowMaps::new(device, &point_settings);
+        let (bind_group, caster_bind_group, point_caster_bind_group) = build_bind_groups(
+            device,
+            &bind_group_layout,
+            &caster_bind_group_layout,
+            &point_caster_bind_group_layout,
+            &sampler,
+            &uniform_buffer,
+            &model_buffer,
+            &maps,
+            &point_maps,
+        );
+
+        Ok(Self {
+            settings,
+            point_settings,
+            bind_group_layout,
+            sampler,
+            uniform_buffer,
+            depth_pipeline,
+            caster_bind_group_layout,
+            point_depth_pipeline,
+            point_caster_bind_group_layout,
+            model_capacity,
+            model_buffer,
+            maps,
+            point_maps,
+            bind_group,
+            caster_bind_group,
+            point_caster_bind_group,
+            deform_bind_group,
+            layer_count: 0,
+            point_layer_count: 0,
+            point_shadow_slots: Vec::new(),
+        })
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// The shadow cube of each point light as of the last `update`, or `None` for lights without
+    /// shadows or beyond the budget. Pass it on to `LightResources::update`.
+    pub fn point_shadow_slots(&self) -> &[Option<u32>] {
+        &self.point_shadow_slots
+    }
+
+    /// Fit the cascades of every shadowed directional light to the camera, pick the shadowed
+    /// point lights for this frame, and upload their projections along with the model matrices
+    /// of the shadow casters. Call once per frame, before `render`.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lights: &Lights,
+        camera: &BasicCamera<Projection>,
+        entities: &[BasicEntity],
+    ) {
+        let mut reallocated = false;
+        if !self.maps.matches(&self.settings) {
+            self.maps = ShadowMaps::new(device, &self.settings);
+            reallocated = true;
+        }
+        if !self.point_maps.matches(&self.point_settings) {
+            self.point_maps = PointShadowMaps::new(device, &self.point_settings);
+            reallocated = true;
+        }
+        if entities.len() > self.model_capacity {
+            self.model_capacity = entities.len().next_power_of_two();
+            self.model_buffer = create_model_buffer(device, self.model_capacity);
+            reallocated = true;
+        }
+        if reallocated {
+            let (bind_group, caster_bind_group, point_caster_bind_group) = build_bind_groups(
+                device,
+                &self.bind_group_layout,
+                &self.caster_bind_group_layout,
+                &self.point_caster_bind_group_layout,
+                &self.sampler,
+                &self.uniform_buffer,
+                &self.model_buffer,
+                &self.maps,
+                &self.point_maps,
+            );
+            self.bind_group = bind_group;
+            self.caster_bind_group = caster_bind_group;
+            self.point_caster_bind_group = point_caster_bind_group;
+        }
+
+        if !entities.is_empty() {
+            let model_bytes = slot_bytes(entities.iter().map(|entity| entity.model_uniforms));
+            queue.write_buffer(&self.model_buffer, 0, &model_bytes);
+        }
+
+        let eye = camera.world_mat4().w_axis.truncate();
+        self.point_shadow_slots =
+            point_shadow_slots(&lights.point, eye, self.point_settings.max_lights);
+        let point_casters = point::caster_uniforms(&lights.point, &self.point_shadow_slots);
+        self.point_layer_count = point_casters.len() as u32;
+        if !point_casters.is_empty() {
+            let caster_bytes = slot_bytes(point_casters.into_iter());
+            queue.write_buffer(&self.point_maps.caster_buffer, 0, &caster_bytes);
+        }
+
+        let lights = &lights.directional;
+
+        let settings = &self.settings;
+        let projection = &camera.projection;
+        let near = projection.near();
+        let far = projection.far().min(settings.max_distance).max(near);
+        let splits = split_distances(
+            near,
+            far,
+            settings.cascade_count as usize,
+            settings.split_lambda,
+        );
+        let camera_world = camera.world_mat4();
+
+        // slots are handed out in order, so the shadowed lights in range are a prefix
+        let cascades: Vec<_> = lights
+            .iter()
+            .zip(shadow_slots(lights))
+            .filter_map(|(light, slot)| {
+                slot.filter(|&slot| slot < settings.max_lights)
+                    .map(|_| light)
+            })
+            .flat_map(|light| {
+                let direction = -light.transform.z_axis();
+                let mut split_near = near;
+                splits.iter().map(move |&split_far| {
+                    let cascade = fit_cascade(
+                        camera_world,
+                        projection,
+                        split_near,
+                        split_far,
+                        direction,
+                        settings.resolution,
+                        settings.caster_margin,
+                    );
+                    split_near = split_far;
+                    cascade
+                })
+            })
+            .collect();
+        self.layer_count = cascades.len() as u32;
+
+        let shadow_uniform = ShadowUniform::new(
+            settings.max_lights,
+            settings.cascade_count,
+            settings.pcf_radius,
+            settings.debug_cascades,
+        )
+        .as_std140();
+        queue.write_buffer(&self.uniform_buffer, 0, shadow_uniform.as_bytes());
+
+        if cascades.is_empty() {
+            return;
+        }
+        let cascade_bytes: Vec<u8> = cascades
+            .iter()
+            .flat_map(|c| {
+                CascadeUniform::new(c.view_projection, c.split_far, c.texel_size, c.depth_range)
+                    .as_std140()
+                    .as_bytes()
+                    .to_vec()
+            })
+            .collect();
+        queue.write_buffer(&self.maps.cascades_buffer, 0, &cascade_bytes);
+        let caster_bytes = slot_bytes(
+            cascades
+                .iter()
+                .map(|c| ShadowCasterUniform::new(c.view_projection)),
+        );
+        queue.write_buffer(&self.maps.caster_buffer, 0, &caster_bytes);
+    }
+
+    /// Add a pass rendering the shadow casters to `graph`. Returns the cascaded shadow maps and the
+    /// shadow cubes, for the passes sampling `bind_group` to read.
+    pub fn add_to_graph<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        entities: &'a [BasicEntity],
+        meshes: &'a [GpuMesh],
+    ) -> (TextureHandle, TextureHandle) {
+        let shadow_map = graph.import_texture(
+            "shadow_map",
+            &self.maps.view,
+            TextureDesc::of(&self.maps.shadow_map),
+        );
+        let point_shadow_map = graph.import_texture(
+            "point_shadow_map",
+            &self.point_maps.cube_view,
+            TextureDesc::of(&self.point_maps.shadow_map),
+        );
+        graph
+            .add_pass("shadows")
+            .write_texture(shadow_map)
+            .write_texture(point_shadow_map)
+            .encode(move |encoder, _| self.render(encoder, entities, meshes));
+        (shadow_map, point_shadow_map)
+    }
+
+    // Render the shadow casters into every shadow map layer and cube face in use. `entities` must
+    // be those passed to the last `update`, and `meshes` indexed by their `BasicEntity::mesh`.
+    fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        entities: &[BasicEntity],
+        meshes: &[GpuMesh],
+    ) {
+        for layer in 0..self.layer_count {
+            render_casters(
+                encoder,
+                &self.maps.layer_views[layer as usize],
+                &self.depth_pipeline,
+                &self.caster_bind_group,
+                &self.deform_bind_group,
+                layer,
+                entities,
+                meshes,
+            );
+        }
+        for layer in 0..self.point_layer_count {
+            render_casters(
+                encoder,
+                &self.point_maps.layer_views[layer as usize],
+                &self.point_depth_pipeline,
+                &self.point_caster_bind_group,
+                &self.deform_bind_group,
+                layer,
+                entities,
+                meshes,
+            );
+        }
+    }
+}
+
+// a depth-only pass over every entity, with the caster uniform of `layer`
+#[allow(clippy::too_many_arguments)]
+fn render_casters(
+    encoder: &mut wgpu::CommandEncoder,
+    layer_view: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    caster_bind_group: &wgpu::BindGroup,
+    deform_bind_group: &wgpu::BindGroup,
+    layer: u32,
+    entities: &[BasicEntity],
+    meshes: &[GpuMesh],
+) {
+    let mut render_pass = wgpu::RenderPassBuilder::new()
+        .depth_stencil_attachment(layer_view, |depth| depth)
+        .begin(encoder);
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(1, deform_bind_group, &[]);
+    for (i, entity) in entities.iter().enumerate() {
+        let mesh = &meshes[entity.mesh];
+        let offsets = [
+            (layer as wgpu::BufferAddress * SLOT_SIZE) as wgpu::DynamicOffset,
+            (i as wgpu::BufferAddress * SLOT_SIZE) as wgpu::DynamicOffset,
+        ];
+        render_pass.set_bind_group(0, caster_bind_group, &offsets);
+        render_pass.set_vertex_buffer(0, mesh.vertices_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.indices_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        // the casters are placed by their model uniforms alone
+        render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+    }
+}
+
+// only the position of each vertex is needed, along with its joints for skinned meshes
+const CASTER_ATTRIBUTES: [wgpu::VertexAttribute; 3] = [
+    wgpu::VertexAttribute {
+        offset: GltfMeshVertex::POSITION_OFFSET,
+        shader_location: 0,
+        format: wgpu::VertexFormat::Float32x4,
+    },
+    wgpu::VertexAttribute {
+        offset: GltfMeshVertex::SKIN_WEIGHT_OFFSET,
+        shader_location: 8,
+        format: wgpu::VertexFormat::Float32x4,
+    },
+    wgpu::VertexAttribute {
+        offset: GltfMeshVertex::SKIN_INDEX_OFFSET,
+        shader_location: 9,
+        format: wgpu::VertexFormat::Uint32x4,
+    },
+];
+
+// both depth passes share the deformation code of the forward pass through `scene.wgsl`
+fn build_depth_shader_module(
+    device: &wgpu::Device,
+    shaders: &ShaderLibrary,
+    name: &str,
+) -> Result<wgpu::ShaderModule, ShaderError> {
+    let shader = shaders.process(name, &BTreeMap::new())?;
+    shader.parse()?;
+    Ok(device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        source: wgpu::ShaderSource::Wgsl(Cow::Owned(shader.source().to_string())),
+        flags: wgpu::ShaderFlags::default(),
+        label: Some(name),
+    }))
+}
+
+fn build_depth_pipeline(
+    device: &wgpu::Device,
+    shaders: &ShaderLibrary,
+    caster_bind_group_layout: &wgpu::BindGroupLayout,
+    deform_bind_group_layout: &wgpu::BindGroupLayout,
+) -> Result<wgpu::RenderPipeline, ShaderError> {
+    let shader_module = build_depth_shader_module(device, shaders, "shadow_depth.wgsl")?;
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[caster_bind_group_layout, deform_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    Ok(
+        wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &shader_module)
+            .add_vertex_buffer::<GltfMeshVertex>(&CASTER_ATTRIBUTES)
+            .depth_format(ShadowResources::DEPTH_FORMAT)
+            .build(device),
+    )
+}
+
+// the builder always adds a color target along with a fragment shader, so this pipeline, which
+// has a fragment shader but only a depth target, is described by hand
+fn build_point_depth_pipeline(
+    device: &wgpu::Device,
+    shaders: &ShaderLibrary,
+    caster_bind_group_layout: &wgpu::BindGroupLayout,
+    deform_bind_group_layout: &wgpu::BindGroupLayout,
+) -> Result<wgpu::RenderPipeline, ShaderError> {
+    let shader_module = build_depth_shader_module(device, shaders, "shadow_point_depth.wgsl")?;
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[caster_bind_group_layout, deform_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    Ok(
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("point_shadow_depth"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GltfMeshVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Vertex,
+                    attributes: &CASTER_ATTRIBUTES,
+                }],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: ShadowResources::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "main",
+                targets: &[],
+            }),
+        }),
+    )
+}
+
+fn create_model_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("shadow_model_buffer"),
+        size: capacity as wgpu::BufferAddress * SLOT_SIZE,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// the forward pass bind group, followed by the caster bind groups of both depth passes
+#[allow(clippy::too_many_arguments)]
+fn build_bind_groups(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    caster_bind_group_layout: &wgpu::BindGroupLayout,
+    point_caster_bind_group_layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    uniform_buffer: &wgpu::Buffer,
+    model_buffer: &wgpu::Buffer,
+    maps: &ShadowMaps,
+    point_maps: &PointShadowMaps,
+) -> (wgpu::BindGroup, wgpu::BindGroup, wgpu::BindGroup) {
+    let model_size = wgpu::BufferSize::new(ModelUniform::std140_size_static() as u64);
+    let bind_group = wgpu::BindGroupBuilder::new()
+        .buffer_bytes(uniform_buffer, 0, None)
+        .buffer_bytes(&maps.cascades_buffer, 0, None)
+        .texture_view(&maps.view)
+        .sampler(sampler)
+        .texture_view(&point_maps.cube_view)
+        .build(device, bind_group_layout);
+    let caster_bind_group = wgpu::BindGroupBuilder::new()
+        .buffer_bytes(
+            &maps.caster_buffer,
+            0,
+            wgpu::BufferSize::new(ShadowCasterUniform::std140_size_static() as u64),
+        )
+        .buffer_bytes(model_buffer, 0, model_size)
+        .build(device, caster_bind_group_layout);
+    let point_caster_bind_group = wgpu::BindGroupBuilder::new()
+        .buffer_bytes(
+            &point_maps.caster_buffer,
+            0,
+            wgpu::BufferSize::new(PointShadowCasterUniform::std140_size_static() as u64),
+        )
+        .buffer_bytes(model_buffer, 0, model_size)
+        .build(device, point_caster_bind_group_layout);
+    (bind_group, caster_bind_group, point_caster_bind_group)
+}
+
+// lay out `uniforms` in consecutive dynamic offset slots
+fn slot_bytes<T: AsStd140>(uniforms: impl Iterator<Item = T>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for uniform in uniforms {
+        let start = bytes.len();
+        bytes.extend_from_slice(uniform.as_std140().as_bytes());
+        bytes.resize(start + SLOT_SIZE as usize, 0);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_shaders_parse() {
+        let library = ShaderLibrary::builtin();
+        for name in ["shadow_depth.wgsl", "shadow_point_depth.wgsl"].iter() {
+            let shader = library
+                .process(name, &BTreeMap::new())
+                .unwrap_or_else(|err| panic!("{}", err));
+            assert!(shader.files().iter().any(|file| file == "scene.wgsl"));
+            shader.parse().unwrap_or_else(|err| panic!("{}", err));
+        }
+    }
+}
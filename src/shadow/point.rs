@@ -0,0 +1,241 @@
+use crate::light::PointLight;
+use crate::uniforms::shadow::PointShadowCasterUniform;
+use nannou::glam::{Mat4, Vec3};
+use nannou::wgpu;
+
+/// Per-light shadow settings of a `PointLight`.
+#[derive(Debug, Clone)]
+pub struct PointShadow {
+    // subtracted from the distance to the light, in world units
+    pub depth_bias: f32,
+    // offsets the sampled position along the surface normal, in world units
+    pub normal_bias: f32,
+    // the shadow distance of a light without a range
+    pub far: f32,
+}
+
+impl Default for PointShadow {
+    fn default() -> Self {
+        Self {
+            depth_bias: 0.02,
+            normal_bias: 0.02,
+            far: 100.0,
+        }
+    }
+}
+
+/// Settings shared by the shadow cubes of every point light.
+#[derive(Debug, Clone)]
+pub struct PointShadowSettings {
+    // the per-frame budget of shadowed point lights; the ones closest to the camera win
+    pub max_lights: u32,
+    // width and height of each cube face
+    pub resolution: u32,
+}
+
+impl Default for PointShadowSettings {
+    fn default() -> Self {
+        Self {
+            max_lights: 4,
+            resolution: 512,
+        }
+    }
+}
+
+// the near plane of every cube face; the shadow map stores linear distances, so this only needs
+// to be small enough not to clip casters right next to the light
+const NEAR: f32 = 0.05;
+
+/// The shadow cube of each point light this frame: up to `max_lights` of the lights that opted
+/// in, closest to `eye` first, or `None`.
+pub fn point_shadow_slots(lights: &[PointLight], eye: Vec3, max_lights: u32) -> Vec<Option<u32>> {
+    let mut candidates: Vec<(usize, f32)> = lights
+        .iter()
+        .enumerate()
+        .filter(|(_, light)| light.shadow.is_some())
+        .map(|(i, light)| (i, light.transform.translation.distance_squared(eye)))
+        .collect();
+    candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut slots = vec![None; lights.len()];
+    for (slot, &(i, _)) in candidates.iter().take(max_lights as usize).enumerate() {
+        slots[i] = Some(slot as u32);
+    }
+    slots
+}
+
+/// The view-projection of each cube face, in the order of the cube map layers: +X, -X, +Y, -Y,
+/// +Z, -Z.
+///
+/// Cube maps are addressed in a left-handed coordinate system, so the faces are rendered with
+/// left-handed matrices to come out the right way around.
+pub fn cube_face_view_projections(position: Vec3, far: f32) -> [Mat4; 6] {
+    let projection = Mat4::perspective_lh(std::f32::consts::FRAC_PI_2, 1.0, NEAR, far);
+    let faces = [
+        (Vec3::X, Vec3::Y),
+        (-Vec3::X, Vec3::Y),
+        (Vec3::Y, -Vec3::Z),
+        (-Vec3::Y, Vec3::Z),
+        (Vec3::Z, Vec3::Y),
+        (-Vec3::Z, Vec3::Y),
+    ];
+    let mut view_projections = [Mat4::IDENTITY; 6];
+    for (view_projection, &(forward, up)) in view_projections.iter_mut().zip(faces.iter()) {
+        *view_projection = projection * Mat4::look_at_lh(position, position + forward, up);
+    }
+    view_projections
+}
+
+// everything sized by `max_lights` and `resolution`
+pub(super) struct PointShadowMaps {
+    max_lights: u32,
+    resolution: u32,
+    pub shadow_map: wgpu::Texture,
+    // one per cube face
+    pub layer_views: Vec<wgpu::TextureView>,
+    // every cube, as sampled by the forward pass
+    pub cube_view: wgpu::TextureView,
+    pub caster_buffer: wgpu::Buffer,
+}
+
+impl PointShadowMaps {
+    pub fn new(device: &wgpu::Device, settings: &PointShadowSettings) -> Self {
+        let layer_count = 6 * settings.max_lights.max(1);
+        let shadow_map = wgpu::TextureBuilder::new()
+            .size([settings.resolution, settings.resolution])
+            .depth(layer_count)
+            .dimension(wgpu::TextureDimension::D2)
+            .format(super::ShadowResources::DEPTH_FORMAT)
+            .usage(wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED)
+            .build(device);
+        let layer_views = (0..layer_count)
+            .map(|layer| shadow_map.view().layer(layer).build())
+            .collect();
+        let cube_view = shadow_map
+            .view()
+            .dimension(wgpu::TextureViewDimension::CubeArray)
+            .build();
+        let caster_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("point_shadow_caster_buffer"),
+            size: layer_count as wgpu::BufferAddress * super::SLOT_SIZE,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            max_lights: settings.max_lights,
+            resolution: settings.resolution,
+            shadow_map,
+            layer_views,
+            cube_view,
+            caster_buffer,
+        }
+    }
+
+    pub fn matches(&self, settings: &PointShadowSettings) -> bool {
+        self.max_lights == settings.max_lights && self.resolution == settings.resolution
+    }
+}
+
+// the caster uniforms of the six faces of every shadowed light, ordered by slot
+pub(super) fn caster_uniforms(
+    lights: &[PointLight],
+    slots: &[Option<u32>],
+) -> Vec<PointShadowCasterUniform> {
+    let mut shadowed: Vec<(u32, &PointLight)> = lights
+        .iter()
+        .zip(slots)
+        .filter_map(|(light, slot)| slot.map(|slot| (slot, light)))
+        .collect();
+    shadowed.sort_by_key(|&(slot, _)| slot);
+    shadowed
+        .into_iter()
+        .flat_map(|(_, light)| {
+            let position = light.transform.translation;
+            let far = light.shadow_far();
+            cube_face_view_projections(position, far)
+                .iter()
+                .map(move |&view_projection| {
+                    PointShadowCasterUniform::new(view_projection, position, far)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::Transform;
+    use crevice::std140::{AsStd140, Std140};
+
+    fn point_light(x: f32, shadow: bool) -> PointLight {
+        PointLight {
+            transform: Transform {
+                translation: Vec3::new(x, 0.0, 0.0),
+                ..Default::default()
+            },
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: Some(10.0 + x),
+            shadow: if shadow {
+                Some(PointShadow::default())
+            } else {
+                None
+            },
+        }
+    }
+
+    fn caster_bytes(uniforms: &[PointShadowCasterUniform]) -> Vec<u8> {
+        uniforms
+            .iter()
+            .flat_map(|uniform| uniform.as_std140().as_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn closest_shadowed_lights_win_the_budget() {
+        // the light at 1.0 is the closest, but casts no shadows
+        let lights: Vec<_> = [
+            (9.0, true),
+            (1.0, false),
+            (3.0, true),
+            (-2.0, true),
+            (6.0, true),
+        ]
+        .iter()
+        .map(|&(x, shadow)| point_light(x, shadow))
+        .collect();
+        let slots = point_shadow_slots(&lights, Vec3::ZERO, 3);
+        assert_eq!(slots, vec![None, None, Some(1), Some(0), Some(2)]);
+
+        let casters = caster_uniforms(&lights, &slots);
+        assert_eq!(casters.len(), 3 * 6);
+        // the faces of each light follow its slot, not its index in `lights`
+        let expected: Vec<_> = [3, 2, 4]
+            .iter()
+            .flat_map(|&i| {
+                let light: &PointLight = &lights[i];
+                let position = light.transform.translation;
+                let far = light.shadow_far();
+                cube_face_view_projections(position, far)
+                    .iter()
+                    .map(|&view_projection| {
+                        PointShadowCasterUniform::new(view_projection, position, far)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(caster_bytes(&casters), caster_bytes(&expected));
+    }
+
+    #[test]
+    fn lights_within_the_budget_all_cast_shadows() {
+        let lights: Vec<_> = (0..3).map(|i| point_light(i as f32, true)).collect();
+        let slots = point_shadow_slots(&lights, Vec3::new(10.0, 0.0, 0.0), 4);
+        assert_eq!(slots, vec![Some(2), Some(1), Some(0)]);
+        assert_eq!(caster_uniforms(&lights, &slots).len(), 3 * 6);
+        assert!(point_shadow_slots(&lights, Vec3::ZERO, 0)
+            .iter()
+            .all(Option::is_none));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::scene_graph::{NodeId, SceneGraph};
+use nannou::glam::Mat4;
+use nannou::wgpu;
+
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#skins
+
+/// A skeleton for linear blend skinning.
+///
+/// The joints are scene graph nodes, so the joint hierarchy is the node hierarchy and animating a
+/// joint is a matter of changing its local transform. Every vertex of a skinned mesh blends the
+/// matrices of up to four joints by its `skin_weight`s.
+#[derive(Debug, Clone)]
+pub struct Skin {
+    pub joints: Vec<NodeId>,
+    // from model space to the local space of each joint in the bind pose, at the same index
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+impl Skin {
+    /// The skinning matrix of every joint: from the model space of the bind pose to world space
+    /// in the current pose. A skinned mesh is placed by its joints alone, so its own world matrix
+    /// plays no part.
+    pub fn joint_matrices<'a>(
+        &'a self,
+        scene_graph: &'a SceneGraph,
+    ) -> impl Iterator<Item = Mat4> + 'a {
+        self.joints
+            .iter()
+            .zip(self.inverse_bind_matrices.iter())
+            .map(move |(&joint, inverse_bind_matrix)| {
+                scene_graph.world_matrix(joint) * *inverse_bind_matrix
+            })
+    }
+}
+
+/// The joint matrices of every skin, packed into one storage buffer that is bound to the vertex
+/// stage of every pass that draws meshes. Each skin owns a contiguous range, starting at its
+/// joint offset.
+pub struct SkinResources {
+    joints_buffer: wgpu::Buffer,
+    joint_offsets: Vec<u32>,
+}
+
+// a column-major mat4x4<f32>
+const JOINT_SIZE: wgpu::BufferAddress = 64;
+
+impl SkinResources {
+    /// The buffer is sized for `skins` once; skins can be posed but not added afterwards.
+    pub fn new(device: &wgpu::Device, skins: &[Skin]) -> Self {
+        let mut joint_offsets = Vec::with_capacity(skins.len());
+        let mut joint_count = 0;
+        for skin in skins {
+            joint_offsets.push(joint_count);
+            joint_count += skin.joints.len() as u32;
+        }
+        // bindings cannot be empty
+        let joints_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("skin_joints_buffer"),
+            size: joint_count.max(1) as wgpu::BufferAddress * JOINT_SIZE,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            joints_buffer,
+            joint_offsets,
+        }
+    }
+
+    pub fn joints_buffer(&self) -> &wgpu::Buffer {
+        &self.joints_buffer
+    }
+
+    /// The index of the first joint matrix of `skin` in the joints buffer, for `ModelUniform`.
+    pub fn joint_offset(&self, skin: Option<usize>) -> Option<u32> {
+        skin.map(|skin| self.joint_offsets[skin])
+    }
+
+    /// Upload the joint matrices of the current pose. Call once per frame, after
+    /// `SceneGraph::update_world_matrices`.
+    pub fn update(&self, queue: &wgpu::Queue, skins: &[Skin], scene_graph: &SceneGraph) {
+        let matrices: Vec<[f32; 16]> = skins
+            .iter()
+            .flat_map(|skin| skin.joint_matrices(scene_graph))
+            .map(|matrix| matrix.to_cols_array())
+            .collect();
+        if !matrices.is_empty() {
+            queue.write_buffer(&self.joints_buffer, 0, bytemuck::cast_slice(&matrices));
+        }
+    }
+}
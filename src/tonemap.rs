@@ -0,0 +1,527 @@
+use crate::reflection::{self, LayoutEntries, PipelineInterface};
+use crate::render_graph::{RenderGraph, TextureHandle};
+use crate::shader::{ShaderError, ShaderLibrary};
+use crate::uniforms::tonemap::{AutoExposureUniform, TonemapUniform};
+use crevice::std140::{AsStd140, Std140};
+use nannou::wgpu;
+use nannou::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use nannou::Frame;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Maps the exposed HDR colors of the frame into the displayable range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemapper {
+    /// Clamps every channel to 1.0.
+    None,
+    Reinhard,
+    /// A fit of the ACES reference rendering transform, contrasty and saturated.
+    AcesFilmic,
+    /// Desaturates bright colors towards white rather than skewing their hue.
+    AgX,
+}
+
+impl Tonemapper {
+    // the `TONEMAPPER_*` constant of `tonemap.wgsl`
+    fn index(self) -> u32 {
+        match self {
+            Tonemapper::None => 0,
+            Tonemapper::Reinhard => 1,
+            Tonemapper::AcesFilmic => 2,
+            Tonemapper::AgX => 3,
+        }
+    }
+
+    /// The operator after this one, wrapping around, for cycling through them at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            Tonemapper::None => Tonemapper::Reinhard,
+            Tonemapper::Reinhard => Tonemapper::AcesFilmic,
+            Tonemapper::AcesFilmic => Tonemapper::AgX,
+            Tonemapper::AgX => Tonemapper::None,
+        }
+    }
+}
+
+/// How the frame is exposed, in stops: each one doubles its colors.
+#[derive(Debug, Clone)]
+pub enum Exposure {
+    Manual {
+        stops: f32,
+    },
+    /// Adapts to the luminance of the frame over time, starting from the last exposure.
+    Auto(AutoExposure),
+}
+
+/// Exposes the frame so that its average luminance, between two percentiles of its histogram,
+/// becomes middle grey.
+#[derive(Debug, Clone)]
+pub struct AutoExposure {
+    // the range of log2 luminance the histogram spans; the pixels outside it are counted at its
+    // ends, and those below it as black
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+    // fractions of the pixels, darkest first, between which the luminance is averaged; the rest
+    // are ignored, such as the sky or specular highlights
+    pub low_percentile: f32,
+    pub high_percentile: f32,
+    // stops added to the exposure, brightening the frame when positive
+    pub compensation: f32,
+    // the rate at which the exposure closes in on its target, per second
+    pub speed: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            min_log_luminance: -8.0,
+            max_log_luminance: 4.0,
+            low_percentile: 0.1,
+            high_percentile: 0.9,
+            compensation: 0.0,
+            speed: 1.5,
+        }
+    }
+}
+
+impl AutoExposure {
+    // the fraction of the way to its target the exposure moves in `dt` seconds
+    fn adaptation(&self, dt: f32) -> f32 {
+        1.0 - (-dt * self.speed).exp()
+    }
+}
+
+/// How the HDR frame is resolved into nannou's frame texture.
+#[derive(Debug, Clone)]
+pub struct Tonemapping {
+    pub tonemapper: Tonemapper,
+    pub exposure: Exposure,
+}
+
+impl Default for Tonemapping {
+    fn default() -> Self {
+        Self {
+            tonemapper: Tonemapper::AcesFilmic,
+            exposure: Exposure::Auto(AutoExposure::default()),
+        }
+    }
+}
+
+impl Tonemapping {
+    /// Switch between the default auto exposure and a manual exposure of 0 stops.
+    pub fn toggle_exposure(&mut self) {
+        self.exposure = match self.exposure {
+            Exposure::Manual { .. } => Exposure::Auto(AutoExposure::default()),
+            Exposure::Auto(_) => Exposure::Manual { stops: 0.0 },
+        };
+    }
+}
+
+/// The passes resolving the HDR target of `FrameResources` into nannou's frame texture, and the
+/// compute passes exposing it automatically.
+///
+/// The auto exposure builds a histogram of the log luminance of each 64 by 64 pixel tile of the
+/// frame, from every fourth pixel, then sums them up and averages them in a second pass. The
+/// exposure lives in a storage buffer, so that it adapts from one frame to the next without a
+/// round trip to the CPU.
+pub struct TonemapResources {
+    tonemap_bind_group_layout: wgpu::BindGroupLayout,
+    exposure_bind_group_layout: wgpu::BindGroupLayout,
+    tonemap_buffer: wgpu::Buffer,
+    auto_exposure_buffer: wgpu::Buffer,
+    // the exposure in stops, read by the tonemapping
+    exposure_buffer: wgpu::Buffer,
+    // the histograms of the tiles, sized to the frame
+    tiles_buffer: wgpu::Buffer,
+    // of the histogram tiles
+    tile_grid: [u32; 2],
+    // sample the frame, and are rebuilt along with it
+    tonemap_bind_group: wgpu::BindGroup,
+    exposure_bind_group: wgpu::BindGroup,
+    pipelines: Pipelines,
+    // whether this frame runs the auto exposure passes
+    auto_exposure: bool,
+}
+
+struct Pipelines {
+    tonemap: wgpu::RenderPipeline,
+    histogram: wgpu::ComputePipeline,
+    average: wgpu::ComputePipeline,
+    // the shaders they were processed from
+    files: Vec<String>,
+}
+
+impl TonemapResources {
+    // the pixels of a side of a histogram tile, see `exposure.wgsl`
+    const TILE_SIZE: u32 = 64;
+    const BIN_COUNT: u32 = 256;
+
+    /// The entries of `[[group(0)]]` of `tonemap.wgsl`.
+    pub fn tonemap_layout_entries() -> LayoutEntries {
+        LayoutEntries::new()
+            .texture(
+                wgpu::ShaderStage::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .uniform_buffer::<TonemapUniform>(wgpu::ShaderStage::FRAGMENT, false)
+            .storage_buffer(wgpu::ShaderStage::FRAGMENT, false, true)
+    }
+
+    /// The entries of `[[group(0)]]` of `exposure_histogram.wgsl` and `exposure_average.wgsl`.
+    pub fn exposure_layout_entries() -> LayoutEntries {
+        LayoutEntries::new()
+            .uniform_buffer::<AutoExposureUniform>(wgpu::ShaderStage::COMPUTE, false)
+            .texture(
+                wgpu::ShaderStage::COMPUTE,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .storage_buffer(wgpu::ShaderStage::COMPUTE, false, false)
+            .storage_buffer(wgpu::ShaderStage::COMPUTE, false, false)
+    }
+
+    /// `source` is the HDR target of `size` pixels the forward pass resolves into.
+    pub fn new(
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+        source: &wgpu::TextureView,
+        size: [u32; 2],
+    ) -> Result<Self, ShaderError> {
+        let tonemap_bind_group_layout = Self::tonemap_layout_entries().build(device);
+        let exposure_bind_group_layout = Self::exposure_layout_entries().build(device);
+        let pipelines = Pipelines::new(
+            device,
+            shaders,
+            &tonemap_bind_group_layout,
+            &exposure_bind_group_layout,
+        )?;
+
+        let tonemap_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("tonemap_buffer"),
+            contents: TonemapUniform::new(0).as_std140().as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let auto_exposure = AutoExposureUniform::new(0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0, 0);
+        let auto_exposure_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("auto_exposure_buffer"),
+            contents: auto_exposure.as_std140().as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let exposure_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("exposure_buffer"),
+            contents: &0.0f32.to_le_bytes(),
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let tile_grid = Self::tile_grid(size);
+        let tiles_buffer = Self::tiles_buffer(device, tile_grid);
+        let tonemap_bind_group = wgpu::BindGroupBuilder::new()
+            .texture_view(source)
+            .buffer_bytes(&tonemap_buffer, 0, None)
+            .buffer_bytes(&exposure_buffer, 0, None)
+            .build(device, &tonemap_bind_group_layout);
+        let exposure_bind_group = build_exposure_bind_group(
+            device,
+            &exposure_bind_group_layout,
+            &auto_exposure_buffer,
+            source,
+            &tiles_buffer,
+            &exposure_buffer,
+        );
+
+        Ok(Self {
+            tonemap_bind_group_layout,
+            exposure_bind_group_layout,
+            tonemap_buffer,
+            auto_exposure_buffer,
+            exposure_buffer,
+            tiles_buffer,
+            tile_grid,
+            tonemap_bind_group,
+            exposure_bind_group,
+            pipelines,
+            auto_exposure: false,
+        })
+    }
+
+    fn tile_grid(size: [u32; 2]) -> [u32; 2] {
+        [
+            (size[0] + Self::TILE_SIZE - 1) / Self::TILE_SIZE,
+            (size[1] + Self::TILE_SIZE - 1) / Self::TILE_SIZE,
+        ]
+    }
+
+    fn tiles_buffer(device: &wgpu::Device, tile_grid: [u32; 2]) -> wgpu::Buffer {
+        let tile_count = (tile_grid[0] * tile_grid[1]).max(1);
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("exposure_tiles_buffer"),
+            size: (tile_count * Self::BIN_COUNT * 4) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Sample the new HDR target `source` of `size` pixels, after `FrameResources::resize`.
+    pub fn resize(&mut self, device: &wgpu::Device, source: &wgpu::TextureView, size: [u32; 2]) {
+        self.tile_grid = Self::tile_grid(size);
+        self.tiles_buffer = Self::tiles_buffer(device, self.tile_grid);
+        self.tonemap_bind_group = wgpu::BindGroupBuilder::new()
+            .texture_view(source)
+            .buffer_bytes(&self.tonemap_buffer, 0, None)
+            .buffer_bytes(&self.exposure_buffer, 0, None)
+            .build(device, &self.tonemap_bind_group_layout);
+        self.exposure_bind_group = build_exposure_bind_group(
+            device,
+            &self.exposure_bind_group_layout,
+            &self.auto_exposure_buffer,
+            source,
+            &self.tiles_buffer,
+            &self.exposure_buffer,
+        );
+    }
+
+    // whether the pipelines need rebuilding after the shaders named `changed` were edited
+    pub fn depends_on(&self, changed: &[String]) -> bool {
+        changed
+            .iter()
+            .any(|name| self.pipelines.files.contains(name))
+    }
+
+    /// Rebuild the pipelines from the current `shaders`, keeping the last ones if that fails.
+    pub fn rebuild_pipelines(
+        &mut self,
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+    ) -> Result<(), ShaderError> {
+        self.pipelines = Pipelines::new(
+            device,
+            shaders,
+            &self.tonemap_bind_group_layout,
+            &self.exposure_bind_group_layout,
+        )?;
+        Ok(())
+    }
+
+    /// Upload this frame's settings; `dt` is the time since the last frame, in seconds.
+    pub fn update(&mut self, queue: &wgpu::Queue, tonemapping: &Tonemapping, dt: f32) {
+        let tonemap = TonemapUniform::new(tonemapping.tonemapper.index());
+        queue.write_buffer(&self.tonemap_buffer, 0, tonemap.as_std140().as_bytes());
+        match &tonemapping.exposure {
+            Exposure::Manual { stops } => {
+                queue.write_buffer(&self.exposure_buffer, 0, &stops.to_le_bytes());
+                self.auto_exposure = false;
+            }
+            Exposure::Auto(auto) => {
+                let uniform = AutoExposureUniform::new(
+                    auto.min_log_luminance,
+                    auto.max_log_luminance,
+                    auto.low_percentile,
+                    auto.high_percentile,
+                    auto.compensation,
+                    auto.adaptation(dt),
+                    self.tile_grid[0],
+                    self.tile_grid[0] * self.tile_grid[1],
+                );
+                queue.write_buffer(
+                    &self.auto_exposure_buffer,
+                    0,
+                    uniform.as_std140().as_bytes(),
+                );
+                self.auto_exposure = true;
+            }
+        }
+    }
+
+    /// Add the passes exposing and tonemapping `source`, the HDR target these resources were
+    /// built with, into `frame`.
+    pub fn add_to_graph<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        source: TextureHandle,
+        frame: &'a Frame,
+    ) {
+        let exposure = graph.import_buffer("exposure");
+        if self.auto_exposure {
+            graph
+                .add_pass("auto_exposure")
+                .read_texture(source)
+                .read_buffer(exposure)
+                .write_buffer(exposure)
+                .encode(move |encoder, _| {
+                    let mut compute_pass =
+                        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                            label: Some("auto_exposure"),
+                        });
+                    compute_pass.set_bind_group(0, &self.exposure_bind_group, &[]);
+                    compute_pass.set_pipeline(&self.pipelines.histogram);
+                    compute_pass.dispatch(self.tile_grid[0], self.tile_grid[1], 1);
+                    compute_pass.set_pipeline(&self.pipelines.average);
+                    compute_pass.dispatch(1, 1, 1);
+                });
+        }
+
+        let frame_texture = graph.import_frame(frame);
+        graph
+            .add_pass("tonemap")
+            .read_texture(source)
+            .read_buffer(exposure)
+            .color_attachment(frame_texture, wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT))
+            .render(move |render_pass, _| {
+                render_pass.set_pipeline(&self.pipelines.tonemap);
+                render_pass.set_bind_group(0, &self.tonemap_bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            });
+    }
+}
+
+impl Pipelines {
+    fn new(
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+        tonemap_bind_group_layout: &wgpu::BindGroupLayout,
+        exposure_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<Self, ShaderError> {
+        let mut files = Vec::new();
+        let mut load = |name: &str, entries: LayoutEntries| {
+            let shader = shaders.process(name, &BTreeMap::new())?;
+            let module = shader.parse()?;
+            reflection::validate_module(
+                name,
+                &module,
+                &PipelineInterface {
+                    vertex_buffers: &[],
+                    bind_groups: &[entries],
+                },
+            )?;
+            files.extend(shader.files().iter().cloned());
+            Ok::<_, ShaderError>(device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(shader.source().to_string())),
+                flags: wgpu::ShaderFlags::default(),
+                label: Some(name),
+            }))
+        };
+        let tonemap_module = load("tonemap.wgsl", TonemapResources::tonemap_layout_entries())?;
+        let histogram_module = load(
+            "exposure_histogram.wgsl",
+            TonemapResources::exposure_layout_entries(),
+        )?;
+        let average_module = load(
+            "exposure_average.wgsl",
+            TonemapResources::exposure_layout_entries(),
+        )?;
+
+        let tonemap_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[tonemap_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let tonemap = wgpu::RenderPipelineBuilder::from_layout(&tonemap_layout, &tonemap_module)
+            .fragment_shader(&tonemap_module)
+            .color_format(Frame::TEXTURE_FORMAT)
+            .color_blend(wgpu::BlendComponent::REPLACE)
+            .alpha_blend(wgpu::BlendComponent::REPLACE)
+            .build(device);
+
+        let exposure_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[exposure_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = |label, module| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&exposure_layout),
+                module,
+                entry_point: "main",
+            })
+        };
+
+        files.sort();
+        files.dedup();
+        Ok(Self {
+            tonemap,
+            histogram: compute_pipeline("exposure_histogram", &histogram_module),
+            average: compute_pipeline("exposure_average", &average_module),
+            files,
+        })
+    }
+}
+
+fn build_exposure_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    auto_exposure_buffer: &wgpu::Buffer,
+    source: &wgpu::TextureView,
+    tiles_buffer: &wgpu::Buffer,
+    exposure_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    wgpu::BindGroupBuilder::new()
+        .buffer_bytes(auto_exposure_buffer, 0, None)
+        .texture_view(source)
+        .buffer_bytes(tiles_buffer, 0, None)
+        .buffer_bytes(exposure_buffer, 0, None)
+        .build(device, layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_cycles_through_every_tonemapper() {
+        let mut tonemapper = Tonemapper::None;
+        let mut indices = Vec::new();
+        for _ in 0..4 {
+            indices.push(tonemapper.index());
+            tonemapper = tonemapper.next();
+        }
+        assert_eq!(tonemapper, Tonemapper::None);
+        indices.sort_unstable();
+        assert_eq!(indices, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn tonemap_shaders_match_their_pipelines() {
+        let library = ShaderLibrary::builtin();
+        let shaders = [
+            ("tonemap.wgsl", TonemapResources::tonemap_layout_entries()),
+            (
+                "exposure_histogram.wgsl",
+                TonemapResources::exposure_layout_entries(),
+            ),
+            (
+                "exposure_average.wgsl",
+                TonemapResources::exposure_layout_entries(),
+            ),
+        ];
+        for (name, entries) in shaders.iter() {
+            let shader = library
+                .process(name, &BTreeMap::new())
+                .unwrap_or_else(|err| panic!("{}", err));
+            let module = shader.parse().unwrap_or_else(|err| panic!("{}", err));
+            reflection::validate_module(
+                name,
+                &module,
+                &PipelineInterface {
+                    vertex_buffers: &[],
+                    bind_groups: std::slice::from_ref(entries),
+                },
+            )
+            .unwrap_or_else(|err| panic!("{}", err));
+        }
+    }
+
+    #[test]
+    fn exposure_adapts_at_its_speed() {
+        let auto = AutoExposure::default();
+        assert_eq!(auto.adaptation(0.0), 0.0);
+        // two frames of `dt` move as far as one of `2 * dt`
+        let once = auto.adaptation(0.1);
+        let twice = 1.0 - (1.0 - once) * (1.0 - once);
+        assert!((twice - auto.adaptation(0.2)).abs() < 1e-6);
+        assert!(auto.adaptation(100.0) > 0.999);
+    }
+}
@@ -1,4 +1,4 @@
-use nannou::prelude::{mat3, mat4, vec3, Mat3, Mat4, Quat, Vec3, Vec4};
+use nannou::prelude::{vec3, Mat4, Quat, Vec3};
 use std::cmp::Ordering;
 // http://www.opengl-tutorial.org/beginners-tutorials/tutorial-3-matrices/#translation-matrices
 
@@ -13,15 +13,17 @@ pub enum Handedness {
 pub trait Transformable {
     fn handededness(&self) -> Handedness;
     fn mat4x4(&self) -> Mat4;
+    #[allow(dead_code)]
     fn x_axis(&self) -> Vec3;
     fn y_axis(&self) -> Vec3;
     fn z_axis(&self) -> Vec3;
 }
+#[allow(dead_code)]
 pub trait Orthonormal {
     fn is_orthonormal(&self) -> bool;
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct Transform {
     pub translation: Vec3,
     pub rotation: Quat,
@@ -51,7 +53,7 @@ impl Transformable for Transform {
 
     fn handededness(&self) -> Handedness {
         let handedness =
-            Vec3::dot(Vec3::cross(self.x_axis(), self.y_axis()), self.y_axis()).partial_cmp(&0.0);
+            Vec3::dot(Vec3::cross(self.x_axis(), self.y_axis()), self.z_axis()).partial_cmp(&0.0);
 
         match handedness {
             Some(Ordering::Less) => Handedness::Left,
@@ -67,6 +69,17 @@ impl Transformable for Transform {
     }
 }
 
+// the identity transform; a derived default would have a zero scale
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
 impl Transform {
     pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
         Self {
@@ -76,3 +89,14 @@ impl Transform {
         }
     }
 }
+
+impl From<Mat4> for Transform {
+    fn from(mat4x4: Mat4) -> Self {
+        let (scale, rotation, translation) = mat4x4.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+}
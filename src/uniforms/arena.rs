@@ -0,0 +1,82 @@
+use crevice::std140::{AsStd140, Std140};
+use nannou::wgpu;
+
+/// One uniform buffer holding every per-frame uniform, each in its own slot bound with a dynamic
+/// offset.
+///
+/// The arena is refilled every frame: `clear` it, `push` the uniforms of the frame and `flush`
+/// them to the GPU in a single `Queue::write_buffer`. Writes are ordered before the commands
+/// submitted after them, so a frame's uniforms never overwrite those the previous frame is still
+/// drawing with, and one buffer is enough.
+pub struct UniformArena {
+    buffer: wgpu::Buffer,
+    // in bytes
+    capacity: wgpu::BufferAddress,
+    bytes: Vec<u8>,
+}
+
+impl UniformArena {
+    // dynamically offset uniforms must be aligned to 256 bytes
+    pub const SLOT_ALIGNMENT: wgpu::BufferAddress = wgpu::BIND_BUFFER_ALIGNMENT;
+
+    /// An arena with room for `slots` uniforms of up to `SLOT_ALIGNMENT` bytes before it grows.
+    pub fn new(device: &wgpu::Device, slots: usize) -> Self {
+        let capacity = slots.max(1) as wgpu::BufferAddress * Self::SLOT_ALIGNMENT;
+        Self {
+            buffer: create_buffer(device, capacity),
+            capacity,
+            bytes: Vec::new(),
+        }
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// The binding size of a `T` in the arena, for `wgpu::BindGroupBuilder::buffer_bytes`.
+    pub fn binding_size<T: AsStd140>() -> Option<wgpu::BufferSize> {
+        wgpu::BufferSize::new(T::std140_size_static() as u64)
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+
+    /// Append `uniform` in a slot of its own, returning the dynamic offset to bind it at.
+    pub fn push<T: AsStd140>(&mut self, uniform: &T) -> wgpu::DynamicOffset {
+        let offset = self.bytes.len();
+        self.bytes.extend_from_slice(uniform.as_std140().as_bytes());
+        let end = align(self.bytes.len() as wgpu::BufferAddress);
+        self.bytes.resize(end as usize, 0);
+        offset as wgpu::DynamicOffset
+    }
+
+    /// Upload everything pushed since the last `clear`. Returns `true` if the buffer had to grow,
+    /// in which case every bind group of the old `buffer` must be rebuilt.
+    pub fn flush(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let len = self.bytes.len() as wgpu::BufferAddress;
+        let grown = len > self.capacity;
+        if grown {
+            self.capacity = len.next_power_of_two();
+            self.buffer = create_buffer(device, self.capacity);
+        }
+        if len > 0 {
+            queue.write_buffer(&self.buffer, 0, &self.bytes);
+        }
+        grown
+    }
+}
+
+fn align(len: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    let alignment = UniformArena::SLOT_ALIGNMENT;
+    (len + alignment - 1) / alignment * alignment
+}
+
+fn create_buffer(device: &wgpu::Device, size: wgpu::BufferAddress) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("uniform_arena"),
+        size,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
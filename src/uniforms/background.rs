@@ -0,0 +1,95 @@
+use crevice::std140::AsStd140;
+use mint::*;
+use nannou::glam::{Mat4, Vec3, Vec4};
+
+// the background pass; what the colors hold depends on `mode`
+#[derive(AsStd140, Clone, Copy)]
+pub struct BackgroundUniform {
+    // from clip space back to world space, to reconstruct view rays
+    inverse_view_projection: ColumnMatrix4<f32>,
+    // world space directions to the directions the environment maps are sampled with
+    rotation: ColumnMatrix4<f32>,
+    // the colors of a gradient
+    zenith: Vector4<f32>,
+    horizon: Vector4<f32>,
+    nadir: Vector4<f32>,
+    // the Perez coefficients of a physical sky, for its (x, y, Y) components
+    perez_a: Vector4<f32>,
+    perez_b: Vector4<f32>,
+    perez_c: Vector4<f32>,
+    perez_d: Vector4<f32>,
+    perez_e: Vector4<f32>,
+    // the (x, y, Y) of a physical sky straight up
+    sky_zenith: Vector4<f32>,
+    // towards the sun of a physical sky
+    sun_direction: Vector4<f32>,
+    // one of the `BACKGROUND_*` constants of `background.wgsl`
+    mode: u32,
+    intensity: f32,
+    // the mip level a skybox is sampled at
+    lod: f32,
+}
+
+impl BackgroundUniform {
+    pub fn new(inverse_view_projection: Mat4, mode: u32) -> Self {
+        let zero = Vector4::from(Vec4::ZERO);
+        Self {
+            inverse_view_projection: ColumnMatrix4::from(inverse_view_projection),
+            rotation: ColumnMatrix4::from(Mat4::IDENTITY),
+            zenith: zero,
+            horizon: zero,
+            nadir: zero,
+            perez_a: zero,
+            perez_b: zero,
+            perez_c: zero,
+            perez_d: zero,
+            perez_e: zero,
+            sky_zenith: zero,
+            sun_direction: zero,
+            mode,
+            intensity: 1.0,
+            lod: 0.0,
+        }
+    }
+
+    // for the skybox and panorama, sampled like the environment maps of the forward pass
+    pub fn with_environment(self, rotation: Mat4, intensity: f32, lod: f32) -> Self {
+        Self {
+            rotation: ColumnMatrix4::from(rotation),
+            intensity,
+            lod,
+            ..self
+        }
+    }
+
+    pub fn with_gradient(self, zenith: Vec3, horizon: Vec3, nadir: Vec3) -> Self {
+        Self {
+            zenith: Vector4::from(zenith.extend(1.0)),
+            horizon: Vector4::from(horizon.extend(1.0)),
+            nadir: Vector4::from(nadir.extend(1.0)),
+            ..self
+        }
+    }
+
+    // `perez` holds the coefficients A to E
+    pub fn with_physical_sky(
+        self,
+        perez: [Vec3; 5],
+        sky_zenith: Vec3,
+        sun_direction: Vec3,
+        intensity: f32,
+    ) -> Self {
+        let [a, b, c, d, e] = perez;
+        Self {
+            intensity,
+            perez_a: Vector4::from(a.extend(0.0)),
+            perez_b: Vector4::from(b.extend(0.0)),
+            perez_c: Vector4::from(c.extend(0.0)),
+            perez_d: Vector4::from(d.extend(0.0)),
+            perez_e: Vector4::from(e.extend(0.0)),
+            sky_zenith: Vector4::from(sky_zenith.extend(0.0)),
+            sun_direction: Vector4::from(sun_direction.extend(0.0)),
+            ..self
+        }
+    }
+}
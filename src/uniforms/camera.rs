@@ -6,6 +6,8 @@ use nannou::glam::Mat4;
 pub struct CameraUniform {
     view_matrix: ColumnMatrix4<f32>,
     projection_matrix: ColumnMatrix4<f32>,
+    // world-space eye position, for view-dependent shading
+    position: Vector4<f32>,
 }
 
 impl CameraUniform {
@@ -13,6 +15,11 @@ impl CameraUniform {
         Self {
             view_matrix: ColumnMatrix4::from(view_matrix),
             projection_matrix: ColumnMatrix4::from(projection_matrix),
+            position: Vector4::from(view_matrix.inverse().w_axis),
         }
     }
+
+    pub fn view_projection(&self) -> Mat4 {
+        Mat4::from(self.projection_matrix) * Mat4::from(self.view_matrix)
+    }
 }
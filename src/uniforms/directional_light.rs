@@ -4,15 +4,32 @@ use nannou::glam::{Mat4, Vec4};
 
 #[derive(AsStd140, Clone, Copy)]
 pub struct DirectionalLightUniforms {
+    // the light shines along the -Z axis of its model matrix
     model_matrix: ColumnMatrix4<f32>,
+    // rgb color, with the intensity in the alpha channel
     color: Vector4<f32>,
+    // the slot of the light in the cascaded shadow map, or -1 if it casts no shadows
+    shadow_index: i32,
+    // world units
+    depth_bias: f32,
+    // shadow map texels
+    normal_bias: f32,
 }
 
 impl DirectionalLightUniforms {
-    pub fn new(model_matrix: Mat4, color: Vec4) -> Self {
+    pub fn new(
+        model_matrix: Mat4,
+        color: Vec4,
+        shadow_index: i32,
+        depth_bias: f32,
+        normal_bias: f32,
+    ) -> Self {
         Self {
             model_matrix: ColumnMatrix4::<f32>::from(model_matrix),
             color: Vector4::<f32>::from(color),
+            shadow_index,
+            depth_bias,
+            normal_bias,
         }
     }
 }
@@ -0,0 +1,47 @@
+use crevice::std140::AsStd140;
+use mint::*;
+use nannou::glam::Mat4;
+
+// the image-based lighting of the forward pass
+#[derive(AsStd140, Clone, Copy)]
+pub struct EnvironmentUniform {
+    // world space directions to the directions the environment maps are sampled with
+    rotation: ColumnMatrix4<f32>,
+    intensity: f32,
+    // the mip level of the prefiltered map for a roughness of 1.0
+    max_prefiltered_lod: f32,
+}
+
+impl EnvironmentUniform {
+    pub fn new(rotation: Mat4, intensity: f32, max_prefiltered_lod: f32) -> Self {
+        Self {
+            rotation: ColumnMatrix4::from(rotation),
+            intensity,
+            max_prefiltered_lod,
+        }
+    }
+}
+
+// bound with a dynamic offset for every face and mip level rendered while baking the environment
+// maps
+#[derive(AsStd140, Clone, Copy)]
+pub struct EnvironmentBakeUniform {
+    // the cube face being rendered, in the order +X, -X, +Y, -Y, +Z, -Z
+    face: u32,
+    // that the prefiltered mip level is filtered for
+    roughness: f32,
+    // texels along an edge of the top mip level of the cube being sampled
+    source_size: f32,
+    sample_count: u32,
+}
+
+impl EnvironmentBakeUniform {
+    pub fn new(face: u32, roughness: f32, source_size: u32, sample_count: u32) -> Self {
+        Self {
+            face,
+            roughness,
+            source_size: source_size as f32,
+            sample_count,
+        }
+    }
+}
@@ -1,6 +1,40 @@
 pub mod model_matrix;
+pub mod world_instance;
 use nannou::wgpu;
 
 pub trait GpuInstance {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::world_instance::WorldInstance;
+    use super::*;
+
+    #[test]
+    fn world_instance_layout_starts_at_its_location_override() {
+        use wgpu::VertexFormat::*;
+        let layout = WorldInstance::desc();
+        assert_eq!(layout.array_stride, 100);
+        assert_eq!(layout.step_mode, wgpu::InputStepMode::Instance);
+
+        // `#[location(10)]` on the first field, counting up from there
+        let expected = [
+            (10, 0, Float32x4),
+            (11, 16, Float32x4),
+            (12, 32, Float32x4),
+            (13, 48, Float32x4),
+            (14, 64, Float32x3),
+            (15, 76, Float32x3),
+            (16, 88, Float32x3),
+        ];
+        assert_eq!(layout.attributes.len(), expected.len());
+        for (attribute, &(location, offset, format)) in
+            layout.attributes.iter().zip(expected.iter())
+        {
+            assert_eq!(attribute.shader_location, location);
+            assert_eq!(attribute.offset, offset);
+            assert_eq!(attribute.format, format);
+        }
+    }
+}
@@ -1,49 +1,39 @@
-use super::GpuInstance;
 use bytemuck::{Pod, Zeroable};
-use nannou::wgpu;
+use demo_derive::GpuInstance;
+use nannou::glam::Mat4;
 
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable, Default)]
+#[derive(Clone, Copy, Default, GpuInstance)]
 pub struct ModelMatrixInstance {
-    /*[[location(10)]] */ model_matrix_0: [f32; 4], //Vector4<f32>,
+    // after the attributes of `GltfMeshVertex`
+    #[location(10)]
+    /*[[location(10)]] */
+    model_matrix_0: [f32; 4], //Vector4<f32>,
     /*[[location(11)]] */ model_matrix_1: [f32; 4], //Vector4<f32>,
     /*[[location(12)]] */ model_matrix_2: [f32; 4], //Vector4<f32>,
     /*[[location(13)]] */ model_matrix_3: [f32; 4], //Vector4<f32>,
 }
 
-impl GpuInstance for ModelMatrixInstance {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        use std::mem;
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<ModelMatrixInstance>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Instance,
-            attributes: &[
-                // A mat4 takes up 4 vertex slots as it is technically 4 Vec4s.
-                // model_matrix_0
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 10,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                // model_matrix_1
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
-                    shader_location: 11,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                // model_matrix_2
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
-                    shader_location: 12,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                // model_matrix_3
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
-                    shader_location: 13,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-            ],
+// four columns of `f32`s under `repr(C)`, so there is no padding to leave uninitialized
+unsafe impl Zeroable for ModelMatrixInstance {}
+unsafe impl Pod for ModelMatrixInstance {}
+
+impl ModelMatrixInstance {
+    pub fn new(model_matrix: Mat4) -> Self {
+        Self {
+            model_matrix_0: model_matrix.x_axis.into(),
+            model_matrix_1: model_matrix.y_axis.into(),
+            model_matrix_2: model_matrix.z_axis.into(),
+            model_matrix_3: model_matrix.w_axis.into(),
         }
     }
+
+    pub fn model_matrix(&self) -> Mat4 {
+        Mat4::from_cols(
+            self.model_matrix_0.into(),
+            self.model_matrix_1.into(),
+            self.model_matrix_2.into(),
+            self.model_matrix_3.into(),
+        )
+    }
 }
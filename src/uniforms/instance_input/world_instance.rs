@@ -0,0 +1,40 @@
+use bytemuck::{Pod, Zeroable};
+use demo_derive::GpuInstance;
+use nannou::glam::{Mat3, Mat4};
+
+/// The world matrix of one instance of a batched draw, along with the matrix transforming its
+/// normals.
+#[repr(C)]
+#[derive(Clone, Copy, Default, GpuInstance)]
+pub struct WorldInstance {
+    // after the attributes of `GltfMeshVertex`
+    #[location(10)]
+    /*[[location(10)]] */
+    model_matrix_0: [f32; 4],
+    /*[[location(11)]] */ model_matrix_1: [f32; 4],
+    /*[[location(12)]] */ model_matrix_2: [f32; 4],
+    /*[[location(13)]] */ model_matrix_3: [f32; 4],
+    /*[[location(14)]] */ normal_matrix_0: [f32; 3],
+    /*[[location(15)]] */ normal_matrix_1: [f32; 3],
+    /*[[location(16)]] */ normal_matrix_2: [f32; 3],
+}
+
+// `repr(C)` with `f32` fields only: no padding, and every bit pattern is a valid instance
+unsafe impl Zeroable for WorldInstance {}
+unsafe impl Pod for WorldInstance {}
+
+impl WorldInstance {
+    pub fn new(model_matrix: Mat4) -> Self {
+        // the inverse transpose keeps normals perpendicular under non-uniform scale
+        let normal_matrix = Mat3::from_mat4(model_matrix).inverse().transpose();
+        Self {
+            model_matrix_0: model_matrix.x_axis.into(),
+            model_matrix_1: model_matrix.y_axis.into(),
+            model_matrix_2: model_matrix.z_axis.into(),
+            model_matrix_3: model_matrix.w_axis.into(),
+            normal_matrix_0: normal_matrix.x_axis.into(),
+            normal_matrix_1: normal_matrix.y_axis.into(),
+            normal_matrix_2: normal_matrix.z_axis.into(),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use crevice::std140::AsStd140;
+
+// the number of valid elements in each light storage buffer, which may have spare capacity
+#[derive(AsStd140, Clone, Copy)]
+pub struct LightCountsUniform {
+    directional: u32,
+    point: u32,
+    spot: u32,
+}
+
+impl LightCountsUniform {
+    pub fn new(directional: u32, point: u32, spot: u32) -> Self {
+        Self {
+            directional,
+            point,
+            spot,
+        }
+    }
+}
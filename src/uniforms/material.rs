@@ -0,0 +1,36 @@
+use crevice::std140::AsStd140;
+use mint::*;
+use nannou::glam::{Vec3, Vec4};
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct MaterialUniform {
+    base_color_factor: Vector4<f32>,
+    emissive_factor: Vector3<f32>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    alpha_cutoff: f32,
+}
+
+impl MaterialUniform {
+    pub fn new(
+        base_color_factor: Vec4,
+        emissive_factor: Vec3,
+        metallic_factor: f32,
+        roughness_factor: f32,
+        normal_scale: f32,
+        occlusion_strength: f32,
+        alpha_cutoff: f32,
+    ) -> Self {
+        Self {
+            base_color_factor: Vector4::<f32>::from(base_color_factor),
+            emissive_factor: Vector3::<f32>::from(emissive_factor),
+            metallic_factor,
+            roughness_factor,
+            normal_scale,
+            occlusion_strength,
+            alpha_cutoff,
+        }
+    }
+}
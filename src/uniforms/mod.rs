@@ -1,5 +1,15 @@
+pub mod arena;
+pub mod background;
 pub mod camera;
 pub mod directional_light;
+pub mod environment;
 pub mod instance_input;
+pub mod light_counts;
+pub mod material;
 pub mod model;
+pub mod point_light;
+pub mod post;
+pub mod shadow;
+pub mod spot_light;
+pub mod tonemap;
 pub mod vertex_input;
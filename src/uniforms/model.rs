@@ -1,16 +1,54 @@
-use bytemuck::{Pod, Zeroable};
+use crate::morph::MorphSlot;
 use crevice::std140::AsStd140;
 use mint::*;
-use nannou::glam::{Mat4, Vec4};
+use nannou::glam::Mat4;
 
 #[derive(AsStd140, Clone, Copy)]
 pub struct ModelUniform {
     model_matrix: ColumnMatrix4<f32>,
+    // the first joint matrix of the entity's skin, or -1 if it is not skinned
+    joint_offset: i32,
+    // the first delta of the entity's morph targets, or -1 if it has none
+    morph_delta_offset: i32,
+    morph_weight_offset: u32,
+    morph_target_count: u32,
+    morph_vertex_count: u32,
 }
 impl ModelUniform {
     pub fn new(model_matrix: Mat4) -> Self {
         Self {
             model_matrix: ColumnMatrix4::<f32>::from(model_matrix),
+            joint_offset: -1,
+            morph_delta_offset: -1,
+            morph_weight_offset: 0,
+            morph_target_count: 0,
+            morph_vertex_count: 0,
+        }
+    }
+
+    pub fn with_joint_offset(self, joint_offset: Option<u32>) -> Self {
+        Self {
+            joint_offset: joint_offset.map_or(-1, |offset| offset as i32),
+            ..self
+        }
+    }
+
+    pub fn with_morph_slot(self, slot: Option<MorphSlot>) -> Self {
+        match slot {
+            Some(slot) => Self {
+                morph_delta_offset: slot.delta_offset as i32,
+                morph_weight_offset: slot.weight_offset,
+                morph_target_count: slot.target_count,
+                morph_vertex_count: slot.vertex_count,
+                ..self
+            },
+            None => Self {
+                morph_delta_offset: -1,
+                morph_weight_offset: 0,
+                morph_target_count: 0,
+                morph_vertex_count: 0,
+                ..self
+            },
         }
     }
 }
@@ -0,0 +1,44 @@
+use crevice::std140::AsStd140;
+use mint::*;
+use nannou::glam::Vec3;
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct PointLightUniforms {
+    position: Vector3<f32>,
+    // 0.0 for an unlimited range
+    range: f32,
+    color: Vector3<f32>,
+    intensity: f32,
+    // the cube of the light in the point shadow map, or -1 if it casts no shadows this frame
+    shadow_index: i32,
+    // world units
+    depth_bias: f32,
+    normal_bias: f32,
+    // the distance encoded as a depth of 1.0 in the shadow cube
+    shadow_far: f32,
+}
+
+impl PointLightUniforms {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        position: Vec3,
+        range: f32,
+        color: Vec3,
+        intensity: f32,
+        shadow_index: i32,
+        depth_bias: f32,
+        normal_bias: f32,
+        shadow_far: f32,
+    ) -> Self {
+        Self {
+            position: Vector3::<f32>::from(position),
+            range,
+            color: Vector3::<f32>::from(color),
+            intensity,
+            shadow_index,
+            depth_bias,
+            normal_bias,
+            shadow_far,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crevice::std140::AsStd140;
+use mint::*;
+use nannou::glam::Mat4;
+
+// the upsampling and compositing passes of the bloom
+#[derive(AsStd140, Clone, Copy)]
+pub struct BloomUniform {
+    // of the tent filter upsampling each mip, in texture coordinates
+    filter_radius: f32,
+    // how much of the bloom is mixed into the frame
+    intensity: f32,
+}
+
+impl BloomUniform {
+    pub fn new(filter_radius: f32, intensity: f32) -> Self {
+        Self {
+            filter_radius,
+            intensity,
+        }
+    }
+}
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct FxaaUniform {
+    // the longest an edge is searched along, in pixels
+    span_max: f32,
+    // keep the search from blowing up on dark and low contrast edges
+    reduce_mul: f32,
+    reduce_min: f32,
+}
+
+impl FxaaUniform {
+    pub fn new(span_max: f32, reduce_mul: f32, reduce_min: f32) -> Self {
+        Self {
+            span_max,
+            reduce_mul,
+            reduce_min,
+        }
+    }
+}
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct SsaoUniform {
+    // from view space to clip space and back, to reconstruct positions from the depth buffer
+    projection: ColumnMatrix4<f32>,
+    inverse_projection: ColumnMatrix4<f32>,
+    // of the hemisphere sampled around each pixel, in view space units
+    radius: f32,
+    // keeps surfaces from occluding themselves
+    bias: f32,
+    // the power the ambient occlusion is raised to
+    intensity: f32,
+    sample_count: u32,
+}
+
+impl SsaoUniform {
+    pub fn new(
+        projection: Mat4,
+        radius: f32,
+        bias: f32,
+        intensity: f32,
+        sample_count: u32,
+    ) -> Self {
+        Self {
+            projection: ColumnMatrix4::from(projection),
+            inverse_projection: ColumnMatrix4::from(projection.inverse()),
+            radius,
+            bias,
+            intensity,
+            sample_count,
+        }
+    }
+}
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct DepthOfFieldUniform {
+    // from clip space back to view space, to find the distance of each pixel
+    inverse_projection: ColumnMatrix4<f32>,
+    // in view space units
+    focus_distance: f32,
+    // the blur of what is infinitely far behind the focus, as a fraction of `max_radius`
+    aperture: f32,
+    // the largest circle of confusion, in pixels
+    max_radius: f32,
+    sample_count: u32,
+}
+
+impl DepthOfFieldUniform {
+    pub fn new(
+        projection: Mat4,
+        focus_distance: f32,
+        aperture: f32,
+        max_radius: f32,
+        sample_count: u32,
+    ) -> Self {
+        Self {
+            inverse_projection: ColumnMatrix4::from(projection.inverse()),
+            focus_distance,
+            aperture,
+            max_radius,
+            sample_count,
+        }
+    }
+}
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct VignetteUniform {
+    // how dark the corners get, from 0.0 to 1.0
+    intensity: f32,
+    // from the center, where 1.0 reaches the corners
+    radius: f32,
+    // over which the darkening fades in, past `radius`
+    smoothness: f32,
+}
+
+impl VignetteUniform {
+    pub fn new(intensity: f32, radius: f32, smoothness: f32) -> Self {
+        Self {
+            intensity,
+            radius,
+            smoothness,
+        }
+    }
+}
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct ChromaticAberrationUniform {
+    // how far red and blue are pulled apart at the edges, in texture coordinates
+    intensity: f32,
+}
+
+impl ChromaticAberrationUniform {
+    pub fn new(intensity: f32) -> Self {
+        Self { intensity }
+    }
+}
@@ -0,0 +1,86 @@
+use crevice::std140::AsStd140;
+use mint::*;
+use nannou::glam::{Mat4, Vec3};
+
+// settings shared by every directional shadow
+#[derive(AsStd140, Clone, Copy)]
+pub struct ShadowUniform {
+    // lights with a shadow index of `light_count` or higher are unshadowed
+    light_count: u32,
+    cascade_count: u32,
+    // the PCF kernel covers `(2 * pcf_radius + 1)^2` texels
+    pcf_radius: u32,
+    debug_cascades: u32,
+}
+
+impl ShadowUniform {
+    pub fn new(
+        light_count: u32,
+        cascade_count: u32,
+        pcf_radius: u32,
+        debug_cascades: bool,
+    ) -> Self {
+        Self {
+            light_count,
+            cascade_count,
+            pcf_radius,
+            debug_cascades: debug_cascades as u32,
+        }
+    }
+}
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct CascadeUniform {
+    // world space to shadow map clip space
+    view_projection: ColumnMatrix4<f32>,
+    // the view-space depth at which the cascade ends
+    split_far: f32,
+    // world-space size of a shadow map texel, used to scale the normal bias
+    texel_size: f32,
+    // world-space depth covered by the cascade, used to convert the depth bias
+    depth_range: f32,
+}
+
+impl CascadeUniform {
+    pub fn new(view_projection: Mat4, split_far: f32, texel_size: f32, depth_range: f32) -> Self {
+        Self {
+            view_projection: ColumnMatrix4::from(view_projection),
+            split_far,
+            texel_size,
+            depth_range,
+        }
+    }
+}
+
+// bound with a dynamic offset for every shadow map layer rendered by the depth pass
+#[derive(AsStd140, Clone, Copy)]
+pub struct ShadowCasterUniform {
+    view_projection: ColumnMatrix4<f32>,
+}
+
+impl ShadowCasterUniform {
+    pub fn new(view_projection: Mat4) -> Self {
+        Self {
+            view_projection: ColumnMatrix4::from(view_projection),
+        }
+    }
+}
+
+// bound with a dynamic offset for every cube face rendered by the point shadow depth pass
+#[derive(AsStd140, Clone, Copy)]
+pub struct PointShadowCasterUniform {
+    view_projection: ColumnMatrix4<f32>,
+    light_position: Vector3<f32>,
+    // the distance mapped to a depth of 1.0
+    far: f32,
+}
+
+impl PointShadowCasterUniform {
+    pub fn new(view_projection: Mat4, light_position: Vec3, far: f32) -> Self {
+        Self {
+            view_projection: ColumnMatrix4::from(view_projection),
+            light_position: Vector3::from(light_position),
+            far,
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crevice::std140::AsStd140;
+use mint::*;
+use nannou::glam::Vec3;
+
+#[derive(AsStd140, Clone, Copy)]
+pub struct SpotLightUniforms {
+    position: Vector3<f32>,
+    // 0.0 for an unlimited range
+    range: f32,
+    direction: Vector3<f32>,
+    intensity: f32,
+    color: Vector3<f32>,
+    // cosines of the cone half-angles, so the shader can compare them against a dot product
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+}
+
+impl SpotLightUniforms {
+    pub fn new(
+        position: Vec3,
+        range: f32,
+        direction: Vec3,
+        intensity: f32,
+        color: Vec3,
+        inner_cone_cos: f32,
+        outer_cone_cos: f32,
+    ) -> Self {
+        Self {
+            position: Vector3::<f32>::from(position),
+            range,
+            direction: Vector3::<f32>::from(direction),
+            intensity,
+            color: Vector3::<f32>::from(color),
+            inner_cone_cos,
+            outer_cone_cos,
+        }
+    }
+}
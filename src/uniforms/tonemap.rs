@@ -0,0 +1,56 @@
+use crevice::std140::AsStd140;
+
+// the resolve of the HDR frame into nannou's frame texture
+#[derive(AsStd140, Clone, Copy)]
+pub struct TonemapUniform {
+    // one of the `TONEMAPPER_*` constants of `tonemap.wgsl`
+    tonemapper: u32,
+}
+
+impl TonemapUniform {
+    pub fn new(tonemapper: u32) -> Self {
+        Self { tonemapper }
+    }
+}
+
+// the histogram and average passes of the auto exposure
+#[derive(AsStd140, Clone, Copy)]
+pub struct AutoExposureUniform {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    // fractions of the pixels, darkest first, between which the luminance is averaged
+    low_percentile: f32,
+    high_percentile: f32,
+    // stops added to the exposure mapping the average luminance to middle grey
+    compensation: f32,
+    // how far the exposure moves towards its target this frame, from 0.0 to 1.0
+    adaptation: f32,
+    // of the histogram pass's tiles
+    tile_columns: u32,
+    tile_count: u32,
+}
+
+impl AutoExposureUniform {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        min_log_luminance: f32,
+        max_log_luminance: f32,
+        low_percentile: f32,
+        high_percentile: f32,
+        compensation: f32,
+        adaptation: f32,
+        tile_columns: u32,
+        tile_count: u32,
+    ) -> Self {
+        Self {
+            min_log_luminance,
+            log_luminance_range: max_log_luminance - min_log_luminance,
+            low_percentile,
+            high_percentile,
+            compensation,
+            adaptation,
+            tile_columns,
+            tile_count,
+        }
+    }
+}
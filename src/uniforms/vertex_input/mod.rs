@@ -4,3 +4,42 @@ use nannou::wgpu;
 pub trait GpuVertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::gltf_mesh_vertex::GltfMeshVertex;
+    use super::*;
+
+    #[test]
+    fn gltf_mesh_vertex_layout_follows_its_fields() {
+        use wgpu::VertexFormat::*;
+        let layout = GltfMeshVertex::desc();
+        assert_eq!(layout.array_stride, 124);
+        assert_eq!(layout.step_mode, wgpu::InputStepMode::Vertex);
+
+        let expected = [
+            (0, Float32x4),
+            (16, Float32x3),
+            (28, Float32x3),
+            (40, Float32x3),
+            (52, Float32x4),
+            (68, Float32x2),
+            (76, Float32x2),
+            (84, Float32x2),
+            (92, Float32x4),
+            (108, Uint32x4),
+        ];
+        assert_eq!(layout.attributes.len(), expected.len());
+        for (location, (attribute, &(offset, format))) in
+            layout.attributes.iter().zip(expected.iter()).enumerate()
+        {
+            assert_eq!(attribute.shader_location, location as u32);
+            assert_eq!(attribute.offset, offset);
+            assert_eq!(attribute.format, format);
+        }
+        assert_eq!(
+            GltfMeshVertex::SKIN_INDEX_OFFSET,
+            layout.attributes[9].offset
+        );
+    }
+}